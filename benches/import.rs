@@ -0,0 +1,93 @@
+use general_pub_sub::{Client, ImportRecord, Message, PubSub};
+use std::convert::Infallible;
+use std::env;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy)]
+struct BenchClient {
+    id: usize,
+}
+
+impl Client<usize, u64> for BenchClient {
+    type Error = Infallible;
+
+    fn get_id(&self) -> usize {
+        self.id
+    }
+
+    fn send(&mut self, _message: &Message<u64>) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+type BenchPubSub = PubSub<BenchClient, usize, u64>;
+
+/// Measures loading subscriptions in bulk with `import`, against adding
+/// each `Client` and calling `sub_id` for each of its subscriptions, with
+/// a subscribe callback set as a server would have.
+///
+/// Usage: cargo bench --bench import -- [clients] [channels per client]
+fn main() {
+    // `cargo bench` passes `--bench`, which is not one of ours.
+    let mut args = env::args().skip(1).filter(|arg| !arg.starts_with("--"));
+    let clients: usize = args.next().map_or(100_000, |arg| arg.parse().unwrap());
+    let channels: usize = args.next().map_or(1, |arg| arg.parse().unwrap());
+
+    let (imported, import) = run(clients, channels, "import", |pubsub| {
+        let records = (0..clients).map(|id| ImportRecord {
+            id,
+            channels: (0..channels)
+                .map(|channel| format!("users.{}.{}", id, channel).into())
+                .collect(),
+        });
+
+        let report = pubsub.import(records, |id| BenchClient { id: *id });
+        assert!(report.errors.is_empty());
+    });
+
+    let (subscribed, sub_id) = run(clients, channels, "sub_id", |pubsub| {
+        for id in 0..clients {
+            pubsub
+                .add_client(BenchClient { id })
+                .expect("Failed to add client.");
+
+            for channel in 0..channels {
+                pubsub
+                    .sub_id(&id, format!("users.{}.{}", id, channel))
+                    .expect("Failed to subscribe.");
+            }
+        }
+    });
+
+    assert_eq!(imported.client_count(), subscribed.client_count());
+    assert_eq!(imported.channel_count(), subscribed.channel_count());
+    assert!(imported.check_invariants().is_ok());
+
+    println!(
+        "import took {:.0}% of the time of sub_id.",
+        100.0 * import.as_secs_f64() / sub_id.as_secs_f64()
+    );
+}
+
+fn run<F>(clients: usize, channels: usize, label: &str, load: F) -> (BenchPubSub, Duration)
+where
+    F: FnOnce(&mut BenchPubSub),
+{
+    let mut pubsub = PubSub::new();
+    pubsub.set_subscribe_callback(|_, _| {});
+
+    let started = Instant::now();
+    load(&mut pubsub);
+    let elapsed = started.elapsed();
+
+    println!(
+        "Loaded {} subscriptions of {} clients with {} in {:?} ({:.0} subscriptions/s).",
+        clients * channels,
+        clients,
+        label,
+        elapsed,
+        (clients * channels) as f64 / elapsed.as_secs_f64(),
+    );
+
+    (pubsub, elapsed)
+}
@@ -0,0 +1,74 @@
+use general_pub_sub::{Client, Message, PubSub};
+use std::convert::Infallible;
+use std::env;
+use std::time::Instant;
+
+#[derive(Clone, Copy)]
+struct BenchClient {
+    id: usize,
+}
+
+impl Client<usize, u64> for BenchClient {
+    type Error = Infallible;
+
+    fn get_id(&self) -> usize {
+        self.id
+    }
+
+    fn send(&mut self, _message: &Message<u64>) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// Measures publish throughput with many pattern subscriptions, once with
+/// the pattern cache and once without it, where each published `Channel`
+/// is matched against the patterns indexed under its prefixes.
+///
+/// Usage: cargo bench --bench pattern_publish -- [patterns] [publishes]
+fn main() {
+    // `cargo bench` passes `--bench`, which is not one of ours.
+    let mut args = env::args().skip(1).filter(|arg| !arg.starts_with("--"));
+    let patterns: usize = args.next().map_or(1_000, |arg| arg.parse().unwrap());
+    let publishes: u64 = args.next().map_or(100_000, |arg| arg.parse().unwrap());
+
+    run(patterns, publishes, "cached", None);
+    run(patterns, publishes, "uncached", Some(0));
+}
+
+fn run(patterns: usize, publishes: u64, label: &str, cache_capacity: Option<usize>) {
+    let mut pubsub = PubSub::new();
+
+    if let Some(capacity) = cache_capacity {
+        pubsub.set_pattern_cache_capacity(capacity);
+    }
+
+    for id in 0..patterns {
+        let client = BenchClient { id };
+
+        pubsub.add_client(client).expect("Failed to add client.");
+        pubsub
+            .psub_client(client, format!("tenant.{}.*.events", id))
+            .expect("Failed to subscribe.");
+    }
+
+    let started = Instant::now();
+
+    for sequence in 0..publishes {
+        let channel = format!("tenant.{}.orders.events", sequence % patterns as u64);
+
+        pubsub
+            .pub_message(&channel, sequence)
+            .expect("Failed to publish.");
+    }
+
+    let elapsed = started.elapsed();
+
+    println!(
+        "Published {} messages against {} patterns, {}, in {:?} ({:.0} messages/s).",
+        publishes,
+        patterns,
+        label,
+        elapsed,
+        publishes as f64 / elapsed.as_secs_f64(),
+    );
+}
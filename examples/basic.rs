@@ -1,4 +1,5 @@
 use general_pub_sub::{Client, Message, PubSub, PubSubError};
+use std::convert::Infallible;
 
 #[derive(Clone, Copy)]
 struct BasicClient {
@@ -12,15 +13,19 @@ impl BasicClient {
 }
 
 impl Client<u32, &str> for BasicClient {
+    type Error = Infallible;
+
     fn get_id(&self) -> u32 {
         self.id
     }
 
-    fn send(&mut self, message: &Message<&str>) {
+    fn send(&mut self, message: &Message<&str>) -> Result<(), Infallible> {
         println!(
             "Client ({}) Received Message from Channel ({}): {}",
             self.id, message.source, message.contents
         );
+
+        Ok(())
     }
 }
 
@@ -33,8 +38,12 @@ fn main() {
     let channel_a = "channel.a";
     let channel_b = "channel.b";
 
-    pubsub.add_client(client_one);
-    pubsub.add_client(client_two);
+    pubsub
+        .add_client(client_one)
+        .expect("Failed to add client.");
+    pubsub
+        .add_client(client_two)
+        .expect("Failed to add client.");
 
     pubsub
         .sub_client(client_one, channel_a)
@@ -46,14 +55,20 @@ fn main() {
         .sub_client(client_one, channel_b)
         .expect("This should not happen");
 
-    pubsub.pub_message(channel_a, "Both clients should receive this message.");
-    pubsub.pub_message(channel_b, "Only Client 1 should receive this message.");
+    pubsub
+        .pub_message(channel_a, "Both clients should receive this message.")
+        .expect("This should not happen");
+    pubsub
+        .pub_message(channel_b, "Only Client 1 should receive this message.")
+        .expect("This should not happen");
 
     pubsub
         .unsub_client(client_one, channel_a)
         .expect("This should not happen");
 
-    pubsub.pub_message(channel_a, "Only Client 2 should receive this message.");
+    pubsub
+        .pub_message(channel_a, "Only Client 2 should receive this message.")
+        .expect("This should not happen");
 
     pubsub.remove_client(client_one);
 
@@ -61,14 +76,28 @@ fn main() {
         .unsub_client(client_two, channel_a)
         .expect("This should not happen");
 
-    pubsub.pub_message(channel_a, "Nobody should receive this message.");
+    pubsub
+        .pub_message(channel_a, "Nobody should receive this message.")
+        .expect("This should not happen");
 
     if let Result::Err(expected_error) = pubsub.unsub_client(client_one, channel_a) {
         match expected_error {
-            PubSubError::ClientNotSubscribedError => {
+            PubSubError::ClientNotSubscribedError { .. } => {
                 println!("This error is expected: {}", expected_error)
             }
             _ => println!("This should not happen: {}", expected_error),
         }
     }
+
+    println!(
+        "{} client(s) left across {} channel(s).",
+        pubsub.client_count(),
+        pubsub.channel_count()
+    );
+
+    for client in pubsub.clear() {
+        println!("Client ({}) was removed on shutdown.", client.get_id());
+    }
+
+    assert!(pubsub.is_empty());
 }
@@ -0,0 +1,504 @@
+use general_pub_sub::{Client, Message, PubSub, PublishReceipt};
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::mpsc::{self, Sender},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// Every connected `Client` is subscribed to this `Channel`, so server-wide
+/// notices (such as shutting down) reach everybody.
+const SERVER_CHANNEL: &str = "server";
+const ROOM_PREFIX: &str = "room.";
+/// `Clients` sent `/watch` are subscribed to this system `Channel`,
+/// `$sys.rooms`, and sent the active rooms each time one is created or
+/// closed.
+const ROOMS_CHANNEL: &str = "rooms";
+
+struct ChatClient {
+    id: SocketAddr,
+    stream: TcpStream,
+}
+
+impl Clone for ChatClient {
+    fn clone(&self) -> ChatClient {
+        ChatClient {
+            id: self.id,
+            stream: self.stream.try_clone().expect("Failed to clone TCP Stream"),
+        }
+    }
+}
+
+impl ChatClient {
+    fn reply(&mut self, line: &str) {
+        if let Err(error) = writeln!(self.stream, "{}", line) {
+            println!("Failed to write to client ({}): {}", self.id, error);
+        }
+    }
+}
+
+impl Client<SocketAddr, String> for ChatClient {
+    type Error = io::Error;
+
+    fn get_id(&self) -> SocketAddr {
+        self.id
+    }
+
+    fn send(&mut self, message: &Message<String>) -> io::Result<()> {
+        writeln!(self.stream, "{}", message.contents)
+    }
+}
+
+/// Reports the clients a published line could not be written to.  They are
+/// disconnected once their reader notices.
+fn report_failures(receipt: PublishReceipt<SocketAddr, io::Error>) {
+    for (id, error) in receipt.failures {
+        println!("Failed to write to client ({}): {}", id, error);
+    }
+}
+
+/// A room gaining its first member or losing its last one, as reported by
+/// the `PubSub` lifecycle callbacks
+enum RoomEvent {
+    Created(String),
+    Closed(String),
+}
+
+enum Event {
+    Connected(ChatClient),
+    Line(SocketAddr, String),
+    Disconnected(SocketAddr),
+}
+
+struct ChatServer {
+    pubsub: PubSub<ChatClient, SocketAddr, String>,
+    clients: HashMap<SocketAddr, ChatClient>,
+    rooms: HashMap<SocketAddr, String>,
+    /// Filled by the lifecycle callbacks, which cannot publish themselves,
+    /// and drained by `announce_rooms`.
+    room_events: Arc<Mutex<Vec<RoomEvent>>>,
+    active_rooms: BTreeSet<String>,
+}
+
+impl ChatServer {
+    fn new() -> ChatServer {
+        let mut pubsub = PubSub::new();
+        let room_events = Arc::new(Mutex::new(Vec::new()));
+
+        let events = room_events.clone();
+        pubsub.set_first_subscriber_callback(move |channel: &str| {
+            if let Some(room) = channel.strip_prefix(ROOM_PREFIX) {
+                events
+                    .lock()
+                    .unwrap()
+                    .push(RoomEvent::Created(room.to_string()));
+            }
+        });
+
+        let events = room_events.clone();
+        pubsub.set_last_unsubscriber_callback(move |channel: &str| {
+            if let Some(room) = channel.strip_prefix(ROOM_PREFIX) {
+                events
+                    .lock()
+                    .unwrap()
+                    .push(RoomEvent::Closed(room.to_string()));
+            }
+        });
+
+        ChatServer {
+            pubsub,
+            clients: HashMap::new(),
+            rooms: HashMap::new(),
+            room_events,
+            active_rooms: BTreeSet::new(),
+        }
+    }
+
+    fn reply(&mut self, id: SocketAddr, line: &str) {
+        if let Some(client) = self.clients.get_mut(&id) {
+            client.reply(line);
+        }
+    }
+
+    fn connect(&mut self, mut client: ChatClient) {
+        println!("New connection: {}", client.id);
+
+        client
+            .reply("Welcome! Commands: /join <room>, /leave, /members, /rooms, /watch, /shutdown");
+
+        self.pubsub
+            .add_client(client.clone())
+            .expect("Failed to add client.");
+        self.pubsub
+            .sub_client(client.clone(), SERVER_CHANNEL)
+            .expect("Failed to subscribe to the server channel.");
+        self.clients.insert(client.id, client);
+    }
+
+    fn disconnect(&mut self, id: SocketAddr) {
+        self.leave(id);
+
+        if let Some(client) = self.clients.remove(&id) {
+            self.pubsub.remove_client(client);
+        }
+    }
+
+    fn join(&mut self, id: SocketAddr, room: &str) {
+        self.leave(id);
+
+        let channel = format!("{}{}", ROOM_PREFIX, room);
+        let client = self.clients[&id].clone();
+
+        match self.pubsub.sub_client(client.clone(), channel.clone()) {
+            Ok(()) => {
+                self.reply(id, &format!("* You joined {}", room));
+
+                let presence = format!("* {} joined {}", id, room);
+                report_failures(
+                    self.pubsub
+                        .pub_message_from(client, &channel, presence)
+                        .expect("Failed to announce presence."),
+                );
+                self.rooms.insert(id, channel);
+            }
+            Err(error) => self.reply(id, &format!("! {}", error)),
+        }
+    }
+
+    fn leave(&mut self, id: SocketAddr) {
+        let channel = match self.rooms.remove(&id) {
+            Some(channel) => channel,
+            None => return,
+        };
+
+        let client = self.clients[&id].clone();
+        let room = &channel[ROOM_PREFIX.len()..];
+
+        self.pubsub
+            .unsub_client(client, &channel)
+            .expect("Failed to leave the room.");
+
+        let presence = format!("* {} left {}", id, room);
+        report_failures(
+            self.pubsub
+                .pub_message(&channel, presence)
+                .expect("Failed to announce presence."),
+        );
+    }
+
+    fn members(&mut self, id: SocketAddr) {
+        let line = match self.rooms.get(&id) {
+            Some(channel) => {
+                let members: Vec<String> = self
+                    .pubsub
+                    .view()
+                    .subscribers(channel)
+                    .map(|member| member.to_string())
+                    .collect();
+
+                format!("* Members: {}", members.join(", "))
+            }
+            None => "! You are not in a room".to_string(),
+        };
+
+        self.reply(id, &line);
+    }
+
+    fn list_rooms(&mut self, id: SocketAddr) {
+        // Empty channels are kept by default, so rooms everybody left are still
+        // listed by the view, just without subscribers.
+        let rooms: Vec<String> = self
+            .pubsub
+            .view()
+            .channels()
+            .filter(|(channel, subscribers)| channel.starts_with(ROOM_PREFIX) && *subscribers > 0)
+            .map(|(channel, subscribers)| {
+                format!("{} ({})", &channel[ROOM_PREFIX.len()..], subscribers)
+            })
+            .collect();
+
+        self.reply(id, &format!("* Rooms: {}", rooms.join(", ")));
+    }
+
+    fn watch(&mut self, id: SocketAddr) {
+        let client = self.clients[&id].clone();
+
+        match self.pubsub.sub_system(client, ROOMS_CHANNEL) {
+            Ok(()) => self.reply(id, "* Watching rooms"),
+            Err(error) => self.reply(id, &format!("! {}", error)),
+        }
+    }
+
+    /// Publishes the active rooms to `ROOMS_CHANNEL` if any room was created
+    /// or closed since the last call.
+    fn announce_rooms(&mut self) {
+        let events: Vec<RoomEvent> = self.room_events.lock().unwrap().drain(..).collect();
+
+        if events.is_empty() {
+            return;
+        }
+
+        for event in events {
+            match event {
+                RoomEvent::Created(room) => self.active_rooms.insert(room),
+                RoomEvent::Closed(room) => self.active_rooms.remove(&room),
+            };
+        }
+
+        let rooms: Vec<&str> = self.active_rooms.iter().map(String::as_str).collect();
+
+        report_failures(
+            self.pubsub
+                .pub_system(
+                    ROOMS_CHANNEL,
+                    format!("* Active rooms: {}", rooms.join(", ")),
+                )
+                .expect("Failed to announce the rooms."),
+        );
+    }
+
+    fn say(&mut self, id: SocketAddr, text: &str) {
+        let channel = match self.rooms.get(&id) {
+            Some(channel) => channel.clone(),
+            None => return self.reply(id, "! Join a room first"),
+        };
+
+        let client = self.clients[&id].clone();
+        let message = format!("[{}] {}: {}", &channel[ROOM_PREFIX.len()..], id, text);
+
+        report_failures(
+            self.pubsub
+                .pub_message_from(client, &channel, message)
+                .expect("Failed to publish to the room."),
+        );
+    }
+
+    /// Handles a line sent by a client, returning `false` once the server
+    /// should shut down.
+    fn handle(&mut self, id: SocketAddr, line: &str) -> bool {
+        let mut words = line.splitn(2, ' ');
+
+        match (words.next(), words.next()) {
+            (Some("/join"), Some(room)) => self.join(id, room.trim()),
+            (Some("/leave"), _) => self.leave(id),
+            (Some("/members"), _) => self.members(id),
+            (Some("/rooms"), _) => self.list_rooms(id),
+            (Some("/watch"), _) => self.watch(id),
+            (Some("/shutdown"), _) => return false,
+            _ => self.say(id, line),
+        }
+
+        true
+    }
+
+    /// Handles an event, returning `false` once the server should shut
+    /// down.
+    fn dispatch(&mut self, event: Event) -> bool {
+        let running = match event {
+            Event::Connected(client) => {
+                self.connect(client);
+                true
+            }
+            Event::Line(id, line) => self.handle(id, &line),
+            Event::Disconnected(id) => {
+                self.disconnect(id);
+                true
+            }
+        };
+
+        self.announce_rooms();
+
+        running
+    }
+
+    fn shutdown(&mut self) {
+        report_failures(
+            self.pubsub
+                .pub_message(SERVER_CHANNEL, "* The server is shutting down. Bye!")
+                .expect("Failed to notify clients."),
+        );
+
+        for client in self.clients.values() {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+fn accept(listener: TcpListener, events: Sender<Event>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                println!("Error establishing connection: {}", error);
+                continue;
+            }
+        };
+
+        let id = stream.peer_addr().unwrap();
+        let reader = BufReader::new(stream.try_clone().expect("Failed to clone TCP Stream"));
+        let events = events.clone();
+
+        if events
+            .send(Event::Connected(ChatClient { id, stream }))
+            .is_err()
+        {
+            return;
+        }
+
+        thread::spawn(move || {
+            for line in reader.lines() {
+                match line {
+                    Ok(line) => {
+                        let _ = events.send(Event::Line(id, line));
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            let _ = events.send(Event::Disconnected(id));
+        });
+    }
+}
+
+/// Connects to the server and sends `script` one line at a time, each after
+/// its delay, returning everything the server sends back.
+fn scripted_client(
+    name: &'static str,
+    address: SocketAddr,
+    script: Vec<(u64, &'static str)>,
+) -> Vec<String> {
+    let mut stream = TcpStream::connect(address).expect("Failed to connect to server.");
+    let reader = BufReader::new(stream.try_clone().expect("Failed to clone TCP Stream"));
+
+    let printer = thread::spawn(move || {
+        let mut received = Vec::new();
+
+        for line in reader.lines() {
+            match line {
+                Ok(line) => {
+                    println!("{} received: {}", name, line);
+                    received.push(line);
+                }
+                Err(_) => break,
+            }
+        }
+
+        received
+    });
+
+    for (delay, line) in script {
+        thread::sleep(Duration::from_millis(delay));
+        writeln!(stream, "{}", line).expect("Failed to write to server.");
+    }
+
+    printer.join().unwrap()
+}
+
+/// Runs the server with two scripted clients until Alice shuts it down,
+/// returning what Alice and Bob received.
+fn run() -> (Vec<String>, Vec<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    println!("Chat server listening on {}", address);
+
+    let (events, received) = mpsc::channel();
+    thread::spawn(move || accept(listener, events));
+
+    let alice = thread::spawn(move || {
+        scripted_client(
+            "Alice",
+            address,
+            vec![
+                (0, "/watch"),
+                (0, "/join lobby"),
+                (200, "Hi Bob!"),
+                (450, "/rooms"),
+                (250, "/shutdown"),
+            ],
+        )
+    });
+
+    let bob = thread::spawn(move || {
+        scripted_client(
+            "Bob",
+            address,
+            vec![
+                (100, "/join lobby"),
+                (200, "/members"),
+                (100, "/leave"),
+                (100, "/join attic"),
+                (100, "/leave"),
+            ],
+        )
+    });
+
+    let mut server = ChatServer::new();
+
+    for event in received {
+        if !server.dispatch(event) {
+            break;
+        }
+    }
+
+    server.shutdown();
+
+    (alice.join().unwrap(), bob.join().unwrap())
+}
+
+fn main() {
+    run();
+}
+
+#[test]
+fn two_clients_chat() {
+    let (alice, bob) = run();
+    let received = |lines: &[String], line: &str| lines.iter().any(|received| received == line);
+    let announced = |lines: &[String], suffix: &str| {
+        lines
+            .iter()
+            .any(|line| line.starts_with("* ") && line.ends_with(suffix))
+    };
+
+    assert!(received(&alice, "* Watching rooms"));
+    assert!(received(&alice, "* You joined lobby"));
+    assert!(announced(&alice, " joined lobby"));
+    assert!(announced(&alice, " left lobby"));
+    assert!(received(&alice, "* Rooms: lobby (1)"));
+
+    // The attic was created and closed while Alice watched the rooms.
+    let rooms: Vec<&String> = alice
+        .iter()
+        .filter(|line| line.starts_with("* Active rooms: "))
+        .collect();
+    assert_eq!(
+        rooms,
+        vec![
+            "* Active rooms: lobby",
+            "* Active rooms: attic, lobby",
+            "* Active rooms: lobby",
+        ]
+    );
+
+    // Nobody is sent their own messages.
+    assert!(!alice.iter().any(|line| line.ends_with("Hi Bob!")));
+    assert!(bob.iter().any(|line| line.ends_with(": Hi Bob!")));
+
+    let members = bob
+        .iter()
+        .find(|line| line.starts_with("* Members: "))
+        .expect("Bob was sent the members");
+    assert_eq!(members.matches(", ").count(), 1);
+
+    assert!(received(&bob, "* You joined attic"));
+    assert!(!bob.iter().any(|line| line.starts_with("* Active rooms: ")));
+
+    for lines in [&alice, &bob].iter() {
+        assert_eq!(
+            lines.last().map(String::as_str),
+            Some("* The server is shutting down. Bye!")
+        );
+    }
+}
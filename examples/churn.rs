@@ -0,0 +1,67 @@
+use general_pub_sub::{Client, Message, PubSub};
+use std::convert::Infallible;
+use std::env;
+use std::time::Instant;
+
+#[derive(Clone, Copy)]
+struct ChurnClient {
+    id: usize,
+}
+
+impl Client<usize, u64> for ChurnClient {
+    type Error = Infallible;
+
+    fn get_id(&self) -> usize {
+        self.id
+    }
+
+    fn send(&mut self, _message: &Message<u64>) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// Connects and disconnects clients, each subscribed to a few `Channels`,
+/// against a `PubSub` holding many idle `Channels`, as a server with high
+/// client churn does.  Removing a client only visits its own subscriptions,
+/// so the time per disconnect should not grow with the number of `Channels`.
+///
+/// Usage: cargo run --release --example churn -- [channels] [clients]
+fn main() {
+    let mut args = env::args().skip(1);
+    let channels: usize = args.next().map_or(50_000, |arg| arg.parse().unwrap());
+    let clients: usize = args.next().map_or(10_000, |arg| arg.parse().unwrap());
+
+    let mut pubsub = PubSub::new();
+
+    for channel in 0..channels {
+        pubsub
+            .channel_handle(format!("room.{}", channel))
+            .expect("Failed to create channel.");
+    }
+
+    let started = Instant::now();
+
+    for id in 0..clients {
+        let client = ChurnClient { id };
+
+        pubsub.add_client(client).expect("Failed to add client.");
+
+        for room in 0..3 {
+            pubsub
+                .sub_client(client, format!("room.{}", (id * 7 + room) % channels))
+                .expect("Failed to subscribe.");
+        }
+
+        pubsub.remove_client(client);
+    }
+
+    let elapsed = started.elapsed();
+
+    println!(
+        "Churned {} clients across {} channels in {:?} ({:?} per client).",
+        clients,
+        channels,
+        elapsed,
+        elapsed / clients as u32,
+    );
+}
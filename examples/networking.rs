@@ -1,14 +1,31 @@
-use general_pub_sub::{Client, Message, PubSub};
+use general_pub_sub::{Client, Message, PubSub, PublishReceipt};
 use std::{
-    borrow::Borrow,
-    io::BufRead,
-    net::{TcpListener, TcpStream},
+    fmt,
+    io::{self, BufReader, Write},
+    net::SocketAddr,
 };
 use std::{
-    io::{BufReader, Write},
-    net::SocketAddr,
+    io::BufRead,
+    net::{TcpListener, TcpStream},
 };
 
+#[derive(Clone, Copy)]
+enum Notice {
+    Joined(SocketAddr),
+    Welcome,
+}
+
+impl fmt::Display for Notice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Notice::Joined(ip_addr) => {
+                write!(f, "A new client ({}) joined the pubsub server!", ip_addr)
+            }
+            Notice::Welcome => write!(f, "Welcome!"),
+        }
+    }
+}
+
 struct TcpClient {
     id: SocketAddr,
     stream: TcpStream,
@@ -29,21 +46,27 @@ impl TcpClient {
     }
 }
 
-impl Client<SocketAddr, &str> for TcpClient {
+impl Client<SocketAddr, Notice> for TcpClient {
+    type Error = io::Error;
+
     fn get_id(&self) -> SocketAddr {
         self.id
     }
 
-    fn send(&mut self, message: &Message<&str>) {
-        if let Result::Err(error) = self.stream.write(
+    fn send(&mut self, message: &Message<Notice>) -> io::Result<()> {
+        self.stream.write_all(
             format!(
                 "Client ({}) Received Message from Channel ({}): {}\n",
                 self.id, message.source, message.contents
             )
             .as_bytes(),
-        ) {
-            println!("Failed to write response to client: {}", error);
-        }
+        )
+    }
+}
+
+fn report_failures(receipt: PublishReceipt<SocketAddr, io::Error>) {
+    for (id, error) in receipt.failures {
+        println!("Failed to write response to client ({}): {}", id, error);
     }
 }
 
@@ -80,18 +103,31 @@ fn main() {
                 let ip_addr = stream.peer_addr().unwrap();
                 println!("New connection: {}", ip_addr);
                 let client = TcpClient::new(ip_addr, stream);
-                pubsub.add_client(client.clone());
+                pubsub
+                    .add_client(client.clone())
+                    .expect("Failed to add client.");
 
                 pubsub
                     .sub_client(client.clone(), channel)
                     .expect("Failed to subscribe to channel.");
 
-                // THIS IS NAUGHTY!  DON'T DO THIS IN REAL LIFE!
-                let message = &*Box::leak(
-                    format!("A new client ({}) pubsub server!", ip_addr).into_boxed_str(),
-                );
+                // Channel names built at runtime can be handed over as owned
+                // `Strings`.
+                let own_channel = format!("clients.{}", ip_addr);
+                pubsub
+                    .sub_client(client.clone(), own_channel.clone())
+                    .expect("Failed to subscribe to channel.");
 
-                pubsub.pub_message(channel, message);
+                report_failures(
+                    pubsub
+                        .pub_message(channel, Notice::Joined(ip_addr))
+                        .expect("Failed to publish to channel."),
+                );
+                report_failures(
+                    pubsub
+                        .pub_message(&own_channel, Notice::Welcome)
+                        .expect("Failed to publish to channel."),
+                );
             }
             Err(e) => {
                 println!("Error establishing connection: {}", e);
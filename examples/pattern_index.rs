@@ -0,0 +1,163 @@
+use general_pub_sub::{
+    ChannelMatcher, Client, CompiledPattern, Message, PatternError, PatternSyntax, PubSub,
+};
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::env;
+
+/// A small xorshift generator, so that every run with the same seed checks
+/// the same names.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 % bound as u64) as usize
+    }
+
+    fn pick<'t>(&mut self, items: &[&'t str]) -> &'t str {
+        items[self.next(items.len())]
+    }
+}
+
+#[derive(Clone, Copy)]
+struct CheckClient {
+    id: usize,
+}
+
+impl Client<usize, u64> for CheckClient {
+    type Error = Infallible;
+
+    fn get_id(&self) -> usize {
+        self.id
+    }
+
+    fn send(&mut self, _message: &Message<u64>) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// `PatternSyntax::Glob` without literal prefixes, so that every pattern is
+/// matched against every `Channel`, as a reference for the prefix index.
+#[derive(Clone, Copy)]
+struct Unindexed;
+
+impl ChannelMatcher for Unindexed {
+    type Compiled = CompiledPattern;
+
+    fn is_pattern(&self, channel: &str) -> bool {
+        PatternSyntax::Glob.is_pattern(channel)
+    }
+
+    fn compile(&self, pattern: &str) -> Result<CompiledPattern, PatternError> {
+        PatternSyntax::Glob.compile(pattern)
+    }
+
+    fn matches(&self, compiled: &CompiledPattern, channel: &str) -> bool {
+        PatternSyntax::Glob.matches(compiled, channel)
+    }
+
+    fn escape<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        PatternSyntax::Glob.escape(name)
+    }
+
+    fn unescape<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        PatternSyntax::Glob.unescape(name)
+    }
+
+    fn specificity(&self, pattern: &str) -> (usize, usize) {
+        PatternSyntax::Glob.specificity(pattern)
+    }
+}
+
+const SEGMENTS: &[&str] = &["a", "b", "ab", "ba", "orders", "order"];
+
+const WILDCARD_SEGMENTS: &[&str] = &["*", "?", "a*", "*b", "or?ers", "*.*", "a?"];
+
+fn name(rng: &mut Rng, wildcards: bool) -> String {
+    let segments: Vec<&str> = (0..1 + rng.next(4))
+        .map(|_| match wildcards && rng.next(3) == 0 {
+            true => rng.pick(WILDCARD_SEGMENTS),
+            false => rng.pick(SEGMENTS),
+        })
+        .collect();
+
+    segments.join(".")
+}
+
+/// Subscribes randomized patterns to a `PubSub` and to one matching every
+/// pattern against every `Channel`, checking that both reach the same
+/// subscribers, in the same order, for randomized `Channels`.
+///
+/// Usage: cargo run --example pattern_index -- [rounds] [seed]
+fn main() {
+    let mut args = env::args().skip(1);
+    let rounds: usize = args.next().map_or(10_000, |arg| arg.parse().unwrap());
+    let seed: u64 = args.next().map_or(0x5eed, |arg| arg.parse().unwrap());
+
+    let mut rng = Rng(seed.max(1));
+    let mut indexed = PubSub::new();
+    let mut reference = PubSub::with_matcher(Unindexed);
+
+    let clients: Vec<CheckClient> = (0..16).map(|id| CheckClient { id }).collect();
+
+    for client in &clients {
+        indexed.add_client(*client).expect("Failed to add client.");
+        reference
+            .add_client(*client)
+            .expect("Failed to add client.");
+    }
+
+    let mut checked = 0;
+
+    for round in 0..rounds {
+        let client = clients[rng.next(clients.len())];
+        let pattern = name(&mut rng, true);
+
+        match rng.next(4) {
+            0 | 1 => {
+                let _ = indexed.psub_client(client, pattern.clone());
+                let _ = reference.psub_client(client, pattern);
+            }
+            2 => {
+                let _ = indexed.punsub_client(client, &pattern);
+                let _ = reference.punsub_client(client, &pattern);
+            }
+            _ => {
+                indexed.gc();
+                reference.gc();
+            }
+        }
+
+        for _ in 0..4 {
+            let channel = name(&mut rng, false);
+
+            let (reference_view, indexed_view) = (reference.view(), indexed.view());
+            let expected = reference_view.matching_subscribers(&channel);
+            let actual = indexed_view.matching_subscribers(&channel);
+
+            if actual != expected {
+                panic!(
+                    "After {} rounds, {:?} reaches {:?} instead of {:?}.",
+                    round + 1,
+                    channel,
+                    actual,
+                    expected
+                );
+            }
+
+            checked += 1;
+        }
+    }
+
+    println!(
+        "Checked {} channels over {} rounds (seed {}) against {} patterns.",
+        checked,
+        rounds,
+        seed,
+        indexed.view().patterns().count(),
+    );
+}
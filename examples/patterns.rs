@@ -1,4 +1,5 @@
-use general_pub_sub::{Client, Message, PubSub};
+use general_pub_sub::{Client, Message, PubSub, Via};
+use std::convert::Infallible;
 
 #[derive(Clone, Copy)]
 struct BasicClient {
@@ -12,15 +13,27 @@ impl BasicClient {
 }
 
 impl Client<u32, &str> for BasicClient {
+    type Error = Infallible;
+
     fn get_id(&self) -> u32 {
         self.id
     }
 
-    fn send(&mut self, message: &Message<&str>) {
+    fn send(&mut self, message: &Message<&str>) -> Result<(), Infallible> {
+        let via = match message.via {
+            Via::Channel => "the channel itself".to_string(),
+            Via::Pattern(pattern) => format!("pattern {}", pattern),
+            Via::Firehose => "the firehose".to_string(),
+            Via::Direct => "a direct message".to_string(),
+            Via::Replay => "a replay".to_string(),
+        };
+
         println!(
-            "Client ({}) Received Message from Channel ({}): {}",
-            self.id, message.source, message.contents
+            "Client ({}) Received Message from Channel ({}) via {}: {}",
+            self.id, message.source, via, message.contents
         );
+
+        Ok(())
     }
 }
 
@@ -35,25 +48,37 @@ fn main() {
 
     let all_channels = "channel.*";
 
-    pubsub.add_client(client_one);
+    pubsub
+        .add_client(client_one)
+        .expect("Failed to add client.");
 
     pubsub
-        .sub_client(client_one, all_channels)
+        .psub_client(client_one, all_channels)
         .expect("This should not happen");
 
-    pubsub.pub_message(channel_a, "Hello from Channel A");
-    pubsub.pub_message(channel_b, "Hello from Channel B");
-    pubsub.pub_message(channel_c, "Hello from Channel C");
+    pubsub
+        .pub_message(channel_a, "Hello from Channel A")
+        .expect("This should not happen");
+    pubsub
+        .pub_message(channel_b, "Hello from Channel B")
+        .expect("This should not happen");
+    pubsub
+        .pub_message(channel_c, "Hello from Channel C")
+        .expect("This should not happen");
 
     pubsub
         .sub_client(client_one, channel_a)
         .expect("This should not happen");
 
-    pubsub.pub_message(channel_a, "Client 1 should only receive this once.");
+    pubsub
+        .pub_message(channel_a, "Client 1 should only receive this once.")
+        .expect("This should not happen");
 
     pubsub
-        .unsub_client(client_one, all_channels)
+        .punsub_client(client_one, all_channels)
         .expect("This should not happen");
 
-    pubsub.pub_message(channel_b, "Nobody should receive this message");
+    pubsub
+        .pub_message(channel_b, "Nobody should receive this message")
+        .expect("This should not happen");
 }
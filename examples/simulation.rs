@@ -0,0 +1,70 @@
+//! Simulates disconnected sessions expiring, without waiting for them
+//!
+//! The `PubSub` reads the time from a `ManualClock`, which only moves when
+//! advanced, so every run expires the same sessions at the same steps.
+
+use general_pub_sub::prelude::*;
+use general_pub_sub::{DropReason, ManualClock};
+use std::time::Duration;
+
+const GRACE: Duration = Duration::from_secs(30);
+const STEP: Duration = Duration::from_secs(10);
+
+fn main() {
+    let clock = ManualClock::new();
+
+    let mut pubsub: PubSub<RecordingClient<u32, String>, u32, String> = PubSub::new();
+    pubsub.set_clock(clock.clone());
+    pubsub.set_detached_buffer_capacity(8);
+
+    let sessions: Vec<_> = (1..=3).map(RecordingClient::new).collect();
+
+    for session in &sessions {
+        pubsub.add_client(session.clone()).unwrap();
+        pubsub.sub_client(session.clone(), "ticks").unwrap();
+    }
+
+    // Session 1 drops at once, session 2 one step later, and session 3
+    // stays connected.
+    pubsub.detach_client(&1, GRACE).unwrap();
+
+    for step in 1..=4 {
+        clock.advance(STEP);
+
+        if step == 1 {
+            pubsub.detach_client(&2, GRACE).unwrap();
+        }
+
+        pubsub
+            .pub_message("ticks", format!("tick {}", step))
+            .unwrap();
+
+        let expired = pubsub.sweep_detached();
+        println!(
+            "after {:?}: {} expired, {:?} still detached",
+            STEP * step,
+            expired,
+            pubsub.pending_identifiers()
+        );
+
+        match step {
+            1 | 2 => assert_eq!(expired, 0),
+            // The grace period of session 1 ends 30 seconds in, with the
+            // ticks buffered for it.
+            3 => assert_eq!(pubsub.pending_identifiers(), vec![&2]),
+            _ => assert!(pubsub.pending_identifiers().is_empty()),
+        }
+    }
+
+    // Ticks 1 to 3 were buffered for session 1, and 1 to 4 for session 2.
+    assert_eq!(pubsub.metrics().drops[&DropReason::DetachedRemoved], 7);
+    assert_eq!(sessions[2].contents().len(), 4);
+    assert!(sessions[0].contents().is_empty());
+    assert!(sessions[1].contents().is_empty());
+    assert!(pubsub.check_invariants().is_ok());
+}
+
+#[test]
+fn sessions_expire_after_their_grace_period() {
+    main();
+}
@@ -0,0 +1,204 @@
+use general_pub_sub::{Client, Message, PubSub, PubSubError};
+use std::convert::Infallible;
+use std::env;
+
+/// A small xorshift generator, so that every run with the same seed performs
+/// the same operations.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 % bound as u64) as usize
+    }
+
+    fn pick<'t>(&mut self, items: &[&'t str]) -> &'t str {
+        items[self.next(items.len())]
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SoakClient {
+    id: usize,
+}
+
+impl Client<usize, u64> for SoakClient {
+    type Error = Infallible;
+
+    fn get_id(&self) -> usize {
+        self.id
+    }
+
+    fn send(&mut self, _message: &Message<u64>) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+const CHANNELS: &[&str] = &[
+    "orders.created",
+    "orders.updated",
+    "orders.eu.created",
+    "users.signup",
+    "users.login",
+    "metrics.cpu",
+];
+
+const PATTERNS: &[&str] = &[
+    "orders.*",
+    "orders.*.created",
+    "*.created",
+    "users.?ogin",
+    "*",
+    "metrics.*",
+];
+
+const INVALID_CHANNELS: &[&str] = &["", " orders", "$sys.rooms"];
+
+/// Runs a randomized population of clients against a `PubSub`, checking its
+/// invariants after every operation, and returns the `PubSub` along with the
+/// number of messages published.
+fn soak(iterations: usize, seed: u64) -> (PubSub<SoakClient, usize, u64>, u64) {
+    let mut rng = Rng(seed.max(1));
+    let mut pubsub = PubSub::new();
+
+    let mut connected = [false; 64];
+    let mut published: u64 = 0;
+
+    for iteration in 0..iterations {
+        let client = SoakClient {
+            id: rng.next(connected.len()),
+        };
+
+        match rng.next(10) {
+            0 => {
+                pubsub.replace_client(client);
+                connected[client.id] = true;
+            }
+            1 => {
+                pubsub.remove_client(client);
+                connected[client.id] = false;
+            }
+            2 | 3 if connected[client.id] => {
+                let result = match rng.next(2) {
+                    0 => pubsub.sub_client(client, rng.pick(CHANNELS)),
+                    _ => pubsub.psub_client(client, rng.pick(PATTERNS)),
+                };
+
+                match result {
+                    Ok(()) | Err(PubSubError::ClientAlreadySubscribedError { .. }) => {}
+                    Err(error) => panic!("Unexpected error subscribing: {}", error),
+                }
+            }
+            4 if connected[client.id] => {
+                let result = match rng.next(2) {
+                    0 => pubsub.unsub_client(client, rng.pick(CHANNELS)),
+                    _ => pubsub.punsub_client(client, rng.pick(PATTERNS)),
+                };
+
+                match result {
+                    Ok(())
+                    | Err(PubSubError::ClientNotSubscribedError { .. })
+                    | Err(PubSubError::ChannelDoesNotExistError { .. }) => {}
+                    Err(error) => panic!("Unexpected error unsubscribing: {}", error),
+                }
+            }
+            5 => {
+                let channel = rng.pick(INVALID_CHANNELS);
+
+                match pubsub.sub_client(client, channel) {
+                    Err(PubSubError::InvalidChannelNameError { .. }) => {}
+                    result => panic!("Subscribing to {:?} gave {:?}", channel, result),
+                }
+            }
+            6 if connected[client.id] && rng.next(10) == 0 => {
+                pubsub
+                    .unsub_all(&client.id)
+                    .expect("Failed to unsubscribe a connected client.");
+            }
+            6 if rng.next(50) == 0 => {
+                pubsub.gc();
+            }
+            _ => {
+                pubsub
+                    .pub_message(rng.pick(CHANNELS), published)
+                    .expect("Failed to publish.");
+                published += 1;
+            }
+        }
+
+        if let Err(violations) = pubsub.check_invariants() {
+            panic!(
+                "Invariants violated after {} iterations: {:?}",
+                iteration + 1,
+                violations
+            );
+        }
+    }
+
+    (pubsub, published)
+}
+
+/// Usage: cargo run --example soak -- [iterations] [seed]
+fn main() {
+    let mut args = env::args().skip(1);
+    let iterations: usize = args.next().map_or(100_000, |arg| arg.parse().unwrap());
+    let seed: u64 = args.next().map_or(0x5eed, |arg| arg.parse().unwrap());
+
+    let (mut pubsub, published) = soak(iterations, seed);
+
+    println!(
+        "Soaked {} iterations (seed {}), publishing {} messages: {} clients, {} channels, {} patterns.",
+        iterations,
+        seed,
+        published,
+        pubsub.client_count(),
+        pubsub.channel_count(),
+        pubsub.pattern_count(),
+    );
+
+    let removed = pubsub.clear();
+    pubsub.shrink_to_fit();
+
+    assert!(pubsub.is_empty());
+    assert!(pubsub.check_invariants().is_ok());
+    println!("Cleared {} clients.", removed.len());
+}
+
+#[test]
+fn the_same_seed_soaks_the_same_way() {
+    let topology = |pubsub: &PubSub<SoakClient, usize, u64>| {
+        let view = pubsub.view();
+        let mut patterns: Vec<_> = view.patterns().collect::<Vec<_>>();
+        patterns.sort_unstable();
+
+        let channels: Vec<_> = view
+            .channels()
+            .map(|(channel, _)| {
+                (
+                    channel,
+                    view.subscribers(channel).copied().collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+
+        format!(
+            "{:?} {:?} {}",
+            channels,
+            patterns,
+            pubsub.stats().deliveries
+        )
+    };
+
+    let (first, published) = soak(5_000, 42);
+    let (second, republished) = soak(5_000, 42);
+    let (other, _) = soak(5_000, 43);
+
+    assert!(published > 0);
+    assert!(first.stats().deliveries > 0);
+    assert_eq!(published, republished);
+    assert_eq!(topology(&first), topology(&second));
+    assert_ne!(topology(&first), topology(&other));
+}
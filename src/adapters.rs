@@ -0,0 +1,345 @@
+//! `Clients` adapting other ways of receiving `Messages`
+
+use crate::{Client, Message, RecordedMessage, UniqueIdentifier};
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::sync::mpsc::{SendError, Sender};
+
+/// A `Client` which forwards every `Message` it receives to an `mpsc`
+/// channel, such as to hand it to a worker thread
+///
+/// `Messages` are forwarded as `RecordedMessages`, which own the name of
+/// their `Channel`.  Once the receiver is dropped, sending fails with the
+/// `SendError` holding the `Message`.
+///
+/// # Examples
+///
+/// ```
+/// use general_pub_sub::adapters::ChannelSenderClient;
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::RecordedMessage;
+/// use std::sync::mpsc;
+/// use std::thread;
+///
+/// let (sender, receiver) = mpsc::channel::<RecordedMessage<&str>>();
+/// let worker = thread::spawn(move || {
+///     receiver
+///         .iter()
+///         .map(|message| format!("{} {}", message.channel, message.contents))
+///         .collect::<Vec<_>>()
+/// });
+///
+/// let client = ChannelSenderClient::new(1, sender);
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(client.clone()).unwrap();
+/// pubsub.psub_client(client, "jobs.*").unwrap();
+///
+/// pubsub.pub_message("jobs.resize", "cat.png").unwrap();
+/// pubsub.pub_message("jobs.crop", "dog.png").unwrap();
+///
+/// // Dropping the `PubSub` drops the last `Sender`, which ends the worker.
+/// drop(pubsub);
+///
+/// assert_eq!(
+///     worker.join().unwrap(),
+///     vec!["jobs.resize cat.png", "jobs.crop dog.png"]
+/// );
+/// ```
+///
+/// A `Client` whose receiver is gone fails, without keeping the `Message`
+/// from the other subscribers:
+///
+/// ```
+/// use general_pub_sub::adapters::ChannelSenderClient;
+/// use general_pub_sub::prelude::*;
+/// use std::sync::mpsc;
+///
+/// let (sender, receiver) = mpsc::channel();
+/// let (other_sender, other_receiver) = mpsc::channel();
+///
+/// let mut pubsub: PubSub<_, u32, &str> = PubSub::new();
+/// pubsub.add_client(ChannelSenderClient::new(1, sender)).unwrap();
+/// pubsub.add_client(ChannelSenderClient::new(2, other_sender)).unwrap();
+/// pubsub.sub_id(&1, "news").unwrap();
+/// pubsub.sub_id(&2, "news").unwrap();
+///
+/// drop(receiver);
+///
+/// let receipt = pubsub.pub_message("news", "extra").unwrap();
+///
+/// let (failed, error) = &receipt.failures[0];
+///
+/// assert_eq!(receipt.delivered, 1);
+/// assert_eq!(*failed, 1);
+/// assert_eq!(error.0.contents, "extra");
+/// assert_eq!(other_receiver.recv().unwrap().contents, "extra");
+/// ```
+#[derive(Clone, Debug)]
+pub struct ChannelSenderClient<TIdentifier, TMessage> {
+    id: TIdentifier,
+    sender: Sender<RecordedMessage<TMessage>>,
+}
+
+impl<TIdentifier, TMessage> ChannelSenderClient<TIdentifier, TMessage> {
+    pub fn new(id: TIdentifier, sender: Sender<RecordedMessage<TMessage>>) -> Self {
+        ChannelSenderClient { id, sender }
+    }
+}
+
+impl<TIdentifier, TMessage> Client<TIdentifier, TMessage>
+    for ChannelSenderClient<TIdentifier, TMessage>
+where
+    TIdentifier: UniqueIdentifier + Clone,
+    TMessage: Clone,
+{
+    type Error = SendError<RecordedMessage<TMessage>>;
+
+    fn get_id(&self) -> TIdentifier {
+        self.id.clone()
+    }
+
+    fn send(&mut self, message: &Message<TMessage>) -> Result<(), Self::Error> {
+        self.sender.send(RecordedMessage {
+            channel: message.source.to_string(),
+            contents: message.contents.clone(),
+            sequence: message.sequence,
+        })
+    }
+}
+
+/// A `Client` which calls a closure with every `Message` it receives
+///
+/// Handy for tests and quick experiments, where defining a `Client` would
+/// be more code than handling the `Messages`.  To keep the `Messages` for
+/// assertions, `CollectingClient` or `RecordingClient` is simpler still.
+///
+/// # Examples
+///
+/// ```
+/// use general_pub_sub::adapters::FnClient;
+/// use general_pub_sub::prelude::*;
+///
+/// let mut log = Vec::new();
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub
+///     .add_client(FnClient::new(1, |message: &Message<&str>| {
+///         log.push(format!("{}: {}", message.source, message.contents))
+///     }))
+///     .unwrap();
+/// pubsub.sub_id(&1, "orders").unwrap();
+///
+/// pubsub.pub_message("orders", "42").unwrap();
+///
+/// // The `PubSub` borrows `log` through the closure until it is dropped.
+/// drop(pubsub);
+/// assert_eq!(log, vec!["orders: 42"]);
+/// ```
+#[derive(Clone)]
+pub struct FnClient<TIdentifier, F> {
+    id: TIdentifier,
+    callback: F,
+}
+
+impl<TIdentifier, F> FnClient<TIdentifier, F> {
+    pub fn new<TMessage>(id: TIdentifier, callback: F) -> Self
+    where
+        F: FnMut(&Message<TMessage>),
+    {
+        FnClient { id, callback }
+    }
+}
+
+impl<TIdentifier, TMessage, F> Client<TIdentifier, TMessage> for FnClient<TIdentifier, F>
+where
+    TIdentifier: UniqueIdentifier + Clone,
+    F: FnMut(&Message<TMessage>),
+{
+    type Error = Infallible;
+
+    fn get_id(&self) -> TIdentifier {
+        self.id.clone()
+    }
+
+    fn send(&mut self, message: &Message<TMessage>) -> Result<(), Infallible> {
+        (self.callback)(message);
+
+        Ok(())
+    }
+}
+
+/// A `Client` which collects every `Message` it receives into a `Vec` of
+/// its own
+///
+/// Unlike `RecordingClient`, clones do not share what they collect: the
+/// `Messages` are read from the `Client` the `PubSub` hands back, such as
+/// by `PubSub::clear`.
+///
+/// # Examples
+///
+/// ```
+/// use general_pub_sub::adapters::CollectingClient;
+/// use general_pub_sub::prelude::*;
+///
+/// let mut pubsub: PubSub<CollectingClient<u32, u32>, _, _> = PubSub::new();
+/// pubsub.add_client(CollectingClient::new(1)).unwrap();
+/// pubsub.sub_id(&1, "orders").unwrap();
+///
+/// pubsub.pub_message("orders", 42u32).unwrap();
+/// pubsub.pub_message("orders", 43u32).unwrap();
+///
+/// let client = pubsub.clear().pop().unwrap();
+/// assert_eq!(client.contents(), vec![42, 43]);
+/// assert_eq!(client.messages()[0].channel, "orders");
+/// ```
+#[derive(Clone, Debug)]
+pub struct CollectingClient<TIdentifier, TMessage> {
+    id: TIdentifier,
+    messages: Vec<RecordedMessage<TMessage>>,
+}
+
+impl<TIdentifier, TMessage> CollectingClient<TIdentifier, TMessage> {
+    pub fn new(id: TIdentifier) -> Self {
+        CollectingClient {
+            id,
+            messages: Vec::new(),
+        }
+    }
+
+    /// The `Messages` collected, in the order they were received.
+    pub fn messages(&self) -> &[RecordedMessage<TMessage>] {
+        &self.messages
+    }
+
+    /// Takes the `Messages` collected.
+    pub fn into_messages(self) -> Vec<RecordedMessage<TMessage>> {
+        self.messages
+    }
+}
+
+impl<TIdentifier, TMessage: Clone> CollectingClient<TIdentifier, TMessage> {
+    /// The contents of the `Messages` collected, in the order they were
+    /// received.
+    pub fn contents(&self) -> Vec<TMessage> {
+        self.messages
+            .iter()
+            .map(|message| message.contents.clone())
+            .collect()
+    }
+}
+
+impl<TIdentifier, TMessage> Client<TIdentifier, TMessage>
+    for CollectingClient<TIdentifier, TMessage>
+where
+    TIdentifier: UniqueIdentifier + Clone,
+    TMessage: Clone,
+{
+    type Error = Infallible;
+
+    fn get_id(&self) -> TIdentifier {
+        self.id.clone()
+    }
+
+    fn send(&mut self, message: &Message<TMessage>) -> Result<(), Infallible> {
+        self.messages.push(RecordedMessage {
+            channel: message.source.to_string(),
+            contents: message.contents.clone(),
+            sequence: message.sequence,
+        });
+
+        Ok(())
+    }
+}
+
+/// A `Client` standing for one connection shared by many logical
+/// subscribers, each known by a tag
+///
+/// The logical subscribers subscribe through `PubSub::sub_tagged`, each with
+/// its own tag, patterns included.  Every `Message` reaching the connection
+/// is written once, with the tags of all of its matching subscriptions, for
+/// the other end to hand to each logical subscriber.  Unsubscribing with
+/// `PubSub::unsub_tagged`, or removing the `Client`, drops their tags.
+///
+/// # Examples
+///
+/// ```
+/// use general_pub_sub::adapters::MultiplexedClient;
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::PatternSubscriptions;
+/// use std::io::Write;
+/// use std::sync::{Arc, Mutex};
+///
+/// // Stands for the socket of the connection.
+/// let socket = Arc::new(Mutex::new(Vec::new()));
+/// let connection = {
+///     let socket = socket.clone();
+///
+///     MultiplexedClient::new("gateway", move |tags: &[u32], message: &Message<&str>| {
+///         writeln!(socket.lock().unwrap(), "{:?} {}", tags, message.contents)
+///     })
+/// };
+/// let written = || String::from_utf8(socket.lock().unwrap().clone()).unwrap();
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.set_pattern_subscriptions(PatternSubscriptions::Detected);
+/// pubsub.add_client(connection.clone()).unwrap();
+/// pubsub.sub_tagged(connection.clone(), "prices.btc", 1).unwrap();
+/// pubsub.sub_tagged(connection.clone(), "prices.*", 2).unwrap();
+///
+/// // Both subscriptions match, yet the connection is written to once.
+/// pubsub.pub_message("prices.btc", "42000").unwrap();
+/// pubsub.pub_message("prices.eth", "3000").unwrap();
+/// assert_eq!(written(), "[1, 2] 42000\n[2] 3000\n");
+///
+/// pubsub.unsub_tagged(connection.clone(), "prices.*", 2).unwrap();
+/// pubsub.pub_message("prices.btc", "42001").unwrap();
+/// assert!(written().ends_with("[1] 42001\n"));
+/// ```
+pub struct MultiplexedClient<TIdentifier, TError, F> {
+    id: TIdentifier,
+    write: F,
+    error: PhantomData<fn() -> TError>,
+}
+
+impl<TIdentifier, TError, F> MultiplexedClient<TIdentifier, TError, F> {
+    /// Creates the `Client` of a connection, which `write` sends each
+    /// `Message` over with the tags it is for.
+    pub fn new<TMessage>(id: TIdentifier, write: F) -> Self
+    where
+        F: FnMut(&[u32], &Message<TMessage>) -> Result<(), TError>,
+    {
+        MultiplexedClient {
+            id,
+            write,
+            error: PhantomData,
+        }
+    }
+}
+
+impl<TIdentifier: Clone, TError, F: Clone> Clone for MultiplexedClient<TIdentifier, TError, F> {
+    fn clone(&self) -> Self {
+        MultiplexedClient {
+            id: self.id.clone(),
+            write: self.write.clone(),
+            error: PhantomData,
+        }
+    }
+}
+
+impl<TIdentifier, TMessage, TError, F> Client<TIdentifier, TMessage>
+    for MultiplexedClient<TIdentifier, TError, F>
+where
+    TIdentifier: UniqueIdentifier + Clone,
+    F: FnMut(&[u32], &Message<TMessage>) -> Result<(), TError>,
+{
+    type Error = TError;
+
+    fn get_id(&self) -> TIdentifier {
+        self.id.clone()
+    }
+
+    fn send(&mut self, message: &Message<TMessage>) -> Result<(), TError> {
+        (self.write)(message.tags, message)
+    }
+}
@@ -0,0 +1,566 @@
+use crate::detached::BufferedMessage;
+use crate::eviction::Failures;
+use crate::routing::StoredVia;
+use crate::{
+    ChannelName, Client, ClientGeneration, EvictionPolicy, Message, Metrics, PubSub, PubSubError,
+    PublishReceipt, UniqueIdentifier,
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::{poll_fn, Future};
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+
+/// A `Client` whose sends are awaited, such as writes to an async socket
+///
+/// The counterpart of `Client` for an `AsyncPubSub`.  Implementations can
+/// write `async fn send`, as long as the future is `Send`.
+///
+/// Requires the `async` feature.
+pub trait AsyncClient<TIdentifier: UniqueIdentifier, TMessage> {
+    /// Why sending a `Message` failed.
+    type Error;
+
+    /// Gets the `ID` of the `Client`. Must be unique.
+    fn get_id(&self) -> TIdentifier;
+
+    /// Sends a `Message` to a `Client`.
+    fn send(
+        &mut self,
+        message: &Message<TMessage>,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+type Outbox<TIdentifier, TMessage> = Arc<Mutex<Vec<(TIdentifier, BufferedMessage<TMessage>)>>>;
+
+/// Stands in for an `AsyncClient` in the `PubSub` routing for it, queueing
+/// the `Messages` delivered to it
+#[derive(Clone)]
+struct Queued<TIdentifier, TMessage> {
+    id: TIdentifier,
+    outbox: Outbox<TIdentifier, TMessage>,
+}
+
+impl<TIdentifier, TMessage> Client<TIdentifier, TMessage> for Queued<TIdentifier, TMessage>
+where
+    TIdentifier: UniqueIdentifier + Clone,
+    TMessage: Clone,
+{
+    type Error = Infallible;
+
+    fn get_id(&self) -> TIdentifier {
+        self.id.clone()
+    }
+
+    fn send(&mut self, message: &Message<TMessage>) -> Result<(), Infallible> {
+        self.outbox.lock().unwrap().push((
+            self.id.clone(),
+            BufferedMessage {
+                channel: message.source.to_string(),
+                contents: message.contents.clone(),
+                sequence: message.sequence,
+                via: StoredVia::new(message.via),
+                tags: message.tags.to_vec(),
+            },
+        ));
+
+        Ok(())
+    }
+}
+
+/// A `PubSub` delivering to `AsyncClients`, awaiting each send
+///
+/// `Messages` are routed exactly like by a `PubSub`, then sent to their
+/// recipients one after the other, in the same order.  `Clients` are
+/// referred to by identifier, since async `Clients` such as sockets are
+/// rarely `Clone`.
+///
+/// A failed send is reported in the `PublishReceipt`, recorded as a
+/// `DropReason::SendFailed` drop, and counted towards eviction like a
+/// failed `Client::send`.  See `set_eviction_policy`.
+///
+/// Requires the `async` feature.
+///
+/// # Examples
+///
+/// ```
+/// use general_pub_sub::{AsyncClient, AsyncPubSub, Message};
+/// use std::convert::Infallible;
+/// use std::future::Future;
+/// use std::pin::pin;
+/// use std::sync::{Arc, Mutex};
+/// use std::task::{Context, Poll, Waker};
+///
+/// struct Inbox {
+///     id: u32,
+///     received: Arc<Mutex<Vec<String>>>,
+/// }
+///
+/// impl AsyncClient<u32, &'static str> for Inbox {
+///     type Error = Infallible;
+///
+///     fn get_id(&self) -> u32 {
+///         self.id
+///     }
+///
+///     async fn send(&mut self, message: &Message<'_, &'static str>) -> Result<(), Infallible> {
+///         let line = format!("{} {}", message.source, message.contents);
+///         self.received.lock().unwrap().push(line);
+///         Ok(())
+///     }
+/// }
+///
+/// // Any executor will do, such as `tokio`'s.
+/// fn block_on<F: Future>(future: F) -> F::Output {
+///     let mut future = pin!(future);
+///     let mut context = Context::from_waker(Waker::noop());
+///
+///     loop {
+///         if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+///             return output;
+///         }
+///     }
+/// }
+///
+/// let received = Arc::new(Mutex::new(Vec::new()));
+///
+/// let mut pubsub = AsyncPubSub::new();
+/// pubsub.add_client(Inbox { id: 1, received: received.clone() }).unwrap();
+/// pubsub.psub_id(&1, "orders.*").unwrap();
+///
+/// let receipt = block_on(pubsub.pub_message("orders.created", "42")).unwrap();
+///
+/// assert_eq!(receipt.delivered, 1);
+/// assert_eq!(*received.lock().unwrap(), vec!["orders.created 42"]);
+/// ```
+pub struct AsyncPubSub<TClient, TIdentifier, TMessage>
+where
+    TClient: AsyncClient<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier + Clone,
+    TMessage: Clone,
+{
+    routing: PubSub<Queued<TIdentifier, TMessage>, TIdentifier, TMessage>,
+    clients: HashMap<TIdentifier, TClient>,
+    /// The failed sends counted towards eviction.  Those of `routing` only
+    /// see the `Queued` stand-ins, which never fail.
+    failures: Failures<TIdentifier>,
+    outbox: Outbox<TIdentifier, TMessage>,
+}
+
+impl<TClient, TIdentifier, TMessage> AsyncPubSub<TClient, TIdentifier, TMessage>
+where
+    TClient: AsyncClient<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier + Clone,
+    TMessage: Clone,
+{
+    /// Creates a new `AsyncPubSub`
+    pub fn new() -> AsyncPubSub<TClient, TIdentifier, TMessage> {
+        AsyncPubSub {
+            routing: PubSub::new(),
+            clients: HashMap::new(),
+            failures: Failures::new(),
+            outbox: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    fn queued(&self, identifier: &TIdentifier) -> Queued<TIdentifier, TMessage> {
+        Queued {
+            id: identifier.clone(),
+            outbox: self.outbox.clone(),
+        }
+    }
+
+    /// Adds a `Client`, as `PubSub::add_client` does.
+    pub fn add_client(&mut self, client: TClient) -> Result<ClientGeneration, PubSubError> {
+        let identifier = client.get_id();
+        let generation = self.routing.add_client(self.queued(&identifier))?;
+
+        self.clients.insert(identifier, client);
+
+        Ok(generation)
+    }
+
+    /// Removes a `Client` and its subscriptions, returning the `Client`.
+    pub fn remove_client(&mut self, identifier: &TIdentifier) -> Option<TClient> {
+        let _ = self.routing.remove_client_by_id(identifier);
+        self.failures.forget(identifier);
+
+        self.clients.remove(identifier)
+    }
+
+    /// Subscribes a `Client` to a `Channel`, as `PubSub::sub_id` does.
+    pub fn sub_id<TChannel: Into<ChannelName>>(
+        &mut self,
+        identifier: &TIdentifier,
+        channel: TChannel,
+    ) -> Result<(), PubSubError> {
+        self.routing.sub_id(identifier, channel)
+    }
+
+    /// Subscribes a `Client` to a pattern, as `PubSub::psub_client` does.
+    pub fn psub_id<TChannel: Into<ChannelName>>(
+        &mut self,
+        identifier: &TIdentifier,
+        pattern: TChannel,
+    ) -> Result<(), PubSubError> {
+        if !self.clients.contains_key(identifier) {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        let queued = self.queued(identifier);
+        self.routing.psub_client(queued, pattern)
+    }
+
+    /// Unsubscribes a `Client` from a `Channel`, as `PubSub::unsub_id` does.
+    pub fn unsub_id(&mut self, identifier: &TIdentifier, channel: &str) -> Result<(), PubSubError> {
+        self.routing.unsub_id(identifier, channel)
+    }
+
+    /// Unsubscribes a `Client` from a pattern, as `PubSub::punsub_client`
+    /// does.
+    pub fn punsub_id(
+        &mut self,
+        identifier: &TIdentifier,
+        pattern: &str,
+    ) -> Result<(), PubSubError> {
+        let queued = self.queued(identifier);
+        self.routing.punsub_client(queued, pattern)
+    }
+
+    /// Sets when a `Client` whose sends keep failing is removed, as
+    /// `PubSub::set_eviction_policy` does.
+    ///
+    /// Sends are counted once they complete, so a `Client` is evicted at
+    /// the end of the publish its last failed send belongs to.
+    ///
+    /// ```
+    /// use general_pub_sub::{AsyncClient, AsyncPubSub, DropReason, EvictionPolicy, Message};
+    /// use std::future::Future;
+    /// use std::pin::pin;
+    /// use std::task::{Context, Poll, Waker};
+    ///
+    /// struct Socket {
+    ///     id: u32,
+    ///     closed: bool,
+    /// }
+    ///
+    /// impl AsyncClient<u32, &'static str> for Socket {
+    ///     type Error = &'static str;
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         self.id
+    ///     }
+    ///
+    ///     async fn send(&mut self, _: &Message<'_, &'static str>) -> Result<(), &'static str> {
+    ///         match self.closed {
+    ///             true => Err("closed"),
+    ///             false => Ok(()),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn block_on<F: Future>(future: F) -> F::Output {
+    ///     let mut future = pin!(future);
+    ///     let mut context = Context::from_waker(Waker::noop());
+    ///
+    ///     loop {
+    ///         if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+    ///             return output;
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut pubsub = AsyncPubSub::new();
+    /// pubsub.set_eviction_policy(EvictionPolicy::AfterConsecutiveFailures(2));
+    /// pubsub.add_client(Socket { id: 1, closed: false }).unwrap();
+    /// pubsub.add_client(Socket { id: 2, closed: true }).unwrap();
+    /// pubsub.sub_id(&1, "news").unwrap();
+    /// pubsub.sub_id(&2, "news").unwrap();
+    ///
+    /// let receipt = block_on(pubsub.pub_message("news", "first")).unwrap();
+    /// assert_eq!(receipt.delivered, 1);
+    /// assert_eq!(receipt.failures, vec![(2, "closed")]);
+    /// assert_eq!(pubsub.client_count(), 2);
+    ///
+    /// block_on(pubsub.pub_message("news", "second")).unwrap();
+    /// assert_eq!(pubsub.client_count(), 1);
+    /// assert!(pubsub.remove_client(&2).is_none());
+    /// assert_eq!(pubsub.metrics().drops.get(&DropReason::SendFailed), Some(&2));
+    ///
+    /// let receipt = block_on(pubsub.pub_message("news", "third")).unwrap();
+    /// assert_eq!(receipt.delivered, 1);
+    /// assert!(receipt.failures.is_empty());
+    /// ```
+    pub fn set_eviction_policy(&mut self, policy: EvictionPolicy) {
+        self.failures.set_policy(policy);
+    }
+
+    /// Sets the callback notified of each `Client` evicted by the
+    /// `EvictionPolicy`, as `PubSub::set_eviction_callback` does.
+    pub fn set_eviction_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&TIdentifier) + Send + Sync + 'static,
+    {
+        self.routing.set_eviction_callback(callback);
+    }
+
+    /// Gets the number of `Clients`.
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Gets the `Metrics` of the routing `PubSub`, as `PubSub::metrics`
+    /// does, including the `Messages` whose sends failed.
+    pub fn metrics(&self) -> Metrics {
+        self.routing.metrics()
+    }
+
+    /// Publishes a `Message` to all `Clients` subscribed to a `Channel`,
+    /// awaiting each send in turn.
+    ///
+    /// Results in a `PubSubError` as `PubSub::pub_message` does.  A failed
+    /// send is listed in the `PublishReceipt`, and does not keep the
+    /// `Message` from the other subscribers.
+    pub async fn pub_message<TInputMessage: Into<TMessage>>(
+        &mut self,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.pub_message_concurrent(channel, msg, 1).await
+    }
+
+    /// Publishes a `Message` to all `Clients` subscribed to a `Channel`,
+    /// with up to `max_in_flight` sends awaited at once.  A `max_in_flight`
+    /// of 0 acts as 1.
+    ///
+    /// Each subscriber is sent the `Message` once, even when several of its
+    /// subscriptions match the `Channel`.  Sends are started in the order
+    /// `pub_message` awaits them, but may complete in any order.  Every
+    /// send has completed by the time the publish does, so a `Client`
+    /// always receives the `Messages` of two publishes in the order they
+    /// were published.
+    ///
+    /// Results in a `PubSubError` as `PubSub::pub_message` does, and lists
+    /// failed sends in the `PublishReceipt` in the order they completed.
+    ///
+    /// # Examples
+    ///
+    /// Publishing to four slow `Clients`, two at a time:
+    ///
+    /// ```
+    /// use general_pub_sub::{AsyncClient, AsyncPubSub, Message};
+    /// use std::convert::Infallible;
+    /// use std::future::Future;
+    /// use std::pin::{pin, Pin};
+    /// use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    /// use std::sync::{Arc, Mutex};
+    /// use std::task::{Context, Poll, Waker};
+    ///
+    /// #[derive(Default)]
+    /// struct Timeline {
+    ///     clock: AtomicU64,
+    ///     in_flight: AtomicUsize,
+    ///     peak: AtomicUsize,
+    ///     // The client, the message, and when its send started and ended.
+    ///     sends: Mutex<Vec<(u32, &'static str, u64, u64)>>,
+    /// }
+    ///
+    /// struct Slow {
+    ///     id: u32,
+    ///     timeline: Arc<Timeline>,
+    /// }
+    ///
+    /// // Gives the other sends a turn, like waiting on a socket would.
+    /// struct YieldNow(bool);
+    ///
+    /// impl Future for YieldNow {
+    ///     type Output = ();
+    ///
+    ///     fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<()> {
+    ///         if self.0 {
+    ///             return Poll::Ready(());
+    ///         }
+    ///
+    ///         self.0 = true;
+    ///         context.waker().wake_by_ref();
+    ///         Poll::Pending
+    ///     }
+    /// }
+    ///
+    /// impl AsyncClient<u32, &'static str> for Slow {
+    ///     type Error = Infallible;
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         self.id
+    ///     }
+    ///
+    ///     async fn send(&mut self, message: &Message<'_, &'static str>) -> Result<(), Infallible> {
+    ///         let timeline = &self.timeline;
+    ///         let start = timeline.clock.fetch_add(1, Ordering::SeqCst);
+    ///         let in_flight = timeline.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+    ///         timeline.peak.fetch_max(in_flight, Ordering::SeqCst);
+    ///
+    ///         YieldNow(false).await;
+    ///
+    ///         timeline.in_flight.fetch_sub(1, Ordering::SeqCst);
+    ///         let end = timeline.clock.fetch_add(1, Ordering::SeqCst);
+    ///         let send = (self.id, message.contents, start, end);
+    ///         timeline.sends.lock().unwrap().push(send);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// fn block_on<F: Future>(future: F) -> F::Output {
+    ///     let mut future = pin!(future);
+    ///     let mut context = Context::from_waker(Waker::noop());
+    ///
+    ///     loop {
+    ///         if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+    ///             return output;
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let timeline = Arc::new(Timeline::default());
+    ///
+    /// let mut pubsub = AsyncPubSub::new();
+    /// for id in 0..4 {
+    ///     pubsub.add_client(Slow { id, timeline: timeline.clone() }).unwrap();
+    ///     pubsub.sub_id(&id, "prices").unwrap();
+    ///     pubsub.psub_id(&id, "pri*").unwrap();
+    /// }
+    ///
+    /// for msg in ["first", "second"] {
+    ///     let receipt = block_on(pubsub.pub_message_concurrent("prices", msg, 2)).unwrap();
+    ///     assert_eq!(receipt.delivered, 4);
+    /// }
+    ///
+    /// assert_eq!(timeline.peak.load(Ordering::SeqCst), 2);
+    ///
+    /// let sends = timeline.sends.lock().unwrap();
+    /// assert_eq!(sends.len(), 8);
+    ///
+    /// for id in 0..4 {
+    ///     let of_client: Vec<_> = sends.iter().filter(|send| send.0 == id).collect();
+    ///     assert_eq!(of_client[0].1, "first");
+    ///     assert_eq!(of_client[1].1, "second");
+    ///     assert!(of_client[0].3 < of_client[1].2);
+    /// }
+    /// ```
+    pub async fn pub_message_concurrent<TInputMessage: Into<TMessage>>(
+        &mut self,
+        channel: &str,
+        msg: TInputMessage,
+        max_in_flight: usize,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        let routed = self.routing.pub_message(channel, msg)?;
+        let queued = std::mem::take(&mut *self.outbox.lock().unwrap());
+
+        let messages: Vec<_> = queued
+            .iter()
+            .map(|(identifier, queued)| {
+                let message = Message {
+                    contents: queued.contents.clone(),
+                    source: &queued.channel,
+                    sequence: queued.sequence,
+                    via: queued.via.as_via(),
+                    tags: &queued.tags,
+                };
+
+                (identifier, message)
+            })
+            .collect();
+
+        let mut clients: HashMap<_, _> = self.clients.iter_mut().collect();
+        let recipients: Vec<_> = messages
+            .iter()
+            .filter_map(|(identifier, message)| {
+                Some((*identifier, clients.remove(identifier)?, message))
+            })
+            .collect();
+
+        let mut waiting = recipients.into_iter().map(|(identifier, client, message)| {
+            (
+                identifier.clone(),
+                message.source,
+                Box::pin(client.send(message)),
+            )
+        });
+
+        let mut receipt = PublishReceipt {
+            channel_existed: routed.channel_existed,
+            ..PublishReceipt::default()
+        };
+
+        let mut in_flight = Vec::new();
+        let mut sent = Vec::new();
+        let max_in_flight = max_in_flight.max(1);
+
+        poll_fn(|context| loop {
+            while in_flight.len() < max_in_flight {
+                match waiting.next() {
+                    Some(send) => in_flight.push(send),
+                    None => break,
+                }
+            }
+
+            let started = in_flight.len();
+
+            in_flight.retain_mut(
+                |(identifier, channel, send)| match send.as_mut().poll(context) {
+                    Poll::Ready(Ok(())) => {
+                        receipt.delivered += 1;
+                        sent.push((identifier.clone(), *channel, Ok(())));
+                        false
+                    }
+                    Poll::Ready(Err(error)) => {
+                        receipt.failures.push((identifier.clone(), error));
+                        sent.push((identifier.clone(), *channel, Err(())));
+                        false
+                    }
+                    Poll::Pending => true,
+                },
+            );
+
+            if in_flight.is_empty() && waiting.len() == 0 {
+                return Poll::Ready(());
+            }
+
+            if in_flight.len() == started {
+                return Poll::Pending;
+            }
+        })
+        .await;
+
+        // The sends borrow the `Clients`, which evicting may remove.
+        drop(in_flight);
+        drop(waiting);
+
+        for (identifier, channel, sent) in sent {
+            self.failures
+                .note(&sent, &identifier, || identifier.clone());
+
+            if sent.is_err() {
+                self.routing.note_send_failed(channel, &identifier);
+            }
+        }
+
+        for identifier in self.failures.take_evicted() {
+            self.clients.remove(&identifier);
+            self.routing.evict(identifier);
+        }
+
+        Ok(receipt)
+    }
+}
+
+impl<TClient, TIdentifier, TMessage> Default for AsyncPubSub<TClient, TIdentifier, TMessage>
+where
+    TClient: AsyncClient<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier + Clone,
+    TMessage: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,63 @@
+use crate::ChannelName;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use wildmatch::WildMatch;
+
+/// The micro-batching configured for `Channels` matching a pattern
+#[derive(Clone)]
+pub(crate) struct MicroBatchRule {
+    pub(crate) pattern: ChannelName,
+    pub(crate) limits: MicroBatchLimits,
+}
+
+impl MicroBatchRule {
+    pub(crate) fn matches(&self, channel: &str) -> bool {
+        WildMatch::new(&self.pattern).matches(channel)
+    }
+}
+
+/// How long, and how many, `Messages` a micro-batch may hold
+#[derive(Clone, Copy)]
+pub(crate) struct MicroBatchLimits {
+    pub(crate) max_delay: Duration,
+    pub(crate) max_batch: usize,
+}
+
+/// The `Messages` held for a single micro-batched `Channel`
+#[derive(Clone)]
+pub(crate) struct MicroBatch<T> {
+    pub(crate) started: Instant,
+    pub(crate) max_delay: Duration,
+    pub(crate) messages: Vec<T>,
+}
+
+impl<T> MicroBatch<T> {
+    pub(crate) fn new(started: Instant, max_delay: Duration) -> MicroBatch<T> {
+        MicroBatch {
+            started,
+            max_delay,
+            messages: Vec::new(),
+        }
+    }
+
+    pub(crate) fn is_due(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.started) >= self.max_delay
+    }
+}
+
+/// The micro-batched `Channels`: the rules choosing them, and the
+/// `Messages` held for each
+#[derive(Clone)]
+pub(crate) struct MicroBatching<T> {
+    pub(crate) rules: Vec<MicroBatchRule>,
+    pub(crate) batches: HashMap<String, MicroBatch<T>>,
+}
+
+impl<T> MicroBatching<T> {
+    pub(crate) fn new() -> MicroBatching<T> {
+        MicroBatching {
+            rules: Vec::new(),
+            batches: HashMap::new(),
+        }
+    }
+}
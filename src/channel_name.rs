@@ -0,0 +1,271 @@
+use crate::PubSubError;
+use std::borrow::{Borrow, Cow};
+use std::fmt;
+use std::ops::Deref;
+
+/// The prefix of `Channel` names reserved for the `PubSub` itself.  See
+/// `PubSub::sub_system`.
+pub const RESERVED_PREFIX: &str = "$sys.";
+
+/// The name of a `Channel` or pattern
+///
+/// Anything naming a `Channel` accepts a `ChannelName`, or whatever converts
+/// into one: a `&'static str` is kept as it is, without allocating, and a
+/// `String`, such as a name formatted at runtime, is taken over.
+///
+/// ```
+/// use general_pub_sub::ChannelName;
+///
+/// let literal = ChannelName::from("user.42");
+/// let formatted = ChannelName::from(format!("user.{}", 42));
+///
+/// assert_eq!(literal, formatted);
+/// assert_eq!(formatted.as_str(), "user.42");
+/// ```
+///
+/// Names built at runtime only have to live until they are handed over,
+/// and are looked up again with a plain `&str`:
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::Sampling;
+///
+/// let client: RecordingClient<_, String> = RecordingClient::new(7u32);
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(client.clone()).unwrap();
+///
+/// let handle = {
+///     let room = format!("room.{}", client.get_id());
+///     pubsub.sub_client(client.clone(), room.clone()).unwrap();
+///     pubsub.sub_tagged(client.clone(), format!("{}.typing", room), 1).unwrap();
+///     pubsub.sub_sampled(client.clone(), format!("{}.cursor", room), Sampling::EveryNth(2)).unwrap();
+///     pubsub.channel_handle(room).unwrap()
+/// };
+///
+/// pubsub.pub_message("room.7", "hello").unwrap();
+/// pubsub.pub_by_handle(handle, "by handle").unwrap();
+/// pubsub.pub_message("room.7.typing", "...").unwrap();
+/// pubsub.pub_message("room.7.cursor", "1,1").unwrap();
+/// pubsub.pub_message("room.7.cursor", "2,2").unwrap();
+/// assert_eq!(client.contents(), vec!["hello", "by handle", "...", "1,1"]);
+///
+/// pubsub.unsub_client(client.clone(), "room.7").unwrap();
+/// pubsub.unsub_tagged(client.clone(), "room.7.typing", 1).unwrap();
+/// assert_eq!(pubsub.view().subscriptions_of(&7).unwrap().channels, vec!["room.7.cursor"]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChannelName(Cow<'static, str>);
+
+impl ChannelName {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&'static str> for ChannelName {
+    fn from(name: &'static str) -> Self {
+        ChannelName(Cow::Borrowed(name))
+    }
+}
+
+impl From<String> for ChannelName {
+    fn from(name: String) -> Self {
+        ChannelName(Cow::Owned(name))
+    }
+}
+
+impl From<Cow<'static, str>> for ChannelName {
+    fn from(name: Cow<'static, str>) -> Self {
+        ChannelName(name)
+    }
+}
+
+impl Deref for ChannelName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for ChannelName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for ChannelName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ChannelName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Why a `Channel` name was rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidChannelNameReason {
+    /// The name is empty, or only whitespace when trimming.
+    Empty,
+    /// The name starts or ends with whitespace.
+    SurroundingWhitespace,
+    /// The name starts with `RESERVED_PREFIX`.
+    Reserved,
+    /// The name is longer than the configured maximum length.
+    TooLong,
+    /// The name was to be taken literally, but the `ChannelMatcher` cannot
+    /// escape its wildcards.  See `PubSub::sub_client_exact`.
+    Unescapable,
+}
+
+impl std::fmt::Display for InvalidChannelNameReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "name is empty"),
+            Self::SurroundingWhitespace => write!(f, "name has surrounding whitespace"),
+            Self::Reserved => write!(
+                f,
+                "name starts with the reserved prefix {}",
+                RESERVED_PREFIX
+            ),
+            Self::TooLong => write!(f, "name is too long"),
+            Self::Unescapable => write!(f, "name has wildcards which cannot be escaped"),
+        }
+    }
+}
+
+/// How surrounding whitespace in a `Channel` name is handled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WhitespacePolicy {
+    /// Names with leading or trailing whitespace are rejected.
+    Reject,
+    /// Leading and trailing whitespace is removed from names.
+    Trim,
+}
+
+/// The rules `Channel` names (and patterns) must follow
+///
+/// The same rules are applied when subscribing, unsubscribing, and
+/// publishing, so any name that can be subscribed to can be published to.
+///
+/// By default, surrounding whitespace is rejected and names may be of any
+/// length.  Empty names and names starting with `RESERVED_PREFIX` are always
+/// rejected, except by the methods for system `Channels`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelNamePolicy {
+    pub whitespace: WhitespacePolicy,
+    /// The maximum length of a name in bytes, if any.
+    pub max_length: Option<usize>,
+}
+
+impl Default for ChannelNamePolicy {
+    fn default() -> Self {
+        ChannelNamePolicy {
+            whitespace: WhitespacePolicy::Reject,
+            max_length: None,
+        }
+    }
+}
+
+impl ChannelNamePolicy {
+    /// Validates a name, returning it trimmed when the policy trims whitespace.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{ChannelNamePolicy, InvalidChannelNameReason, WhitespacePolicy};
+    /// use InvalidChannelNameReason::*;
+    ///
+    /// let strict = ChannelNamePolicy { max_length: Some(8), ..ChannelNamePolicy::default() };
+    /// let trim = ChannelNamePolicy { whitespace: WhitespacePolicy::Trim, ..strict };
+    ///
+    /// let cases: &[(&ChannelNamePolicy, &str, Result<&str, InvalidChannelNameReason>)] = &[
+    ///     (&strict, "news", Ok("news")),
+    ///     (&strict, "a.b-c_9", Ok("a.b-c_9")),
+    ///     (&strict, "12345678", Ok("12345678")),
+    ///     (&strict, "123456789", Err(TooLong)),
+    ///     (&strict, "ééééé", Err(TooLong)),
+    ///     (&strict, "", Err(Empty)),
+    ///     (&strict, "   ", Err(Empty)),
+    ///     (&strict, " news", Err(SurroundingWhitespace)),
+    ///     (&strict, "news\n", Err(SurroundingWhitespace)),
+    ///     (&strict, "the news", Ok("the news")),
+    ///     (&strict, "$sys.x", Err(Reserved)),
+    ///     (&strict, "$sys", Ok("$sys")),
+    ///     (&trim, " news ", Ok("news")),
+    ///     (&trim, " 12345678 ", Ok("12345678")),
+    ///     (&trim, "\t", Err(Empty)),
+    ///     (&trim, " $sys.x", Err(Reserved)),
+    /// ];
+    ///
+    /// for (policy, name, expected) in cases {
+    ///     let validated = policy.validate(name).map_err(|error| match error {
+    ///         PubSubError::InvalidChannelNameError { reason, .. } => reason,
+    ///         error => panic!("{:?}", error),
+    ///     });
+    ///     assert_eq!(validated, *expected, "{:?}", name);
+    ///
+    ///     // Subscribing and publishing agree with the policy.
+    ///     let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///     let mut pubsub = PubSub::new();
+    ///     pubsub.set_channel_name_policy(**policy);
+    ///     pubsub.add_client(client.clone()).unwrap();
+    ///     let subscribed = pubsub.sub_client(client.clone(), *name);
+    ///     let published = pubsub.pub_message(name, "hello");
+    ///     assert_eq!(subscribed.is_ok(), expected.is_ok(), "{:?}", name);
+    ///     assert_eq!(published.is_ok(), expected.is_ok(), "{:?}", name);
+    /// }
+    /// ```
+    pub fn validate<'n>(&self, name: &'n str) -> Result<&'n str, PubSubError> {
+        let invalid = |reason| PubSubError::InvalidChannelNameError {
+            name: name.to_string(),
+            reason,
+        };
+
+        let trimmed = name.trim();
+
+        let name = match self.whitespace {
+            WhitespacePolicy::Trim => trimmed,
+            WhitespacePolicy::Reject if trimmed.len() != name.len() && !trimmed.is_empty() => {
+                return Err(invalid(InvalidChannelNameReason::SurroundingWhitespace));
+            }
+            WhitespacePolicy::Reject => name,
+        };
+
+        if name.trim().is_empty() {
+            return Err(invalid(InvalidChannelNameReason::Empty));
+        }
+
+        if name.starts_with(RESERVED_PREFIX) {
+            return Err(invalid(InvalidChannelNameReason::Reserved));
+        }
+
+        if self
+            .max_length
+            .is_some_and(|max_length| name.len() > max_length)
+        {
+            return Err(invalid(InvalidChannelNameReason::TooLong));
+        }
+
+        Ok(name)
+    }
+
+    /// Validates a name like `validate`, keeping it borrowed or owned as it
+    /// was given.
+    pub(crate) fn validate_name(&self, name: ChannelName) -> Result<ChannelName, PubSubError> {
+        match name.0 {
+            Cow::Borrowed(name) => Ok(ChannelName::from(self.validate(name)?)),
+            Cow::Owned(name) => {
+                let valid = self.validate(&name)?;
+
+                match valid.len() == name.len() {
+                    true => Ok(ChannelName::from(name)),
+                    false => Ok(ChannelName::from(valid.to_string())),
+                }
+            }
+        }
+    }
+}
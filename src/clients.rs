@@ -0,0 +1,185 @@
+use crate::{Client, Message, UniqueIdentifier};
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+
+/// A `Client` which prints every `Message` it receives
+///
+/// Handy for examples and quick experiments.
+///
+/// # Examples
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+///
+/// let mut pubsub: PubSub<_, _, &str> = PubSub::new();
+/// pubsub.add_client(PrintClient::new(1)).unwrap();
+/// pubsub.sub_client(PrintClient::new(1), "greetings").unwrap();
+///
+/// // Prints "Client (1) Received Message from Channel (greetings): Hello!"
+/// pubsub.pub_message("greetings", "Hello!").unwrap();
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrintClient<TIdentifier> {
+    id: TIdentifier,
+}
+
+impl<TIdentifier> PrintClient<TIdentifier> {
+    pub fn new(id: TIdentifier) -> Self {
+        PrintClient { id }
+    }
+}
+
+impl<TIdentifier, TMessage> Client<TIdentifier, TMessage> for PrintClient<TIdentifier>
+where
+    TIdentifier: UniqueIdentifier + Clone + Display,
+    TMessage: Display,
+{
+    type Error = Infallible;
+
+    fn get_id(&self) -> TIdentifier {
+        self.id.clone()
+    }
+
+    fn send(&mut self, message: &Message<TMessage>) -> Result<(), Infallible> {
+        println!(
+            "Client ({}) Received Message from Channel ({}): {}",
+            self.id, message.source, message.contents
+        );
+
+        Ok(())
+    }
+}
+
+/// A `Message` received by a `RecordingClient` or forwarded by a
+/// `ChannelSenderClient`, owning the name of its `Channel`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedMessage<TMessage> {
+    /// The `Channel` the `Message` was published to.
+    pub channel: String,
+    pub contents: TMessage,
+    pub sequence: u64,
+}
+
+/// A `Client` which records every `Message` it receives
+///
+/// Clones share their recording, so a clone kept aside sees everything the
+/// one added to the `PubSub` receives.
+///
+/// # Examples
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+///
+/// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(client.clone()).unwrap();
+/// pubsub.psub_client(client.clone(), "orders.*").unwrap();
+///
+/// pubsub.pub_message("orders.created", "order 42").unwrap();
+/// pubsub.pub_message("orders.shipped", "order 41").unwrap();
+///
+/// assert_eq!(client.contents(), vec!["order 42", "order 41"]);
+/// assert_eq!(client.received()[0].channel, "orders.created");
+/// assert_eq!(client.received_on("orders.shipped")[0].contents, "order 41");
+/// ```
+#[derive(Clone, Debug)]
+pub struct RecordingClient<TIdentifier, TMessage> {
+    id: TIdentifier,
+    received: Arc<Mutex<Vec<RecordedMessage<TMessage>>>>,
+}
+
+impl<TIdentifier, TMessage> RecordingClient<TIdentifier, TMessage> {
+    pub fn new(id: TIdentifier) -> Self {
+        RecordingClient {
+            id,
+            received: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl<TIdentifier, TMessage: Clone> RecordingClient<TIdentifier, TMessage> {
+    /// Gets the `Messages` received so far, in the order they were received.
+    pub fn received(&self) -> Vec<RecordedMessage<TMessage>> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Gets the `Messages` received so far on a `Channel`, in the order they
+    /// were received.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.sub_client(client.clone(), "sports").unwrap();
+    ///
+    /// pubsub.pub_message("news", "first").unwrap();
+    /// pubsub.pub_message("sports", "goal").unwrap();
+    /// pubsub.pub_message("news", "second").unwrap();
+    ///
+    /// let news = client.received_on("news");
+    /// assert_eq!(
+    ///     news.iter().map(|message| message.contents).collect::<Vec<_>>(),
+    ///     vec!["first", "second"]
+    /// );
+    /// assert!(news.iter().all(|message| message.channel == "news"));
+    /// assert!(news[0].sequence < news[1].sequence);
+    /// assert_eq!(client.received_on("sports").len(), 1);
+    /// assert!(client.received_on("weather").is_empty());
+    ///
+    /// client.clear();
+    /// assert!(client.received_on("news").is_empty());
+    /// ```
+    pub fn received_on(&self, channel: &str) -> Vec<RecordedMessage<TMessage>> {
+        self.received
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|message| message.channel == channel)
+            .cloned()
+            .collect()
+    }
+
+    /// Gets the contents of the `Messages` received so far, in the order
+    /// they were received.
+    pub fn contents(&self) -> Vec<TMessage> {
+        self.received
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|message| message.contents.clone())
+            .collect()
+    }
+
+    /// Forgets the `Messages` received so far.
+    pub fn clear(&self) {
+        self.received.lock().unwrap().clear();
+    }
+}
+
+impl<TIdentifier, TMessage> Client<TIdentifier, TMessage> for RecordingClient<TIdentifier, TMessage>
+where
+    TIdentifier: UniqueIdentifier + Clone,
+    TMessage: Clone,
+{
+    type Error = Infallible;
+
+    fn get_id(&self) -> TIdentifier {
+        self.id.clone()
+    }
+
+    fn send(&mut self, message: &Message<TMessage>) -> Result<(), Infallible> {
+        self.received.lock().unwrap().push(RecordedMessage {
+            channel: message.source.to_string(),
+            contents: message.contents.clone(),
+            sequence: message.sequence,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+//! Adapters for code written against the 0.1 API
+//!
+//! Switching an import from `general_pub_sub::{...}` to
+//! `general_pub_sub::compat::{...}` keeps such code compiling and behaving
+//! as before, with deprecation warnings pointing at what to migrate.
+//!
+//! # Migrating
+//!
+//! - `PubSub::pub_message` returns a `Result`.  It fails when the `Channel`
+//!   name is rejected by the `ChannelNamePolicy`, or is a pattern while
+//!   `PatternPublishBehavior::Error` is in effect, which is the default.
+//!   Handle the `Result`, and call `set_pattern_publish_behavior` with
+//!   `PatternPublishBehavior::Literal` if publishing to names containing
+//!   wildcards is intended.
+//!   Its `Ok` value is a `PublishReceipt` counting the `Clients` the
+//!   `Message` was delivered to.
+//! - `Message` gained the `sequence`, `via`, and `tags` fields.  Code
+//!   constructing a `Message` itself, such as tests of a `Client`, has to
+//!   fill them in; code only reading a `Message` is unaffected.
+//! - `PubSub::add_client` returns a `Result` with the `ClientGeneration` of
+//!   the `Client`.  It fails when a `Client` with the same identifier was
+//!   already added, where it used to replace it; call `replace_client` when
+//!   that is intended.
+//! - `PubSub` no longer has a lifetime parameter: `PubSub<'a, C, I, M>` is
+//!   now `PubSub<C, I, M>`.  `Channel` names are taken as a `ChannelName`,
+//!   which string literals and `Strings` convert into; a name borrowed for
+//!   less than `'static` has to be turned into a `String` first.
+//! - `PubSub::sub_client` and `PubSub::unsub_client` take names literally,
+//!   wildcards included.  Subscribe to patterns with `psub_client` and
+//!   `punsub_client`, or call `set_pattern_subscriptions` with
+//!   `PatternSubscriptions::Detected` to keep detecting them.
+//! - `Client::send` returns a `Result`, with the new associated `Error`
+//!   type, and so do `send_on` and `send_batch`.  A `Client` which cannot
+//!   fail declares `type Error = std::convert::Infallible` and returns
+//!   `Ok(())`.  Failures are listed in the `PublishReceipt`, and
+//!   delivery goes on to the other subscribers.
+//! - The `ClientAlreadySubscribedError`, `ClientNotSubscribedError`, and
+//!   `ChannelDoesNotExistError` variants of `PubSubError` carry the name of
+//!   the `Channel` in question; match them with `{ .. }`.  Failures about
+//!   the firehose, a `ChannelHandle`, or a `SubscriptionId` have variants
+//!   of their own.  `PubSubError` can be compared with `==`.
+//! - The other methods of `PubSub` are unchanged.
+
+pub use crate::{Client, Message, PubSubError, UniqueIdentifier};
+
+use crate::{PatternPublishBehavior, PatternSubscriptions};
+use std::ops::{Deref, DerefMut};
+
+/// A `PubSub` with the 0.1 publishing semantics
+///
+/// Publishing never fails, and names containing wildcards are published to
+/// as literal `Channels`.  Every other method of the current `PubSub` is
+/// available through `Deref`.
+///
+/// ```
+/// # #![allow(deprecated)]
+/// use general_pub_sub::compat::PubSub;
+/// use general_pub_sub::RecordingClient;
+///
+/// let old: RecordingClient<_, &str> = RecordingClient::new(1);
+/// let new: RecordingClient<_, &str> = RecordingClient::new(1);
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(old.clone());
+/// pubsub.sub_client(old.clone(), "orders.*").unwrap();
+///
+/// // Adding a `Client` again replaces it, as in 0.1.
+/// pubsub.add_client(new.clone());
+/// assert_eq!(pubsub.client_count(), 1);
+///
+/// // Names are detected as patterns, and publishing never fails.
+/// pubsub.pub_message("orders.new", "created");
+/// pubsub.pub_message("orders.*", "literal");
+/// pubsub.pub_message("", "rejected");
+/// assert!(old.contents().is_empty());
+/// assert_eq!(new.contents(), vec!["created", "literal"]);
+///
+/// let mut current = pubsub.into_inner();
+/// assert!(current.pub_message("", "rejected").is_err());
+/// ```
+pub struct PubSub<TClient: Client<TIdentifier, TMessage>, TIdentifier: UniqueIdentifier, TMessage> {
+    inner: crate::PubSub<TClient, TIdentifier, TMessage>,
+}
+
+impl<TClient: Client<TIdentifier, TMessage>, TIdentifier: UniqueIdentifier, TMessage: Clone>
+    PubSub<TClient, TIdentifier, TMessage>
+{
+    /// Creates a new `PubSub`
+    pub fn new() -> PubSub<TClient, TIdentifier, TMessage> {
+        let mut inner = crate::PubSub::new();
+        inner.set_pattern_publish_behavior(PatternPublishBehavior::Literal);
+        inner.set_pattern_subscriptions(PatternSubscriptions::Detected);
+
+        PubSub { inner }
+    }
+
+    /// Adds a `Client` to the `PubSub`, replacing the `Client` with the
+    /// same identifier if there is one.
+    pub fn add_client(&mut self, client: TClient) {
+        self.inner.replace_client(client);
+    }
+
+    /// Publishes a `Message` to all `Clients` subscribed to the provided
+    /// `Channel`, ignoring any error.
+    #[deprecated(
+        note = "use `general_pub_sub::PubSub::pub_message`, which returns a `Result` instead of dropping the `Message` on error"
+    )]
+    pub fn pub_message<TInputMessage: Into<TMessage>>(
+        &mut self,
+        channel: &str,
+        msg: TInputMessage,
+    ) {
+        let _ = self.inner.pub_message(channel, msg);
+    }
+
+    /// Unwraps the current `PubSub`.
+    pub fn into_inner(self) -> crate::PubSub<TClient, TIdentifier, TMessage> {
+        self.inner
+    }
+}
+
+impl<TClient: Client<TIdentifier, TMessage>, TIdentifier: UniqueIdentifier, TMessage: Clone> Default
+    for PubSub<TClient, TIdentifier, TMessage>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<TClient, TIdentifier, TMessage> Deref for PubSub<TClient, TIdentifier, TMessage>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+{
+    type Target = crate::PubSub<TClient, TIdentifier, TMessage>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<TClient, TIdentifier, TMessage> DerefMut for PubSub<TClient, TIdentifier, TMessage>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
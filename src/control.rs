@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+pub(crate) type ControlEncoder<TMessage> = Arc<dyn Fn(ControlEvent) -> TMessage + Send + Sync>;
+
+/// What a control `Message` confirms
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlEventKind {
+    Subscribed,
+    Unsubscribed,
+}
+
+/// A change to the subscriptions of a `Client`, confirmed to that `Client`
+///
+/// See `PubSub::set_control_encoder`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ControlEvent<'c> {
+    pub kind: ControlEventKind,
+    /// The `Channel` or pattern subscribed to or unsubscribed from, or an
+    /// empty name for the firehose.
+    pub channel: &'c str,
+    /// The number of `Channels` and patterns the `Client` remains subscribed
+    /// to after the change.
+    pub subscriptions: usize,
+}
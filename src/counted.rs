@@ -0,0 +1,18 @@
+/// What subscribing a `Client` to a `Channel` it is already subscribed to
+/// does
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicateSubscriptions {
+    /// The subscription is rejected with
+    /// `PubSubError::ClientAlreadySubscribedError`.
+    Reject,
+    /// The subscription is counted, and the `Client` stays subscribed until
+    /// it has been unsubscribed as many times.  `Messages` are still
+    /// delivered to it once.
+    Counted,
+}
+
+/// Refers to a single subscription made with `PubSub::sub_with_id`
+///
+/// Ids are only meaningful to the `PubSub` that created them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SubscriptionId(pub(crate) u64);
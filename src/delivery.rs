@@ -0,0 +1,146 @@
+use crate::drops::{DropReason, Drops};
+use crate::eviction::Failures;
+use crate::lock_cell::LockCell;
+use crate::routing;
+use crate::{ChannelName, Client, Message, UniqueIdentifier};
+use std::collections::{BTreeSet, HashMap};
+
+/// What became of `Messages` sent to a single recipient
+pub(crate) enum Outcome<TIdentifier, TError> {
+    /// The `Client` took the `Messages`.  Holds the `once` subscriptions
+    /// they spent, to be removed with `PubSub::unsub_spent`.
+    Sent(Vec<(String, TIdentifier)>),
+    /// The `Client` failed to take the `Messages`, which was counted
+    /// towards its eviction and recorded as `DropReason::SendFailed`.
+    Failed(TIdentifier, TError),
+    /// There is no `Client` with the identifier.
+    Absent,
+}
+
+/// The `Clients` a `Sender` sends to
+///
+/// A `PubSub` sends to its `Clients` through `&mut`, while a `SharedPubSub`
+/// publishing under its read lock locks each one in turn.
+pub(crate) trait Recipients<TIdentifier> {
+    type Client;
+
+    /// Calls `f` with the `Client` with an identifier, if there is one.
+    fn with_client<R, F>(&mut self, identifier: &TIdentifier, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut Self::Client) -> R;
+}
+
+impl<TClient, TIdentifier: UniqueIdentifier> Recipients<TIdentifier>
+    for &mut HashMap<TIdentifier, TClient>
+{
+    type Client = TClient;
+
+    fn with_client<R, F>(&mut self, identifier: &TIdentifier, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut TClient) -> R,
+    {
+        self.get_mut(identifier).map(f)
+    }
+}
+
+/// The parts of a `PubSub` that send `Messages` to its `Clients`, borrowed
+/// apart from its `Routing` so that recipients resolved from it can be sent
+/// to
+pub(crate) struct Sender<'p, TClients, TIdentifier> {
+    pub(crate) clients: TClients,
+    pub(crate) failures: &'p LockCell<Failures<TIdentifier>>,
+    pub(crate) drops: &'p Drops<TIdentifier>,
+    /// The `once` subscriptions, taken out of the `Routing` when it is
+    /// borrowed.
+    pub(crate) once: &'p HashMap<ChannelName, BTreeSet<TIdentifier>>,
+    /// Whether each `Client` is flushed right after it is sent to, rather
+    /// than by the caller once it is done sending.
+    pub(crate) flush: bool,
+}
+
+impl<TClients, TIdentifier> Sender<'_, TClients, TIdentifier>
+where
+    TClients: Recipients<TIdentifier>,
+    TIdentifier: UniqueIdentifier,
+{
+    /// Sends a `Message` to the `Client` with an identifier.
+    pub(crate) fn send_one<TMessage>(
+        &mut self,
+        identifier: &TIdentifier,
+        message: &Message<TMessage>,
+    ) -> Outcome<TIdentifier, <TClients::Client as Client<TIdentifier, TMessage>>::Error>
+    where
+        TClients::Client: Client<TIdentifier, TMessage>,
+    {
+        self.send(
+            identifier,
+            message.source,
+            std::slice::from_ref(message),
+            |client| client.send_on(message.source, message),
+        )
+    }
+
+    /// Sends the `Messages` held in the micro-batch of a `Channel` to the
+    /// `Client` with an identifier, all at once.
+    pub(crate) fn send_batch<TMessage>(
+        &mut self,
+        identifier: &TIdentifier,
+        channel: &str,
+        messages: &[Message<TMessage>],
+    ) -> Outcome<TIdentifier, <TClients::Client as Client<TIdentifier, TMessage>>::Error>
+    where
+        TClients::Client: Client<TIdentifier, TMessage>,
+    {
+        self.send(identifier, channel, messages, |client| {
+            client.send_batch(messages)
+        })
+    }
+
+    fn send<TMessage, F>(
+        &mut self,
+        identifier: &TIdentifier,
+        channel: &str,
+        messages: &[Message<TMessage>],
+        send: F,
+    ) -> Outcome<TIdentifier, <TClients::Client as Client<TIdentifier, TMessage>>::Error>
+    where
+        TClients::Client: Client<TIdentifier, TMessage>,
+        F: FnOnce(
+            &mut TClients::Client,
+        )
+            -> Result<(), <TClients::Client as Client<TIdentifier, TMessage>>::Error>,
+    {
+        let (failures, drops, once, flush) = (self.failures, self.drops, self.once, self.flush);
+
+        let outcome = self.clients.with_client(identifier, |client| {
+            let sent = send(client);
+            failures.lock().note(&sent, identifier, || client.get_id());
+
+            if flush {
+                let flushed = client.flush();
+                failures
+                    .lock()
+                    .note_flush(&flushed, identifier, || client.get_id());
+            }
+
+            match sent {
+                Ok(()) => Outcome::Sent(
+                    messages
+                        .iter()
+                        .filter_map(|message| {
+                            routing::spent_once(once, message.source, message.via, identifier)
+                        })
+                        .map(|subscription| (subscription, client.get_id()))
+                        .collect(),
+                ),
+                Err(error) => {
+                    drops.record(channel, DropReason::SendFailed, Some(identifier));
+
+                    Outcome::Failed(client.get_id(), error)
+                }
+            }
+        });
+
+        outcome.unwrap_or(Outcome::Absent)
+    }
+}
@@ -0,0 +1,97 @@
+use crate::memory::Sizer;
+use crate::routing::StoredVia;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::mem::size_of;
+use std::time::Instant;
+
+/// A `Message` kept past its publish, such as for a detached subscriber
+/// until its `Client` is added
+#[derive(Clone)]
+pub(crate) struct BufferedMessage<TMessage> {
+    pub(crate) channel: String,
+    pub(crate) contents: TMessage,
+    pub(crate) sequence: u64,
+    pub(crate) via: StoredVia,
+    pub(crate) tags: Vec<u32>,
+}
+
+impl<TMessage> BufferedMessage<TMessage> {
+    /// The bytes the `Message` takes while buffered, as counted by
+    /// `PubSub::memory_estimate`.
+    pub(crate) fn size(&self, sizer: &Sizer<TMessage>) -> usize {
+        size_of::<BufferedMessage<TMessage>>()
+            + sizer.heap(&self.contents)
+            + self.channel.len()
+            + self.via.heap_size()
+            + self.tags.len() * size_of::<u32>()
+    }
+}
+
+/// The state of an identifier whose subscriptions are kept without a
+/// `Client`, either restored before the `Client` was added or detached
+/// from it for a grace period
+#[derive(Clone)]
+pub(crate) struct Detached<TMessage> {
+    pub(crate) since: Instant,
+    /// When the grace period of a detached `Client` is over.
+    pub(crate) expires: Option<Instant>,
+    pub(crate) buffered: VecDeque<BufferedMessage<TMessage>>,
+}
+
+impl<TMessage> Detached<TMessage> {
+    pub(crate) fn new(since: Instant) -> Detached<TMessage> {
+        Detached {
+            since,
+            expires: None,
+            buffered: VecDeque::new(),
+        }
+    }
+
+    /// Buffers a `Message`, dropping the oldest one when `capacity` is
+    /// reached, and returns the `Message` dropped, if any.
+    #[must_use]
+    pub(crate) fn buffer(
+        &mut self,
+        message: BufferedMessage<TMessage>,
+        capacity: usize,
+    ) -> Option<BufferedMessage<TMessage>> {
+        if capacity == 0 {
+            return Some(message);
+        }
+
+        let dropped = match self.buffered.len() >= capacity {
+            true => self.buffered.pop_front(),
+            false => None,
+        };
+
+        self.buffered.push_back(message);
+
+        dropped
+    }
+
+    /// The bytes taken by the `Messages` buffered.
+    pub(crate) fn buffered_size(&self, sizer: &Sizer<TMessage>) -> usize {
+        self.buffered
+            .iter()
+            .map(|message| message.size(sizer))
+            .sum()
+    }
+}
+
+/// The identifiers whose subscriptions are kept without a `Client`
+#[derive(Clone)]
+pub(crate) struct Detachments<TIdentifier, TMessage> {
+    pub(crate) identifiers: HashMap<TIdentifier, Detached<TMessage>>,
+    /// How many `Messages` are buffered for each identifier.
+    pub(crate) capacity: usize,
+}
+
+impl<TIdentifier, TMessage> Detachments<TIdentifier, TMessage> {
+    pub(crate) fn new() -> Detachments<TIdentifier, TMessage> {
+        Detachments {
+            identifiers: HashMap::new(),
+            capacity: 0,
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::lock_cell::LockCell;
+
+pub(crate) type DropCallback<TIdentifier> = Arc<dyn Fn(DroppedMessage<TIdentifier>) + Send + Sync>;
+
+/// Why a `Message` was not delivered
+///
+/// See `PubSub::set_drop_callback`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DropReason {
+    /// Nobody was left to receive the `Message`: nobody is subscribed to
+    /// the `Channel`, or every subscriber was filtered or sampled out or is
+    /// the sender.
+    /// Reported once per `Message`, without an identifier.
+    NoRecipients,
+    /// A sampled subscription skipped the `Message`.
+    SampledOut,
+    /// The filter of the subscription rejected the `Message`.  See
+    /// `PubSub::sub_client_filtered`.
+    FilteredOut,
+    /// The subscriber is not a `Client` of the `PubSub`.  See
+    /// `StaleIdPolicy`.
+    StaleSubscriber,
+    /// The `Message` was buffered for a detached subscriber whose buffer
+    /// was full, so the oldest `Message` in it was dropped, or buffering is
+    /// off.
+    DetachedBufferFull,
+    /// A detached subscriber was removed, swept, or expired with the
+    /// `Message` still buffered.
+    DetachedRemoved,
+    /// The recipient captured when the `Message` was published was removed
+    /// before a deferred or micro-batched delivery.
+    RecipientRemoved,
+    /// The `Message` was dropped from the history of the `Channel` to stay
+    /// within the memory budget.
+    HistoryShed,
+    /// The retained `Message` of the `Channel` was dropped to stay within
+    /// the memory budget.  See `PubSub::set_shed_order`.
+    RetainedShed,
+    /// Republishing the `Message` failed, such as because the rewritten
+    /// name was rejected.  Reported for the rewritten name.
+    RepublishFailed,
+    /// The `Message` was pending in `DeliveryMode::Deferred` when
+    /// `PubSub::discard_pending` was called.
+    PendingDiscarded,
+    /// Sending the `Message` to the subscriber failed.  For a micro-batch,
+    /// reported once for the whole `Client::send_batch`.
+    SendFailed,
+}
+
+/// A `Message` that was not delivered
+///
+/// See `PubSub::set_drop_callback`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DroppedMessage<'c, TIdentifier> {
+    /// The `Channel` the `Message` was published to.
+    pub channel: &'c str,
+    pub reason: DropReason,
+    /// The subscriber the `Message` was not delivered to, when the drop
+    /// concerns a single one.
+    pub identifier: Option<&'c TIdentifier>,
+}
+
+/// Counts dropped `Messages` and reports them to the drop callback
+///
+/// Every place a `Message` is dropped goes through `record`, so that none
+/// goes unobserved.
+#[derive(Clone)]
+pub(crate) struct Drops<TIdentifier> {
+    counts: LockCell<BTreeMap<DropReason, u64>>,
+    pub(crate) callback: Option<DropCallback<TIdentifier>>,
+}
+
+impl<TIdentifier> Drops<TIdentifier> {
+    pub(crate) fn new() -> Drops<TIdentifier> {
+        Drops {
+            counts: LockCell::default(),
+            callback: None,
+        }
+    }
+
+    pub(crate) fn record(
+        &self,
+        channel: &str,
+        reason: DropReason,
+        identifier: Option<&TIdentifier>,
+    ) {
+        *self.counts.lock().entry(reason).or_insert(0) += 1;
+
+        if let Some(callback) = &self.callback {
+            callback(DroppedMessage {
+                channel,
+                reason,
+                identifier,
+            });
+        }
+    }
+
+    pub(crate) fn count(&self, reason: DropReason) -> u64 {
+        self.counts.lock().get(&reason).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn counts(&self) -> BTreeMap<DropReason, u64> {
+        self.counts.lock().clone()
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::Arc;
+
+pub(crate) type EvictionCallback<TIdentifier> = Arc<dyn Fn(&TIdentifier) + Send + Sync>;
+
+/// When a `Client` whose sends keep failing is removed from the `PubSub`
+///
+/// See `PubSub::set_eviction_policy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// `Clients` are never removed for failing to send.  The default.
+    #[default]
+    Never,
+    /// A `Client` is removed, with all of its subscriptions, once this many
+    /// sends to it in a row have failed.  A failed `Client::flush` counts
+    /// as a failed send.  A successful send starts the count over.  A
+    /// threshold of 0 acts as 1.
+    AfterConsecutiveFailures(u32),
+}
+
+/// Counts the consecutive send failures of each `Client`, and collects
+/// those reaching the threshold of the `EvictionPolicy`
+#[derive(Clone)]
+pub(crate) struct Failures<TIdentifier> {
+    policy: EvictionPolicy,
+    counts: HashMap<TIdentifier, u32>,
+    /// The `Clients` whose last flush failed.  Their successful sends do
+    /// not start the count over until a flush succeeds again, so that a
+    /// `Client` which takes every send but fails every flush is evicted.
+    unflushed: HashSet<TIdentifier>,
+    evicted: Vec<TIdentifier>,
+}
+
+impl<TIdentifier: Eq + Hash> Failures<TIdentifier> {
+    pub(crate) fn new() -> Failures<TIdentifier> {
+        Failures {
+            policy: EvictionPolicy::default(),
+            counts: HashMap::new(),
+            unflushed: HashSet::new(),
+            evicted: Vec::new(),
+        }
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: EvictionPolicy) {
+        self.policy = policy;
+
+        if policy == EvictionPolicy::Never {
+            self.counts.clear();
+            self.unflushed.clear();
+        }
+    }
+
+    /// Notes a send to a `Client`, counting it towards eviction when it
+    /// failed.  `owned` is only called for failures.
+    pub(crate) fn note<E, F: FnOnce() -> TIdentifier>(
+        &mut self,
+        sent: &Result<(), E>,
+        identifier: &TIdentifier,
+        owned: F,
+    ) {
+        if self.policy == EvictionPolicy::Never {
+            return;
+        }
+
+        if sent.is_ok() {
+            if !self.unflushed.contains(identifier) {
+                self.counts.remove(identifier);
+            }
+
+            return;
+        }
+
+        self.count_failure(owned());
+    }
+
+    /// Notes a flush of a `Client`, counting it towards eviction when it
+    /// failed.  A successful flush does not start the count over, since
+    /// the sends before it may have failed.
+    pub(crate) fn note_flush<E, F: Fn() -> TIdentifier>(
+        &mut self,
+        flushed: &Result<(), E>,
+        identifier: &TIdentifier,
+        owned: F,
+    ) {
+        if self.policy == EvictionPolicy::Never {
+            return;
+        }
+
+        if flushed.is_ok() {
+            self.unflushed.remove(identifier);
+            return;
+        }
+
+        self.count_failure(owned());
+
+        if self.counts.contains_key(identifier) {
+            self.unflushed.insert(owned());
+        } else {
+            self.unflushed.remove(identifier);
+        }
+    }
+
+    fn count_failure(&mut self, identifier: TIdentifier) {
+        let threshold = match self.policy {
+            EvictionPolicy::Never => return,
+            EvictionPolicy::AfterConsecutiveFailures(threshold) => threshold.max(1),
+        };
+
+        match self.counts.entry(identifier) {
+            Entry::Occupied(mut entry) => {
+                *entry.get_mut() += 1;
+
+                if *entry.get() >= threshold {
+                    self.evicted.push(entry.remove_entry().0);
+                }
+            }
+            Entry::Vacant(entry) => match threshold {
+                1 => self.evicted.push(entry.into_key()),
+                _ => {
+                    entry.insert(1);
+                }
+            },
+        }
+    }
+
+    /// Forgets the failures of a `Client` which is gone or replaced,
+    /// including an eviction not yet taken.
+    pub(crate) fn forget(&mut self, identifier: &TIdentifier) {
+        self.counts.remove(identifier);
+        self.unflushed.remove(identifier);
+        self.evicted.retain(|evicted| evicted != identifier);
+    }
+
+    /// Whether there are `Clients` for `take_evicted` to take.
+    pub(crate) fn has_evicted(&self) -> bool {
+        !self.evicted.is_empty()
+    }
+
+    /// Takes the `Clients` which reached the threshold since the last call.
+    pub(crate) fn take_evicted(&mut self) -> Vec<TIdentifier> {
+        std::mem::take(&mut self.evicted)
+    }
+}
@@ -0,0 +1,93 @@
+use crate::ChannelName;
+use std::{collections::HashMap, hash::Hash, sync::Arc};
+
+pub(crate) type MessageFilter<TMessage> = Arc<dyn Fn(&TMessage) -> bool + Send + Sync>;
+
+/// The filters of the subscriptions made with `PubSub::sub_client_filtered`,
+/// by subscription and subscriber
+///
+/// Kept apart from the `Routing`, which does not know the type of the
+/// `Messages`.
+#[derive(Clone)]
+pub(crate) struct Filters<TIdentifier, TMessage> {
+    filters: HashMap<ChannelName, HashMap<TIdentifier, MessageFilter<TMessage>>>,
+}
+
+impl<TIdentifier: Eq + Hash, TMessage> Filters<TIdentifier, TMessage> {
+    pub(crate) fn new() -> Filters<TIdentifier, TMessage> {
+        Filters {
+            filters: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        subscription: ChannelName,
+        identifier: TIdentifier,
+        filter: MessageFilter<TMessage>,
+    ) {
+        self.filters
+            .entry(subscription)
+            .or_default()
+            .insert(identifier, filter);
+    }
+
+    /// Whether the subscription of `identifier` to `subscription` lets the
+    /// `Message` through, as it does when it has no filter.
+    pub(crate) fn admits(
+        &self,
+        subscription: &str,
+        identifier: &TIdentifier,
+        contents: &TMessage,
+    ) -> bool {
+        match self
+            .filters
+            .get(subscription)
+            .and_then(|filters| filters.get(identifier))
+        {
+            Some(filter) => filter(contents),
+            None => true,
+        }
+    }
+
+    pub(crate) fn remove(&mut self, subscription: &str, identifier: &TIdentifier) {
+        if let Some(filters) = self.filters.get_mut(subscription) {
+            filters.remove(identifier);
+
+            if filters.is_empty() {
+                self.filters.remove(subscription);
+            }
+        }
+    }
+
+    pub(crate) fn remove_subscription(&mut self, subscription: &str) {
+        self.filters.remove(subscription);
+    }
+
+    /// Drops the filters of the subscriptions that are gone.
+    pub(crate) fn retain<F>(&mut self, mut subscribed: F)
+    where
+        F: FnMut(&str, &TIdentifier) -> bool,
+    {
+        self.filters.retain(|subscription, filters| {
+            filters.retain(|identifier, _| subscribed(subscription, identifier));
+            !filters.is_empty()
+        });
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.filters.clear();
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.filters.shrink_to_fit();
+
+        for filters in self.filters.values_mut() {
+            filters.shrink_to_fit();
+        }
+    }
+}
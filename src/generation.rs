@@ -0,0 +1,9 @@
+/// The generation of a `Client`, returned by `PubSub::add_client`
+///
+/// Each time a `Client` with the same identifier is added, it gets a higher
+/// generation than any before it, even after being removed in between.
+/// This tells a `Client` apart from an earlier one that reused its
+/// identifier, such as a reconnect from a recycled port.  The generation
+/// of every identifier ever added is kept for as long as the `PubSub`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClientGeneration(pub(crate) u64);
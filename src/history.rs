@@ -0,0 +1,129 @@
+use crate::memory::Sizer;
+use std::collections::{HashMap, VecDeque};
+
+/// The outcome of `PubSub::resume`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResumeOutcome {
+    /// The buffered `Messages` after the last one seen were replayed.
+    Resumed { replayed: usize },
+    /// Some `Messages` after the last one seen are no longer buffered, so
+    /// nothing was replayed.  The `Client` should refresh its state from
+    /// scratch; it is subscribed either way.
+    GapDetected { earliest_available: Option<u64> },
+}
+
+/// The most recent `Messages` published to a `Channel`
+#[derive(Clone)]
+pub(crate) struct ChannelHistory<TMessage> {
+    pub(crate) messages: VecDeque<(u64, TMessage)>,
+    /// The sequence of the most recent `Message` dropped from the buffer.
+    pub(crate) evicted_through: Option<u64>,
+}
+
+impl<TMessage> ChannelHistory<TMessage> {
+    pub(crate) fn new() -> ChannelHistory<TMessage> {
+        ChannelHistory {
+            messages: VecDeque::new(),
+            evicted_through: None,
+        }
+    }
+
+    /// Buffers a `Message`, dropping the oldest ones beyond `capacity`, and
+    /// returns the bytes freed.
+    pub(crate) fn record(
+        &mut self,
+        sequence: u64,
+        contents: TMessage,
+        capacity: usize,
+        sizer: &Sizer<TMessage>,
+    ) -> usize {
+        self.messages.push_back((sequence, contents));
+
+        self.shrink_to(capacity, sizer)
+    }
+
+    /// Drops the oldest buffered `Messages` until at most `capacity` remain,
+    /// returning the bytes freed.
+    pub(crate) fn shrink_to(&mut self, capacity: usize, sizer: &Sizer<TMessage>) -> usize {
+        let dropped = self.messages.len().saturating_sub(capacity);
+
+        (0..dropped)
+            .filter_map(|_| self.shed_oldest())
+            .map(|(_, contents)| sizer.entry(&contents))
+            .sum()
+    }
+
+    /// Drops the oldest buffered `Message`, returning it.
+    pub(crate) fn shed_oldest(&mut self) -> Option<(u64, TMessage)> {
+        let (sequence, contents) = self.messages.pop_front()?;
+        self.evicted_through = Some(sequence);
+
+        Some((sequence, contents))
+    }
+
+    /// Checks whether every `Message` after `last_seen` is still buffered.
+    pub(crate) fn is_complete_after(&self, last_seen: u64) -> bool {
+        self.evicted_through
+            .is_none_or(|evicted| evicted <= last_seen)
+    }
+
+    pub(crate) fn after(&self, last_seen: u64) -> impl Iterator<Item = &(u64, TMessage)> + '_ {
+        self.messages
+            .iter()
+            .filter(move |(sequence, _)| *sequence > last_seen)
+    }
+
+    pub(crate) fn earliest(&self) -> Option<u64> {
+        self.messages.front().map(|(sequence, _)| *sequence)
+    }
+}
+
+/// The `Messages` kept once published: the history of each literal
+/// `Channel`, and its retained `Message`
+#[derive(Clone)]
+pub(crate) struct Retention<TMessage> {
+    /// The history capacity of `Channels` not given their own.
+    pub(crate) capacity: usize,
+    /// The history capacities given with `PubSub::set_history`, by prefix.
+    pub(crate) capacities: Vec<(String, usize)>,
+    pub(crate) histories: HashMap<String, ChannelHistory<TMessage>>,
+    pub(crate) retained: HashMap<String, (u64, TMessage)>,
+}
+
+impl<TMessage> Retention<TMessage> {
+    pub(crate) fn new() -> Retention<TMessage> {
+        Retention {
+            capacity: 0,
+            capacities: Vec::new(),
+            histories: HashMap::new(),
+            retained: HashMap::new(),
+        }
+    }
+
+    /// The number of `Messages` kept for a `Channel`.
+    pub(crate) fn capacity_for(&self, channel: &str) -> usize {
+        self.capacities
+            .iter()
+            .filter(|(prefix, _)| channel.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.capacity, |(_, capacity)| *capacity)
+    }
+
+    /// Drops the buffered `Messages` beyond the capacity of each `Channel`,
+    /// returning the bytes freed.
+    pub(crate) fn shrink(&mut self, sizer: &Sizer<TMessage>) -> usize {
+        let mut histories = std::mem::take(&mut self.histories);
+        let mut dropped = 0;
+
+        histories.retain(|channel, history| {
+            let capacity = self.capacity_for(channel);
+            dropped += history.shrink_to(capacity, sizer);
+
+            capacity > 0
+        });
+
+        self.histories = histories;
+
+        dropped
+    }
+}
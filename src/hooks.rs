@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+pub(crate) type SubscriptionCallback<TIdentifier> = Arc<dyn Fn(&TIdentifier, &str) + Send + Sync>;
+pub(crate) type ChannelCallback = Arc<dyn Fn(&str) + Send + Sync>;
+pub(crate) type ClientCallback<TIdentifier> = Arc<dyn Fn(&TIdentifier) + Send + Sync>;
+
+/// The callbacks notified of changes to the subscriptions and `Clients` of
+/// a `PubSub`
+///
+/// They are called while the `PubSub` is being changed, with only the
+/// identifier and name involved, so they cannot call back into it.
+#[derive(Clone)]
+pub(crate) struct Hooks<TIdentifier> {
+    pub(crate) subscribe: Option<SubscriptionCallback<TIdentifier>>,
+    pub(crate) unsubscribe: Option<SubscriptionCallback<TIdentifier>>,
+    pub(crate) first_subscriber: Option<ChannelCallback>,
+    pub(crate) last_unsubscriber: Option<ChannelCallback>,
+    pub(crate) client_added: Option<ClientCallback<TIdentifier>>,
+    pub(crate) client_removed: Option<ClientCallback<TIdentifier>>,
+}
+
+impl<TIdentifier> Hooks<TIdentifier> {
+    pub(crate) fn new() -> Hooks<TIdentifier> {
+        Hooks {
+            subscribe: None,
+            unsubscribe: None,
+            first_subscriber: None,
+            last_unsubscriber: None,
+            client_added: None,
+            client_removed: None,
+        }
+    }
+
+    /// Notifies of a new subscription, and of the `Channel` gaining its
+    /// first subscriber when `first` is set.
+    pub(crate) fn subscribed(&self, identifier: &TIdentifier, channel: &str, first: bool) {
+        if let Some(callback) = &self.subscribe {
+            callback(identifier, channel);
+        }
+
+        if first {
+            if let Some(callback) = &self.first_subscriber {
+                callback(channel);
+            }
+        }
+    }
+
+    /// Notifies of a removed subscription, and of the `Channel` losing its
+    /// last subscriber when `last` is set.
+    pub(crate) fn unsubscribed(&self, identifier: &TIdentifier, channel: &str, last: bool) {
+        if let Some(callback) = &self.unsubscribe {
+            callback(identifier, channel);
+        }
+
+        if last {
+            self.emptied(channel);
+        }
+    }
+
+    /// Notifies of a `Channel` losing its last subscriber.
+    pub(crate) fn emptied(&self, channel: &str) {
+        if let Some(callback) = &self.last_unsubscriber {
+            callback(channel);
+        }
+    }
+
+    pub(crate) fn client_added(&self, identifier: &TIdentifier) {
+        if let Some(callback) = &self.client_added {
+            callback(identifier);
+        }
+    }
+
+    pub(crate) fn client_removed(&self, identifier: &TIdentifier) {
+        if let Some(callback) = &self.client_removed {
+            callback(identifier);
+        }
+    }
+}
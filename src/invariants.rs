@@ -0,0 +1,39 @@
+/// An inconsistency in the internal state of a `PubSub`
+///
+/// Reported by `PubSub::check_invariants`.  A `PubSub` mutated only through
+/// its own methods should never have any of these, with the exception of
+/// `UnknownSubscriber`: under `UnknownSubscriberPolicy::Allow`, `Clients`
+/// may be subscribed before they are added.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvariantViolation<TIdentifier> {
+    /// A subscriber is not a `Client` of the `PubSub`.  The `Channel` is
+    /// `None` for the firehose.
+    UnknownSubscriber {
+        channel: Option<String>,
+        identifier: TIdentifier,
+    },
+    /// The lookup of a literal `Channel` by name does not lead back to the
+    /// same `Channel`.
+    MisindexedChannel { channel: String },
+    /// A sampler exists for a subscription that does not.
+    OrphanedSampler {
+        channel: String,
+        identifier: TIdentifier,
+    },
+    /// A tag exists for a subscription that does not.
+    OrphanedTag {
+        channel: String,
+        identifier: TIdentifier,
+    },
+    /// A pattern has no match statistics.
+    MissingPatternStats { pattern: String },
+    /// A subscription is missing from the index of subscriptions by
+    /// subscriber, or the index lists a subscription that does not exist.
+    UnindexedSubscription {
+        channel: String,
+        identifier: TIdentifier,
+    },
+    /// The running memory estimate has drifted from the memory counted
+    /// from scratch.
+    MemoryMiscounted { estimated: usize, counted: usize },
+}
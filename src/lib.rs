@@ -1,11 +1,102 @@
-use itertools::Itertools;
+use batching::{MicroBatch, MicroBatchLimits, MicroBatchRule, MicroBatching};
+use control::ControlEncoder;
+use delivery::{Outcome, Sender};
+use detached::{BufferedMessage, Detached, Detachments};
+use drops::Drops;
+use eviction::{EvictionCallback, Failures};
+use filters::Filters;
+use history::{ChannelHistory, Retention};
+use hooks::Hooks;
+use limited::Rotations;
+use lock_cell::LockCell;
+use memory::{ShedCallback, Sizer};
+use metrics::PublishMetrics;
+use routing::{ChannelSelector, Routing, Sampler, StoredVia, Subscription, FIREHOSE};
+use stale::StaleCallback;
+use std::borrow::Cow;
 use std::error::Error;
-use std::marker::PhantomData;
+use std::mem::size_of;
+use std::sync::Arc;
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap},
     hash::Hash,
+    time::{Duration, Instant},
 };
-use wildmatch::WildMatch;
+use trace::{PayloadHasher, Recording};
+
+pub mod adapters;
+#[cfg(feature = "async")]
+mod async_client;
+mod batching;
+mod channel_name;
+mod clients;
+pub mod compat;
+mod control;
+mod counted;
+mod delivery;
+mod detached;
+mod drops;
+mod eviction;
+mod filters;
+mod generation;
+mod history;
+mod hooks;
+mod invariants;
+mod limited;
+mod lock_cell;
+#[cfg(feature = "log")]
+mod log_client;
+mod match_cache;
+mod matcher;
+mod memory;
+mod metrics;
+mod options;
+mod ownership;
+mod pattern_index;
+mod pattern_syntax;
+pub mod prelude;
+mod rewrite;
+mod routing;
+mod stale;
+pub mod sync;
+mod template;
+pub mod testing;
+mod time;
+mod trace;
+mod view;
+
+#[cfg(feature = "async")]
+pub use async_client::{AsyncClient, AsyncPubSub};
+pub use channel_name::{
+    ChannelName, ChannelNamePolicy, InvalidChannelNameReason, WhitespacePolicy, RESERVED_PREFIX,
+};
+pub use clients::{PrintClient, RecordedMessage, RecordingClient};
+pub use control::{ControlEvent, ControlEventKind};
+pub use counted::{DuplicateSubscriptions, SubscriptionId};
+pub use drops::{DropReason, DroppedMessage};
+pub use eviction::EvictionPolicy;
+pub use generation::ClientGeneration;
+pub use history::ResumeOutcome;
+pub use invariants::InvariantViolation;
+pub use limited::LimitedFanOut;
+#[cfg(feature = "log")]
+pub use log_client::LogClient;
+pub use matcher::{ChannelMatcher, ExactMatcher, PatternError};
+pub use memory::{ShedEvent, ShedOrder, SizeOf};
+pub use metrics::{Histogram, MaxFanOut, Metrics, PubSubStats};
+pub use options::SubscribeOptions;
+pub use ownership::{ChannelOwnershipPolicy, OwnerRemoval};
+pub use pattern_syntax::{CompiledPattern, PatternSyntax};
+pub use rewrite::ChannelRewrite;
+pub use routing::{
+    ChannelHandle, EmptyChannels, PatternPublishBehavior, PatternResolution, PatternSubscriptions,
+    Sampling,
+};
+pub use stale::{StaleIdPolicy, StaleSubscriber, UnknownSubscriberPolicy};
+pub use template::{ChannelTemplate, TemplateArgs, TemplateParam};
+pub use time::{Clock, ManualClock, SystemClock};
+pub use trace::{replay, ReplayMismatch, Trace, TraceEvent};
+pub use view::{OwnedView, PubSubView, SubscriptionInfo};
 
 /// A Unique Identifier
 ///
@@ -18,6 +109,150 @@ impl<TIdentifier: Ord + Hash> UniqueIdentifier for TIdentifier {}
 pub struct Message<'a, TMessage> {
     pub contents: TMessage,
     pub source: &'a str,
+    /// The position of the `Message` among all `Messages` sent by the
+    /// `PubSub`, across every `Channel`.
+    ///
+    /// Each `Client` receives its live `Messages`, those delivered by a
+    /// publish or a `flush`, in increasing `sequence` order, whatever the
+    /// `DeliveryMode`.  Retained, replayed and resumed `Messages` keep the
+    /// `sequence` they were published with, so they arrive out of order
+    /// with the live ones.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.set_history("news", 10);
+    /// pubsub.pub_message("news", "first").unwrap();
+    /// pubsub.pub_message("news", "second").unwrap();
+    /// pubsub.replay("news", &1, 10).unwrap();
+    ///
+    /// let sequences: Vec<u64> = client.received().iter().map(|m| m.sequence).collect();
+    /// assert!(sequences[0] < sequences[1]);
+    /// // The replay repeats the original sequences.
+    /// assert_eq!(sequences[2..], sequences[..2]);
+    /// ```
+    pub sequence: u64,
+    /// The subscription the `Message` reached the `Client` through.
+    pub via: Via<'a>,
+    /// The tags of every subscription of the `Client` matching the
+    /// `Channel`, sorted and without duplicates.  See `PubSub::sub_tagged`.
+    pub tags: &'a [u32],
+}
+
+/// The subscription a `Message` is delivered through
+///
+/// A `Client` receives each `Message` once, even when several of its
+/// subscriptions match the `Channel`.  A literal subscription takes
+/// precedence over patterns, and patterns take precedence over the
+/// firehose.  Among patterns, the most specific one is reported, as ranked
+/// by `PatternResolution`.
+///
+/// # Examples
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::adapters::FnClient;
+/// use std::sync::{Arc, Mutex};
+///
+/// let log = Arc::new(Mutex::new(Vec::new()));
+/// let client = {
+///     let log = log.clone();
+///
+///     FnClient::new(1, move |message: &Message<&str>| {
+///         let pattern = match message.via {
+///             Via::Pattern(pattern) => Some(pattern.to_string()),
+///             _ => None,
+///         };
+///         log.lock().unwrap().push((message.source.to_string(), pattern));
+///     })
+/// };
+/// let drain = || std::mem::take(&mut *log.lock().unwrap());
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(client.clone()).unwrap();
+/// pubsub.psub_client(client.clone(), "a.*").unwrap();
+/// pubsub.psub_client(client.clone(), "a.?").unwrap();
+/// pubsub.sub_client(client.clone(), "a.b").unwrap();
+///
+/// // The literal subscription wins, and the `Message` arrives once.
+/// pubsub.pub_message("a.b", "exact").unwrap();
+/// assert_eq!(drain(), vec![("a.b".to_string(), None)]);
+/// assert_eq!(pubsub.winning_pattern(&1, "a.b"), Some("a.b"));
+///
+/// // `a.*` and `a.?` are equally specific, so the first by name wins.
+/// pubsub.unsub_client(client.clone(), "a.b").unwrap();
+/// pubsub.pub_message("a.b", "tied").unwrap();
+/// assert_eq!(drain(), vec![("a.b".to_string(), Some("a.*".to_string()))]);
+///
+/// // Only `a.*` matches `a.bc`.
+/// pubsub.pub_message("a.bc", "longer").unwrap();
+/// assert_eq!(drain(), vec![("a.bc".to_string(), Some("a.*".to_string()))]);
+///
+/// pubsub.punsub_client(client.clone(), "a.*").unwrap();
+/// pubsub.pub_message("a.b", "single").unwrap();
+/// assert_eq!(drain(), vec![("a.b".to_string(), Some("a.?".to_string()))]);
+/// assert_eq!(pubsub.winning_pattern(&1, "a.b"), Some("a.?"));
+/// ```
+///
+/// The firehose is reported only when nothing else matches, and `Messages`
+/// whose recipients are captured at publish keep the subscription they
+/// were captured through:
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::adapters::FnClient;
+/// use general_pub_sub::RecipientCapture;
+/// use std::sync::{Arc, Mutex};
+///
+/// let log = Arc::new(Mutex::new(Vec::new()));
+/// let client = {
+///     let log = log.clone();
+///
+///     FnClient::new(1, move |message: &Message<&str>| {
+///         log.lock().unwrap().push(format!("{} {:?}", message.source, message.via));
+///     })
+/// };
+/// let drain = || std::mem::take(&mut *log.lock().unwrap());
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(client.clone()).unwrap();
+/// pubsub.sub_firehose(client.clone()).unwrap();
+/// pubsub.psub_client(client.clone(), "orders.*").unwrap();
+///
+/// pubsub.pub_message("orders.new", "matched").unwrap();
+/// pubsub.pub_message("news", "unmatched").unwrap();
+/// assert_eq!(drain(), vec!["orders.new Pattern(\"orders.*\")", "news Firehose"]);
+///
+/// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtPublish));
+/// pubsub.pub_message("orders.paid", "captured").unwrap();
+/// pubsub.punsub_client(client.clone(), "orders.*").unwrap();
+/// pubsub.pub_message("orders.paid", "after").unwrap();
+/// pubsub.flush();
+/// assert_eq!(
+///     drain(),
+///     vec!["orders.paid Pattern(\"orders.*\")", "orders.paid Firehose"]
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Via<'a> {
+    /// A subscription to the `Channel` itself.  Control `Messages` and
+    /// retained `Messages` are also delivered through the `Channel`.
+    Channel,
+    /// A subscription to the given pattern.
+    Pattern(&'a str),
+    /// The firehose.
+    Firehose,
+    /// No subscription: the `Message` was sent to the `Client` directly
+    /// with `PubSub::send_to` or `PubSub::broadcast`.  Its `source` is
+    /// empty.
+    Direct,
+    /// No subscription: the `Message` was sent again from the history of
+    /// its `Channel` by `PubSub::replay` or `PubSub::resume`.
+    Replay,
 }
 
 /// A PubSub Client
@@ -29,22 +264,31 @@ pub struct Message<'a, TMessage> {
 ///
 /// Message can also be of any type.
 ///
+/// Sending a `Message` can fail, with the `Client`'s own `Error` type.
+/// `Clients` which cannot fail use `std::convert::Infallible`.
+///
 /// # Examples
 ///
 /// Basic Usage:
 ///
 /// ```
+/// use general_pub_sub::{Client, Message};
+/// use std::convert::Infallible;
+///
 /// struct BasicClient {
-///   id: u32   
+///   id: u32
 /// }
 ///
 /// impl Client<u32, &str> for BasicClient {
+///   type Error = Infallible;
+///
 ///   fn get_id(&self) -> u32 {
 ///      return self.id;
 ///   }
 ///
-///   fn send(&self, message: &str) {
-///       println!("Client ({}) Received: {}", self.id, message);
+///   fn send(&mut self, message: &Message<&str>) -> Result<(), Infallible> {
+///       println!("Client ({}) Received: {}", self.id, message.contents);
+///       Ok(())
 ///   }
 /// }
 /// ```
@@ -52,32 +296,40 @@ pub struct Message<'a, TMessage> {
 /// Multi-client Example:
 ///
 /// ```
+/// use general_pub_sub::{Client, Message};
+/// use std::io::{self, Write};
+///
 /// struct ConsoleClient {
 ///   id: u32
 /// }
 ///
 /// impl Client<u32, &str> for ConsoleClient {
+///   type Error = io::Error;
+///
 ///   fn get_id(&self) -> u32 {
 ///      return self.id;
 ///   }
 ///
-///   fn send(&self, message: &str) {
-///       println!("Client ({}) Received: {}", self.id, message);
+///   fn send(&mut self, message: &Message<&str>) -> io::Result<()> {
+///       writeln!(io::stdout(), "Client ({}) Received: {}", self.id, message.contents)
 ///   }
 /// }
 ///
 /// struct TcpClient {
-///   id: &str,
+///   id: String,
 ///   stream: std::net::TcpStream
 /// }
 ///
-/// impl Client<&str, &str> for TcpClient {
-///   fn get_id(&self) -> &str {
-///     return self.id;
+/// impl Client<String, &str> for TcpClient {
+///   type Error = io::Error;
+///
+///   fn get_id(&self) -> String {
+///     return self.id.clone();
 ///   }
 ///
-///   fn send(&self, message: &str) {
-///     self.stream.write(format!("Client ({}) Received: {}", self.id, message).as_bytes())
+///   fn send(&mut self, message: &Message<&str>) -> io::Result<()> {
+///     let response = format!("Client ({}) Received: {}", self.id, message.contents);
+///     self.stream.write_all(response.as_bytes())
 ///   }
 /// }
 ///
@@ -86,73 +338,617 @@ pub struct Message<'a, TMessage> {
 ///   Tcp(TcpClient)
 /// }
 ///
-/// impl Client<&str, &str> for Clients {
-///   fn get_id(&self) -> &str {
+/// impl Client<String, &str> for Clients {
+///   type Error = io::Error;
+///
+///   fn get_id(&self) -> String {
 ///     match self {
 ///       Self::Console(client) => client.get_id().to_string(),
 ///       Self::Tcp(client) => client.get_id()
 ///     }
 ///   }
 ///
-///   fn send(&self, message: &str) {
+///   fn send(&mut self, message: &Message<&str>) -> io::Result<()> {
 ///     match self {
 ///       Self::Console(client) => client.send(message),
-///       Self::Console(client) => client.send(message)
+///       Self::Tcp(client) => client.send(message)
 ///     }
 ///   }
 /// }
 /// ```
 pub trait Client<TIdentifier: UniqueIdentifier, TMessage> {
+    /// Why sending a `Message` failed.
+    type Error;
+
     /// Gets the `ID` of the `Client`. Must be unique.
     fn get_id(&self) -> TIdentifier;
 
     /// Sends a `Message` to a `Client`.
-    fn send(&mut self, message: &Message<TMessage>);
+    ///
+    /// A failure is reported to the publisher (see `PubSub::pub_message`)
+    /// and to the drop callback, and does not keep the `Message` from the
+    /// other subscribers.
+    fn send(&mut self, message: &Message<TMessage>) -> Result<(), Self::Error>;
+
+    /// Sends a `Message` published to `channel` to a `Client`.
+    ///
+    /// The `PubSub` delivers every `Message` through this method.  `channel`
+    /// is always the concrete `Channel` the `Message` was published to,
+    /// never the pattern that matched it, and is the same as
+    /// `Message::source`.  It is passed separately so that a `Client` which
+    /// only cares about the name can override this method and keep its
+    /// `send` unchanged; a `Client` which needs the rest of the envelope,
+    /// such as `Message::via`, can read it from the `Message` instead.  The
+    /// default implementation ignores `channel` and calls `send`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::PatternSubscriptions;
+    /// use std::convert::Infallible;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// struct ByChannel {
+    ///     id: u32,
+    ///     seen: Arc<Mutex<Vec<String>>>,
+    /// }
+    ///
+    /// impl Client<u32, &str> for ByChannel {
+    ///     type Error = Infallible;
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         self.id
+    ///     }
+    ///
+    ///     fn send(&mut self, _: &Message<&str>) -> Result<(), Infallible> {
+    ///         unreachable!("the PubSub delivers through send_on")
+    ///     }
+    ///
+    ///     fn send_on(&mut self, channel: &str, message: &Message<&str>) -> Result<(), Infallible> {
+    ///         self.seen.lock().unwrap().push(format!("{}: {}", channel, message.contents));
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let seen = Arc::new(Mutex::new(Vec::new()));
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_pattern_subscriptions(PatternSubscriptions::Detected);
+    /// pubsub.add_client(ByChannel { id: 1, seen: seen.clone() }).unwrap();
+    /// pubsub.sub_id(&1, "news").unwrap();
+    /// pubsub.sub_id(&1, "orders.*").unwrap();
+    ///
+    /// pubsub.pub_message("news", "headline").unwrap();
+    /// pubsub.pub_message("orders.paid", "#42").unwrap();
+    /// assert_eq!(*seen.lock().unwrap(), vec!["news: headline", "orders.paid: #42"]);
+    /// ```
+    fn send_on(&mut self, channel: &str, message: &Message<TMessage>) -> Result<(), Self::Error> {
+        let _ = channel;
+        self.send(message)
+    }
+
+    /// Flushes any output the `Client` has buffered.
+    ///
+    /// The `PubSub` calls this once per recipient, after all of that
+    /// recipient's `Messages` for a call, such as a publish, a `flush` of
+    /// deferred `Messages`, or a `replay`, have been sent.  A failure counts
+    /// towards the `EvictionPolicy` like a failed send.  The default
+    /// implementation does nothing.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::EvictionPolicy;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[derive(Default)]
+    /// struct Counts {
+    ///     sends: usize,
+    ///     flushes: usize,
+    /// }
+    ///
+    /// struct Buffered {
+    ///     id: u32,
+    ///     counts: Arc<Mutex<Counts>>,
+    ///     broken: bool,
+    /// }
+    ///
+    /// impl Client<u32, &str> for Buffered {
+    ///     type Error = &'static str;
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         self.id
+    ///     }
+    ///
+    ///     fn send(&mut self, _: &Message<&str>) -> Result<(), &'static str> {
+    ///         self.counts.lock().unwrap().sends += 1;
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> Result<(), &'static str> {
+    ///         self.counts.lock().unwrap().flushes += 1;
+    ///
+    ///         match self.broken {
+    ///             true => Err("connection reset"),
+    ///             false => Ok(()),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let totals = |counts: &Mutex<Counts>| {
+    ///     let counts = counts.lock().unwrap();
+    ///     (counts.sends, counts.flushes)
+    /// };
+    /// let counts = Arc::new(Mutex::new(Counts::default()));
+    /// let mut pubsub = PubSub::new();
+    /// pubsub
+    ///     .add_client(Buffered { id: 1, counts: counts.clone(), broken: false })
+    ///     .unwrap();
+    /// pubsub.sub_id(&1, "news").unwrap();
+    /// pubsub.set_history("news", 10);
+    ///
+    /// // One publish: one send and one flush.
+    /// pubsub.pub_message("news", "update").unwrap();
+    /// assert_eq!(totals(&counts), (1, 1));
+    ///
+    /// // Five more, then replaying all six: six sends, one flush.
+    /// for _ in 0..5 {
+    ///     pubsub.pub_message("news", "update").unwrap();
+    /// }
+    /// *counts.lock().unwrap() = Counts::default();
+    /// assert_eq!(pubsub.replay("news", &1, 10), Ok(6));
+    /// assert_eq!(totals(&counts), (6, 1));
+    ///
+    /// // Failed flushes count towards eviction.
+    /// pubsub.replace_client(Buffered { id: 1, counts: counts.clone(), broken: true });
+    /// pubsub.set_eviction_policy(EvictionPolicy::AfterConsecutiveFailures(2));
+    /// pubsub.pub_message("news", "update").unwrap();
+    /// assert_eq!(pubsub.client_count(), 1);
+    /// pubsub.pub_message("news", "update").unwrap();
+    /// assert_eq!(pubsub.client_count(), 0);
+    /// ```
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Sends several `Messages` published to the same `Channel`, in the
+    /// order they were published.
+    ///
+    /// Used to deliver micro-batched `Channels` (see
+    /// `PubSub::set_micro_batch`).  The default implementation sends each
+    /// `Message` in turn with `send_on`, stopping at the first failure.
+    fn send_batch(&mut self, messages: &[Message<TMessage>]) -> Result<(), Self::Error> {
+        for message in messages {
+            self.send_on(message.source, message)?;
+        }
+
+        Ok(())
+    }
 }
 
 /// PubSubError is used for errors specific to `PubSub` (such as adding or removing `Client`s)
-#[derive(Debug)]
+///
+/// Errors about a `Channel` name it, and compare by value:
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+///
+/// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(client.clone()).unwrap();
+/// pubsub.sub_client(client.clone(), "news").unwrap();
+///
+/// let error = pubsub.sub_client(client.clone(), "news").unwrap_err();
+/// assert_eq!(
+///     error,
+///     PubSubError::ClientAlreadySubscribedError { channel: "news".to_string() }
+/// );
+/// assert_eq!(error.to_string(), "Client already subscribed to channel \"news\".");
+/// assert_eq!(error.clone(), error);
+///
+/// assert_eq!(
+///     pubsub.unsub_client(client.clone(), "sports"),
+///     Err(PubSubError::ChannelDoesNotExistError { channel: "sports".to_string() })
+/// );
+/// assert_eq!(
+///     pubsub.unsub_firehose(client.clone()),
+///     Err(PubSubError::NotOnFirehoseError)
+/// );
+/// pubsub.sub_firehose(client.clone()).unwrap();
+/// assert_eq!(
+///     pubsub.sub_firehose(client.clone()),
+///     Err(PubSubError::AlreadyOnFirehoseError)
+/// );
+///
+/// let id = pubsub.sub_with_id(client.clone(), "weather").unwrap();
+/// pubsub.unsub_by_id(id).unwrap();
+/// assert_eq!(pubsub.unsub_by_id(id), Err(PubSubError::UnknownSubscriptionIdError));
+/// assert_eq!(
+///     pubsub.unsub_client(client.clone(), "weather"),
+///     Err(PubSubError::ClientNotSubscribedError { channel: "weather".to_string() })
+/// );
+/// assert_eq!(pubsub.sub_id(&2, "news"), Err(PubSubError::ClientDoesNotExistError));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum PubSubError {
-    ClientAlreadySubscribedError,
-    ClientNotSubscribedError,
-    ChannelDoesNotExistError,
+    ClientAlreadySubscribedError {
+        channel: String,
+    },
+    ClientNotSubscribedError {
+        channel: String,
+    },
+    ChannelDoesNotExistError {
+        channel: String,
+    },
+    /// The `ChannelHandle` does not refer to a `Channel` of this `PubSub`.
+    InvalidChannelHandleError,
+    AlreadyOnFirehoseError,
+    NotOnFirehoseError,
+    /// The `SubscriptionId` was already unsubscribed, or never issued by
+    /// this `PubSub`.
+    UnknownSubscriptionIdError,
     ClientWithIdentifierAlreadyExistsError,
     ClientDoesNotExistError,
+    InvalidChannelNameError {
+        name: String,
+        reason: InvalidChannelNameReason,
+    },
+    PublishedToPatternError {
+        channel: String,
+    },
+    MemoryBudgetExceededError,
+    NotChannelOwnerError {
+        channel: String,
+    },
+    StaleGenerationError,
+    InvalidChannelRewriteError {
+        template: String,
+    },
+    InvalidChannelTemplateError {
+        template: String,
+    },
+    InvalidPatternError {
+        pattern: String,
+        reason: PatternError,
+    },
 }
 
 impl Error for PubSubError {}
 impl std::fmt::Display for PubSubError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::ClientAlreadySubscribedError => {
-                write!(f, "Client already subscribed to channel.")
+            Self::ClientAlreadySubscribedError { channel } => {
+                write!(f, "Client already subscribed to channel {:?}.", channel)
+            }
+            Self::ClientNotSubscribedError { channel } => {
+                write!(f, "Client is not subscribed to channel {:?}.", channel)
             }
-            Self::ClientNotSubscribedError => write!(f, "Client is not subscribed to channel."),
-            Self::ChannelDoesNotExistError => write!(f, "Channel does not exist."),
+            Self::ChannelDoesNotExistError { channel } => {
+                write!(f, "Channel {:?} does not exist.", channel)
+            }
+            Self::InvalidChannelHandleError => write!(f, "Channel handle is not valid."),
+            Self::AlreadyOnFirehoseError => write!(f, "Client already on the firehose."),
+            Self::NotOnFirehoseError => write!(f, "Client is not on the firehose."),
+            Self::UnknownSubscriptionIdError => write!(f, "Subscription id is not known."),
             Self::ClientDoesNotExistError => write!(f, "Client does not exist."),
             Self::ClientWithIdentifierAlreadyExistsError => {
                 write!(f, "Client with that identifier already exists.")
             }
+            Self::InvalidChannelNameError { name, reason } => {
+                write!(f, "Invalid channel name {:?}: {}.", name, reason)
+            }
+            Self::PublishedToPatternError { channel } => {
+                write!(f, "Cannot publish to the pattern {:?}.", channel)
+            }
+            Self::MemoryBudgetExceededError => write!(f, "Memory budget exceeded."),
+            Self::NotChannelOwnerError { channel } => {
+                write!(f, "Channel {:?} is owned by another client.", channel)
+            }
+            Self::StaleGenerationError => {
+                write!(f, "Client belongs to an older generation.")
+            }
+            Self::InvalidChannelRewriteError { template } => write!(
+                f,
+                "Rewrite template {:?} has more placeholders than the pattern has wildcards.",
+                template
+            ),
+            Self::InvalidChannelTemplateError { template } => write!(
+                f,
+                "Channel template {:?} is malformed or does not match its argument types.",
+                template
+            ),
+            Self::InvalidPatternError { pattern, reason } => {
+                write!(f, "Pattern {:?} is malformed: {}.", pattern, reason)
+            }
+        }
+    }
+}
+
+/// The failure of `PubSub::sub_client_many` or `PubSub::unsub_client_many`,
+/// none of whose changes were applied
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BulkSubError {
+    /// The `Channel` that could not be subscribed to or unsubscribed from,
+    /// or `None` when the failure concerns them all, such as when there is
+    /// no `Client` with the identifier.
+    pub channel: Option<String>,
+    /// Why the `Channel` could not be subscribed to or unsubscribed from.
+    pub error: PubSubError,
+}
+
+impl Error for BulkSubError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl std::fmt::Display for BulkSubError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.channel {
+            Some(channel) => write!(f, "Channel {:?}: {}", channel, self.error),
+            None => self.error.fmt(f),
+        }
+    }
+}
+
+/// A single mutation of a `PubSub`
+///
+/// A list of these can be applied with `PubSub::apply`.  Apart from adding
+/// a `Client`, they refer to `Clients` by identifier, like `sub_id` does.
+///
+/// `Op` does not implement the `serde` traits, since the crate has no
+/// `serde` feature: a control plane decodes its own wire format into `Ops`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Op<TClient, TIdentifier> {
+    /// Applied with `PubSub::add_client`.
+    AddClient(TClient),
+    /// Applied with `PubSub::remove_client_by_id`.
+    RemoveClient(TIdentifier),
+    /// Applied with `PubSub::sub_id`.
+    Sub(TIdentifier, ChannelName),
+    /// Applied with `PubSub::unsub_id`.
+    Unsub(TIdentifier, ChannelName),
+}
+
+/// When a deferred `Message` resolves the `Clients` it is delivered to
+///
+/// See `DeliveryMode::Deferred`.
+///
+/// # Examples
+///
+/// A `Client` subscribing between the publish and the flush only receives
+/// the `Message` captured at flush time:
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+///
+/// let early: RecordingClient<_, &str> = RecordingClient::new(1);
+/// let late: RecordingClient<_, &str> = RecordingClient::new(2);
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(early.clone()).unwrap();
+/// pubsub.add_client(late.clone()).unwrap();
+/// pubsub.sub_client(early.clone(), "news").unwrap();
+///
+/// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtPublish));
+/// pubsub.pub_message("news", "captured at publish").unwrap();
+///
+/// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtFlush));
+/// pubsub.pub_message("news", "captured at flush").unwrap();
+///
+/// pubsub.sub_client(late.clone(), "news").unwrap();
+/// assert_eq!(pubsub.pending_count(), 2);
+/// pubsub.flush();
+///
+/// assert_eq!(
+///     early.contents(),
+///     vec!["captured at publish", "captured at flush"]
+/// );
+/// assert_eq!(late.contents(), vec!["captured at flush"]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecipientCapture {
+    /// Recipients are resolved when the `Message` is published, so `Clients`
+    /// subscribing before the flush, or detached when it is published, do
+    /// not receive it.
+    AtPublish,
+    /// Recipients are resolved when the `Message` is flushed, so the
+    /// subscriptions in place at flush time decide who receives it.
+    AtFlush,
+}
+
+/// How published `Messages` are delivered
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeliveryMode {
+    /// `Messages` are delivered as soon as they are published.
+    Immediate,
+    /// `Messages` are queued until `PubSub::flush` delivers them, or
+    /// `PubSub::discard_pending` drops them.
+    Deferred(RecipientCapture),
+}
+
+/// The outcome of `PubSub::flush`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeliveryReport {
+    /// The number of pending `Messages` that were flushed.
+    pub messages: usize,
+    /// The number of times a `Message` was sent to a `Client`.
+    pub deliveries: usize,
+    /// The number of stale subscribers skipped.  See `StaleIdPolicy`.
+    pub stale: usize,
+    /// The number of times sending to a `Client` failed.  Each failure is
+    /// also reported as `DropReason::SendFailed`.
+    pub failed: usize,
+}
+
+/// The outcome of publishing a `Message`, such as with `PubSub::pub_message`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublishReceipt<TIdentifier, TError> {
+    /// The number of `Clients` the `Message` was sent to.  Each is counted
+    /// once however many of its subscriptions match, and subscribers that
+    /// are not connected `Clients` are not counted.  Deliveries left to
+    /// `PubSub::flush` or a micro-batch are not counted either.
+    pub delivered: usize,
+    /// Whether anything was subscribed to the `Channel`, by name, through a
+    /// pattern, or through the firehose, connected or not.
+    pub channel_existed: bool,
+    /// The `Clients` sending the `Message` to failed, with their errors,
+    /// in delivery order.  They are not counted as delivered.  Failures of
+    /// deliveries left to `PubSub::flush` or a micro-batch are only
+    /// reported to the drop callback.
+    pub failures: Vec<(TIdentifier, TError)>,
+}
+
+impl<TIdentifier, TError> Default for PublishReceipt<TIdentifier, TError> {
+    fn default() -> Self {
+        PublishReceipt {
+            delivered: 0,
+            channel_existed: false,
+            failures: Vec::new(),
         }
     }
 }
 
+/// A `Client` and its subscriptions, as loaded by `PubSub::import`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportRecord<TIdentifier> {
+    pub id: TIdentifier,
+    /// The `Channels` and patterns the `Client` is subscribed to.
+    pub channels: Vec<ChannelName>,
+}
+
+/// The outcome of `PubSub::gc`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GcReport {
+    /// The number of subscriptions removed because their subscriber is not
+    /// a `Client` of the `PubSub`.
+    pub stale_subscriptions: usize,
+    /// The number of patterns removed because nobody is subscribed to them.
+    pub empty_patterns: usize,
+}
+
+/// The outcome of `PubSub::import`
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// The number of `Clients` created by the import.
+    pub clients: usize,
+    /// The number of subscriptions added by the import.
+    pub subscriptions: usize,
+    /// The subscriptions that were rejected, as the index of their
+    /// `ImportRecord` along with the reason.
+    pub errors: Vec<(usize, PubSubError)>,
+}
+
+/// The subscriptions of a `PubSub`, without its `Clients`, as taken by
+/// `PubSub::subscription_snapshot` and loaded by `PubSub::apply_snapshot`
+///
+/// Made of plain `Strings` and identifiers, so that it can be saved in any
+/// format, such as across a restart.
+///
+/// It does not implement the `serde` traits, since the crate has no `serde`
+/// feature: applications convert it to and from their own format.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubscriptionSnapshot<TIdentifier> {
+    /// Each literal `Channel`, in creation order, with its subscribers in
+    /// order.  Names are kept escaped, as the `PubSub` stores them.
+    pub channels: Vec<(String, Vec<TIdentifier>)>,
+    /// Each pattern, by name, with its subscribers in order.
+    pub patterns: Vec<(String, Vec<TIdentifier>)>,
+    pub firehose: Vec<TIdentifier>,
+}
+
+#[derive(Clone)]
+struct PendingMessage<TIdentifier, TMessage> {
+    channel: String,
+    contents: TMessage,
+    sequence: u64,
+    recipients: Option<Vec<(TIdentifier, StoredVia, Vec<u32>)>>,
+    /// The sender, which is not delivered the `Message` when the recipients
+    /// are resolved at flush time.
+    excluded: Option<TIdentifier>,
+}
+
+impl<TIdentifier, TMessage> PendingMessage<TIdentifier, TMessage> {
+    /// The bytes the `Message` takes while pending, as counted by
+    /// `PubSub::memory_estimate`.
+    fn size(&self, sizer: &Sizer<TMessage>) -> usize {
+        let recipients = self
+            .recipients
+            .iter()
+            .flatten()
+            .map(|(_, via, tags)| {
+                size_of::<(TIdentifier, StoredVia, Vec<u32>)>()
+                    + via.heap_size()
+                    + tags.len() * size_of::<u32>()
+            })
+            .sum::<usize>();
+
+        Self::reserved(&self.channel, &self.contents, sizer) + recipients
+    }
+
+    /// The bytes a `Message` takes while pending, before its recipients
+    /// are captured.
+    fn reserved(channel: &str, contents: &TMessage, sizer: &Sizer<TMessage>) -> usize {
+        size_of::<PendingMessage<TIdentifier, TMessage>>() + channel.len() + sizer.heap(contents)
+    }
+}
+
 /// A PubSub
 #[derive(Clone)]
 pub struct PubSub<
-    'a,
     TClient: Client<TIdentifier, TMessage>,
     TIdentifier: UniqueIdentifier,
     TMessage,
+    TMatcher: ChannelMatcher = PatternSyntax,
 > {
     clients: HashMap<TIdentifier, TClient>,
-    channels: HashMap<&'a str, BTreeSet<TIdentifier>>,
-    pattern_channels: HashMap<&'a str, BTreeSet<TIdentifier>>,
-    phantom: PhantomData<TMessage>,
-}
-
-fn channel_is_pattern(channel: &str) -> bool {
-    channel.contains('*') || channel.contains('?')
+    routing: Routing<TIdentifier, TMatcher>,
+    channel_names: ChannelNamePolicy,
+    delivery_mode: DeliveryMode,
+    pending: Vec<PendingMessage<TIdentifier, TMessage>>,
+    publish_metrics: LockCell<PublishMetrics>,
+    next_sequence: LockCell<u64>,
+    control_encoder: Option<ControlEncoder<TMessage>>,
+    retention: Retention<TMessage>,
+    pattern_publish: PatternPublishBehavior,
+    pattern_subscriptions: PatternSubscriptions,
+    memory_budget: Option<usize>,
+    /// The bytes taken by history, retained, pending, and buffered
+    /// `Messages`, kept up to date as they are stored and dropped.  See
+    /// `memory_estimate`.
+    stored_bytes: usize,
+    shed_callback: Option<ShedCallback>,
+    shed_order: ShedOrder,
+    sizer: Sizer<TMessage>,
+    gc_interval: Option<usize>,
+    mutations_since_gc: usize,
+    recording: Option<Recording<TIdentifier, TMessage>>,
+    owners: HashMap<String, TIdentifier>,
+    ownership: ChannelOwnershipPolicy,
+    micro_batching: MicroBatching<PendingMessage<TIdentifier, TMessage>>,
+    detached: Detachments<TIdentifier, TMessage>,
+    duplicate_subscriptions: DuplicateSubscriptions,
+    /// The number of subscriptions of each `Client` counted more than once.
+    subscription_counts: HashMap<ChannelName, HashMap<TIdentifier, usize>>,
+    subscription_ids: HashMap<SubscriptionId, (TIdentifier, ChannelName)>,
+    next_subscription_id: u64,
+    generations: HashMap<TIdentifier, u64>,
+    /// The `from_pattern` of each rule, as given and compiled, and its
+    /// rewrite.
+    republish_rules: Vec<(
+        ChannelName,
+        ChannelSelector<TMatcher::Compiled>,
+        ChannelRewrite,
+    )>,
+    /// Set while publishing republished `Messages`, so they are not
+    /// republished again.
+    republishing: bool,
+    stale_policy: StaleIdPolicy,
+    unknown_subscribers: UnknownSubscriberPolicy,
+    stale_callback: Option<StaleCallback<TIdentifier>>,
+    limited_fan_out: LimitedFanOut,
+    rotations: Rotations,
+    drops: Drops<TIdentifier>,
+    failures: LockCell<Failures<TIdentifier>>,
+    eviction_callback: Option<EvictionCallback<TIdentifier>>,
+    filters: Filters<TIdentifier, TMessage>,
 }
 
 /// Implementation for a `PubSub`
@@ -163,134 +959,7388 @@ fn channel_is_pattern(channel: &str) -> bool {
 /// 2. Add one or more `Clients`.
 /// 3. Subscribe the `Clients` to `Channels` of interest.
 /// 4. Publish `Messages` to the `Channels`. The `Message` is broadcast to all `Clients` subscribed to the `Channel`.
+impl<TClient: Client<TIdentifier, TMessage>, TIdentifier: UniqueIdentifier, TMessage: Clone>
+    PubSub<TClient, TIdentifier, TMessage>
+{
+    /// Creates a new `PubSub`
+    ///
+    /// All `Clients` of the `PubSub` must use the same type of `Identifier`
+    /// and receive the same type of `Message`.  Patterns use
+    /// `PatternSyntax::Glob`.
+    pub fn new() -> PubSub<TClient, TIdentifier, TMessage> {
+        Self::with_matcher(PatternSyntax::Glob)
+    }
+
+    /// Creates a new `PubSub` whose patterns use `syntax`
+    ///
+    /// The syntax decides which subscriptions are patterns and what they
+    /// match, so it cannot change once there are subscriptions.
+    pub fn with_pattern_syntax(syntax: PatternSyntax) -> PubSub<TClient, TIdentifier, TMessage> {
+        Self::with_matcher(syntax)
+    }
+}
+
 impl<
-        'a,
         TClient: Client<TIdentifier, TMessage>,
         TIdentifier: UniqueIdentifier,
-        TMessage: Clone + Copy,
-    > PubSub<'a, TClient, TIdentifier, TMessage>
+        TMessage: Clone,
+        TMatcher: ChannelMatcher,
+    > PubSub<TClient, TIdentifier, TMessage, TMatcher>
 {
-    /// Creates a new `PubSub`
+    /// Creates a new `PubSub` whose patterns are matched by `matcher`
     ///
-    /// All `Clients` of the `PubSub` must use the same type of `Identifier`
-    /// and receive the same type of `Message`.
-    pub fn new() -> PubSub<'a, TClient, TIdentifier, TMessage> {
+    /// See `ChannelMatcher`.
+    pub fn with_matcher(matcher: TMatcher) -> PubSub<TClient, TIdentifier, TMessage, TMatcher> {
         PubSub {
             clients: HashMap::new(),
-            channels: HashMap::new(),
-            pattern_channels: HashMap::new(),
-            phantom: PhantomData,
+            routing: Routing::new(matcher),
+            channel_names: ChannelNamePolicy::default(),
+            delivery_mode: DeliveryMode::Immediate,
+            pending: Vec::new(),
+            publish_metrics: LockCell::new(PublishMetrics::new()),
+            next_sequence: LockCell::new(0),
+            control_encoder: None,
+            retention: Retention::new(),
+            pattern_publish: PatternPublishBehavior::Error,
+            pattern_subscriptions: PatternSubscriptions::Explicit,
+            memory_budget: None,
+            stored_bytes: 0,
+            shed_callback: None,
+            shed_order: ShedOrder::History,
+            sizer: Sizer::new(),
+            gc_interval: None,
+            mutations_since_gc: 0,
+            recording: None,
+            owners: HashMap::new(),
+            ownership: ChannelOwnershipPolicy::default(),
+            micro_batching: MicroBatching::new(),
+            detached: Detachments::new(),
+            duplicate_subscriptions: DuplicateSubscriptions::Reject,
+            subscription_counts: HashMap::new(),
+            subscription_ids: HashMap::new(),
+            next_subscription_id: 0,
+            generations: HashMap::new(),
+            republish_rules: Vec::new(),
+            republishing: false,
+            stale_policy: StaleIdPolicy::default(),
+            unknown_subscribers: UnknownSubscriberPolicy::default(),
+            stale_callback: None,
+            limited_fan_out: LimitedFanOut::default(),
+            rotations: Rotations::default(),
+            drops: Drops::new(),
+            failures: LockCell::new(Failures::new()),
+            eviction_callback: None,
+            filters: Filters::new(),
         }
     }
 
-    /// Adds a `Client` to the `PubSub`
-    pub fn add_client(&mut self, client: TClient) {
-        let token = client.get_id();
-        self.clients.insert(token, client);
+    /// Adds a `Client` to the `PubSub`, returning its `ClientGeneration`.
+    ///
+    /// If subscriptions restored with `restore_subscriptions` are waiting
+    /// for the `Client`, they become live: each is confirmed like a new
+    /// subscription, then the `Messages` buffered for the `Client` are
+    /// delivered in the order they were published.
+    ///
+    /// Results in a `PubSubError` when a `Client` with the same identifier
+    /// was already added.  Use `replace_client` to swap it out.
+    pub fn add_client(&mut self, client: TClient) -> Result<ClientGeneration, PubSubError> {
+        if self.clients.contains_key(&client.get_id()) {
+            return Err(PubSubError::ClientWithIdentifierAlreadyExistsError);
+        }
+
+        Ok(self.replace_client(client))
     }
 
-    // Unsubscribes a `Client` from all `Channels` and removes the `Client` from the `PubSub`.
-    pub fn remove_client(&mut self, client: TClient) {
-        let identifier = &client.get_id();
-        self.clients.remove(identifier);
+    /// Adds a `Client` to the `PubSub` like `add_client`, replacing the
+    /// `Client` with the same identifier if there is one.
+    ///
+    /// The subscriptions of the replaced `Client` are kept, so they deliver
+    /// to the new `Client` from now on, and the replaced `Client` receives
+    /// nothing more.  The new `Client` gets a new `ClientGeneration`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let old: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let new: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(old.clone()).unwrap();
+    /// pubsub.sub_client(old.clone(), "news").unwrap();
+    ///
+    /// assert!(matches!(
+    ///     pubsub.add_client(new.clone()),
+    ///     Err(PubSubError::ClientWithIdentifierAlreadyExistsError)
+    /// ));
+    ///
+    /// pubsub.replace_client(new.clone());
+    /// pubsub.pub_message("news", "fresh").unwrap();
+    ///
+    /// assert!(old.contents().is_empty());
+    /// assert_eq!(new.contents(), vec!["fresh"]);
+    /// ```
+    pub fn replace_client(&mut self, client: TClient) -> ClientGeneration {
+        self.record(|| TraceEvent::AddClient(client.get_id()));
 
-        for subbed_clients in self.channels.values_mut() {
-            subbed_clients.remove(identifier);
+        let generation = self.generations.entry(client.get_id()).or_insert(0);
+        *generation += 1;
+        let generation = ClientGeneration(*generation);
+
+        let reattached = self
+            .detached
+            .identifiers
+            .remove(&client.get_id())
+            .map(|detached| {
+                let subscriptions = self.routing.subscriptions(&client.get_id());
+                let identifiers: Vec<TIdentifier> =
+                    subscriptions.iter().map(|_| client.get_id()).collect();
+
+                (client.get_id(), detached, subscriptions, identifiers)
+            });
+
+        if let Some((_, detached, _, _)) = &reattached {
+            self.stored_bytes -= detached.buffered_size(&self.sizer);
         }
 
-        for subbed_clients in self.pattern_channels.values_mut() {
-            subbed_clients.remove(identifier);
+        let token = client.get_id();
+        self.failures.get_mut().forget(&token);
+
+        if !self.clients.contains_key(&token) {
+            self.routing.hooks.client_added(&token);
         }
-    }
 
-    fn get_channels_for_subscription(
-        &mut self,
-        channel: &'a str,
-    ) -> &mut HashMap<&'a str, BTreeSet<TIdentifier>> {
-        match channel_is_pattern(channel) {
-            true => &mut self.pattern_channels,
-            false => &mut self.channels,
+        self.clients.insert(token, client);
+
+        if let Some((identifier, detached, subscriptions, identifiers)) = reattached {
+            for (channel, identifier) in subscriptions.into_iter().zip(identifiers) {
+                self.confirm(identifier, ControlEventKind::Subscribed, &channel);
+            }
+
+            let mut spent = Vec::new();
+            let mut sender = Sender {
+                clients: &mut self.clients,
+                failures: &self.failures,
+                drops: &self.drops,
+                once: &self.routing.once,
+                flush: false,
+            };
+
+            for buffered in detached.buffered {
+                let message = Message {
+                    contents: buffered.contents,
+                    source: &buffered.channel,
+                    sequence: buffered.sequence,
+                    via: buffered.via.as_via(),
+                    tags: &buffered.tags,
+                };
+
+                if let Outcome::Sent(spends) = sender.send_one(&identifier, &message) {
+                    spent.extend(spends);
+                }
+            }
+
+            self.flush_client(&identifier);
+            self.unsub_spent(spent);
+            self.evict_failing();
         }
+
+        self.count_mutation();
+
+        generation
     }
 
-    /// Subscribes a `Client` to a `Channel`.
+    /// Unsubscribes a `Client` from all `Channels` and removes the `Client` from the `PubSub`.
     ///
-    /// Results in a `PubSubError` when a `Client` attempts to subscribe to a
-    /// `Channel` that it is already subscribed to.
-    pub fn sub_client(&mut self, client: TClient, channel: &'a str) -> Result<(), PubSubError> {
-        let target_channels = self.get_channels_for_subscription(channel);
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    ///
+    /// pubsub.remove_client(client.clone());
+    ///
+    /// pubsub.pub_message("news", "unheard").unwrap();
+    /// assert!(client.contents().is_empty());
+    /// ```
+    ///
+    /// Only the removed `Client`'s own subscriptions are touched; others on
+    /// the same `Channels` keep theirs, and a `Client` added again under the
+    /// same identifier starts with none:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let leaving: RecordingClient<_, &str> = RecordingClient::new(1u32);
+    /// let staying: RecordingClient<_, &str> = RecordingClient::new(2u32);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(leaving.clone()).unwrap();
+    /// pubsub.add_client(staying.clone()).unwrap();
+    ///
+    /// for channel in ["news", "sports", "weather"].iter() {
+    ///     pubsub.sub_client(leaving.clone(), *channel).unwrap();
+    ///     pubsub.sub_client(staying.clone(), *channel).unwrap();
+    /// }
+    /// assert!(pubsub.sub_client(leaving.clone(), "news").is_err());
+    /// assert_eq!(pubsub.view().subscriptions_of(&1).unwrap().channels.len(), 3);
+    ///
+    /// pubsub.remove_client(leaving.clone());
+    /// assert!(pubsub.view().subscriptions_of(&1).is_none());
+    /// assert_eq!(pubsub.view().subscriptions_of(&2).unwrap().channels.len(), 3);
+    /// assert!(pubsub.check_invariants().is_ok());
+    ///
+    /// pubsub.add_client(leaving.clone()).unwrap();
+    /// assert!(pubsub.view().subscriptions_of(&1).unwrap().channels.is_empty());
+    ///
+    /// pubsub.pub_message("sports", "goal").unwrap();
+    /// assert!(leaving.contents().is_empty());
+    /// assert_eq!(staying.contents(), vec!["goal"]);
+    /// ```
+    pub fn remove_client(&mut self, client: TClient) {
+        self.remove_identifier(&client.get_id());
 
-        let subbed_clients = target_channels.entry(channel).or_insert_with(BTreeSet::new);
+        self.record(|| TraceEvent::RemoveClient(client.get_id()));
+        self.count_mutation();
+    }
 
-        let result = subbed_clients.insert(client.get_id());
+    /// Removes a `Client` like `remove_client`, unless it belongs to another
+    /// generation than `generation`.
+    ///
+    /// Results in a `PubSubError` when there is no `Client` with the
+    /// identifier, or when it was added again since `generation`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let first: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let reconnected: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// let stale = pubsub.add_client(first.clone()).unwrap();
+    /// pubsub.remove_client(first.clone());
+    /// assert_eq!(pubsub.client_generation(&1), None);
+    ///
+    /// // The identifier is reused, and gets a higher generation.
+    /// let current = pubsub.add_client(reconnected.clone()).unwrap();
+    /// assert!(current > stale);
+    /// assert_eq!(pubsub.client_generation(&1), Some(current));
+    /// pubsub.sub_client(reconnected.clone(), "news").unwrap();
+    ///
+    /// // The late cleanup of the first connection leaves the new one alone.
+    /// assert_eq!(
+    ///     pubsub.unsub_client_gen(first.clone(), "news", stale),
+    ///     Err(PubSubError::StaleGenerationError)
+    /// );
+    /// assert_eq!(
+    ///     pubsub.remove_client_gen(&1, stale),
+    ///     Err(PubSubError::StaleGenerationError)
+    /// );
+    /// pubsub.pub_message("news", "still here").unwrap();
+    /// assert_eq!(reconnected.contents(), vec!["still here"]);
+    ///
+    /// // Replacing the `Client` moves to the next generation too.
+    /// let replaced = pubsub.replace_client(reconnected.clone());
+    /// assert!(replaced > current);
+    /// assert_eq!(
+    ///     pubsub.remove_client_gen(&1, current),
+    ///     Err(PubSubError::StaleGenerationError)
+    /// );
+    ///
+    /// pubsub.remove_client_gen(&1, replaced).unwrap();
+    /// assert_eq!(pubsub.client_count(), 0);
+    /// assert_eq!(
+    ///     pubsub.remove_client_gen(&1, replaced),
+    ///     Err(PubSubError::ClientDoesNotExistError)
+    /// );
+    /// ```
+    pub fn remove_client_gen(
+        &mut self,
+        identifier: &TIdentifier,
+        generation: ClientGeneration,
+    ) -> Result<(), PubSubError> {
+        self.check_generation(identifier, generation)?;
 
-        if result {
-            Ok(())
-        } else {
-            Err(PubSubError::ClientAlreadySubscribedError)
-        }
+        self.remove_client_by_id(identifier)
     }
 
-    /// Unsubscribes a `Client` from a `Channel`
+    /// Removes the `Client` with an identifier, like `remove_client` but
+    /// without handing over the `Client` itself.  See `sub_id`.
     ///
-    /// Results in a `PubSubError` when a `Client` attempts to unsubscribe
-    /// from a `Channel` it is not subscribed to.
-    pub fn unsub_client(&mut self, client: TClient, channel: &'a str) -> Result<(), PubSubError> {
-        let target_channels = self.get_channels_for_subscription(channel);
+    /// Results in a `PubSubError` when there is no `Client` with the
+    /// identifier.
+    pub fn remove_client_by_id(&mut self, identifier: &TIdentifier) -> Result<(), PubSubError> {
+        let identifier = self
+            .clients
+            .get(identifier)
+            .ok_or(PubSubError::ClientDoesNotExistError)?
+            .get_id();
 
-        if let Some(subbed_clients) = target_channels.get_mut(channel) {
-            match subbed_clients.remove(&client.get_id()) {
-                true => Ok(()),
-                false => Err(PubSubError::ClientNotSubscribedError),
-            }
-        } else {
-            Err(PubSubError::ChannelDoesNotExistError)
-        }
+        self.remove_identifier(&identifier);
+        self.record(|| TraceEvent::RemoveClient(identifier));
+        self.count_mutation();
+
+        Ok(())
     }
 
-    /// Publishes a `Message` to all `Clients` subscribed to the provided `Channel`.
-    pub fn pub_message<TInputMessage: Into<TMessage>>(
+    /// Unsubscribes a `Client` like `unsub_client`, unless it belongs to
+    /// another generation than `generation`.
+    ///
+    /// Results in a `PubSubError` as `remove_client_gen` does, or as
+    /// `unsub_client` does.
+    pub fn unsub_client_gen(
         &mut self,
+        client: TClient,
         channel: &str,
-        msg: TInputMessage,
-    ) {
-        let msg_ref = msg.into();
+        generation: ClientGeneration,
+    ) -> Result<(), PubSubError> {
+        self.check_generation(&client.get_id(), generation)?;
 
-        let message = Message {
-            contents: msg_ref,
-            source: channel,
-        };
+        self.unsub_client(client, channel)
+    }
 
-        let pattern_client_identifiers = self
-            .pattern_channels
-            .iter()
-            .filter(|(pattern, _)| WildMatch::new(pattern) == channel)
-            .map(|(_, clients)| clients.iter())
-            .flatten();
+    /// Gets the `ClientGeneration` of the `Client` with an identifier, if
+    /// there is one.
+    pub fn client_generation(&self, identifier: &TIdentifier) -> Option<ClientGeneration> {
+        match self.clients.contains_key(identifier) {
+            true => self
+                .generations
+                .get(identifier)
+                .copied()
+                .map(ClientGeneration),
+            false => None,
+        }
+    }
 
-        let subbed_clients = self.channels.get_mut(channel);
-        let subbed_client_identifiers = subbed_clients.iter().map(|client| client.iter()).flatten();
+    /// Gets the number of `Clients` in the `PubSub`.
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
 
-        let unique_client_identifiers = subbed_client_identifiers
-            .chain(pattern_client_identifiers)
-            .unique();
+    /// Gets the number of literal `Channels`, including those nobody is
+    /// subscribed to anymore.  See `set_empty_channels`.
+    pub fn channel_count(&self) -> usize {
+        self.routing.channels.len()
+    }
 
-        for identifier in unique_client_identifiers {
-            if let Some(client) = self.clients.get_mut(identifier) {
-                client.send(&message);
-            }
-        }
+    /// Gets the number of patterns, including those nobody is subscribed to
+    /// anymore until `gc` removes them.
+    pub fn pattern_count(&self) -> usize {
+        self.routing.pattern_channels.len()
     }
-}
 
-impl<
-        'a,
-        TClient: Client<TIdentifier, TMessage>,
-        TIdentifier: UniqueIdentifier,
-        TMessage: Clone + Copy,
-    > Default for PubSub<'a, TClient, TIdentifier, TMessage>
-{
-    fn default() -> Self {
-        Self::new()
+    /// Checks whether the `PubSub` has no `Clients`, `Channels`, patterns,
+    /// or subscribers of any kind, as after `clear`.
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+            && self.detached.identifiers.is_empty()
+            && self.routing.channels.is_empty()
+            && self.routing.pattern_channels.is_empty()
+            && self.routing.firehose.is_empty()
+    }
+
+    /// Removes every `Client`, subscription, `Channel`, and pattern,
+    /// returning the removed `Clients`, in no particular order, so that
+    /// they can be shut down.
+    ///
+    /// Removals are reported to the lifecycle callbacks as if each `Client`
+    /// was removed with `remove_client`.  Settings, history, and retained
+    /// `Messages` are kept.  `Messages` still pending or micro-batched have
+    /// nobody left to reach, so they are dropped when delivered.  The
+    /// `ChannelHandles` of the removed `Channels` become invalid.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let clients: Vec<RecordingClient<u32, &str>> = (1..=3).map(RecordingClient::new).collect();
+    /// let mut pubsub = PubSub::new();
+    ///
+    /// for client in &clients {
+    ///     pubsub.add_client(client.clone()).unwrap();
+    ///     pubsub.sub_client(client.clone(), format!("user.{}", client.get_id())).unwrap();
+    /// }
+    /// pubsub.psub_client(clients[0].clone(), "user.*").unwrap();
+    ///
+    /// assert_eq!(pubsub.client_count(), 3);
+    /// assert_eq!(pubsub.channel_count(), 3);
+    /// assert_eq!(pubsub.pattern_count(), 1);
+    ///
+    /// let mut removed: Vec<u32> = pubsub.clear().iter().map(Client::get_id).collect();
+    /// removed.sort();
+    ///
+    /// assert_eq!(removed, vec![1, 2, 3]);
+    /// assert!(pubsub.is_empty());
+    /// assert_eq!((pubsub.client_count(), pubsub.channel_count()), (0, 0));
+    /// assert!(pubsub.check_invariants().is_ok());
+    /// ```
+    pub fn clear(&mut self) -> Vec<TClient> {
+        let identifiers: Vec<TIdentifier> = self.clients.values().map(Client::get_id).collect();
+        let mut removed = Vec::with_capacity(identifiers.len());
+
+        for identifier in identifiers {
+            removed.extend(self.remove_identifier(&identifier));
+            self.record(|| TraceEvent::RemoveClient(identifier));
+        }
+
+        for (identifier, detached) in std::mem::take(&mut self.detached.identifiers) {
+            self.stored_bytes -= detached.buffered_size(&self.sizer);
+
+            for buffered in detached.buffered {
+                self.drops.record(
+                    &buffered.channel,
+                    DropReason::DetachedRemoved,
+                    Some(&identifier),
+                );
+            }
+        }
+
+        let names: Vec<ChannelName> = self
+            .routing
+            .channels
+            .keys()
+            .chain(self.routing.pattern_channels.keys())
+            .cloned()
+            .collect();
+
+        for name in names {
+            self.routing.remove_channel(&name);
+        }
+
+        self.routing.firehose.clear();
+        self.filters.clear();
+        self.subscription_counts.clear();
+        self.subscription_ids.clear();
+        self.count_mutation();
+
+        removed
+    }
+
+    /// Releases the capacity the `PubSub` keeps around after a burst of
+    /// `Clients`, subscriptions, or `Messages`, such as once a spike of
+    /// connections is over.
+    ///
+    /// The slots of removed `Channels` are kept, so that their
+    /// `ChannelHandles` stay invalid.
+    pub fn shrink_to_fit(&mut self) {
+        self.clients.shrink_to_fit();
+        self.detached.identifiers.shrink_to_fit();
+        self.generations.shrink_to_fit();
+        self.pending.shrink_to_fit();
+        self.micro_batching.batches.shrink_to_fit();
+        self.retention.histories.shrink_to_fit();
+        self.subscription_counts.shrink_to_fit();
+        self.subscription_ids.shrink_to_fit();
+        self.filters.shrink_to_fit();
+        self.routing.shrink_to_fit();
+    }
+
+    fn check_generation(
+        &self,
+        identifier: &TIdentifier,
+        generation: ClientGeneration,
+    ) -> Result<(), PubSubError> {
+        match self.client_generation(identifier) {
+            Some(current) if current == generation => Ok(()),
+            Some(_) => Err(PubSubError::StaleGenerationError),
+            None => Err(PubSubError::ClientDoesNotExistError),
+        }
+    }
+
+    fn remove_identifier(&mut self, identifier: &TIdentifier) -> Option<TClient> {
+        let removed = self.clients.remove(identifier);
+        self.failures.get_mut().forget(identifier);
+
+        if let Some(detached) = self.detached.identifiers.remove(identifier) {
+            self.discard_detached(identifier, detached);
+        }
+
+        self.routing.remove_subscriber(identifier);
+        self.prune_filters();
+
+        if removed.is_some() {
+            self.routing.hooks.client_removed(identifier);
+        }
+
+        for counts in self.subscription_counts.values_mut() {
+            counts.remove(identifier);
+        }
+        self.subscription_ids
+            .retain(|_, (subscriber, _)| subscriber != identifier);
+
+        if self.ownership.on_owner_removed == OwnerRemoval::Release {
+            self.owners.retain(|_, owner| owner != identifier);
+        }
+
+        removed
+    }
+
+    /// Drops the `Messages` buffered for a detached identifier taken out of
+    /// `detached`.
+    fn discard_detached(&mut self, identifier: &TIdentifier, detached: Detached<TMessage>) {
+        self.stored_bytes -= detached.buffered_size(&self.sizer);
+
+        for buffered in detached.buffered {
+            self.drops.record(
+                &buffered.channel,
+                DropReason::DetachedRemoved,
+                Some(identifier),
+            );
+        }
+    }
+
+    /// Removes a `Client` for a grace period, such as while it reconnects,
+    /// keeping its subscriptions.
+    ///
+    /// While detached, `Messages` for the `Client` are buffered as for
+    /// restored subscriptions (see `restore_subscriptions`), up to the
+    /// capacity set with `set_detached_buffer_capacity`.  `reattach_client`
+    /// delivers them and resumes live delivery.  Once the grace period is
+    /// over, `sweep_detached` removes the `Client` like `remove_client`.
+    ///
+    /// Only `Messages` whose recipients are resolved while the `Client` is
+    /// detached are buffered.  Those published in
+    /// `RecipientCapture::AtPublish` or to a micro-batched `Channel` (see
+    /// `set_micro_batch`) are captured for the `Clients` of the `PubSub`
+    /// alone, so a detached `Client` never receives them, and one detached
+    /// after they were published has them dropped as
+    /// `DropReason::RecipientRemoved`.
+    ///
+    /// Results in a `PubSubError` when there is no `Client` with the
+    /// identifier.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{DropReason, RecipientCapture};
+    /// use std::time::Duration;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let grace = Duration::from_secs(30);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_detached_buffer_capacity(2);
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    ///
+    /// // Up to two `Messages` are buffered, then the oldest are dropped.
+    /// pubsub.detach_client(&1, grace).unwrap();
+    /// assert_eq!(pubsub.pending_identifiers(), vec![&1]);
+    /// for contents in ["a", "b", "c", "d"] {
+    ///     pubsub.pub_message("news", contents).unwrap();
+    /// }
+    /// assert!(client.contents().is_empty());
+    /// assert_eq!(pubsub.metrics().drops.get(&DropReason::DetachedBufferFull), Some(&2));
+    ///
+    /// // Adding the `Client` back replays the buffer, then resumes.
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// assert_eq!(client.contents(), vec!["c", "d"]);
+    /// assert!(pubsub.pending_identifiers().is_empty());
+    /// pubsub.pub_message("news", "e").unwrap();
+    /// assert_eq!(client.contents(), vec!["c", "d", "e"]);
+    ///
+    /// // Recipients captured at publish skip the detached `Client`.
+    /// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtPublish));
+    /// pubsub.detach_client(&1, grace).unwrap();
+    /// pubsub.pub_message("news", "f").unwrap();
+    /// pubsub.flush();
+    /// pubsub.reattach_client(client.clone()).unwrap();
+    /// assert_eq!(client.contents(), vec!["c", "d", "e"]);
+    ///
+    /// // As do those captured before it was detached.
+    /// pubsub.pub_message("news", "g").unwrap();
+    /// pubsub.detach_client(&1, grace).unwrap();
+    /// pubsub.flush();
+    /// pubsub.reattach_client(client.clone()).unwrap();
+    /// assert_eq!(client.contents(), vec!["c", "d", "e"]);
+    /// assert_eq!(pubsub.metrics().drops.get(&DropReason::RecipientRemoved), Some(&1));
+    /// ```
+    pub fn detach_client(
+        &mut self,
+        identifier: &TIdentifier,
+        grace: Duration,
+    ) -> Result<(), PubSubError> {
+        let (identifier, client) = self
+            .clients
+            .remove_entry(identifier)
+            .ok_or(PubSubError::ClientDoesNotExistError)?;
+
+        self.failures.get_mut().forget(&identifier);
+        self.routing.hooks.client_removed(&identifier);
+        let now = self.routing.clock.now();
+
+        self.detached.identifiers.insert(
+            identifier,
+            Detached {
+                expires: now.checked_add(grace),
+                ..Detached::new(now)
+            },
+        );
+
+        self.record(|| TraceEvent::Detach(client.get_id()));
+        self.count_mutation();
+
+        Ok(())
+    }
+
+    /// Adds back a `Client` detached with `detach_client`, delivering the
+    /// `Messages` buffered for it in order.
+    ///
+    /// Behaves exactly like `add_client`, except that it results in a
+    /// `PubSubError` when the identifier is not detached, such as when its
+    /// grace period was already swept.
+    pub fn reattach_client(&mut self, client: TClient) -> Result<(), PubSubError> {
+        if !self.detached.identifiers.contains_key(&client.get_id()) {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        self.add_client(client)?;
+
+        Ok(())
+    }
+
+    /// Removes every `Client` detached with `detach_client` whose grace
+    /// period is over, along with its subscriptions and buffered
+    /// `Messages`, returning how many were removed.
+    ///
+    /// Meant to be called on a timer.  Grace periods are measured with the
+    /// `Clock`.  The buffered `Messages` are dropped as
+    /// `DropReason::DetachedRemoved`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{DropReason, ManualClock};
+    /// use std::time::Duration;
+    ///
+    /// let clock = ManualClock::new();
+    /// let quick: RecordingClient<_, &str> = RecordingClient::new(1u32);
+    /// let slow: RecordingClient<_, &str> = RecordingClient::new(2u32);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_clock(clock.clone());
+    /// pubsub.set_detached_buffer_capacity(10);
+    /// pubsub.add_client(quick.clone()).unwrap();
+    /// pubsub.add_client(slow.clone()).unwrap();
+    /// pubsub.sub_client(quick.clone(), "news").unwrap();
+    /// pubsub.sub_client(slow.clone(), "news").unwrap();
+    ///
+    /// pubsub.detach_client(&1, Duration::from_secs(10)).unwrap();
+    /// pubsub.detach_client(&2, Duration::from_secs(60)).unwrap();
+    /// pubsub.pub_message("news", "a").unwrap();
+    /// pubsub.pub_message("news", "b").unwrap();
+    ///
+    /// assert_eq!(pubsub.sweep_detached(), 0);
+    ///
+    /// clock.advance(Duration::from_secs(10));
+    /// assert_eq!(pubsub.sweep_detached(), 1);
+    /// assert_eq!(pubsub.pending_identifiers(), vec![&2]);
+    /// assert_eq!(pubsub.metrics().drops.get(&DropReason::DetachedRemoved), Some(&2));
+    /// assert!(pubsub.reattach_client(quick.clone()).is_err());
+    /// assert!(pubsub.check_invariants().is_ok());
+    ///
+    /// pubsub.reattach_client(slow.clone()).unwrap();
+    /// assert_eq!(slow.contents(), vec!["a", "b"]);
+    /// assert!(quick.contents().is_empty());
+    /// ```
+    pub fn sweep_detached(&mut self) -> usize {
+        let now = self.routing.clock.now();
+
+        let (expired, detached): (HashMap<_, _>, HashMap<_, _>) =
+            std::mem::take(&mut self.detached.identifiers)
+                .into_iter()
+                .partition(|(_, detached)| detached.expires.is_some_and(|expires| expires <= now));
+
+        self.detached.identifiers = detached;
+        let removed = expired.len();
+
+        for (identifier, detached) in expired {
+            self.discard_detached(&identifier, detached);
+            self.remove_identifier(&identifier);
+            self.record(|| TraceEvent::RemoveClient(identifier));
+            self.count_mutation();
+        }
+
+        removed
+    }
+
+    /// Gets the `ChannelHandle` for a literal `Channel`, creating the `Channel`
+    /// if it does not exist yet.
+    ///
+    /// The name is always treated as a literal `Channel`, even if it contains
+    /// wildcard characters.
+    ///
+    /// Results in a `PubSubError` when the name is rejected by the
+    /// `ChannelNamePolicy`.
+    pub fn channel_handle<TChannel: Into<ChannelName>>(
+        &mut self,
+        name: TChannel,
+    ) -> Result<ChannelHandle, PubSubError> {
+        let name = self.channel_names.validate_name(name.into())?;
+
+        Ok(self.routing.channel_handle(name))
+    }
+
+    /// Creates `Channels` and patterns ahead of their first subscription,
+    /// such as on startup, so that the first subscriptions and publishes
+    /// do not pay for creating them.
+    ///
+    /// `channels` are created like with `channel_handle`, and `patterns`
+    /// are registered without subscribers.  Then each of `channels` is
+    /// checked against the patterns, so that publishing to one with a
+    /// single subscriber and no matching pattern skips matching from the
+    /// start.  Patterns without subscribers reach nobody, so warming up
+    /// does not change who receives what, but `gc` removes them again.
+    ///
+    /// Results in a `PubSubError` when a name is rejected by the
+    /// `ChannelNamePolicy`.  The names before it are already warmed up.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.warm_up(["orders.new", "orders.paid"], ["audit.*"]).unwrap();
+    ///
+    /// assert_eq!(pubsub.view().channels().count(), 2);
+    /// assert_eq!(pubsub.view().patterns().count(), 1);
+    ///
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "orders.new").unwrap();
+    /// pubsub.pub_message("orders.new", "first").unwrap();
+    /// pubsub.pub_message("orders.paid", "unheard").unwrap();
+    ///
+    /// assert_eq!(client.contents(), vec!["first"]);
+    /// ```
+    pub fn warm_up<C, P>(&mut self, channels: C, patterns: P) -> Result<(), PubSubError>
+    where
+        C: IntoIterator,
+        C::Item: Into<ChannelName>,
+        P: IntoIterator,
+        P::Item: Into<ChannelName>,
+    {
+        let mut handles = Vec::new();
+
+        for channel in channels {
+            handles.push(self.channel_handle(channel)?);
+        }
+
+        for pattern in patterns {
+            let pattern = self.channel_names.validate_name(pattern.into())?;
+            self.routing.add_pattern(pattern)?;
+        }
+
+        for handle in handles {
+            self.routing.prime_unmatched(handle);
+        }
+
+        Ok(())
+    }
+
+    /// Warms up the `Channels` and patterns of a snapshot, such as one
+    /// taken from the `PubSub` this one replaces, without their
+    /// subscriptions.  See `warm_up`.
+    pub fn warm_from_snapshot(
+        &mut self,
+        snapshot: &OwnedView<TIdentifier, TMatcher>,
+    ) -> Result<(), PubSubError> {
+        self.warm_up(
+            snapshot.channels().map(|(channel, _)| channel.to_string()),
+            snapshot.patterns().map(|(pattern, _)| pattern.to_string()),
+        )
+    }
+
+    /// Sets the rules `Channel` names must follow.
+    ///
+    /// Only subsequent calls are affected; existing subscriptions are kept.
+    pub fn set_channel_name_policy(&mut self, policy: ChannelNamePolicy) {
+        self.channel_names = policy;
+    }
+
+    /// Subscribes a `Client` to a `Channel`.
+    ///
+    /// The name is either borrowed for as long as the `PubSub` lives, as a
+    /// literal is, or handed over as an owned `String`, such as one built at
+    /// runtime.  Only owned names are allocated, and only when subscribing.
+    ///
+    /// Under the default `PatternSubscriptions::Explicit`, the name is a
+    /// literal `Channel` even when it contains wildcards, and patterns are
+    /// subscribed to with `psub_client`.  The wildcards are escaped with
+    /// `ChannelMatcher::escape`, and the escaped name is the one to publish
+    /// to and which `Messages` report.
+    ///
+    /// Results in a `PubSubError` when a `Client` attempts to subscribe to a
+    /// `Channel` that it is already subscribed to, unless subscriptions are
+    /// `DuplicateSubscriptions::Counted`, or when the `Channel` name is
+    /// rejected by the `ChannelNamePolicy`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    ///
+    /// // Patterns and literal names may overlap; each `Message` arrives once.
+    /// pubsub.psub_client(client.clone(), "news.*").unwrap();
+    /// pubsub.sub_client(client.clone(), "news.sports").unwrap();
+    /// assert_eq!(
+    ///     pubsub.sub_client(client.clone(), "news.sports"),
+    ///     Err(PubSubError::ClientAlreadySubscribedError {
+    ///         channel: "news.sports".to_string()
+    ///     })
+    /// );
+    ///
+    /// pubsub.pub_message("news.sports", "goal").unwrap();
+    /// assert_eq!(client.contents(), vec!["goal"]);
+    ///
+    /// pubsub.sub_client(client.clone(), format!("user.{}", 42)).unwrap();
+    /// pubsub.pub_message("user.42", "hello").unwrap();
+    /// assert_eq!(client.contents(), vec!["goal", "hello"]);
+    /// ```
+    pub fn sub_client<TChannel: Into<ChannelName>>(
+        &mut self,
+        client: TClient,
+        channel: TChannel,
+    ) -> Result<(), PubSubError> {
+        let channel = self.subscription_name(channel.into())?;
+        self.sub_identifier(channel, |_| client.get_id())
+    }
+
+    /// Subscribes a `Client` to a literal `Channel`, taking any wildcards in
+    /// its name literally whatever the `PatternSubscriptions`.
+    ///
+    /// Meant for names that cannot be sanitized, such as ones made from
+    /// user input.  The name is escaped as `sub_client` does under
+    /// `PatternSubscriptions::Explicit`.
+    ///
+    /// Results in a `PubSubError` when the `ChannelMatcher` cannot escape
+    /// the name, or as `sub_client` does.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{ChannelMatcher, PatternSubscriptions, PatternSyntax};
+    ///
+    /// let literal: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let other: RecordingClient<_, &str> = RecordingClient::new(2);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_pattern_subscriptions(PatternSubscriptions::Detected);
+    /// pubsub.add_client(literal.clone()).unwrap();
+    /// pubsub.add_client(other.clone()).unwrap();
+    ///
+    /// let resource = "uploads/*?.png";
+    /// pubsub.sub_client_exact(literal.clone(), resource).unwrap();
+    /// pubsub.sub_client(other.clone(), "uploads/a1.png").unwrap();
+    ///
+    /// let escaped = PatternSyntax::Glob.escape(resource);
+    /// assert_eq!(escaped, r"uploads/\*\?.png");
+    ///
+    /// pubsub.pub_message(&escaped, "literal").unwrap();
+    /// pubsub.pub_message("uploads/a1.png", "other").unwrap();
+    ///
+    /// assert_eq!(literal.contents(), vec!["literal"]);
+    /// assert_eq!(other.contents(), vec!["other"]);
+    ///
+    /// pubsub.unsub_client(literal.clone(), &escaped).unwrap();
+    /// ```
+    pub fn sub_client_exact<TChannel: Into<ChannelName>>(
+        &mut self,
+        client: TClient,
+        channel: TChannel,
+    ) -> Result<(), PubSubError> {
+        let channel = self.channel_names.validate_name(channel.into())?;
+        let channel = self.escape_name(channel)?;
+        self.sub_identifier(channel, |_| client.get_id())
+    }
+
+    /// Subscribes a `Client` to a system `Channel`: `name` after
+    /// `RESERVED_PREFIX`, which other methods reject.
+    ///
+    /// System `Channels` carry what the application publishes about the
+    /// `PubSub` itself, such as the `Channels` created and closed as told
+    /// by `set_first_subscriber_callback`, with `pub_system`.  `name` is
+    /// validated like any other name, and taken literally.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let watcher: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(watcher.clone()).unwrap();
+    ///
+    /// assert!(pubsub.sub_client(watcher.clone(), "$sys.rooms").is_err());
+    /// pubsub.sub_system(watcher.clone(), "rooms").unwrap();
+    /// assert_eq!(pubsub.view().subscriptions_of(&1).unwrap().channels, vec!["$sys.rooms"]);
+    ///
+    /// pubsub.pub_system("rooms", "lobby").unwrap();
+    /// assert!(pubsub.pub_message("$sys.rooms", "forged").is_err());
+    /// assert_eq!(watcher.received()[0].channel, "$sys.rooms");
+    ///
+    /// pubsub.unsub_system(watcher.clone(), "rooms").unwrap();
+    /// pubsub.pub_system("rooms", "unheard").unwrap();
+    /// assert_eq!(watcher.contents(), vec!["lobby"]);
+    /// ```
+    pub fn sub_system(&mut self, client: TClient, name: &str) -> Result<(), PubSubError> {
+        let channel = self.system_name(name)?;
+        self.sub_identifier(channel, |_| client.get_id())
+    }
+
+    /// Subscribes a `Client` to a pattern, whatever the
+    /// `PatternSubscriptions`.
+    ///
+    /// Results in a `PubSubError` when the name has no wildcards, when the
+    /// pattern is malformed, or as `sub_client` does.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    ///
+    /// pubsub.psub_client(client.clone(), "news.*").unwrap();
+    /// pubsub.pub_message("news.sports", "goal").unwrap();
+    ///
+    /// pubsub.punsub_client(client.clone(), "news.*").unwrap();
+    /// pubsub.pub_message("news.sports", "unheard").unwrap();
+    ///
+    /// assert_eq!(client.contents(), vec!["goal"]);
+    ///
+    /// assert!(matches!(
+    ///     pubsub.psub_client(client.clone(), "news"),
+    ///     Err(PubSubError::InvalidPatternError { .. })
+    /// ));
+    /// ```
+    pub fn psub_client<TChannel: Into<ChannelName>>(
+        &mut self,
+        client: TClient,
+        pattern: TChannel,
+    ) -> Result<(), PubSubError> {
+        let pattern = self.channel_names.validate_name(pattern.into())?;
+        self.check_pattern(&pattern)?;
+        self.sub_identifier(pattern, |_| client.get_id())
+    }
+
+    /// Unsubscribes a `Client` from a pattern, whatever the
+    /// `PatternSubscriptions`.
+    ///
+    /// Results in a `PubSubError` when the name has no wildcards, or as
+    /// `unsub_client` does.
+    pub fn punsub_client(&mut self, client: TClient, pattern: &str) -> Result<(), PubSubError> {
+        let pattern = self.channel_names.validate(pattern)?;
+        self.check_pattern(pattern)?;
+        self.unsub_identifier(pattern, |_| client.get_id())
+    }
+
+    /// Subscribes the `Client` with an identifier to a `Channel`, like
+    /// `sub_client` but without handing over the `Client` itself, which
+    /// therefore does not need to be `Clone`.
+    ///
+    /// Not to be confused with `sub_with_id`, which returns a
+    /// `SubscriptionId`.
+    ///
+    /// Results in a `PubSubError` when there is no `Client` with the
+    /// identifier, or as `sub_client` does.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use std::sync::mpsc::{channel, SendError, Sender};
+    ///
+    /// // Not `Clone`, like a `Client` owning a connection.
+    /// struct Connection {
+    ///     id: u32,
+    ///     outbox: Sender<String>,
+    /// }
+    ///
+    /// impl Client<u32, &str> for Connection {
+    ///     type Error = SendError<String>;
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         self.id
+    ///     }
+    ///
+    ///     fn send(&mut self, message: &Message<&str>) -> Result<(), SendError<String>> {
+    ///         self.outbox.send(message.contents.to_string())
+    ///     }
+    /// }
+    ///
+    /// let (outbox, inbox) = channel();
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(Connection { id: 1, outbox }).unwrap();
+    ///
+    /// pubsub.sub_id(&1, "news").unwrap();
+    /// pubsub.pub_message("news", "heard").unwrap();
+    /// pubsub.unsub_id(&1, "news").unwrap();
+    /// pubsub.pub_message("news", "unheard").unwrap();
+    ///
+    /// assert_eq!(inbox.try_iter().collect::<Vec<_>>(), vec!["heard"]);
+    ///
+    /// assert!(matches!(
+    ///     pubsub.sub_id(&2, "news"),
+    ///     Err(PubSubError::ClientDoesNotExistError)
+    /// ));
+    ///
+    /// pubsub.remove_client_by_id(&1).unwrap();
+    /// assert!(pubsub.remove_client_by_id(&1).is_err());
+    /// ```
+    pub fn sub_id<TChannel: Into<ChannelName>>(
+        &mut self,
+        identifier: &TIdentifier,
+        channel: TChannel,
+    ) -> Result<(), PubSubError> {
+        if !self.clients.contains_key(identifier) {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        let channel = self.subscription_name(channel.into())?;
+        self.sub_identifier(channel, |clients| clients[identifier].get_id())
+    }
+
+    /// Makes sure the `Client` with an identifier is subscribed to a
+    /// `Channel`, returning whether a new subscription was made.
+    ///
+    /// Unlike `sub_id`, being already subscribed is not an error, even
+    /// under `DuplicateSubscriptions::Counted`, where the count is left as
+    /// it is.  Handy for reconnections and retried requests.
+    ///
+    /// Results in a `PubSubError` when there is no `Client` with the
+    /// identifier, or when the `Channel` name is rejected.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    ///
+    /// assert!(pubsub.ensure_subscribed(&1, "news").unwrap());
+    /// assert!(!pubsub.ensure_subscribed(&1, "news").unwrap());
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 1);
+    ///
+    /// assert!(pubsub.ensure_unsubscribed(&1, "news").unwrap());
+    /// assert!(!pubsub.ensure_unsubscribed(&1, "news").unwrap());
+    ///
+    /// assert!(matches!(
+    ///     pubsub.ensure_subscribed(&2, "news"),
+    ///     Err(PubSubError::ClientDoesNotExistError)
+    /// ));
+    /// ```
+    pub fn ensure_subscribed<TChannel: Into<ChannelName>>(
+        &mut self,
+        identifier: &TIdentifier,
+        channel: TChannel,
+    ) -> Result<bool, PubSubError> {
+        if !self.clients.contains_key(identifier) {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        let channel = self.subscription_name(channel.into())?;
+
+        if self.routing.is_subscribed(identifier, &channel) {
+            return Ok(false);
+        }
+
+        self.sub_identifier(channel, |clients| clients[identifier].get_id())?;
+
+        Ok(true)
+    }
+
+    /// Subscribes the `Client` with an identifier to a `Channel` as the
+    /// `SubscribeOptions` say, returning whether a new subscription was
+    /// made.
+    ///
+    /// `sub_id` is the same as passing `SubscribeOptions::default()`, except
+    /// for the result.  It is only `false` when the options are
+    /// `idempotent` and the `Client` is already subscribed.
+    ///
+    /// Results in a `PubSubError` when there is no `Client` with the
+    /// identifier, or as `sub_id` does.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{PatternSubscriptions, SubscribeOptions};
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_pattern_subscriptions(PatternSubscriptions::Detected);
+    /// pubsub.add_client(client.clone()).unwrap();
+    ///
+    /// let once = SubscribeOptions {
+    ///     once: true,
+    ///     ..SubscribeOptions::default()
+    /// };
+    /// assert!(pubsub.sub_client_with(&1, "orders.*", once).unwrap());
+    ///
+    /// pubsub.pub_message("orders.new", "first").unwrap();
+    /// pubsub.pub_message("orders.new", "second").unwrap();
+    /// assert_eq!(client.contents(), vec!["first"]);
+    /// assert_eq!(pubsub.subscription_count_for(&1, "orders.*"), 0);
+    ///
+    /// let idempotent = SubscribeOptions {
+    ///     idempotent: true,
+    ///     ..SubscribeOptions::default()
+    /// };
+    /// assert!(pubsub.sub_client_with(&1, "news", idempotent).unwrap());
+    /// assert!(!pubsub.sub_client_with(&1, "news", idempotent).unwrap());
+    /// assert!(pubsub.sub_client_with(&1, "news", SubscribeOptions::default()).is_err());
+    /// ```
+    pub fn sub_client_with<TChannel: Into<ChannelName>>(
+        &mut self,
+        identifier: &TIdentifier,
+        channel: TChannel,
+        options: SubscribeOptions,
+    ) -> Result<bool, PubSubError> {
+        if !self.clients.contains_key(identifier) {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        let channel = self.subscription_name(channel.into())?;
+
+        if options.idempotent && self.routing.is_subscribed(identifier, &channel) {
+            return Ok(false);
+        }
+
+        self.sub_identifier_with(
+            Subscription::Name(&channel),
+            |clients| clients[identifier].get_id(),
+            options.deliver_retained,
+        )?;
+
+        // A `Client` failing to take its retained `Messages` may have been
+        // evicted while subscribing.
+        if options.once && self.routing.is_subscribed(identifier, &channel) {
+            self.routing
+                .once
+                .entry(channel)
+                .or_default()
+                .insert(self.clients[identifier].get_id());
+        }
+
+        Ok(true)
+    }
+
+    /// Removes the `SubscribeOptions::once` subscriptions that delivered a
+    /// `Message`, whatever their count.
+    fn unsub_spent(&mut self, spent: Vec<(String, TIdentifier)>) {
+        for (subscription, identifier) in spent {
+            if !self.clients.contains_key(&identifier)
+                || !self.routing.is_subscribed(&identifier, &subscription)
+            {
+                continue;
+            }
+
+            if let Some(counts) = self.subscription_counts.get_mut(subscription.as_str()) {
+                counts.remove(&identifier);
+            }
+
+            self.subscription_ids.retain(|_, (subscriber, channel)| {
+                *subscriber != identifier || channel.as_ref() != subscription
+            });
+
+            let _ =
+                self.unsub_identifier(&subscription, |pubsub| pubsub.clients[&identifier].get_id());
+        }
+    }
+
+    /// Makes sure the `Client` with an identifier is not subscribed to a
+    /// `Channel`, returning whether a subscription was removed.
+    ///
+    /// Unlike `unsub_id`, not being subscribed is not an error, and under
+    /// `DuplicateSubscriptions::Counted` the subscription is removed
+    /// whatever its count.
+    ///
+    /// Results in a `PubSubError` when there is no `Client` with the
+    /// identifier, or when the `Channel` name is rejected.
+    pub fn ensure_unsubscribed(
+        &mut self,
+        identifier: &TIdentifier,
+        channel: &str,
+    ) -> Result<bool, PubSubError> {
+        if !self.clients.contains_key(identifier) {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        let channel = self.unsubscription_name(channel)?;
+
+        if !self.routing.is_subscribed(identifier, &channel) {
+            return Ok(false);
+        }
+
+        if let Some(counts) = self.subscription_counts.get_mut(channel.as_ref()) {
+            counts.remove(identifier);
+        }
+
+        self.unsub_identifier(&channel, |pubsub| pubsub.clients[identifier].get_id())?;
+
+        Ok(true)
+    }
+
+    /// Subscribes the `Client` with an identifier to several `Channels` at
+    /// once, like `sub_id` does to each of them, but either to all of them
+    /// or, on failure, to none.
+    ///
+    /// Every `Channel` is checked, and the memory for all the subscriptions
+    /// reserved, before any subscription is made, so a failure leaves no
+    /// trace: no callback is notified and no control or retained `Message`
+    /// is sent.  Results in a `BulkSubError` naming the first `Channel`
+    /// that `sub_id` would reject, such as one the `Client` is already
+    /// subscribed to, or listed twice unless subscriptions are
+    /// `DuplicateSubscriptions::Counted`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "billing").unwrap();
+    ///
+    /// let error = pubsub
+    ///     .sub_client_many(&1, &["news", "orders", "billing"])
+    ///     .unwrap_err();
+    ///
+    /// assert_eq!(error.channel.as_deref(), Some("billing"));
+    /// assert!(matches!(
+    ///     error.error,
+    ///     PubSubError::ClientAlreadySubscribedError { .. }
+    /// ));
+    /// // Neither of the other `Channels` was subscribed to.
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 0);
+    /// assert_eq!(pubsub.subscription_count_for(&1, "orders"), 0);
+    ///
+    /// pubsub.sub_client_many(&1, &["news", "orders"]).unwrap();
+    /// pubsub.unsub_client_many(&1, &["news", "billing"]).unwrap();
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 0);
+    /// assert_eq!(pubsub.subscription_count_for(&1, "orders"), 1);
+    /// assert_eq!(pubsub.subscription_count_for(&1, "billing"), 0);
+    /// ```
+    ///
+    /// A failure partway through the list has no side effects:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let client: RecordingClient<_, String> = RecordingClient::new(1);
+    /// let notified = Arc::new(AtomicUsize::new(0));
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.set_control_encoder(|event| format!("{:?} {}", event.kind, event.channel));
+    /// pubsub.pub_message_retained("news", "retained".to_string()).unwrap();
+    ///
+    /// let counter = notified.clone();
+    /// pubsub.set_subscribe_callback(move |_, _| {
+    ///     counter.fetch_add(1, Ordering::Relaxed);
+    /// });
+    ///
+    /// let error = pubsub
+    ///     .sub_client_many(&1, &["news", "orders", "news", "sports"])
+    ///     .unwrap_err();
+    /// assert_eq!(error.channel.as_deref(), Some("news"));
+    ///
+    /// assert_eq!(notified.load(Ordering::Relaxed), 0);
+    /// assert!(client.contents().is_empty());
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 0);
+    /// assert!(pubsub.check_invariants().is_ok());
+    ///
+    /// pubsub.sub_client_many(&1, &["news", "orders"]).unwrap();
+    /// assert_eq!(notified.load(Ordering::Relaxed), 2);
+    /// assert_eq!(
+    ///     client.contents(),
+    ///     vec!["Subscribed news", "retained", "Subscribed orders"]
+    /// );
+    /// ```
+    pub fn sub_client_many(
+        &mut self,
+        identifier: &TIdentifier,
+        channels: &[&str],
+    ) -> Result<(), BulkSubError> {
+        if !self.clients.contains_key(identifier) {
+            return Err(BulkSubError {
+                channel: None,
+                error: PubSubError::ClientDoesNotExistError,
+            });
+        }
+
+        let rejected = |channel: &str, error| BulkSubError {
+            channel: Some(channel.to_string()),
+            error,
+        };
+
+        let mut names: Vec<ChannelName> = Vec::with_capacity(channels.len());
+
+        for channel in channels {
+            let name = self
+                .subscription_name(ChannelName::from(channel.to_string()))
+                .and_then(|name| self.check_sub(Subscription::Name(&name), identifier))
+                .map_err(|error| rejected(channel, error))?;
+
+            if self.duplicate_subscriptions == DuplicateSubscriptions::Reject
+                && names.contains(&name)
+            {
+                let error = PubSubError::ClientAlreadySubscribedError {
+                    channel: name.to_string(),
+                };
+                return Err(rejected(channel, error));
+            }
+
+            names.push(name);
+        }
+
+        self.reserve_memory(names.len() * size_of::<TIdentifier>())
+            .map_err(|error| BulkSubError {
+                channel: None,
+                error,
+            })?;
+
+        for name in names {
+            // A `Client` failing to take its retained `Messages` may have
+            // been evicted, along with the subscriptions made so far.
+            if !self.clients.contains_key(identifier) {
+                break;
+            }
+
+            self.commit_sub(
+                Subscription::Name(&name),
+                name.clone(),
+                &|clients: &HashMap<TIdentifier, TClient>| clients[identifier].get_id(),
+                true,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Unsubscribes the `Client` with an identifier from several `Channels`
+    /// at once, like `unsub_id` does from each of them, but either from all
+    /// of them or, on failure, from none.
+    ///
+    /// Results in a `BulkSubError` naming the first `Channel` that
+    /// `unsub_id` would reject, such as one the `Client` is not subscribed
+    /// to, or listed more times than it is subscribed.  See
+    /// `sub_client_many`.
+    pub fn unsub_client_many(
+        &mut self,
+        identifier: &TIdentifier,
+        channels: &[&str],
+    ) -> Result<(), BulkSubError> {
+        if !self.clients.contains_key(identifier) {
+            return Err(BulkSubError {
+                channel: None,
+                error: PubSubError::ClientDoesNotExistError,
+            });
+        }
+
+        let mut names: Vec<Cow<str>> = Vec::with_capacity(channels.len());
+
+        for channel in channels {
+            let name = self
+                .unsubscription_name(channel)
+                .map_err(|error| BulkSubError {
+                    channel: Some(channel.to_string()),
+                    error,
+                })?;
+
+            let listed = names.iter().filter(|listed| **listed == name).count();
+
+            if listed >= self.subscription_count_for(identifier, &name) {
+                return Err(BulkSubError {
+                    channel: Some(channel.to_string()),
+                    error: PubSubError::ClientNotSubscribedError {
+                        channel: name.to_string(),
+                    },
+                });
+            }
+
+            names.push(name);
+        }
+
+        // Checked above, so none of these can fail.
+        for name in &names {
+            // A `Client` failing to take a confirmation may have been
+            // evicted, along with its remaining subscriptions.
+            if !self.clients.contains_key(identifier) {
+                break;
+            }
+
+            let _ = self.unsub_identifier(name, |pubsub| pubsub.clients[identifier].get_id());
+        }
+
+        Ok(())
+    }
+
+    fn check_pattern(&self, pattern: &str) -> Result<(), PubSubError> {
+        match self.routing.is_pattern(pattern) {
+            true => Ok(()),
+            false => Err(PubSubError::InvalidPatternError {
+                pattern: pattern.to_string(),
+                reason: PatternError::new("the pattern has no wildcards"),
+            }),
+        }
+    }
+
+    /// Escapes the wildcards of a literal `Channel` name.
+    fn escape_literal<'n>(&self, channel: &'n str) -> Result<Cow<'n, str>, PubSubError> {
+        let escaped = self.routing.matcher.escape(channel);
+
+        match self.routing.is_pattern(&escaped) {
+            true => Err(PubSubError::InvalidChannelNameError {
+                name: channel.to_string(),
+                reason: InvalidChannelNameReason::Unescapable,
+            }),
+            false => Ok(escaped),
+        }
+    }
+
+    /// Escapes a literal `Channel` name like `escape_literal`, keeping it
+    /// as it was given when there is nothing to escape.
+    fn escape_name(&self, channel: ChannelName) -> Result<ChannelName, PubSubError> {
+        let escaped = match self.escape_literal(&channel)? {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(escaped) => Some(escaped),
+        };
+
+        Ok(escaped.map_or(channel, ChannelName::from))
+    }
+
+    /// The system `Channel` named `name` after `RESERVED_PREFIX`, escaped so
+    /// that it is taken literally.
+    fn system_name(&self, name: &str) -> Result<ChannelName, PubSubError> {
+        let name = self.channel_names.validate(name)?;
+        let channel = ChannelName::from(format!("{}{}", RESERVED_PREFIX, name));
+
+        self.escape_name(channel)
+    }
+
+    /// Validates a name given to a subscribing method, escaping it under
+    /// `PatternSubscriptions::Explicit`.
+    fn subscription_name(&self, channel: ChannelName) -> Result<ChannelName, PubSubError> {
+        let channel = self.channel_names.validate_name(channel)?;
+
+        match self.pattern_subscriptions {
+            PatternSubscriptions::Explicit => self.escape_name(channel),
+            PatternSubscriptions::Detected => Ok(channel),
+        }
+    }
+
+    /// Validates a name given to an unsubscribing method like
+    /// `subscription_name`.
+    fn unsubscription_name<'n>(&self, channel: &'n str) -> Result<Cow<'n, str>, PubSubError> {
+        let channel = self.channel_names.validate(channel)?;
+
+        match self.pattern_subscriptions {
+            PatternSubscriptions::Explicit => self.escape_literal(channel),
+            PatternSubscriptions::Detected => Ok(Cow::Borrowed(channel)),
+        }
+    }
+
+    fn sub_identifier<F>(&mut self, channel: ChannelName, get_id: F) -> Result<(), PubSubError>
+    where
+        F: Fn(&HashMap<TIdentifier, TClient>) -> TIdentifier,
+    {
+        self.sub_identifier_with(Subscription::Name(&channel), get_id, true)
+    }
+
+    /// Subscribes the identifier returned by `get_id`, applying the
+    /// policies, accounting and notifications every subscription goes
+    /// through.
+    fn sub_identifier_with<F>(
+        &mut self,
+        subscription: Subscription,
+        get_id: F,
+        deliver_retained: bool,
+    ) -> Result<(), PubSubError>
+    where
+        F: Fn(&HashMap<TIdentifier, TClient>) -> TIdentifier,
+    {
+        let channel = self.prepare_sub(subscription, &get_id)?;
+        self.commit_sub(subscription, channel, &get_id, deliver_retained);
+
+        Ok(())
+    }
+
+    /// Makes a subscription loaded in bulk, checked and counted like any
+    /// other, but neither confirmed, nor sent retained `Messages`, nor
+    /// recorded.
+    fn load_sub<F>(&mut self, subscription: Subscription, get_id: F) -> Result<(), PubSubError>
+    where
+        F: Fn(&HashMap<TIdentifier, TClient>) -> TIdentifier,
+    {
+        let channel = self.prepare_sub(subscription, &get_id)?;
+
+        if self.add_sub(subscription, channel, &get_id) {
+            self.count_mutation();
+        }
+
+        Ok(())
+    }
+
+    /// Checks a subscription with `check_sub`, then reserves its memory.
+    /// Returns the name of the subscription.
+    fn prepare_sub<F>(
+        &mut self,
+        subscription: Subscription,
+        get_id: &F,
+    ) -> Result<ChannelName, PubSubError>
+    where
+        F: Fn(&HashMap<TIdentifier, TClient>) -> TIdentifier,
+    {
+        let channel = self.check_sub(subscription, &get_id(&self.clients))?;
+
+        if !self.counts_again(subscription, &get_id(&self.clients), &channel) {
+            self.reserve_memory(size_of::<TIdentifier>())?;
+        }
+
+        Ok(channel)
+    }
+
+    /// Checks everything that would make `sub_identifier_with` fail, other
+    /// than the memory budget, without changing anything.  Returns the name
+    /// of the subscription.
+    fn check_sub(
+        &self,
+        subscription: Subscription,
+        identifier: &TIdentifier,
+    ) -> Result<ChannelName, PubSubError> {
+        let channel = match subscription {
+            Subscription::Name(channel) => channel.clone(),
+            Subscription::Handle(handle) => self.routing.channel_name(handle)?,
+            Subscription::Firehose => ChannelName::from(FIREHOSE),
+        };
+
+        if self.unknown_subscribers == UnknownSubscriberPolicy::Reject
+            && !self.clients.contains_key(identifier)
+            && !self.detached.identifiers.contains_key(identifier)
+        {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        if self.counts_again(subscription, identifier, &channel) {
+            return Ok(channel);
+        }
+
+        match subscription {
+            Subscription::Firehose if self.routing.firehose.contains(identifier) => {
+                Err(PubSubError::AlreadyOnFirehoseError)
+            }
+            Subscription::Firehose => Ok(channel),
+            _ if self.routing.is_subscribed(identifier, &channel) => {
+                Err(PubSubError::ClientAlreadySubscribedError {
+                    channel: channel.to_string(),
+                })
+            }
+            Subscription::Name(_) => self.routing.check_subscription(&channel).map(|_| channel),
+            Subscription::Handle(_) => Ok(channel),
+        }
+    }
+
+    /// Whether subscribing again only counts the subscription once more,
+    /// under `DuplicateSubscriptions::Counted`.
+    fn counts_again(
+        &self,
+        subscription: Subscription,
+        identifier: &TIdentifier,
+        channel: &str,
+    ) -> bool {
+        self.duplicate_subscriptions == DuplicateSubscriptions::Counted
+            && !matches!(subscription, Subscription::Firehose)
+            && self.routing.is_subscribed(identifier, channel)
+    }
+
+    /// Makes a subscription that passed `prepare_sub`, confirming it and
+    /// sending it the retained `Messages`.
+    fn commit_sub<F>(
+        &mut self,
+        subscription: Subscription,
+        channel: ChannelName,
+        get_id: &F,
+        deliver_retained: bool,
+    ) where
+        F: Fn(&HashMap<TIdentifier, TClient>) -> TIdentifier,
+    {
+        if !self.add_sub(subscription, channel.clone(), get_id) {
+            return;
+        }
+
+        // Taken before anything is sent, since a failed send may evict the
+        // `Client`.
+        let identifier = get_id(&self.clients);
+        let confirmed = self.confirm(
+            get_id(&self.clients),
+            ControlEventKind::Subscribed,
+            &channel,
+        );
+        let retained = deliver_retained && self.deliver_retained(&channel, &identifier, get_id);
+
+        if confirmed || retained {
+            self.flush_delivered(&identifier);
+        }
+
+        self.record(|| match subscription {
+            Subscription::Firehose => TraceEvent::SubFirehose(identifier),
+            _ => TraceEvent::Sub(identifier, channel.to_string()),
+        });
+        self.count_mutation();
+    }
+
+    /// Adds a subscription that passed `prepare_sub` to the `Routing`, or
+    /// counts it once more, returning whether it was added.
+    fn add_sub<F>(&mut self, subscription: Subscription, channel: ChannelName, get_id: &F) -> bool
+    where
+        F: Fn(&HashMap<TIdentifier, TClient>) -> TIdentifier,
+    {
+        if self.counts_again(subscription, &get_id(&self.clients), &channel) {
+            let identifier = get_id(&self.clients);
+
+            *self
+                .subscription_counts
+                .entry(channel)
+                .or_default()
+                .entry(identifier)
+                .or_insert(1) += 1;
+
+            return false;
+        }
+
+        let clients = &self.clients;
+        let subscribed = self.routing.subscribe_to(subscription, || get_id(clients));
+        debug_assert_eq!(subscribed, Ok(true), "checked by check_sub");
+
+        true
+    }
+
+    /// Sends a new subscriber the retained `Messages` of the `Channels` its
+    /// subscription matches, oldest first, returning whether there were
+    /// any.  Stops early if the subscriber is evicted.
+    fn deliver_retained<F>(
+        &mut self,
+        subscription: &str,
+        identifier: &TIdentifier,
+        get_id: &F,
+    ) -> bool
+    where
+        F: Fn(&HashMap<TIdentifier, TClient>) -> TIdentifier,
+    {
+        if self.retention.retained.is_empty() {
+            return false;
+        }
+
+        let mut channels = match self.routing.is_pattern(subscription) {
+            true => self
+                .routing
+                .names_matching(
+                    subscription,
+                    self.retention.retained.keys().map(String::as_str),
+                )
+                .unwrap_or_default(),
+            false => self
+                .retention
+                .retained
+                .get_key_value(subscription)
+                .map(|(channel, _)| channel.as_str())
+                .into_iter()
+                .collect(),
+        };
+
+        channels.sort_by_key(|channel| self.retention.retained[*channel].0);
+
+        let retained: Vec<(String, u64, TMessage)> = channels
+            .into_iter()
+            .filter(|channel| {
+                self.filters.admits(
+                    subscription,
+                    identifier,
+                    &self.retention.retained[*channel].1,
+                )
+            })
+            .map(|channel| {
+                let (sequence, contents) = &self.retention.retained[channel];
+                (channel.to_string(), *sequence, contents.clone())
+            })
+            .collect();
+
+        let delivered = !retained.is_empty();
+
+        for (channel, sequence, contents) in retained {
+            if !self.clients.contains_key(identifier) {
+                break;
+            }
+
+            let spent = self.deliver_to(
+                get_id(&self.clients),
+                &channel,
+                contents,
+                sequence,
+                StoredVia::Channel,
+            );
+            self.unsub_spent(spent);
+        }
+
+        delivered
+    }
+
+    /// Subscribes a `Client` to the `Channel` referred to by a `ChannelHandle`.
+    ///
+    /// The subscription is made like `sub_client` makes it: callbacks are
+    /// notified, the retained `Message` is sent, and
+    /// `DuplicateSubscriptions` applies.
+    ///
+    /// Results in a `PubSubError` when the handle does not refer to a
+    /// `Channel` of this `PubSub`, such as once the `Channel` was removed, or
+    /// when the subscription is refused as `sub_client` would refuse it.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{EmptyChannels, UnknownSubscriberPolicy};
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    ///
+    /// pubsub.pub_message_retained("news", "retained").unwrap();
+    /// let news = pubsub.channel_handle("news").unwrap();
+    /// pubsub.sub_by_handle(client.clone(), news).unwrap();
+    /// assert_eq!(client.contents(), vec!["retained"]);
+    ///
+    /// // Once the `Channel` is removed, its handle is invalid, even after a
+    /// // `Channel` with the same name, or in the same slot, is created.
+    /// pubsub.remove_channel("news");
+    /// let sports = pubsub.channel_handle("sports").unwrap();
+    /// let news_again = pubsub.channel_handle("news").unwrap();
+    /// assert_ne!(news, news_again);
+    /// assert_eq!(
+    ///     pubsub.sub_by_handle(client.clone(), news),
+    ///     Err(PubSubError::InvalidChannelHandleError)
+    /// );
+    /// pubsub.sub_by_handle(client.clone(), sports).unwrap();
+    /// pubsub.sub_by_handle(client.clone(), news_again).unwrap();
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 1);
+    /// assert_eq!(pubsub.subscription_count_for(&1, "sports"), 1);
+    ///
+    /// // Likewise once the `Channel` is pruned for having no subscribers.
+    /// pubsub.set_empty_channels(EmptyChannels::Remove);
+    /// pubsub.unsub_client(client.clone(), "sports").unwrap();
+    /// assert_eq!(
+    ///     pubsub.sub_by_handle(client.clone(), sports),
+    ///     Err(PubSubError::InvalidChannelHandleError)
+    /// );
+    /// let sports_again = pubsub.channel_handle("sports").unwrap();
+    /// pubsub.sub_by_handle(client.clone(), sports_again).unwrap();
+    ///
+    /// // The same checks as `sub_client` apply.
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Reject);
+    /// let stranger: RecordingClient<_, &str> = RecordingClient::new(2);
+    /// assert_eq!(
+    ///     pubsub.sub_by_handle(stranger, news_again),
+    ///     Err(PubSubError::ClientDoesNotExistError)
+    /// );
+    /// assert!(pubsub.sub_by_handle(client.clone(), news_again).is_err());
+    /// ```
+    pub fn sub_by_handle(
+        &mut self,
+        client: TClient,
+        handle: ChannelHandle,
+    ) -> Result<(), PubSubError> {
+        self.sub_identifier_with(Subscription::Handle(handle), |_| client.get_id(), true)
+    }
+
+    /// Subscribes a `Client` to every literal `Channel` that currently exists.
+    ///
+    /// Each subscription is made like `sub_client` makes it.  `Channels`
+    /// the `Client` is already subscribed to are skipped, as are those the
+    /// subscription is refused to, such as once the memory budget is spent.
+    /// Returns the number of new subscriptions.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let subscribed = Arc::new(Mutex::new(Vec::new()));
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    ///
+    /// let log = subscribed.clone();
+    /// pubsub.set_subscribe_callback(move |_, channel| log.lock().unwrap().push(channel.to_string()));
+    /// pubsub.channel_handle("news").unwrap();
+    /// pubsub.pub_message_retained("sports", "retained").unwrap();
+    /// pubsub.channel_handle("sports").unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    ///
+    /// assert_eq!(pubsub.sub_all_existing(client.clone()), 1);
+    /// assert_eq!(*subscribed.lock().unwrap(), vec!["news", "sports"]);
+    /// assert_eq!(client.contents(), vec!["retained"]);
+    /// assert_eq!(pubsub.sub_all_existing(client.clone()), 0);
+    /// ```
+    pub fn sub_all_existing(&mut self, client: TClient) -> usize {
+        let identifier = client.get_id();
+        let handles: Vec<ChannelHandle> = self
+            .routing
+            .live_entries()
+            .filter(|(_, entry)| !entry.subscribers.contains(&identifier))
+            .map(|(handle, _)| handle)
+            .collect();
+
+        handles
+            .into_iter()
+            .filter(|handle| {
+                self.sub_identifier_with(Subscription::Handle(*handle), |_| client.get_id(), true)
+                    .is_ok()
+            })
+            .count()
+    }
+
+    /// Makes a `Client` a recipient of every `Message` published to any
+    /// `Channel`, whether or not the `Channel` exists.
+    ///
+    /// This is cheaper than subscribing to the `"*"` pattern, since no
+    /// matching is involved.  A `Client` reached through both the firehose
+    /// and a normal subscription still receives each `Message` once.
+    ///
+    /// Results in a `PubSubError` when the `Client` is already on the firehose.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let auditor: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(auditor.clone()).unwrap();
+    /// pubsub.sub_firehose(auditor.clone()).unwrap();
+    ///
+    /// pubsub.pub_message("anything", "seen").unwrap();
+    /// assert_eq!(auditor.contents(), vec!["seen"]);
+    /// ```
+    ///
+    /// Callbacks and control `Messages` report the firehose under an empty
+    /// name:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::PubSubError;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let auditor: RecordingClient<_, String> = RecordingClient::new(1);
+    /// let changes = Arc::new(Mutex::new(Vec::new()));
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(auditor.clone()).unwrap();
+    /// pubsub.set_control_encoder(|event| format!("{:?} {:?}", event.kind, event.channel));
+    ///
+    /// let log = changes.clone();
+    /// pubsub.set_subscribe_callback(move |_, name| log.lock().unwrap().push(format!("+{:?}", name)));
+    /// let log = changes.clone();
+    /// pubsub.set_unsubscribe_callback(move |_, name| log.lock().unwrap().push(format!("-{:?}", name)));
+    ///
+    /// pubsub.sub_firehose(auditor.clone()).unwrap();
+    /// assert_eq!(pubsub.sub_firehose(auditor.clone()), Err(PubSubError::AlreadyOnFirehoseError));
+    /// pubsub.unsub_firehose(auditor.clone()).unwrap();
+    ///
+    /// assert_eq!(*changes.lock().unwrap(), vec!["+\"\"", "-\"\""]);
+    /// assert_eq!(auditor.contents(), vec!["Subscribed \"\"", "Unsubscribed \"\""]);
+    /// ```
+    pub fn sub_firehose(&mut self, client: TClient) -> Result<(), PubSubError> {
+        self.sub_identifier_with(Subscription::Firehose, |_| client.get_id(), false)
+    }
+
+    /// Removes a `Client` from the firehose.
+    ///
+    /// Results in a `PubSubError` when the `Client` is not on the firehose.
+    pub fn unsub_firehose(&mut self, client: TClient) -> Result<(), PubSubError> {
+        if !self.routing.unsubscribe_firehose(&client.get_id()) {
+            return Err(PubSubError::NotOnFirehoseError);
+        }
+
+        if self.confirm(client.get_id(), ControlEventKind::Unsubscribed, FIREHOSE) {
+            self.flush_delivered(&client.get_id());
+        }
+
+        self.record(|| TraceEvent::UnsubFirehose(client.get_id()));
+        self.count_mutation();
+
+        Ok(())
+    }
+
+    /// Subscribes a `Client` to a `Channel`, delivering only a sample of the
+    /// `Messages` published to it.
+    ///
+    /// Other subscribers of the `Channel` are unaffected.  When the `Client`
+    /// reaches a `Channel` through several subscriptions, the
+    /// `PatternResolution` decides which of them are sampled.
+    ///
+    /// `Sampling::Rate` draws from a generator that can be seeded with
+    /// `set_sampling_seed` for reproducible results.
+    ///
+    /// Results in a `PubSubError` when a `Client` attempts to subscribe to a
+    /// `Channel` that it is already subscribed to.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{RecipientCapture, Sampling};
+    ///
+    /// let modes = [
+    ///     DeliveryMode::Immediate,
+    ///     DeliveryMode::Deferred(RecipientCapture::AtPublish),
+    ///     DeliveryMode::Deferred(RecipientCapture::AtFlush),
+    /// ];
+    ///
+    /// for mode in modes {
+    ///     let every_third: RecordingClient<_, u32> = RecordingClient::new(1);
+    ///     let half: RecordingClient<_, u32> = RecordingClient::new(2);
+    ///     let full: RecordingClient<_, u32> = RecordingClient::new(3);
+    ///
+    ///     let mut pubsub = PubSub::new();
+    ///     pubsub.set_delivery_mode(mode);
+    ///     pubsub.set_sampling_seed(42);
+    ///
+    ///     for client in [&every_third, &half, &full] {
+    ///         pubsub.add_client(client.clone()).unwrap();
+    ///     }
+    ///     pubsub.sub_sampled(every_third.clone(), "cpu", Sampling::EveryNth(3)).unwrap();
+    ///     pubsub.sub_sampled(half.clone(), "cpu", Sampling::Rate(0.5)).unwrap();
+    ///     pubsub.sub_client(full.clone(), "cpu").unwrap();
+    ///     pubsub.sub_client(full.clone(), "mem").unwrap();
+    ///
+    ///     // Sequences are shared by every `Channel`: `cpu` gets the even ones.
+    ///     for reading in 0..10u32 {
+    ///         pubsub.pub_message("cpu", reading).unwrap();
+    ///         pubsub.pub_message("mem", reading).unwrap();
+    ///     }
+    ///     pubsub.flush();
+    ///
+    ///     let sequences = |client: &RecordingClient<u32, u32>| -> Vec<u64> {
+    ///         client.received().iter().map(|message| message.sequence).collect()
+    ///     };
+    ///
+    ///     assert_eq!(sequences(&every_third), vec![0, 6, 12, 18]);
+    ///     assert_eq!(sequences(&half), vec![0, 6, 8, 18]);
+    ///     assert_eq!(sequences(&full), (0..20).collect::<Vec<_>>());
+    ///     assert_eq!(pubsub.sampled_out(), 12);
+    /// }
+    /// ```
+    pub fn sub_sampled<TChannel: Into<ChannelName>>(
+        &mut self,
+        client: TClient,
+        channel: TChannel,
+        sampling: Sampling,
+    ) -> Result<(), PubSubError> {
+        let channel = self.subscription_name(channel.into())?;
+        let identifier = client.get_id();
+
+        self.sub_identifier(channel.clone(), |_| client.get_id())?;
+
+        // A `Client` failing to take its retained `Messages` may have been
+        // evicted while subscribing.
+        if !self.routing.is_subscribed(&identifier, &channel) {
+            return Ok(());
+        }
+
+        self.routing
+            .samplers
+            .entry(channel)
+            .or_default()
+            .insert(identifier, LockCell::new(Sampler { sampling, seen: 0 }));
+
+        Ok(())
+    }
+
+    /// Subscribes a `Client` to a `Channel` on behalf of a logical
+    /// subscriber identified by `tag`.
+    ///
+    /// This lets a single `Client`, such as one connection multiplexing many
+    /// consumers, hold the same subscription for several of them.  The
+    /// `Client` is subscribed once, however many tags it holds for the
+    /// `Channel`, and each `Message` is sent to it once, carrying in `tags`
+    /// the tags of all of its matching subscriptions, patterns included.
+    ///
+    /// Results in a `PubSubError` when the `Client` already holds the tag for
+    /// the `Channel`, or when the `Channel` name is rejected by the
+    /// `ChannelNamePolicy`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::adapters::FnClient;
+    /// use general_pub_sub::PatternSubscriptions;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let frames = Arc::new(Mutex::new(Vec::new()));
+    /// let gateway = {
+    ///     let frames = frames.clone();
+    ///
+    ///     FnClient::new(1, move |message: &Message<&str>| {
+    ///         frames.lock().unwrap().push((message.contents, message.tags.to_vec()));
+    ///     })
+    /// };
+    /// let drain = || std::mem::take(&mut *frames.lock().unwrap());
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_pattern_subscriptions(PatternSubscriptions::Detected);
+    /// pubsub.add_client(gateway.clone()).unwrap();
+    /// pubsub.sub_tagged(gateway.clone(), "news", 10).unwrap();
+    /// pubsub.sub_tagged(gateway.clone(), "news", 20).unwrap();
+    /// pubsub.sub_tagged(gateway.clone(), "news.*", 30).unwrap();
+    /// assert!(pubsub.sub_tagged(gateway.clone(), "news", 10).is_err());
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 1);
+    ///
+    /// // One frame, carrying the tags of every matching subscription.
+    /// pubsub.pub_message("news", "headline").unwrap();
+    /// pubsub.pub_message("news.sports", "score").unwrap();
+    /// assert_eq!(drain(), vec![("headline", vec![10, 20]), ("score", vec![30])]);
+    ///
+    /// pubsub.unsub_tagged(gateway.clone(), "news", 10).unwrap();
+    /// pubsub.pub_message("news", "update").unwrap();
+    /// assert_eq!(drain(), vec![("update", vec![20])]);
+    ///
+    /// // The last tag unsubscribes the `Client`.
+    /// pubsub.unsub_tagged(gateway.clone(), "news", 20).unwrap();
+    /// pubsub.pub_message("news", "unheard").unwrap();
+    /// assert!(drain().is_empty());
+    /// assert!(pubsub.unsub_tagged(gateway.clone(), "news", 20).is_err());
+    /// assert!(pubsub.check_invariants().is_ok());
+    /// ```
+    pub fn sub_tagged<TChannel: Into<ChannelName>>(
+        &mut self,
+        client: TClient,
+        channel: TChannel,
+        tag: u32,
+    ) -> Result<(), PubSubError> {
+        let channel = self.subscription_name(channel.into())?;
+        let identifier = client.get_id();
+
+        let has_tag = self
+            .routing
+            .tags
+            .get(channel.as_ref())
+            .and_then(|tags| tags.get(&identifier))
+            .is_some_and(|tags| tags.contains(&tag));
+
+        if has_tag {
+            return Err(PubSubError::ClientAlreadySubscribedError {
+                channel: channel.to_string(),
+            });
+        }
+
+        if !self.routing.is_subscribed(&identifier, &channel) {
+            self.sub_identifier(channel.clone(), |_| client.get_id())?;
+
+            // A `Client` failing to take its retained `Messages` may have
+            // been evicted while subscribing.
+            if !self.routing.is_subscribed(&identifier, &channel) {
+                return Ok(());
+            }
+        }
+
+        self.routing
+            .tags
+            .entry(channel)
+            .or_default()
+            .entry(identifier)
+            .or_default()
+            .insert(tag);
+
+        Ok(())
+    }
+
+    /// Removes a tag from the subscription of a `Client` to a `Channel`,
+    /// unsubscribing the `Client` once it holds no more tags for it.
+    ///
+    /// Results in a `PubSubError` when the `Client` does not hold the tag for
+    /// the `Channel`.
+    pub fn unsub_tagged(
+        &mut self,
+        client: TClient,
+        channel: &str,
+        tag: u32,
+    ) -> Result<(), PubSubError> {
+        let channel = self.unsubscription_name(channel)?;
+        let identifier = client.get_id();
+
+        let tags = self
+            .routing
+            .tags
+            .get_mut(channel.as_ref())
+            .and_then(|tags| tags.get_mut(&identifier))
+            .filter(|tags| tags.contains(&tag))
+            .ok_or_else(|| PubSubError::ClientNotSubscribedError {
+                channel: channel.to_string(),
+            })?;
+
+        tags.remove(&tag);
+
+        if tags.is_empty() {
+            self.unsub_identifier(&channel, |_| client.get_id())?;
+        }
+
+        Ok(())
+    }
+
+    /// Subscribes the `Client` with an identifier to a `Channel`, delivering
+    /// only the `Messages` for which `filter` returns `true`.
+    ///
+    /// The filter is kept with the subscription and called before each
+    /// delivery through it.  For a pattern, that is for every `Channel` it
+    /// matches.  Retained `Messages` sent when subscribing are filtered too.
+    /// Other subscribers of the `Channel` are unaffected.  When the `Client`
+    /// reaches a `Channel` through several subscriptions, the
+    /// `PatternResolution` decides which of their filters apply, as it does
+    /// for sampling.
+    ///
+    /// Rejected deliveries are reported as `DropReason::FilteredOut`.  The
+    /// filter is dropped when the subscription is removed, however that
+    /// happens.
+    ///
+    /// Results in a `PubSubError` when there is no `Client` with the
+    /// identifier, or when it is already subscribed to the `Channel`, even
+    /// under `DuplicateSubscriptions::Counted`, or as `sub_id` does.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::PatternSubscriptions;
+    ///
+    /// #[derive(Clone, Debug, PartialEq)]
+    /// struct Order {
+    ///     amount: u32,
+    /// }
+    ///
+    /// let large: RecordingClient<_, Order> = RecordingClient::new(1);
+    /// let all: RecordingClient<_, Order> = RecordingClient::new(2);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_pattern_subscriptions(PatternSubscriptions::Detected);
+    /// pubsub.add_client(large.clone()).unwrap();
+    /// pubsub.add_client(all.clone()).unwrap();
+    ///
+    /// pubsub
+    ///     .sub_client_filtered(&1, "orders.*", |order: &Order| order.amount > 1000)
+    ///     .unwrap();
+    /// pubsub.sub_client(all.clone(), "orders.eu").unwrap();
+    ///
+    /// for amount in [500, 1500, 800, 2500] {
+    ///     pubsub.pub_message("orders.eu", Order { amount }).unwrap();
+    /// }
+    /// pubsub.pub_message("orders.us", Order { amount: 3000 }).unwrap();
+    ///
+    /// let amounts = |client: &RecordingClient<u32, Order>| -> Vec<u32> {
+    ///     client.contents().iter().map(|order| order.amount).collect()
+    /// };
+    /// assert_eq!(amounts(&large), vec![1500, 2500, 3000]);
+    /// assert_eq!(amounts(&all), vec![500, 1500, 800, 2500]);
+    ///
+    /// pubsub.unsub_id(&1, "orders.*").unwrap();
+    /// pubsub.sub_id(&1, "orders.*").unwrap();
+    /// pubsub.pub_message("orders.eu", Order { amount: 10 }).unwrap();
+    /// assert_eq!(amounts(&large), vec![1500, 2500, 3000, 10]);
+    /// ```
+    pub fn sub_client_filtered<TChannel, F>(
+        &mut self,
+        identifier: &TIdentifier,
+        channel: TChannel,
+        filter: F,
+    ) -> Result<(), PubSubError>
+    where
+        TChannel: Into<ChannelName>,
+        F: Fn(&TMessage) -> bool + Send + Sync + 'static,
+    {
+        if !self.clients.contains_key(identifier) {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        let channel = self.subscription_name(channel.into())?;
+
+        if self.routing.is_subscribed(identifier, &channel) {
+            return Err(PubSubError::ClientAlreadySubscribedError {
+                channel: channel.to_string(),
+            });
+        }
+
+        // Set before subscribing, so that retained `Messages` are filtered.
+        self.filters.insert(
+            channel.clone(),
+            self.clients[identifier].get_id(),
+            Arc::new(filter),
+        );
+
+        let subscribed =
+            self.sub_identifier(channel.clone(), |clients| clients[identifier].get_id());
+
+        if subscribed.is_err() {
+            self.filters.remove(&channel, identifier);
+        }
+
+        subscribed
+    }
+
+    /// Seeds the generator used by `Sampling::Rate` subscriptions.
+    pub fn set_sampling_seed(&mut self, seed: u64) {
+        *self.routing.sample_rng.get_mut() = routing::SampleRng::new(seed);
+    }
+
+    /// Gets the number of deliveries skipped by sampled subscriptions.
+    pub fn sampled_out(&self) -> u64 {
+        self.drops.count(DropReason::SampledOut)
+    }
+
+    /// Gets the publish `Metrics` of the `PubSub`.
+    ///
+    /// Fan-out is recorded when a `Message` is delivered, so in
+    /// `DeliveryMode::Deferred` it is recorded by `flush`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{MaxFanOut, RecipientCapture};
+    ///
+    /// let clients: Vec<RecordingClient<u32, &str>> = (1..=11).map(RecordingClient::new).collect();
+    /// let mut pubsub = PubSub::new();
+    ///
+    /// for client in &clients {
+    ///     pubsub.add_client(client.clone()).unwrap();
+    /// }
+    /// for (channel, recipients) in [("one", 1), ("two", 2), ("ten", 10), ("eleven", 11)] {
+    ///     for client in &clients[..recipients] {
+    ///         pubsub.sub_client(client.clone(), channel).unwrap();
+    ///     }
+    /// }
+    ///
+    /// for channel in ["nobody", "one", "two", "ten", "eleven"] {
+    ///     pubsub.pub_message(channel, "hello").unwrap();
+    /// }
+    ///
+    /// // Deferred `Messages` are counted once they are flushed.
+    /// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtPublish));
+    /// pubsub.pub_message("one", "later").unwrap();
+    /// assert_eq!(pubsub.metrics().fan_out.count(), 5);
+    /// assert_eq!(pubsub.metrics().pending, 1);
+    /// pubsub.flush();
+    ///
+    /// let metrics = pubsub.metrics();
+    /// assert_eq!(
+    ///     metrics.fan_out.buckets().collect::<Vec<_>>(),
+    ///     vec![
+    ///         (Some(0), 1),
+    ///         (Some(1), 2),
+    ///         (Some(10), 2),
+    ///         (Some(100), 1),
+    ///         (Some(1_000), 0),
+    ///         (None, 0),
+    ///     ]
+    /// );
+    /// assert_eq!(metrics.publish_latency_ns.count(), 6);
+    /// assert_eq!(
+    ///     metrics.max_fan_out,
+    ///     Some(MaxFanOut { recipients: 11, channel: "eleven".to_string() })
+    /// );
+    /// assert_eq!(metrics.pending, 0);
+    /// ```
+    pub fn metrics(&self) -> Metrics {
+        let publish_metrics = self.publish_metrics.lock();
+
+        Metrics {
+            fan_out: publish_metrics.fan_out.clone(),
+            publish_latency_ns: publish_metrics.publish_latency_ns.clone(),
+            max_fan_out: publish_metrics.max_fan_out.clone(),
+            sampled_out: self.drops.count(DropReason::SampledOut),
+            drops: self.drops.counts(),
+            pending: self.pending.len(),
+            pattern_cache_entries: self.routing.match_cache.lock().len(),
+        }
+    }
+
+    /// Gets the `PubSubStats` of the `PubSub`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::UnknownSubscriberPolicy;
+    ///
+    /// let clients: Vec<RecordingClient<u32, &str>> = (1..=3).map(RecordingClient::new).collect();
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_channel_stats(true);
+    ///
+    /// pubsub.add_client(clients[0].clone()).unwrap();
+    /// pubsub.add_client(clients[1].clone()).unwrap();
+    /// pubsub.sub_client(clients[0].clone(), "orders.created").unwrap();
+    /// pubsub.psub_client(clients[1].clone(), "orders.*").unwrap();
+    /// // Never added, so deliveries to it are skipped.
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Allow);
+    /// pubsub.sub_client(clients[2].clone(), "orders.created").unwrap();
+    ///
+    /// pubsub.pub_message("orders.created", "a").unwrap();
+    /// pubsub.pub_message("orders.shipped", "b").unwrap();
+    /// pubsub.pub_message("news", "c").unwrap();
+    ///
+    /// let stats = pubsub.stats();
+    /// assert_eq!(stats.published, 3);
+    /// assert_eq!(stats.deliveries, 3);
+    /// assert_eq!(stats.skipped_stale, 1);
+    /// assert_eq!(stats.per_channel["orders.shipped"], 1);
+    /// assert_eq!((stats.clients, stats.channels, stats.patterns), (2, 1, 1));
+    ///
+    /// pubsub.reset_stats();
+    /// pubsub.pub_message("orders.shipped", "d").unwrap();
+    ///
+    /// let stats = pubsub.stats();
+    /// assert_eq!((stats.published, stats.deliveries, stats.skipped_stale), (1, 1, 0));
+    /// assert_eq!(stats.per_channel.len(), 1);
+    /// assert_eq!(stats.clients, 2);
+    /// ```
+    pub fn stats(&self) -> PubSubStats {
+        let stale = self.drops.count(DropReason::StaleSubscriber);
+        let publish_metrics = self.publish_metrics.lock();
+
+        PubSubStats {
+            published: publish_metrics.published,
+            deliveries: publish_metrics.deliveries,
+            skipped_stale: stale - publish_metrics.stale_baseline,
+            per_channel: publish_metrics.per_channel.clone().unwrap_or_default(),
+            clients: self.clients.len(),
+            channels: self.routing.channels.len(),
+            patterns: self.routing.pattern_channels.len(),
+        }
+    }
+
+    /// Starts the counters of the `PubSubStats` over.  `Metrics` are kept.
+    pub fn reset_stats(&mut self) {
+        let stale = self.drops.count(DropReason::StaleSubscriber);
+
+        self.publish_metrics.get_mut().reset_stats(stale);
+    }
+
+    /// Sets whether `PubSubStats::per_channel` counts the `Messages`
+    /// published to each `Channel`.  Off by default, since it keeps an
+    /// entry for every `Channel` ever published to.  Turning it off
+    /// forgets the counts.
+    pub fn set_channel_stats(&mut self, enabled: bool) {
+        match enabled {
+            true => {
+                self.publish_metrics
+                    .get_mut()
+                    .per_channel
+                    .get_or_insert_with(HashMap::new);
+            }
+            false => self.publish_metrics.get_mut().per_channel = None,
+        }
+    }
+
+    /// Sets the `Clock` every time-dependent feature reads the time from.
+    ///
+    /// Defaults to the `SystemClock`.  Times already recorded are kept, so
+    /// the new `Clock` should not run behind them.
+    pub fn set_clock<C: Clock + 'static>(&mut self, clock: C) {
+        self.routing.clock = Arc::new(clock);
+    }
+
+    /// Gets how often each pattern has matched a published `Channel`.
+    ///
+    /// Each entry holds the pattern, its number of matches, and when it last
+    /// matched, if ever.  A publish counts at most once per pattern, however
+    /// many `Clients` are subscribed to it.  Entries are sorted by pattern.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{Clock, ManualClock};
+    /// use std::time::Duration;
+    ///
+    /// let alice: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let bob: RecordingClient<_, &str> = RecordingClient::new(2);
+    /// let clock = ManualClock::new();
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_clock(clock.clone());
+    /// pubsub.add_client(alice.clone()).unwrap();
+    /// pubsub.add_client(bob.clone()).unwrap();
+    /// pubsub.psub_client(alice.clone(), "orders.*").unwrap();
+    /// pubsub.psub_client(bob.clone(), "orders.*").unwrap();
+    /// pubsub.psub_client(bob.clone(), "audit.*").unwrap();
+    ///
+    /// pubsub.pub_message("orders.new", "first").unwrap();
+    /// clock.advance(Duration::from_secs(5));
+    /// let last = clock.now();
+    /// pubsub.pub_message("orders.paid", "second").unwrap();
+    /// pubsub.pub_message("news", "unmatched").unwrap();
+    ///
+    /// assert_eq!(
+    ///     pubsub.pattern_match_stats(),
+    ///     vec![("audit.*", 0, None), ("orders.*", 2, Some(last))]
+    /// );
+    /// ```
+    pub fn pattern_match_stats(&self) -> Vec<(&str, u64, Option<Instant>)> {
+        let pattern_stats = self.routing.pattern_stats.lock();
+
+        let mut stats: Vec<_> = self
+            .routing
+            .pattern_channels
+            .keys()
+            .filter_map(|pattern| {
+                let stats = pattern_stats.get(pattern)?;
+
+                Some((pattern.as_ref(), stats.matches, stats.last_matched))
+            })
+            .collect();
+
+        stats.sort_by_key(|(pattern, _, _)| *pattern);
+
+        stats
+    }
+
+    /// Lists the patterns that have never matched a published `Channel`,
+    /// despite being subscribed to for at least `older_than`.
+    ///
+    /// Such patterns are likely dead, yet are still tested against every
+    /// published `Channel`.  The patterns are sorted.
+    pub fn never_matched_patterns(&self, older_than: Duration) -> Vec<&str> {
+        let now = self.routing.clock.now();
+
+        let pattern_stats = self.routing.pattern_stats.lock();
+
+        let mut patterns: Vec<_> = self
+            .routing
+            .pattern_channels
+            .keys()
+            .filter(|pattern| {
+                pattern_stats.get(*pattern).is_some_and(|stats| {
+                    stats.matches == 0 && now.duration_since(stats.created) >= older_than
+                })
+            })
+            .map(|pattern| pattern.as_ref())
+            .collect();
+
+        patterns.sort_unstable();
+
+        patterns
+    }
+
+    /// Unsubscribes a `Client` from a `Channel`
+    ///
+    /// Results in a `PubSubError` when a `Client` attempts to unsubscribe
+    /// from a `Channel` it is not subscribed to.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    ///
+    /// pubsub.unsub_client(client.clone(), "news").unwrap();
+    /// assert!(pubsub.unsub_client(client.clone(), "news").is_err());
+    ///
+    /// pubsub.pub_message("news", "unheard").unwrap();
+    /// assert!(client.contents().is_empty());
+    /// ```
+    pub fn unsub_client(&mut self, client: TClient, channel: &str) -> Result<(), PubSubError> {
+        let channel = self.unsubscription_name(channel)?;
+        self.unsub_identifier(&channel, |_| client.get_id())
+    }
+
+    /// Unsubscribes a `Client` from a system `Channel`.  See `sub_system`.
+    pub fn unsub_system(&mut self, client: TClient, name: &str) -> Result<(), PubSubError> {
+        let channel = self.system_name(name)?;
+        self.unsub_identifier(&channel, |_| client.get_id())
+    }
+
+    /// Unsubscribes the `Client` with an identifier from a `Channel`, like
+    /// `unsub_client` but without handing over the `Client` itself.  See
+    /// `sub_id`.
+    ///
+    /// Not to be confused with `unsub_by_id`, which takes a
+    /// `SubscriptionId`.
+    ///
+    /// Results in a `PubSubError` when there is no `Client` with the
+    /// identifier, or as `unsub_client` does.
+    pub fn unsub_id(&mut self, identifier: &TIdentifier, channel: &str) -> Result<(), PubSubError> {
+        if !self.clients.contains_key(identifier) {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        let channel = self.unsubscription_name(channel)?;
+        self.unsub_identifier(&channel, |pubsub| pubsub.clients[identifier].get_id())
+    }
+
+    /// Unsubscribes the `Client` with an identifier from every `Channel`
+    /// and pattern, keeping it a `Client` of the `PubSub`, and returns how
+    /// many subscriptions were removed.
+    ///
+    /// Subscriptions held more than once under
+    /// `DuplicateSubscriptions::Counted` are removed outright, and count
+    /// once.  The firehose subscription is kept; see `unsub_firehose`.
+    ///
+    /// Results in a `PubSubError` when there is no `Client` with the
+    /// identifier.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    ///
+    /// for channel in ["news", "sports", "weather", "orders.*"].iter() {
+    ///     pubsub.sub_client(client.clone(), *channel).unwrap();
+    /// }
+    ///
+    /// assert_eq!(pubsub.unsub_all(&1).unwrap(), 4);
+    ///
+    /// for channel in ["news", "sports", "weather", "orders.created"].iter() {
+    ///     pubsub.pub_message(channel, "unheard").unwrap();
+    /// }
+    /// assert!(client.contents().is_empty());
+    ///
+    /// assert!(matches!(
+    ///     pubsub.unsub_all(&2),
+    ///     Err(PubSubError::ClientDoesNotExistError)
+    /// ));
+    /// ```
+    pub fn unsub_all(&mut self, identifier: &TIdentifier) -> Result<usize, PubSubError> {
+        if !self.clients.contains_key(identifier) {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        let subscriptions = self.routing.subscriptions(identifier);
+
+        for channel in &subscriptions {
+            // A `Client` failing to take a confirmation may have been
+            // evicted, along with its remaining subscriptions.
+            if !self.clients.contains_key(identifier) {
+                break;
+            }
+
+            if let Some(counts) = self.subscription_counts.get_mut(channel.as_ref()) {
+                counts.remove(identifier);
+            }
+
+            self.unsub_identifier(channel, |pubsub| pubsub.clients[identifier].get_id())?;
+        }
+
+        self.subscription_ids
+            .retain(|_, (subscriber, _)| subscriber != identifier);
+
+        Ok(subscriptions.len())
+    }
+
+    fn unsub_identifier<F>(&mut self, channel: &str, get_id: F) -> Result<(), PubSubError>
+    where
+        F: Fn(&Self) -> TIdentifier,
+    {
+        let identifier = get_id(self);
+
+        if let Some(counts) = self.subscription_counts.get_mut(channel) {
+            if let Some(count) = counts.get_mut(&identifier) {
+                *count -= 1;
+
+                if *count == 1 {
+                    counts.remove(&identifier);
+                }
+
+                return Ok(());
+            }
+        }
+
+        self.routing.unsubscribe(channel, &identifier)?;
+        self.routing.remove_sampler(channel, &identifier);
+        self.routing.remove_tags(channel, &identifier);
+        self.routing.remove_once(channel, &identifier);
+        self.filters.remove(channel, &identifier);
+
+        if self.confirm(get_id(self), ControlEventKind::Unsubscribed, channel) {
+            self.flush_delivered(&identifier);
+        }
+
+        self.record(|| TraceEvent::Unsub(identifier, channel.to_string()));
+        self.count_mutation();
+
+        Ok(())
+    }
+
+    /// Sets what subscribing a `Client` to a `Channel` it is already
+    /// subscribed to does.
+    ///
+    /// Counts already held are kept when switching back to
+    /// `DuplicateSubscriptions::Reject`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::DuplicateSubscriptions;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// assert!(pubsub.sub_client(client.clone(), "news").is_err());
+    ///
+    /// pubsub.set_duplicate_subscriptions(DuplicateSubscriptions::Counted);
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 3);
+    ///
+    /// // Delivered once, however many times the `Client` is subscribed.
+    /// pubsub.pub_message("news", "first").unwrap();
+    /// assert_eq!(client.contents(), vec!["first"]);
+    ///
+    /// pubsub.unsub_client(client.clone(), "news").unwrap();
+    /// pubsub.unsub_client(client.clone(), "news").unwrap();
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 1);
+    /// pubsub.pub_message("news", "second").unwrap();
+    ///
+    /// pubsub.unsub_client(client.clone(), "news").unwrap();
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 0);
+    /// assert!(pubsub.unsub_client(client.clone(), "news").is_err());
+    /// pubsub.pub_message("news", "third").unwrap();
+    /// assert_eq!(client.contents(), vec!["first", "second"]);
+    /// ```
+    pub fn set_duplicate_subscriptions(&mut self, duplicates: DuplicateSubscriptions) {
+        self.duplicate_subscriptions = duplicates;
+    }
+
+    /// Subscribes a `Client` to a `Channel` like `sub_client`, returning a
+    /// `SubscriptionId` that `unsub_by_id` unsubscribes.
+    ///
+    /// With `DuplicateSubscriptions::Counted`, independent parts of a
+    /// `Client` can each hold their own subscription to the same `Channel`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::DuplicateSubscriptions;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_duplicate_subscriptions(DuplicateSubscriptions::Counted);
+    /// pubsub.add_client(client.clone()).unwrap();
+    ///
+    /// let widget = pubsub.sub_with_id(client.clone(), "news").unwrap();
+    /// let ticker = pubsub.sub_with_id(client.clone(), "news").unwrap();
+    /// assert_ne!(widget, ticker);
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 2);
+    ///
+    /// pubsub.unsub_by_id(widget).unwrap();
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 1);
+    /// assert_eq!(
+    ///     pubsub.unsub_by_id(widget),
+    ///     Err(PubSubError::UnknownSubscriptionIdError)
+    /// );
+    ///
+    /// pubsub.unsub_by_id(ticker).unwrap();
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 0);
+    /// ```
+    pub fn sub_with_id<TChannel: Into<ChannelName>>(
+        &mut self,
+        client: TClient,
+        channel: TChannel,
+    ) -> Result<SubscriptionId, PubSubError> {
+        let channel = self.subscription_name(channel.into())?;
+        let identifier = client.get_id();
+
+        self.sub_identifier(channel.clone(), |_| client.get_id())?;
+
+        let id = SubscriptionId(self.next_subscription_id);
+        self.next_subscription_id += 1;
+        self.subscription_ids.insert(id, (identifier, channel));
+
+        Ok(id)
+    }
+
+    /// Unsubscribes the subscription made with `sub_with_id`.
+    ///
+    /// Results in a `PubSubError` when the subscription was already
+    /// unsubscribed, or its `Client` removed.
+    pub fn unsub_by_id(&mut self, id: SubscriptionId) -> Result<(), PubSubError>
+    where
+        TIdentifier: Clone,
+    {
+        let (identifier, channel) = self
+            .subscription_ids
+            .remove(&id)
+            .ok_or(PubSubError::UnknownSubscriptionIdError)?;
+
+        self.unsub_identifier(&channel, |_| identifier.clone())
+    }
+
+    /// Gets how many times a `Client` is subscribed to a `Channel` or
+    /// pattern, which is at most 1 unless subscriptions are
+    /// `DuplicateSubscriptions::Counted`.
+    pub fn subscription_count_for(&self, identifier: &TIdentifier, channel: &str) -> usize {
+        if !self.routing.is_subscribed(identifier, channel) {
+            return 0;
+        }
+
+        self.subscription_counts
+            .get(channel)
+            .and_then(|counts| counts.get(identifier))
+            .copied()
+            .unwrap_or(1)
+    }
+
+    /// Sets the encoder of control `Messages`, confirming subscription
+    /// changes to the `Clients` making them.
+    ///
+    /// Once set, `sub_client`, `sub_by_handle`, and `unsub_client` send the
+    /// affected `Client` a control `Message` on the `Channel` in question,
+    /// built by `encoder`.  Control `Messages` take the same path as
+    /// published `Messages`: in `DeliveryMode::Deferred` they are queued
+    /// behind the `Messages` already pending, so a `Client` never receives a
+    /// `Message` from a `Channel` after the confirmation of unsubscribing
+    /// from it.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{ControlEvent, RecipientCapture};
+    ///
+    /// let client: RecordingClient<_, String> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_control_encoder(|event: ControlEvent| {
+    ///     format!("{:?} {} ({})", event.kind, event.channel, event.subscriptions)
+    /// });
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.psub_client(client.clone(), "orders.*").unwrap();
+    /// assert_eq!(client.contents(), vec!["Subscribed news (1)", "Subscribed orders.* (2)"]);
+    ///
+    /// // The confirmation is queued behind the pending `Message`.
+    /// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtPublish));
+    /// pubsub.pub_message("news", "update").unwrap();
+    /// pubsub.unsub_client(client.clone(), "news").unwrap();
+    /// pubsub.flush();
+    /// assert_eq!(
+    ///     client.contents()[2..],
+    ///     ["update".to_string(), "Unsubscribed news (1)".to_string()]
+    /// );
+    ///
+    /// pubsub.clear_control_encoder();
+    /// pubsub.punsub_client(client.clone(), "orders.*").unwrap();
+    /// pubsub.flush();
+    /// assert_eq!(client.contents().len(), 4);
+    /// ```
+    pub fn set_control_encoder<F>(&mut self, encoder: F)
+    where
+        F: Fn(ControlEvent) -> TMessage + Send + Sync + 'static,
+    {
+        self.control_encoder = Some(Arc::new(encoder));
+    }
+
+    /// Stops sending control `Messages`.
+    pub fn clear_control_encoder(&mut self) {
+        self.control_encoder = None;
+    }
+
+    /// Sends a control `Message` if there is a control encoder, returning
+    /// whether there is one.  The `Client` is left for the caller to flush.
+    fn confirm(&mut self, identifier: TIdentifier, kind: ControlEventKind, channel: &str) -> bool {
+        let encoder = match &self.control_encoder {
+            Some(encoder) => encoder,
+            None => return false,
+        };
+
+        let contents = encoder(ControlEvent {
+            kind,
+            channel,
+            subscriptions: self.routing.subscription_count(&identifier),
+        });
+
+        let sequence = self.take_sequence();
+
+        self.deliver_to(identifier, channel, contents, sequence, StoredVia::Channel);
+
+        true
+    }
+
+    /// Flushes a `Client` once the `Messages` sent to it by a call are all
+    /// sent, counting a failure towards eviction.
+    fn flush_client(&mut self, identifier: &TIdentifier) {
+        if let Some(client) = self.clients.get_mut(identifier) {
+            let flushed = client.flush();
+            self.failures
+                .get_mut()
+                .note_flush(&flushed, identifier, || client.get_id());
+        }
+    }
+
+    /// Flushes a `Client` after `deliver_to` sent it its `Messages`.  In
+    /// `DeliveryMode::Deferred` they were queued instead, and `flush`
+    /// flushes their recipients.
+    fn flush_delivered(&mut self, identifier: &TIdentifier) {
+        if self.delivery_mode == DeliveryMode::Immediate {
+            self.flush_client(identifier);
+        }
+    }
+
+    /// Sends a `Message` to a single `Client`, through the same path as
+    /// published `Messages`, returning the `once` subscriptions it spent.
+    ///
+    /// The `Client` is not flushed, so that a caller sending it several
+    /// `Messages` can flush it once with `flush_client`.  Nor are the spent
+    /// subscriptions removed, so that control `Messages` spend none.
+    fn deliver_to(
+        &mut self,
+        identifier: TIdentifier,
+        channel: &str,
+        contents: TMessage,
+        sequence: u64,
+        via: StoredVia,
+    ) -> Vec<(String, TIdentifier)> {
+        match self.delivery_mode {
+            DeliveryMode::Immediate => {
+                let message = Message {
+                    contents,
+                    source: channel,
+                    sequence,
+                    via: via.as_via(),
+                    tags: &[],
+                };
+
+                let outcome = Sender {
+                    clients: &mut self.clients,
+                    failures: &self.failures,
+                    drops: &self.drops,
+                    once: &self.routing.once,
+                    flush: false,
+                }
+                .send_one(&identifier, &message);
+
+                self.evict_failing();
+
+                match outcome {
+                    Outcome::Sent(spent) => spent,
+                    Outcome::Failed(..) | Outcome::Absent => Vec::new(),
+                }
+            }
+            DeliveryMode::Deferred(_) => {
+                self.queue_pending(PendingMessage {
+                    channel: channel.to_string(),
+                    contents,
+                    sequence,
+                    recipients: Some(vec![(identifier, via, Vec::new())]),
+                    excluded: None,
+                });
+
+                Vec::new()
+            }
+        }
+    }
+
+    /// Sets the maximum memory, in bytes, the `PubSub` may use, or `None` for
+    /// no limit (the default).
+    ///
+    /// When a subscription or a deferred `Message` would exceed the budget,
+    /// the oldest buffered history `Messages` are dropped first, or the
+    /// retained ones as set with `set_shed_order`, each reported to the shed
+    /// callback.  If that is not enough, the operation
+    /// fails with `PubSubError::MemoryBudgetExceededError`.  Publishing a
+    /// `Message` only fails when it would be queued in
+    /// `DeliveryMode::Deferred`; otherwise it is just left out of the history.
+    ///
+    /// `Clients` added with `add_client` or `import` are counted but never
+    /// rejected.  Subscriptions loaded in bulk by `import`,
+    /// `restore_subscriptions`, or `apply_snapshot` are rejected like any
+    /// other, each reported as an error of the `ImportReport`.
+    /// See `memory_estimate` for what is counted.
+    pub fn set_memory_budget(&mut self, budget: Option<usize>) {
+        self.memory_budget = budget;
+    }
+
+    /// Sets the callback notified of each history or retained `Message`
+    /// dropped to stay within the memory budget.
+    pub fn set_shed_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(ShedEvent) + Send + Sync + 'static,
+    {
+        self.shed_callback = Some(Arc::new(callback));
+    }
+
+    /// Sets whether history or retained `Messages` are dropped to stay
+    /// within the memory budget, in which order.
+    ///
+    /// Defaults to `ShedOrder::History`, which never drops retained
+    /// `Messages`.  Retained `Messages` dropped are recorded as
+    /// `DropReason::RetainedShed`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{DropReason, ShedOrder};
+    ///
+    /// let mut pubsub: PubSub<RecordingClient<u32, u32>, u32, u32> = PubSub::new();
+    /// pubsub.add_client(RecordingClient::new(1)).unwrap();
+    /// pubsub.pub_message_retained("lamp", 1u32).unwrap();
+    ///
+    /// // No history to drop, so no room for the subscription.
+    /// pubsub.set_memory_budget(Some(pubsub.memory_estimate()));
+    /// assert!(pubsub.sub_id(&1, "lamp").is_err());
+    ///
+    /// pubsub.set_shed_order(ShedOrder::HistoryThenRetained);
+    /// pubsub.sub_id(&1, "lamp").unwrap();
+    /// assert_eq!(pubsub.metrics().drops[&DropReason::RetainedShed], 1);
+    /// assert!(pubsub.clear_retained("lamp").is_none());
+    /// assert!(pubsub.check_invariants().is_ok());
+    /// ```
+    pub fn set_shed_order(&mut self, order: ShedOrder) {
+        self.shed_order = order;
+    }
+
+    /// Sets how the memory owned by the contents of a `Message` is counted
+    /// towards `memory_estimate` and the memory budget, such as
+    /// `String::heap_size` from `SizeOf`.
+    ///
+    /// By default only the size of `TMessage` itself is counted, which
+    /// undercounts contents owning a buffer.  The `Messages` already stored
+    /// are counted again with the new size.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{DropReason, SizeOf};
+    ///
+    /// let mut pubsub: PubSub<RecordingClient<u32, String>, u32, String> = PubSub::new();
+    /// pubsub.add_client(RecordingClient::new(1)).unwrap();
+    /// pubsub.set_history_capacity(64);
+    /// pubsub.set_heap_size(String::heap_size);
+    ///
+    /// let empty = pubsub.memory_estimate();
+    /// pubsub.pub_message("feed", "x".repeat(1000)).unwrap();
+    /// let entry = pubsub.memory_estimate() - empty;
+    /// assert!(entry >= 1000);
+    ///
+    /// // Room for three of the payloads: publishing more sheds the oldest.
+    /// pubsub.set_memory_budget(Some(empty + 3 * entry));
+    /// for _ in 0..9 {
+    ///     pubsub.pub_message("feed", "x".repeat(1000)).unwrap();
+    /// }
+    ///
+    /// assert!(pubsub.memory_estimate() <= empty + 3 * entry);
+    /// assert_eq!(pubsub.metrics().drops[&DropReason::HistoryShed], 7);
+    /// assert_eq!(pubsub.replay("feed", &1, 64).unwrap(), 3);
+    /// assert!(pubsub.check_invariants().is_ok());
+    /// ```
+    pub fn set_heap_size<F>(&mut self, heap_size: F)
+    where
+        F: Fn(&TMessage) -> usize + Send + Sync + 'static,
+    {
+        self.sizer.heap_size = Some(Arc::new(heap_size));
+        self.stored_bytes = self.count_stored_bytes();
+    }
+
+    /// Sets how publishes treat subscribers which are not `Clients` of the
+    /// `PubSub`.
+    ///
+    /// Defaults to `StaleIdPolicy::SkipAndCollect`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{
+    ///     RecipientCapture, StaleIdPolicy, StaleSubscriber, UnknownSubscriberPolicy,
+    /// };
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let stale = Arc::new(Mutex::new(Vec::new()));
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Allow);
+    /// pubsub.set_stale_callback({
+    ///     let stale = stale.clone();
+    ///
+    ///     move |subscriber: StaleSubscriber<u32>| {
+    ///         let channel = subscriber.channel.to_string();
+    ///         stale.lock().unwrap().push((channel, *subscriber.identifier));
+    ///     }
+    /// });
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.sub_client(RecordingClient::new(2), "news").unwrap();
+    /// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtFlush));
+    ///
+    /// pubsub.set_stale_id_policy(StaleIdPolicy::Skip);
+    /// pubsub.pub_message("news", "skipped").unwrap();
+    /// assert_eq!(pubsub.flush().stale, 0);
+    /// assert!(stale.lock().unwrap().is_empty());
+    ///
+    /// pubsub.set_stale_id_policy(StaleIdPolicy::SkipAndCollect);
+    /// pubsub.pub_message("news", "collected").unwrap();
+    /// assert_eq!(pubsub.flush().stale, 1);
+    /// assert_eq!(*stale.lock().unwrap(), vec![("news".to_string(), 2)]);
+    /// assert!(pubsub.check_invariants().is_err());
+    ///
+    /// // Purging reports the subscriber one last time.
+    /// pubsub.set_stale_id_policy(StaleIdPolicy::Purge);
+    /// pubsub.pub_message("news", "purged").unwrap();
+    /// pubsub.pub_message("news", "clean").unwrap();
+    /// assert_eq!(pubsub.flush().stale, 1);
+    /// assert_eq!(stale.lock().unwrap().len(), 2);
+    /// assert!(pubsub.check_invariants().is_ok());
+    ///
+    /// assert_eq!(client.contents(), vec!["skipped", "collected", "purged", "clean"]);
+    /// ```
+    pub fn set_stale_id_policy(&mut self, policy: StaleIdPolicy) {
+        self.stale_policy = policy;
+    }
+
+    /// Sets whether `Clients` can be subscribed before they are added.
+    ///
+    /// Defaults to `UnknownSubscriberPolicy::Reject`.  Subscriptions made
+    /// before the policy was set are kept; `purge_dangling` removes those
+    /// without a `Client`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::UnknownSubscriberPolicy;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// assert_eq!(
+    ///     pubsub.sub_client(client.clone(), "news"),
+    ///     Err(PubSubError::ClientDoesNotExistError)
+    /// );
+    ///
+    /// // Subscribing ahead of `add_client`.
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Allow);
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.pub_message("news", "unheard").unwrap();
+    ///
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.pub_message("news", "heard").unwrap();
+    /// assert_eq!(client.contents(), vec!["heard"]);
+    /// ```
+    pub fn set_unknown_subscriber_policy(&mut self, policy: UnknownSubscriberPolicy) {
+        self.unknown_subscribers = policy;
+    }
+
+    /// Sets the callback notified of each stale subscriber skipped by a
+    /// publish, unless the `StaleIdPolicy` is `StaleIdPolicy::Skip`.
+    pub fn set_stale_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(StaleSubscriber<TIdentifier>) + Send + Sync + 'static,
+    {
+        self.stale_callback = Some(Arc::new(callback));
+    }
+
+    /// Sets when `Clients` whose sends keep failing are removed, such as
+    /// `Clients` whose connection broke.
+    ///
+    /// An evicted `Client` is removed like with `remove_client`, once the
+    /// publish, flush, or micro-batch whose send reached the threshold is
+    /// done, and reported to the eviction callback.  Failed sends of control
+    /// and replayed `Messages` count as well, but the `Client` is only
+    /// removed by the next of those.  Defaults to `EvictionPolicy::Never`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::EvictionPolicy;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[derive(Clone)]
+    /// struct Flaky {
+    ///     id: u32,
+    ///     fails: Arc<Mutex<bool>>,
+    /// }
+    ///
+    /// impl Client<u32, &str> for Flaky {
+    ///     type Error = ();
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         self.id
+    ///     }
+    ///
+    ///     fn send(&mut self, _message: &Message<&str>) -> Result<(), ()> {
+    ///         match *self.fails.lock().unwrap() {
+    ///             true => Err(()),
+    ///             false => Ok(()),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let flaky = Flaky { id: 1, fails: Arc::new(Mutex::new(true)) };
+    /// let evicted = Arc::new(Mutex::new(Vec::new()));
+    /// let log = evicted.clone();
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_eviction_policy(EvictionPolicy::AfterConsecutiveFailures(2));
+    /// pubsub.set_eviction_callback(move |id: &u32| log.lock().unwrap().push(*id));
+    /// pubsub.add_client(flaky.clone()).unwrap();
+    /// pubsub.sub_client(flaky.clone(), "news").unwrap();
+    ///
+    /// // A successful send starts the count over.
+    /// pubsub.pub_message("news", "fails").unwrap();
+    /// *flaky.fails.lock().unwrap() = false;
+    /// pubsub.pub_message("news", "sent").unwrap();
+    /// *flaky.fails.lock().unwrap() = true;
+    /// pubsub.pub_message("news", "fails").unwrap();
+    /// assert!(pubsub.view().contains_client(&1));
+    ///
+    /// pubsub.pub_message("news", "fails again").unwrap();
+    /// assert!(!pubsub.view().contains_client(&1));
+    /// assert_eq!(pubsub.view().subscribers("news").count(), 0);
+    /// assert_eq!(*evicted.lock().unwrap(), vec![1]);
+    ///
+    /// // So do the sends of replayed `Messages`.
+    /// pubsub.set_history("log", 4);
+    /// pubsub.pub_message("log", "a").unwrap();
+    /// pubsub.pub_message("log", "b").unwrap();
+    /// pubsub.add_client(Flaky { id: 2, fails: flaky.fails.clone() }).unwrap();
+    ///
+    /// pubsub.replay("log", &2, 4).unwrap();
+    /// assert!(!pubsub.view().contains_client(&2));
+    /// assert_eq!(*evicted.lock().unwrap(), vec![1, 2]);
+    /// ```
+    pub fn set_eviction_policy(&mut self, policy: EvictionPolicy) {
+        self.failures.get_mut().set_policy(policy);
+    }
+
+    /// Sets the callback notified of each `Client` evicted by the
+    /// `EvictionPolicy`.
+    pub fn set_eviction_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&TIdentifier) + Send + Sync + 'static,
+    {
+        self.eviction_callback = Some(Arc::new(callback));
+    }
+
+    /// Sets the callback notified of each subscription added to a `Channel`
+    /// or pattern, with the identifier of the subscriber and the name it
+    /// subscribed to.
+    ///
+    /// Like the other lifecycle callbacks, it is called while the `PubSub`
+    /// is being changed, and only receives the identifier and name by
+    /// reference, so it cannot call back into the `PubSub`.  The firehose
+    /// is reported under an empty name.
+    pub fn set_subscribe_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&TIdentifier, &str) + Send + Sync + 'static,
+    {
+        self.routing.hooks.subscribe = Some(Arc::new(callback));
+    }
+
+    /// Sets the callback notified of each subscription removed from a
+    /// `Channel` or pattern, whether by unsubscribing, removing or evicting
+    /// the `Client`, or purging stale subscribers.
+    pub fn set_unsubscribe_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&TIdentifier, &str) + Send + Sync + 'static,
+    {
+        self.routing.hooks.unsubscribe = Some(Arc::new(callback));
+    }
+
+    /// Sets the callback notified when a `Channel` or pattern gains its
+    /// first subscriber, such as to start feeding it.
+    ///
+    /// It is called exactly once each time the subscribers go from none to
+    /// one, after the subscribe callback, and the last unsubscriber callback
+    /// is called exactly once each time they go back to none, however the
+    /// last subscription is removed.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let events = Arc::new(Mutex::new(Vec::new()));
+    /// let mut pubsub: PubSub<RecordingClient<u32, &str>, _, _> = PubSub::new();
+    ///
+    /// let log = events.clone();
+    /// pubsub.set_first_subscriber_callback(move |channel| {
+    ///     log.lock().unwrap().push(format!("start {}", channel));
+    /// });
+    /// let log = events.clone();
+    /// pubsub.set_last_unsubscriber_callback(move |channel| {
+    ///     log.lock().unwrap().push(format!("stop {}", channel));
+    /// });
+    ///
+    /// for id in 1..=2 {
+    ///     pubsub.add_client(RecordingClient::new(id)).unwrap();
+    ///     pubsub.sub_id(&id, "prices").unwrap();
+    /// }
+    ///
+    /// pubsub.unsub_id(&1, "prices").unwrap();
+    /// pubsub.remove_client_by_id(&2).unwrap();
+    ///
+    /// assert_eq!(*events.lock().unwrap(), vec!["start prices", "stop prices"]);
+    /// ```
+    pub fn set_first_subscriber_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.routing.hooks.first_subscriber = Some(Arc::new(callback));
+    }
+
+    /// Sets the callback notified when a `Channel` or pattern loses its last
+    /// subscriber.  See `set_first_subscriber_callback`.
+    pub fn set_last_unsubscriber_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.routing.hooks.last_unsubscriber = Some(Arc::new(callback));
+    }
+
+    /// Sets the callback notified of each identifier becoming a `Client`
+    /// of the `PubSub`, through `add_client` or the like.  `import` is not
+    /// reported.
+    ///
+    /// Replacing a `Client` with `replace_client` is not reported, since
+    /// the identifier stays a `Client` throughout.  `reattach_client` is
+    /// reported like `add_client`.
+    pub fn set_client_added_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&TIdentifier) + Send + Sync + 'static,
+    {
+        self.routing.hooks.client_added = Some(Arc::new(callback));
+    }
+
+    /// Sets the callback notified of each `Client` removed from the
+    /// `PubSub`, after its subscriptions were removed.
+    ///
+    /// `detach_client` is reported like `remove_client`, though the
+    /// subscriptions of a detached `Client` are only removed once it is
+    /// swept.
+    pub fn set_client_removed_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&TIdentifier) + Send + Sync + 'static,
+    {
+        self.routing.hooks.client_removed = Some(Arc::new(callback));
+    }
+
+    /// Removes the `Clients` the `EvictionPolicy` evicted since the last
+    /// call.
+    /// Records a `Message` whose send by an `AsyncPubSub` failed, like a
+    /// failed `Client::send`.
+    #[cfg(feature = "async")]
+    fn note_send_failed(&mut self, channel: &str, identifier: &TIdentifier) {
+        self.drops
+            .record(channel, DropReason::SendFailed, Some(identifier));
+    }
+
+    fn evict_failing(&mut self) {
+        for identifier in self.failures.get_mut().take_evicted() {
+            self.evict(identifier);
+        }
+    }
+
+    fn evict(&mut self, identifier: TIdentifier) {
+        if let Some(callback) = &self.eviction_callback {
+            callback(&identifier);
+        }
+
+        self.remove_identifier(&identifier);
+        self.record(|| TraceEvent::RemoveClient(identifier));
+        self.count_mutation();
+    }
+
+    /// Sets the callback notified of each `Message` that is not delivered,
+    /// whatever the reason.
+    ///
+    /// Drops are counted by reason in `Metrics::drops` whether or not a
+    /// callback is set.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{DropReason, DroppedMessage, UnknownSubscriberPolicy};
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let dropped = Arc::new(Mutex::new(Vec::new()));
+    /// let log = dropped.clone();
+    ///
+    /// let mut pubsub: PubSub<RecordingClient<u32, &str>, u32, &str> = PubSub::new();
+    /// pubsub.set_drop_callback(move |drop: DroppedMessage<u32>| {
+    ///     log.lock()
+    ///         .unwrap()
+    ///         .push((drop.channel.to_string(), drop.reason, drop.identifier.copied()));
+    /// });
+    ///
+    /// // Subscribed, but never added.
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Allow);
+    /// pubsub.sub_client(RecordingClient::new(7), "news").unwrap();
+    ///
+    /// pubsub.pub_message("news", "stale").unwrap();
+    /// pubsub.pub_message("sports", "nobody").unwrap();
+    ///
+    /// assert_eq!(
+    ///     *dropped.lock().unwrap(),
+    ///     vec![
+    ///         ("news".to_string(), DropReason::StaleSubscriber, Some(7)),
+    ///         ("sports".to_string(), DropReason::NoRecipients, None),
+    ///     ]
+    /// );
+    /// assert_eq!(pubsub.metrics().drops[&DropReason::NoRecipients], 1);
+    /// ```
+    pub fn set_drop_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(DroppedMessage<TIdentifier>) + Send + Sync + 'static,
+    {
+        self.drops.callback = Some(Arc::new(callback));
+    }
+
+    /// Removes the stale subscribers found by a publish, when the
+    /// `StaleIdPolicy` is `StaleIdPolicy::Purge`.
+    fn purge_stale(&mut self, stale: usize) {
+        if stale == 0 || self.stale_policy != StaleIdPolicy::Purge {
+            return;
+        }
+
+        self.purge_dangling();
+    }
+
+    /// Removes every subscriber which is not a `Client` of the `PubSub`
+    /// from all `Channels`, patterns, and the firehose, returning how many
+    /// subscriptions were removed.
+    ///
+    /// Such subscribers are skipped by every publish, as set by the
+    /// `StaleIdPolicy`, but otherwise stay subscribed.  Identifiers waiting
+    /// for their `Client` after `detach_client` or `restore_subscriptions`
+    /// are kept.  Unlike `gc`, this leaves patterns without subscribers in
+    /// place.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::UnknownSubscriberPolicy;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let never_added: RecordingClient<_, &str> = RecordingClient::new(2);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Allow);
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.sub_client(never_added.clone(), "news").unwrap();
+    /// pubsub.psub_client(never_added.clone(), "sports.*").unwrap();
+    ///
+    /// assert_eq!(pubsub.purge_dangling(), 2);
+    /// assert_eq!(pubsub.purge_dangling(), 0);
+    ///
+    /// let subscribers: Vec<_> = pubsub.view().subscribers("news").copied().collect();
+    /// assert_eq!(subscribers, vec![1]);
+    /// assert!(pubsub.check_invariants().is_ok());
+    /// ```
+    pub fn purge_dangling(&mut self) -> usize {
+        let clients = &self.clients;
+        let detached = &self.detached.identifiers;
+
+        let removed = self.routing.retain_subscribers(|identifier| {
+            clients.contains_key(identifier) || detached.contains_key(identifier)
+        });
+
+        self.prune_filters();
+
+        removed
+    }
+
+    /// Drops the filters of subscriptions removed other than by
+    /// unsubscribing, such as along with their subscriber.
+    fn prune_filters(&mut self) {
+        if self.filters.is_empty() {
+            return;
+        }
+
+        let routing = &self.routing;
+        self.filters
+            .retain(|subscription, identifier| routing.is_subscribed(identifier, subscription));
+    }
+
+    /// Estimates the memory used by the `PubSub`, in bytes.
+    ///
+    /// The estimate covers `Clients`, `Channels`, subscriptions, history,
+    /// and pending `Messages`, by the size of their types.  Memory owned by
+    /// the contents of `Messages` is included as set with `set_heap_size`.
+    /// Other memory owned indirectly, such as the heap buffer of a `String`
+    /// identifier, is not included, nor is the overhead of the collections
+    /// holding them.
+    ///
+    /// The estimate is kept up to date as things are added and removed, so
+    /// it takes constant time.
+    ///
+    /// # Example
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::ShedEvent;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let mut pubsub: PubSub<RecordingClient<u32, String>, u32, String> = PubSub::new();
+    /// pubsub.add_client(RecordingClient::new(1)).unwrap();
+    /// pubsub.channel_handle("news").unwrap();
+    /// pubsub.set_history_capacity(8);
+    ///
+    /// let empty = pubsub.memory_estimate();
+    /// for _ in 0..4 {
+    ///     pubsub.pub_message("news", "headline".to_string()).unwrap();
+    /// }
+    /// let with_history = pubsub.memory_estimate();
+    /// assert!(with_history > empty);
+    ///
+    /// // Just enough room for two of the buffered `Messages`: the oldest
+    /// // ones are shed to make room for a subscription.
+    /// let shed = Arc::new(Mutex::new(Vec::new()));
+    /// let seen = shed.clone();
+    /// pubsub.set_shed_callback(move |event: ShedEvent| seen.lock().unwrap().push(event.sequence));
+    ///
+    /// let entry = (with_history - empty) / 4;
+    /// pubsub.set_memory_budget(Some(empty + 2 * entry + std::mem::size_of::<u32>()));
+    /// pubsub.sub_id(&1, "news").unwrap();
+    ///
+    /// assert_eq!(*shed.lock().unwrap(), vec![0, 1]);
+    /// assert!(pubsub.memory_estimate() <= empty + 2 * entry + std::mem::size_of::<u32>());
+    /// assert!(pubsub.check_invariants().is_ok());
+    ///
+    /// // Pending `Messages` count until they are flushed.
+    /// pubsub.set_memory_budget(None);
+    /// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtPublish));
+    /// pubsub.pub_message("news", "late".to_string()).unwrap();
+    /// assert!(pubsub.check_invariants().is_ok());
+    ///
+    /// pubsub.flush();
+    /// pubsub.set_history_capacity(0);
+    /// assert_eq!(pubsub.memory_estimate(), empty + std::mem::size_of::<u32>());
+    /// assert!(pubsub.check_invariants().is_ok());
+    /// ```
+    pub fn memory_estimate(&self) -> usize {
+        self.estimate_memory(self.routing.indexed_subscriptions, self.stored_bytes)
+    }
+
+    /// Estimates the memory used given the number of subscriptions to
+    /// `Channels` and patterns, and the bytes taken by stored `Messages`.
+    fn estimate_memory(&self, subscriptions: usize, stored_bytes: usize) -> usize {
+        let clients = self.clients.len() * size_of::<(TIdentifier, TClient)>();
+
+        let channels =
+            self.routing.channel_entries.len() * size_of::<routing::ChannelEntry<TIdentifier>>();
+
+        let subscriptions =
+            (subscriptions + self.routing.firehose.len()) * size_of::<TIdentifier>();
+
+        clients + channels + subscriptions + stored_bytes
+    }
+
+    /// Counts the bytes kept track of in `stored_bytes` from scratch, to
+    /// check them.
+    fn count_stored_bytes(&self) -> usize {
+        let history = self
+            .retention
+            .histories
+            .values()
+            .flat_map(|history| history.messages.iter())
+            .map(|(_, contents)| self.sizer.entry(contents))
+            .sum::<usize>();
+
+        let retained = self
+            .retention
+            .retained
+            .iter()
+            .map(|(channel, (_, contents))| self.retained_size(channel, contents))
+            .sum::<usize>();
+
+        let pending = self
+            .pending
+            .iter()
+            .chain(
+                self.micro_batching
+                    .batches
+                    .values()
+                    .flat_map(|batch| batch.messages.iter()),
+            )
+            .map(|pending| pending.size(&self.sizer))
+            .sum::<usize>();
+
+        let buffered = self
+            .detached
+            .identifiers
+            .values()
+            .map(|detached| detached.buffered_size(&self.sizer))
+            .sum::<usize>();
+
+        history + retained + pending + buffered
+    }
+
+    fn retained_size(&self, channel: &str, contents: &TMessage) -> usize {
+        channel.len() + self.sizer.entry(contents)
+    }
+
+    /// Makes room for `size` more bytes within the memory budget, dropping
+    /// `Messages` in the `ShedOrder` if needed.
+    fn reserve_memory(&mut self, size: usize) -> Result<(), PubSubError> {
+        let budget = match self.memory_budget {
+            Some(budget) if size > 0 => budget,
+            _ => return Ok(()),
+        };
+
+        while self.memory_estimate() + size > budget {
+            let shed = match self.shed_order {
+                order if order.retained_first() => self.shed_retained() || self.shed_history(),
+                order if order.sheds_retained() => self.shed_history() || self.shed_retained(),
+                _ => self.shed_history(),
+            };
+
+            if !shed {
+                return Err(PubSubError::MemoryBudgetExceededError);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drops the oldest history `Message` of any `Channel`, returning
+    /// whether there was one.
+    fn shed_history(&mut self) -> bool {
+        let oldest = self
+            .retention
+            .histories
+            .iter_mut()
+            .filter_map(|(channel, history)| Some((history.earliest()?, channel, history)))
+            .min_by_key(|(sequence, _, _)| *sequence);
+
+        let (channel, (sequence, contents)) = match oldest {
+            Some((_, channel, history)) => match history.shed_oldest() {
+                Some(shed) => (channel, shed),
+                None => return false,
+            },
+            None => return false,
+        };
+
+        self.stored_bytes -= self.sizer.entry(&contents);
+        self.drops.record(channel, DropReason::HistoryShed, None);
+
+        if let Some(callback) = &self.shed_callback {
+            callback(ShedEvent { channel, sequence });
+        }
+
+        true
+    }
+
+    /// Drops the oldest retained `Message` of any `Channel`, returning
+    /// whether there was one.
+    fn shed_retained(&mut self) -> bool {
+        let oldest = self
+            .retention
+            .retained
+            .iter()
+            .min_by_key(|(_, (sequence, _))| *sequence)
+            .map(|(channel, _)| channel.clone());
+
+        let channel = match oldest {
+            Some(channel) => channel,
+            None => return false,
+        };
+
+        if let Some((sequence, contents)) = self.retention.retained.remove(&channel) {
+            self.stored_bytes -= self.retained_size(&channel, &contents);
+            self.drops.record(&channel, DropReason::RetainedShed, None);
+
+            if let Some(callback) = &self.shed_callback {
+                callback(ShedEvent {
+                    channel: &channel,
+                    sequence,
+                });
+            }
+        }
+
+        true
+    }
+
+    /// Sets how many of the most recent `Messages` are kept for each literal
+    /// `Channel`, so that `Clients` can `resume` from them or be sent a
+    /// `replay`.
+    ///
+    /// History is off (a capacity of 0) by default.  `Channels` given their
+    /// own capacity with `set_history` keep it.  Lowering the capacity drops
+    /// the oldest buffered `Messages` right away.
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.retention.capacity = capacity;
+        self.shrink_histories();
+    }
+
+    /// Sets how many of the most recent `Messages` are kept for the
+    /// `Channels` whose names start with `prefix`, such as a single
+    /// `Channel` or `"sensors."`, in place of the capacity set with
+    /// `set_history_capacity`.
+    ///
+    /// When several prefixes apply to a `Channel`, the longest one wins.
+    /// `Channels` with a capacity of 0 buffer nothing, and allocate
+    /// nothing.  Lowering a capacity drops the oldest buffered `Messages`
+    /// right away.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, u32> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.set_history("sensors.", 2);
+    /// pubsub.set_history("sensors.noisy", 0);
+    ///
+    /// for reading in 0..3u32 {
+    ///     pubsub.pub_message("sensors.door", reading).unwrap();
+    ///     pubsub.pub_message("sensors.noisy", reading).unwrap();
+    ///     pubsub.pub_message("chat", reading).unwrap();
+    /// }
+    ///
+    /// assert_eq!(pubsub.replay("sensors.door", &1, 10).unwrap(), 2);
+    /// assert_eq!(pubsub.replay("sensors.noisy", &1, 10).unwrap(), 0);
+    /// assert_eq!(pubsub.replay("chat", &1, 10).unwrap(), 0);
+    /// assert_eq!(client.contents(), vec![1, 2]);
+    /// ```
+    pub fn set_history<TPrefix: Into<String>>(&mut self, prefix: TPrefix, capacity: usize) {
+        let prefix = prefix.into();
+
+        match self
+            .retention
+            .capacities
+            .iter_mut()
+            .find(|(configured, _)| *configured == prefix)
+        {
+            Some((_, configured)) => *configured = capacity,
+            None => self.retention.capacities.push((prefix, capacity)),
+        }
+
+        self.shrink_histories();
+    }
+
+    /// Drops the buffered `Messages` beyond the capacity of each `Channel`.
+    fn shrink_histories(&mut self) {
+        self.stored_bytes -= self.retention.shrink(&self.sizer);
+    }
+
+    /// Sends a `Client` up to `count` of the most recent `Messages` buffered
+    /// for a `Channel`, oldest first, returning how many were sent.
+    ///
+    /// The `Messages` keep their original `sequence`, and are delivered
+    /// `Via::Replay` so the `Client` can tell them from live traffic.  The
+    /// `Client` need not be subscribed to the `Channel`.  See
+    /// `set_history` for which `Messages` are buffered.
+    ///
+    /// Results in a `PubSubError` when no `Client` has the identifier.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let watcher: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_history("orders.", 3);
+    /// pubsub.add_client(watcher.clone()).unwrap();
+    /// pubsub.psub_client(watcher.clone(), "orders.*").unwrap();
+    ///
+    /// pubsub.pub_message("orders.created", "a").unwrap();
+    /// pubsub.pub_message("orders.created", "b").unwrap();
+    ///
+    /// // Fewer `Messages` are buffered than asked for.
+    /// assert_eq!(pubsub.replay("orders.created", &1, 10).unwrap(), 2);
+    /// assert_eq!(watcher.contents(), vec!["a", "b", "a", "b"]);
+    ///
+    /// // The oldest are the first to go once the history is full.
+    /// for msg in ["c", "d", "e"].iter() {
+    ///     pubsub.pub_message("orders.created", *msg).unwrap();
+    /// }
+    /// watcher.clear();
+    ///
+    /// assert_eq!(pubsub.replay("orders.created", &1, 10).unwrap(), 3);
+    /// assert_eq!(watcher.contents(), vec!["c", "d", "e"]);
+    ///
+    /// watcher.clear();
+    /// pubsub.replay("orders.created", &1, 2).unwrap();
+    /// assert_eq!(watcher.contents(), vec!["d", "e"]);
+    /// ```
+    pub fn replay(
+        &mut self,
+        channel: &str,
+        identifier: &TIdentifier,
+        count: usize,
+    ) -> Result<usize, PubSubError> {
+        let identifier = self
+            .clients
+            .get(identifier)
+            .ok_or(PubSubError::ClientDoesNotExistError)?
+            .get_id();
+        let channel = self.channel_names.validate(channel)?;
+
+        let replayed: Vec<(u64, TMessage)> = match self.retention.histories.get(channel) {
+            Some(history) => {
+                let skipped = history.messages.len().saturating_sub(count);
+                history.messages.iter().skip(skipped).cloned().collect()
+            }
+            None => Vec::new(),
+        };
+
+        let count = replayed.len();
+
+        for (sequence, contents) in replayed {
+            // A `Client` evicted for failing is sent nothing more.
+            let recipient = match self.clients.get(&identifier) {
+                Some(client) => client.get_id(),
+                None => break,
+            };
+
+            let spent = self.deliver_to(recipient, channel, contents, sequence, StoredVia::Replay);
+            self.unsub_spent(spent);
+        }
+
+        if count > 0 {
+            self.flush_delivered(&identifier);
+        }
+
+        Ok(count)
+    }
+
+    /// Subscribes a `Client` to a literal `Channel`, first replaying the
+    /// buffered `Messages` published after the one with the sequence
+    /// `last_seen`.
+    ///
+    /// Replayed `Messages` keep their original `sequence`.  The replay and
+    /// the subscription happen under the same exclusive borrow, so no
+    /// `Message` published to the `Channel` can be missed or received twice.
+    /// The subscription is made like `sub_client` makes it, except that the
+    /// retained `Message` is not sent, since the replay covers it.
+    ///
+    /// When some of the `Messages` after `last_seen` have already been
+    /// dropped from the history, nothing is replayed and
+    /// `ResumeOutcome::GapDetected` is returned instead.
+    ///
+    /// Results in a `PubSubError` when the `Channel` name is rejected by the
+    /// `ChannelNamePolicy`, when the `Client` is already subscribed to it,
+    /// or when the subscription is refused as `sub_client` would refuse it.
+    /// Nothing is replayed or changed then.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::sync::SharedPubSub;
+    /// use general_pub_sub::ResumeOutcome;
+    /// use std::sync::mpsc::channel;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::thread;
+    ///
+    /// let client: RecordingClient<u32, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_history("orders", 8);
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "orders").unwrap();
+    /// pubsub.pub_message("orders", "a").unwrap();
+    ///
+    /// // The `Client` drops off after seeing "a", and misses "b" and "c".
+    /// let first = client.received()[0].sequence;
+    /// pubsub.unsub_client(client.clone(), "orders").unwrap();
+    /// client.clear();
+    /// pubsub.pub_message("orders", "b").unwrap();
+    /// pubsub.pub_message("orders", "c").unwrap();
+    ///
+    /// let shared = SharedPubSub::from(pubsub);
+    ///
+    /// // Publish from another thread as soon as the subscription is made,
+    /// // while `resume` is still replaying.
+    /// let publisher = Arc::new(Mutex::new(None));
+    /// {
+    ///     let (shared, publisher) = (shared.clone(), publisher.clone());
+    ///
+    ///     shared.clone().set_subscribe_callback(move |_, _| {
+    ///         let shared = shared.clone();
+    ///         let (started, waiting) = channel();
+    ///
+    ///         *publisher.lock().unwrap() = Some(thread::spawn(move || {
+    ///             started.send(()).unwrap();
+    ///             shared.pub_message("orders", "d").unwrap();
+    ///         }));
+    ///
+    ///         waiting.recv().unwrap();
+    ///     });
+    /// }
+    ///
+    /// let outcome = shared.resume(client.clone(), "orders", first);
+    /// assert_eq!(outcome, Ok(ResumeOutcome::Resumed { replayed: 2 }));
+    ///
+    /// let publisher = publisher.lock().unwrap().take().unwrap();
+    /// publisher.join().unwrap();
+    ///
+    /// // Neither missed nor received twice, and in order.
+    /// assert_eq!(client.contents(), vec!["b", "c", "d"]);
+    /// let sequences: Vec<u64> = client.received().iter().map(|m| m.sequence).collect();
+    /// assert!(sequences.windows(2).all(|pair| pair[0] < pair[1]));
+    ///
+    /// // Already subscribed: nothing is replayed again.
+    /// assert!(shared.resume(client.clone(), "orders", first).is_err());
+    /// assert_eq!(client.contents().len(), 3);
+    /// ```
+    ///
+    /// The replayed `Messages` arrive `Via::Replay`, and the live ones
+    /// through the subscription:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use std::convert::Infallible;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// #[derive(Clone)]
+    /// struct Replays(Arc<Mutex<Vec<bool>>>);
+    ///
+    /// impl Client<u32, &str> for Replays {
+    ///     type Error = Infallible;
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         1
+    ///     }
+    ///
+    ///     fn send(&mut self, message: &Message<&str>) -> Result<(), Infallible> {
+    ///         self.0.lock().unwrap().push(message.via == Via::Replay);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let client = Replays(Arc::new(Mutex::new(Vec::new())));
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_history("orders", 8);
+    /// pubsub.add_client(client.clone()).unwrap();
+    ///
+    /// pubsub.pub_message("orders", "a").unwrap();
+    /// pubsub.pub_message("orders", "b").unwrap();
+    /// pubsub.resume(client.clone(), "orders", 0).unwrap();
+    /// pubsub.pub_message("orders", "c").unwrap();
+    ///
+    /// assert_eq!(*client.0.lock().unwrap(), vec![true, false]);
+    /// ```
+    pub fn resume<TChannel: Into<ChannelName>>(
+        &mut self,
+        client: TClient,
+        channel: TChannel,
+        last_seen: u64,
+    ) -> Result<ResumeOutcome, PubSubError> {
+        let channel = self.subscription_name(channel.into())?;
+
+        if self.routing.is_subscribed(&client.get_id(), &channel) {
+            return Err(PubSubError::ClientAlreadySubscribedError {
+                channel: channel.to_string(),
+            });
+        }
+
+        let replay: Result<Vec<(u64, TMessage)>, Option<u64>> =
+            match self.retention.histories.get(channel.as_ref()) {
+                Some(history) if !history.is_complete_after(last_seen) => Err(history.earliest()),
+                Some(history) => Ok(history.after(last_seen).cloned().collect()),
+                None => Ok(Vec::new()),
+            };
+
+        self.sub_identifier_with(Subscription::Name(&channel), |_| client.get_id(), false)?;
+
+        let replay = match replay {
+            Ok(replay) => replay,
+            Err(earliest_available) => {
+                return Ok(ResumeOutcome::GapDetected { earliest_available });
+            }
+        };
+
+        let replayed = replay.len();
+
+        for (sequence, contents) in replay {
+            let spent = self.deliver_to(
+                client.get_id(),
+                &channel,
+                contents,
+                sequence,
+                StoredVia::Replay,
+            );
+            self.unsub_spent(spent);
+        }
+
+        if replayed > 0 {
+            self.flush_delivered(&client.get_id());
+        }
+
+        Ok(ResumeOutcome::Resumed { replayed })
+    }
+
+    /// Publishes a `Message` like `pub_message`, keeping it as the retained
+    /// `Message` of the `Channel` in place of any previous one.
+    ///
+    /// Each new subscription, made with `sub_client`, `sub_client_exact`,
+    /// `psub_client`, or `sub_id`, is sent the retained `Message` of every
+    /// `Channel` it matches right away, so that subscribers of state-like
+    /// `Channels` learn the current value without waiting for the next
+    /// publish.  Retained `Messages` keep their original `sequence`, and are
+    /// delivered through the `Channel` in the order they were published.
+    ///
+    /// Results in a `PubSubError` as `pub_message` does, or when the
+    /// `Channel` name is a pattern while `PatternPublishBehavior::FanOut` is
+    /// in effect.  Nothing is retained when publishing fails.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let early: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let late: RecordingClient<_, &str> = RecordingClient::new(2);
+    /// let watcher: RecordingClient<_, &str> = RecordingClient::new(3);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// for client in [&early, &late, &watcher].iter() {
+    ///     pubsub.add_client((*client).clone()).unwrap();
+    /// }
+    ///
+    /// // Subscribed before the retained `Message`, so received once.
+    /// pubsub.sub_client(early.clone(), "lamp.status").unwrap();
+    /// pubsub.pub_message_retained("lamp.status", "off").unwrap();
+    /// pubsub.pub_message_retained("lamp.status", "on").unwrap();
+    /// pubsub.pub_message_retained("door.status", "open").unwrap();
+    /// assert_eq!(early.contents(), vec!["off", "on"]);
+    ///
+    /// // Subscribed after, so sent the latest retained `Message` only.
+    /// pubsub.sub_client(late.clone(), "lamp.status").unwrap();
+    /// assert_eq!(late.contents(), vec!["on"]);
+    ///
+    /// // A pattern is sent those of every `Channel` it matches.
+    /// pubsub.psub_client(watcher.clone(), "*.status").unwrap();
+    /// assert_eq!(watcher.contents(), vec!["on", "open"]);
+    ///
+    /// pubsub.clear_retained("lamp.status");
+    /// pubsub.unsub_client(late.clone(), "lamp.status").unwrap();
+    /// pubsub.sub_client(late.clone(), "lamp.status").unwrap();
+    /// assert_eq!(late.contents(), vec!["on"]);
+    /// ```
+    pub fn pub_message_retained<TInputMessage: Into<TMessage>>(
+        &mut self,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        let name = self.channel_names.validate(channel)?;
+
+        if self.routing.is_pattern(name) && self.pattern_publish != PatternPublishBehavior::Literal
+        {
+            return Err(PubSubError::PublishedToPatternError {
+                channel: name.to_string(),
+            });
+        }
+
+        let contents = msg.into();
+        let sequence = *self.next_sequence.get_mut();
+        let receipt = self.pub_message(name, contents.clone())?;
+
+        self.stored_bytes += self.retained_size(name, &contents);
+
+        if let Some((_, replaced)) = self
+            .retention
+            .retained
+            .insert(name.to_string(), (sequence, contents))
+        {
+            self.stored_bytes -= self.retained_size(name, &replaced);
+        }
+
+        Ok(receipt)
+    }
+
+    /// Forgets the retained `Message` of a `Channel`, returning it.
+    pub fn clear_retained(&mut self, channel: &str) -> Option<TMessage> {
+        let channel = self.channel_names.validate(channel).ok()?;
+
+        let (_, contents) = self.retention.retained.remove(channel)?;
+        self.stored_bytes -= self.retained_size(channel, &contents);
+
+        Some(contents)
+    }
+
+    /// Publishes a `Message` to all `Clients` subscribed to the provided `Channel`.
+    ///
+    /// A `Channel` name containing wildcards is handled according to the
+    /// `PatternPublishBehavior`, which rejects it by default.
+    ///
+    /// `Clients` connected to the `PubSub` are all handed the same
+    /// `Message`, but the contents are cloned wherever they are kept: for
+    /// history, for detached and micro-batched recipients, and for
+    /// republishing.  Wrap expensive contents in an `Arc` to keep those
+    /// clones cheap.
+    ///
+    /// Results in a `PubSubError` when the `Channel` name is rejected by the
+    /// `ChannelNamePolicy`, or is a pattern while publishing to patterns is
+    /// not allowed.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let alice: RecordingClient<_, &str> = RecordingClient::new("alice");
+    /// let bob: RecordingClient<_, &str> = RecordingClient::new("bob");
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(alice.clone()).unwrap();
+    /// pubsub.add_client(bob.clone()).unwrap();
+    /// pubsub.psub_client(alice.clone(), "orders.*").unwrap();
+    /// pubsub.sub_client(bob.clone(), "orders.shipped").unwrap();
+    ///
+    /// pubsub.pub_message("orders.created", "new").unwrap();
+    /// pubsub.pub_message("orders.shipped", "sent").unwrap();
+    ///
+    /// assert_eq!(alice.contents(), vec!["new", "sent"]);
+    /// assert_eq!(bob.contents(), vec!["sent"]);
+    ///
+    /// assert!(matches!(
+    ///     pubsub.pub_message("orders.*", "everyone"),
+    ///     Err(PubSubError::PublishedToPatternError { .. })
+    /// ));
+    /// ```
+    ///
+    /// The `PublishReceipt` tells how many `Clients` the `Message` reached,
+    /// each counted once:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{PublishReceipt, UnknownSubscriberPolicy};
+    ///
+    /// let alice: RecordingClient<_, &str> = RecordingClient::new("alice");
+    /// let bob: RecordingClient<_, &str> = RecordingClient::new("bob");
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Allow);
+    /// pubsub.add_client(alice.clone()).unwrap();
+    /// pubsub.sub_client(alice.clone(), "orders.shipped").unwrap();
+    /// pubsub.psub_client(alice.clone(), "orders.*").unwrap();
+    ///
+    /// // Subscribed, but never added.
+    /// pubsub.sub_client(bob.clone(), "alerts").unwrap();
+    ///
+    /// let receipt = |delivered, channel_existed| PublishReceipt {
+    ///     delivered,
+    ///     channel_existed,
+    ///     failures: Vec::new(),
+    /// };
+    ///
+    /// assert_eq!(pubsub.pub_message("news", "quiet").unwrap(), receipt(0, false));
+    /// assert_eq!(pubsub.pub_message("alerts", "unheard").unwrap(), receipt(0, true));
+    /// assert_eq!(pubsub.pub_message("orders.created", "new").unwrap(), receipt(1, true));
+    /// assert_eq!(pubsub.pub_message("orders.shipped", "sent").unwrap(), receipt(1, true));
+    ///
+    /// assert_eq!(alice.contents(), vec!["new", "sent"]);
+    /// ```
+    ///
+    /// A `Client` failing to send does not keep the `Message` from the
+    /// other subscribers.  Its error is listed in the `PublishReceipt`,
+    /// and it is not counted as delivered:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::DropReason;
+    /// use std::io;
+    ///
+    /// #[derive(Clone)]
+    /// enum Peer {
+    ///     Connected(RecordingClient<u32, &'static str>),
+    ///     Disconnected(u32),
+    /// }
+    ///
+    /// impl Client<u32, &'static str> for Peer {
+    ///     type Error = io::ErrorKind;
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         match self {
+    ///             Peer::Connected(client) => client.get_id(),
+    ///             Peer::Disconnected(id) => *id,
+    ///         }
+    ///     }
+    ///
+    ///     fn send(&mut self, message: &Message<&'static str>) -> Result<(), io::ErrorKind> {
+    ///         match self {
+    ///             Peer::Connected(client) => {
+    ///                 client.send(message).map_err(|never| match never {})
+    ///             }
+    ///             Peer::Disconnected(_) => Err(io::ErrorKind::BrokenPipe),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let first = RecordingClient::new(1);
+    /// let last = RecordingClient::new(3);
+    /// let peers = [
+    ///     Peer::Connected(first.clone()),
+    ///     Peer::Disconnected(2),
+    ///     Peer::Connected(last.clone()),
+    /// ];
+    ///
+    /// let mut pubsub = PubSub::new();
+    ///
+    /// for peer in &peers {
+    ///     pubsub.add_client(peer.clone()).unwrap();
+    ///     pubsub.sub_client(peer.clone(), "news").unwrap();
+    /// }
+    ///
+    /// let receipt = pubsub.pub_message("news", "extra").unwrap();
+    ///
+    /// assert_eq!(receipt.delivered, 2);
+    /// assert_eq!(receipt.failures, vec![(2, io::ErrorKind::BrokenPipe)]);
+    /// assert_eq!(first.contents(), vec!["extra"]);
+    /// assert_eq!(last.contents(), vec!["extra"]);
+    /// assert_eq!(pubsub.metrics().drops[&DropReason::SendFailed], 1);
+    /// ```
+    ///
+    /// Owned contents are published the same way:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let alice: RecordingClient<_, String> = RecordingClient::new("alice");
+    /// let bob: RecordingClient<_, String> = RecordingClient::new("bob");
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(alice.clone()).unwrap();
+    /// pubsub.add_client(bob.clone()).unwrap();
+    /// pubsub.sub_client(alice.clone(), "news").unwrap();
+    /// pubsub.sub_client(bob.clone(), "news").unwrap();
+    ///
+    /// pubsub.pub_message("news", format!("{} new orders", 3)).unwrap();
+    ///
+    /// assert_eq!(alice.contents(), vec!["3 new orders".to_string()]);
+    /// assert_eq!(bob.contents(), alice.contents());
+    /// ```
+    ///
+    /// With `Arc` contents, a large payload is allocated once however many
+    /// `Clients` it is delivered to, and keeping it only clones the `Arc`:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// static CLONES: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// struct Frame(Vec<u8>);
+    ///
+    /// impl Clone for Frame {
+    ///     fn clone(&self) -> Frame {
+    ///         CLONES.fetch_add(1, Ordering::SeqCst);
+    ///         Frame(self.0.clone())
+    ///     }
+    /// }
+    ///
+    /// let clients: Vec<RecordingClient<_, Arc<Frame>>> =
+    ///     (0..10_000).map(RecordingClient::new).collect();
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_history_capacity(1);
+    ///
+    /// for client in &clients {
+    ///     pubsub.add_client(client.clone()).unwrap();
+    ///     pubsub.sub_client(client.clone(), "video").unwrap();
+    /// }
+    ///
+    /// pubsub.pub_message("video", Frame(vec![0; 1 << 20])).unwrap();
+    ///
+    /// assert_eq!(CLONES.load(Ordering::SeqCst), 0);
+    ///
+    /// let first = &clients[0].contents()[0];
+    /// assert!(clients
+    ///     .iter()
+    ///     .all(|client| Arc::ptr_eq(&client.contents()[0], first)));
+    /// ```
+    ///
+    /// A `Channel` with a single subscriber, which no pattern, firehose, or
+    /// sampled subscription can reach, is delivered to without matching
+    /// patterns.  Anything that could reach it takes the full path again:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::Sampling;
+    ///
+    /// let owner: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let watcher: RecordingClient<_, &str> = RecordingClient::new(2);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(owner.clone()).unwrap();
+    /// pubsub.add_client(watcher.clone()).unwrap();
+    /// pubsub.sub_client(owner.clone(), "user.1").unwrap();
+    /// pubsub.psub_client(watcher.clone(), "order.*").unwrap();
+    ///
+    /// pubsub.pub_message("user.1", "a").unwrap();
+    /// pubsub.pub_message("user.1", "b").unwrap();
+    /// assert_eq!(pubsub.pattern_match_stats()[0].1, 0);
+    ///
+    /// // A new matching pattern is noticed on the next publish.
+    /// pubsub.psub_client(watcher.clone(), "user.*").unwrap();
+    /// pubsub.pub_message("user.1", "c").unwrap();
+    /// pubsub.punsub_client(watcher.clone(), "user.*").unwrap();
+    ///
+    /// // As are the firehose and sampled subscriptions.
+    /// pubsub.sub_firehose(watcher.clone()).unwrap();
+    /// pubsub.pub_message("user.1", "d").unwrap();
+    /// pubsub.unsub_firehose(watcher.clone()).unwrap();
+    /// pubsub.unsub_client(owner.clone(), "user.1").unwrap();
+    /// pubsub.sub_sampled(owner.clone(), "user.1", Sampling::EveryNth(2)).unwrap();
+    /// pubsub.pub_message("user.1", "e").unwrap();
+    /// pubsub.pub_message("user.1", "f").unwrap();
+    ///
+    /// assert_eq!(owner.contents(), vec!["a", "b", "c", "d", "e"]);
+    /// assert_eq!(watcher.contents(), vec!["c", "d"]);
+    /// ```
+    pub fn pub_message<TInputMessage: Into<TMessage>>(
+        &mut self,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.pub_message_excluding(channel, msg.into(), None)
+    }
+
+    /// Publishes a `Message` on behalf of one of the `Clients`, delivering it
+    /// to every subscriber of the `Channel` except the sender.
+    ///
+    /// Otherwise behaves exactly like `pub_message`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::RecipientCapture;
+    ///
+    /// let alice: RecordingClient<_, &str> = RecordingClient::new("alice");
+    /// let bob: RecordingClient<_, &str> = RecordingClient::new("bob");
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// for client in [&alice, &bob].iter() {
+    ///     pubsub.add_client((*client).clone()).unwrap();
+    ///     pubsub.sub_client((*client).clone(), "chat").unwrap();
+    /// }
+    ///
+    /// pubsub.pub_message_from(alice.clone(), "chat", "hi bob").unwrap();
+    ///
+    /// assert!(alice.contents().is_empty());
+    /// assert_eq!(bob.contents(), vec!["hi bob"]);
+    ///
+    /// // The sender stays excluded when recipients are resolved at flush,
+    /// // even through a pattern it subscribed to in the meantime.
+    /// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtFlush));
+    /// pubsub.pub_message_from(bob.clone(), "chat", "hi alice").unwrap();
+    /// pubsub.psub_client(bob.clone(), "ch*").unwrap();
+    /// pubsub.flush();
+    ///
+    /// assert_eq!(alice.contents(), vec!["hi alice"]);
+    /// assert_eq!(bob.contents(), vec!["hi bob"]);
+    /// ```
+    pub fn pub_message_from<TInputMessage: Into<TMessage>>(
+        &mut self,
+        sender: TClient,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.pub_message_excluding(channel, msg.into(), Some(&|| sender.get_id()))
+    }
+
+    /// Publishes a `Message` to a system `Channel`, like `pub_message`.
+    /// See `sub_system`.
+    pub fn pub_system<TInputMessage: Into<TMessage>>(
+        &mut self,
+        name: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        let channel = self.system_name(name)?;
+        let handle = self.routing.channels.get(channel.as_str()).copied();
+
+        self.publish(&channel, handle, msg.into(), None)
+    }
+
+    /// Publishes a `Message` to every subscriber of the `Channel` except the
+    /// `Client` with the given identifier, whether it subscribed to the
+    /// `Channel` itself or to a pattern matching it.
+    ///
+    /// Like `pub_message_from`, for when only the identifier of the sender
+    /// is at hand.  Otherwise behaves exactly like `pub_message`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let exact: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let pattern: RecordingClient<_, &str> = RecordingClient::new(2);
+    /// let both: RecordingClient<_, &str> = RecordingClient::new(3);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// for client in [&exact, &pattern, &both].iter() {
+    ///     pubsub.add_client((*client).clone()).unwrap();
+    /// }
+    /// pubsub.sub_client(exact.clone(), "chat.lobby").unwrap();
+    /// pubsub.psub_client(pattern.clone(), "chat.*").unwrap();
+    /// pubsub.sub_client(both.clone(), "chat.lobby").unwrap();
+    /// pubsub.psub_client(both.clone(), "chat.*").unwrap();
+    ///
+    /// pubsub.pub_message_except("chat.lobby", "from 1", &1).unwrap();
+    /// pubsub.pub_message_except("chat.lobby", "from 2", &2).unwrap();
+    /// pubsub.pub_message_except("chat.lobby", "from 3", &3).unwrap();
+    ///
+    /// assert_eq!(exact.contents(), vec!["from 2", "from 3"]);
+    /// assert_eq!(pattern.contents(), vec!["from 1", "from 3"]);
+    /// assert_eq!(both.contents(), vec!["from 1", "from 2"]);
+    /// ```
+    pub fn pub_message_except<TInputMessage: Into<TMessage>>(
+        &mut self,
+        channel: &str,
+        msg: TInputMessage,
+        exclude: &TIdentifier,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError>
+    where
+        TIdentifier: Clone,
+    {
+        self.pub_message_excluding(channel, msg.into(), Some(&|| exclude.clone()))
+    }
+
+    /// Publishes a `Message` on behalf of the `Client` with the given
+    /// identifier, delivering it to every subscriber of the `Channel`
+    /// except the sender, like `pub_message_except`.
+    ///
+    /// The `Message` does not name its sender, since it is not generic over
+    /// identifiers: contents which need one carry it themselves.
+    ///
+    /// Results in a `PubSubError` when no `Client` has the identifier, or
+    /// as `pub_message` does.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let alice: RecordingClient<_, String> = RecordingClient::new("alice");
+    /// let bob: RecordingClient<_, String> = RecordingClient::new("bob");
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(alice.clone()).unwrap();
+    /// pubsub.add_client(bob.clone()).unwrap();
+    /// pubsub.sub_client(alice.clone(), "chat").unwrap();
+    /// pubsub.psub_client(alice.clone(), "ch*").unwrap();
+    /// pubsub.psub_client(bob.clone(), "ch*").unwrap();
+    ///
+    /// let sender = "alice";
+    /// pubsub.pub_from(&sender, "chat", format!("{}: hi", sender)).unwrap();
+    /// assert!(pubsub.pub_from(&"carol", "chat", "hi").is_err());
+    ///
+    /// assert!(alice.contents().is_empty());
+    /// assert_eq!(bob.contents(), vec!["alice: hi"]);
+    /// ```
+    pub fn pub_from<TInputMessage: Into<TMessage>>(
+        &mut self,
+        sender: &TIdentifier,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError>
+    where
+        TIdentifier: Clone,
+    {
+        if !self.clients.contains_key(sender) {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        self.pub_message_except(channel, msg, sender)
+    }
+
+    /// Sends a `Message` to a single `Client`, whatever its subscriptions.
+    ///
+    /// The `Message` is delivered `Via::Direct`, with an empty `source`, so
+    /// the `Client` can tell it from those published to its `Channels`.  It
+    /// otherwise takes the same path as published `Messages`: in
+    /// `DeliveryMode::Deferred` it is queued until the next `flush`, and a
+    /// failed send is recorded and counts towards eviction.  It is neither
+    /// kept in any history nor recorded in the metrics of a `Channel`.
+    ///
+    /// Results in a `PubSubError` when no `Client` has the identifier.
+    /// Otherwise returns a `PublishReceipt` rather than nothing, since the
+    /// send itself can fail: its `failures` hold the error of the `Client`,
+    /// as for a publish.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    ///
+    /// // The `Client` has no subscriptions.
+    /// let receipt = pubsub.send_to(&1, "bad request").unwrap();
+    ///
+    /// assert_eq!(receipt.delivered, 1);
+    /// assert_eq!(client.received()[0].channel, "");
+    /// assert_eq!(client.contents(), vec!["bad request"]);
+    /// assert!(pubsub.send_to(&2, "bad request").is_err());
+    /// ```
+    pub fn send_to<TInputMessage: Into<TMessage>>(
+        &mut self,
+        identifier: &TIdentifier,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        let identifier = self
+            .clients
+            .get(identifier)
+            .ok_or(PubSubError::ClientDoesNotExistError)?
+            .get_id();
+
+        Ok(self.send_direct(vec![identifier], msg.into()))
+    }
+
+    /// Sends a `Message` to every `Client`, exactly once, whatever their
+    /// subscriptions.  Detached subscribers are not sent it.
+    ///
+    /// Delivered like by `send_to`.  The `PublishReceipt` counts the
+    /// `Clients` sent the `Message` in `delivered`, and holds the errors of
+    /// those it failed to be sent to, which a bare count would lose.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let subscribed: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let idle: RecordingClient<_, &str> = RecordingClient::new(2);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(subscribed.clone()).unwrap();
+    /// pubsub.add_client(idle.clone()).unwrap();
+    /// pubsub.sub_client(subscribed.clone(), "news").unwrap();
+    /// pubsub.psub_client(subscribed.clone(), "*").unwrap();
+    ///
+    /// let receipt = pubsub.broadcast("shutting down");
+    ///
+    /// assert_eq!(receipt.delivered, 2);
+    /// assert!(receipt.failures.is_empty());
+    /// assert_eq!(subscribed.contents(), vec!["shutting down"]);
+    /// assert_eq!(idle.contents(), vec!["shutting down"]);
+    /// ```
+    pub fn broadcast<TInputMessage: Into<TMessage>>(
+        &mut self,
+        msg: TInputMessage,
+    ) -> PublishReceipt<TIdentifier, TClient::Error> {
+        let mut identifiers: Vec<_> = self.clients.values().map(Client::get_id).collect();
+        identifiers.sort();
+
+        self.send_direct(identifiers, msg.into())
+    }
+
+    fn send_direct(
+        &mut self,
+        identifiers: Vec<TIdentifier>,
+        contents: TMessage,
+    ) -> PublishReceipt<TIdentifier, TClient::Error> {
+        let sequence = self.take_sequence();
+
+        let mut receipt = PublishReceipt::default();
+
+        match self.delivery_mode {
+            DeliveryMode::Immediate => {
+                let mut sender = Sender {
+                    clients: &mut self.clients,
+                    failures: &self.failures,
+                    drops: &self.drops,
+                    once: &self.routing.once,
+                    flush: true,
+                };
+
+                for identifier in identifiers {
+                    let message = Message {
+                        contents: contents.clone(),
+                        source: "",
+                        sequence,
+                        via: Via::Direct,
+                        tags: &[],
+                    };
+
+                    match sender.send_one(&identifier, &message) {
+                        Outcome::Sent(_) => receipt.delivered += 1,
+                        Outcome::Failed(identifier, error) => {
+                            receipt.failures.push((identifier, error));
+                        }
+                        Outcome::Absent => {}
+                    }
+                }
+
+                self.evict_failing();
+            }
+            DeliveryMode::Deferred(_) => {
+                let recipients = identifiers
+                    .into_iter()
+                    .map(|identifier| (identifier, StoredVia::Direct, Vec::new()))
+                    .collect();
+
+                self.queue_pending(PendingMessage {
+                    channel: String::new(),
+                    contents,
+                    sequence,
+                    recipients: Some(recipients),
+                    excluded: None,
+                });
+            }
+        }
+
+        receipt
+    }
+
+    fn pub_message_excluding(
+        &mut self,
+        channel: &str,
+        msg: TMessage,
+        excluded: Option<&dyn Fn() -> TIdentifier>,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        let channel = self.channel_names.validate(channel)?;
+
+        if self.routing.is_pattern(channel) {
+            match self.pattern_publish {
+                PatternPublishBehavior::Error => {
+                    return Err(PubSubError::PublishedToPatternError {
+                        channel: channel.to_string(),
+                    });
+                }
+                PatternPublishBehavior::FanOut => {
+                    let mut receipt = PublishReceipt::default();
+
+                    for (channel, handle) in self.routing.channels_matching(channel)? {
+                        let excluded = excluded.map(|excluded| excluded());
+                        let published =
+                            self.publish(&channel, Some(handle), msg.clone(), excluded)?;
+
+                        receipt.delivered += published.delivered;
+                        receipt.channel_existed |= published.channel_existed;
+                        receipt.failures.extend(published.failures);
+                    }
+
+                    return Ok(receipt);
+                }
+                PatternPublishBehavior::Literal => {}
+            }
+        }
+
+        let handle = self.routing.channels.get(channel).copied();
+
+        self.publish(channel, handle, msg, excluded.map(|excluded| excluded()))
+    }
+
+    /// Publishes a `Message` to at most `max_recipients` of the `Clients`
+    /// subscribed to a `Channel`, returning the identifiers of those picked,
+    /// such as to let the first few responders claim work.
+    ///
+    /// Which subscribers are picked is set with `set_limited_fan_out`.
+    /// Detached and stale subscribers are never picked, so they do not use
+    /// up the limit.  The `Message` is delivered according to the
+    /// `DeliveryMode` like any other, but only to those picked, so it is
+    /// neither kept in the history of the `Channel` nor republished.
+    ///
+    /// When the `Message` is sent right away, the `Clients` it failed to
+    /// be sent to are left out of those returned: they used up the limit
+    /// without receiving it.  In `DeliveryMode::Deferred` and on
+    /// micro-batched `Channels` it is sent later, so every `Client` picked
+    /// is returned.
+    ///
+    /// Results in a `PubSubError` as `pub_message` does, or when the
+    /// `Channel` name is a pattern while `PatternPublishBehavior::FanOut` is
+    /// in effect.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::LimitedFanOut;
+    ///
+    /// let workers: Vec<RecordingClient<_, &str>> = (1..=3).map(RecordingClient::new).collect();
+    ///
+    /// let mut pubsub = PubSub::new();
+    ///
+    /// for worker in &workers {
+    ///     pubsub.add_client(worker.clone()).unwrap();
+    ///     pubsub.sub_client(worker.clone(), "jobs").unwrap();
+    /// }
+    ///
+    /// assert_eq!(pubsub.pub_limited("jobs", "a", 1).unwrap(), vec![1]);
+    /// assert_eq!(pubsub.pub_limited("jobs", "b", 1).unwrap(), vec![1]);
+    ///
+    /// pubsub.set_limited_fan_out(LimitedFanOut::RoundRobin);
+    ///
+    /// assert_eq!(pubsub.pub_limited("jobs", "c", 1).unwrap(), vec![1]);
+    /// assert_eq!(pubsub.pub_limited("jobs", "d", 1).unwrap(), vec![2]);
+    /// assert_eq!(pubsub.pub_limited("jobs", "e", 2).unwrap(), vec![3, 1]);
+    ///
+    /// assert_eq!(workers[0].contents(), vec!["a", "b", "c", "e"]);
+    /// assert_eq!(workers[1].contents(), vec!["d"]);
+    /// assert_eq!(workers[2].contents(), vec!["e"]);
+    /// ```
+    ///
+    /// A `Client` whose send fails is picked but not returned:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// struct Worker {
+    ///     id: u32,
+    ///     online: bool,
+    /// }
+    ///
+    /// impl Client<u32, &str> for Worker {
+    ///     type Error = &'static str;
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         self.id
+    ///     }
+    ///
+    ///     fn send(&mut self, _: &Message<&str>) -> Result<(), &'static str> {
+    ///         match self.online {
+    ///             true => Ok(()),
+    ///             false => Err("offline"),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(Worker { id: 1, online: false }).unwrap();
+    /// pubsub.add_client(Worker { id: 2, online: true }).unwrap();
+    /// pubsub.sub_id(&1, "jobs").unwrap();
+    /// pubsub.sub_id(&2, "jobs").unwrap();
+    ///
+    /// assert_eq!(pubsub.pub_limited("jobs", "a", 1).unwrap(), vec![]);
+    /// assert_eq!(pubsub.pub_limited("jobs", "b", 2).unwrap(), vec![2]);
+    /// ```
+    pub fn pub_limited<TInputMessage: Into<TMessage>>(
+        &mut self,
+        channel: &str,
+        msg: TInputMessage,
+        max_recipients: usize,
+    ) -> Result<Vec<TIdentifier>, PubSubError> {
+        let channel = self.channel_names.validate(channel)?;
+
+        if self.routing.is_pattern(channel)
+            && self.pattern_publish != PatternPublishBehavior::Literal
+        {
+            return Err(PubSubError::PublishedToPatternError {
+                channel: channel.to_string(),
+            });
+        }
+
+        self.check_owner(channel, None)?;
+
+        let micro_batch = self.micro_batch_limits(channel);
+        let contents = msg.into();
+
+        let pending_size = match (self.delivery_mode, micro_batch) {
+            (DeliveryMode::Immediate, None) => 0,
+            _ => PendingMessage::<TIdentifier, TMessage>::reserved(channel, &contents, &self.sizer),
+        };
+        self.reserve_memory(pending_size)?;
+        self.publish_metrics.get_mut().record_publish(channel);
+
+        let handle = self.routing.channels.get(channel).copied();
+        let mut recipients = self.connected_recipients(handle, channel, &contents, None);
+
+        if self.limited_fan_out == LimitedFanOut::RoundRobin {
+            let start = self.rotations.advance(channel, recipients.len());
+            recipients.rotate_left(start);
+        }
+
+        recipients.truncate(max_recipients);
+
+        let mut picked: Vec<TIdentifier> = recipients
+            .iter()
+            .filter_map(|(identifier, _, _)| self.clients.get(identifier))
+            .map(Client::get_id)
+            .collect();
+
+        self.record_recipients(channel, &contents, None, &recipients);
+
+        let sequence = self.take_sequence();
+
+        match (self.delivery_mode, micro_batch) {
+            (DeliveryMode::Immediate, None) => {
+                let mut message = Message {
+                    contents,
+                    source: channel,
+                    sequence,
+                    via: Via::Channel,
+                    tags: &[],
+                };
+
+                let mut delivered = 0;
+                let mut spent = Vec::new();
+                let mut sender = Sender {
+                    clients: &mut self.clients,
+                    failures: &self.failures,
+                    drops: &self.drops,
+                    once: &self.routing.once,
+                    flush: true,
+                };
+
+                for (identifier, via, tags) in &recipients {
+                    message.via = via.as_via();
+                    message.tags = tags;
+
+                    match sender.send_one(identifier, &message) {
+                        Outcome::Sent(spends) => {
+                            delivered += 1;
+                            spent.extend(spends);
+                        }
+                        Outcome::Failed(..) => picked.retain(|picked| picked != identifier),
+                        Outcome::Absent => {}
+                    }
+                }
+
+                self.publish_metrics
+                    .get_mut()
+                    .record_fan_out(channel, delivered);
+                self.unsub_spent(spent);
+                self.evict_failing();
+            }
+            (DeliveryMode::Immediate, Some(limits)) => self.hold_in_micro_batch(
+                PendingMessage {
+                    channel: channel.to_string(),
+                    contents,
+                    sequence,
+                    recipients: Some(recipients),
+                    excluded: None,
+                },
+                limits,
+            ),
+            (DeliveryMode::Deferred(_), _) => self.queue_pending(PendingMessage {
+                channel: channel.to_string(),
+                contents,
+                sequence,
+                recipients: Some(recipients),
+                excluded: None,
+            }),
+        }
+
+        Ok(picked)
+    }
+
+    /// Publishes one `Message` to several `Channels` at once, delivering it
+    /// once to each `Client` subscribed to any of them, exactly or through
+    /// a pattern.
+    ///
+    /// A `Client` reached through several of the `Channels` receives the
+    /// `Message` from the first of them in the order they are listed: that
+    /// `Channel` is its `source`, and decides its `via` and `tags`.  All
+    /// recipients receive the same `sequence`.  The `Message` is delivered
+    /// according to the `DeliveryMode` like any other, but, as with
+    /// `pub_limited`, is neither kept in the history of the `Channels` nor
+    /// republished.  The `PublishReceipt` counts each recipient once.
+    ///
+    /// Results in a `PubSubError`, before anything is delivered, as
+    /// `pub_limited` does for any of the `Channels`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let user: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let org: RecordingClient<_, &str> = RecordingClient::new(2);
+    /// let both: RecordingClient<_, &str> = RecordingClient::new(3);
+    /// let watcher: RecordingClient<_, &str> = RecordingClient::new(4);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// for client in [&user, &org, &both, &watcher].iter() {
+    ///     pubsub.add_client((*client).clone()).unwrap();
+    /// }
+    /// pubsub.sub_client(user.clone(), "user.42").unwrap();
+    /// pubsub.sub_client(org.clone(), "org.7.events").unwrap();
+    /// pubsub.sub_client(both.clone(), "user.42").unwrap();
+    /// pubsub.sub_client(both.clone(), "org.7.events").unwrap();
+    /// // Matches two of the three `Channels`.
+    /// pubsub.psub_client(watcher.clone(), "org.*").unwrap();
+    ///
+    /// let channels = ["user.42", "org.7.events", "org.7.audit"];
+    /// let receipt = pubsub.pub_message_multi(&channels, "renamed").unwrap();
+    ///
+    /// assert_eq!(receipt.delivered, 4);
+    /// for client in [&user, &org, &both, &watcher].iter() {
+    ///     assert_eq!(client.contents(), vec!["renamed"]);
+    /// }
+    /// assert_eq!(both.received()[0].channel, "user.42");
+    /// assert_eq!(watcher.received()[0].channel, "org.7.events");
+    /// ```
+    pub fn pub_message_multi<TInputMessage: Into<TMessage>>(
+        &mut self,
+        channels: &[&str],
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        let mut names: Vec<&str> = Vec::with_capacity(channels.len());
+
+        for channel in channels {
+            let channel = self.channel_names.validate(channel)?;
+
+            if self.routing.is_pattern(channel)
+                && self.pattern_publish != PatternPublishBehavior::Literal
+            {
+                return Err(PubSubError::PublishedToPatternError {
+                    channel: channel.to_string(),
+                });
+            }
+
+            self.check_owner(channel, None)?;
+
+            if !names.contains(&channel) {
+                names.push(channel);
+            }
+        }
+
+        let contents = msg.into();
+
+        let pending_size = names
+            .iter()
+            .filter(|channel| {
+                self.delivery_mode != DeliveryMode::Immediate
+                    || self.micro_batch_limits(channel).is_some()
+            })
+            .map(|channel| {
+                PendingMessage::<TIdentifier, TMessage>::reserved(channel, &contents, &self.sizer)
+            })
+            .sum();
+        self.reserve_memory(pending_size)?;
+
+        let sequence = self.take_sequence();
+
+        let mut receipt = PublishReceipt::default();
+        let mut reached = BTreeSet::new();
+        let mut spent = Vec::new();
+
+        for channel in names {
+            self.publish_metrics.get_mut().record_publish(channel);
+
+            let handle = self.routing.channels.get(channel).copied();
+            receipt.channel_existed |= self.routing.has_subscribers(channel, handle);
+
+            let mut recipients = self.connected_recipients(handle, channel, &contents, None);
+            recipients.retain(|(identifier, _, _)| match self.clients.get(identifier) {
+                Some(client) => reached.insert(client.get_id()),
+                None => false,
+            });
+
+            self.record_recipients(channel, &contents, None, &recipients);
+
+            if recipients.is_empty() {
+                continue;
+            }
+
+            match (self.delivery_mode, self.micro_batch_limits(channel)) {
+                (DeliveryMode::Immediate, None) => {
+                    let mut message = Message {
+                        contents: contents.clone(),
+                        source: channel,
+                        sequence,
+                        via: Via::Channel,
+                        tags: &[],
+                    };
+
+                    let mut delivered = 0;
+                    let mut sender = Sender {
+                        clients: &mut self.clients,
+                        failures: &self.failures,
+                        drops: &self.drops,
+                        once: &self.routing.once,
+                        flush: true,
+                    };
+
+                    for (identifier, via, tags) in &recipients {
+                        message.via = via.as_via();
+                        message.tags = tags;
+
+                        match sender.send_one(identifier, &message) {
+                            Outcome::Sent(spends) => {
+                                delivered += 1;
+                                spent.extend(spends);
+                            }
+                            Outcome::Failed(identifier, error) => {
+                                receipt.failures.push((identifier, error));
+                            }
+                            Outcome::Absent => {}
+                        }
+                    }
+
+                    self.publish_metrics
+                        .get_mut()
+                        .record_fan_out(channel, delivered);
+                    receipt.delivered += delivered;
+                }
+                (DeliveryMode::Immediate, Some(limits)) => self.hold_in_micro_batch(
+                    PendingMessage {
+                        channel: channel.to_string(),
+                        contents: contents.clone(),
+                        sequence,
+                        recipients: Some(recipients),
+                        excluded: None,
+                    },
+                    limits,
+                ),
+                (DeliveryMode::Deferred(_), _) => self.queue_pending(PendingMessage {
+                    channel: channel.to_string(),
+                    contents: contents.clone(),
+                    sequence,
+                    recipients: Some(recipients),
+                    excluded: None,
+                }),
+            }
+        }
+
+        self.unsub_spent(spent);
+        self.evict_failing();
+
+        Ok(receipt)
+    }
+
+    /// Sets which subscribers `pub_limited` picks.
+    pub fn set_limited_fan_out(&mut self, fan_out: LimitedFanOut) {
+        self.limited_fan_out = fan_out;
+    }
+
+    fn micro_batch_limits(&self, channel: &str) -> Option<MicroBatchLimits> {
+        match self.delivery_mode {
+            DeliveryMode::Immediate => self
+                .micro_batching
+                .rules
+                .iter()
+                .find(|rule| rule.matches(channel))
+                .map(|rule| rule.limits),
+            DeliveryMode::Deferred(_) => None,
+        }
+    }
+
+    /// Sets what publishing to a `Channel` name containing wildcards does.
+    pub fn set_pattern_publish_behavior(&mut self, behavior: PatternPublishBehavior) {
+        self.pattern_publish = behavior;
+    }
+
+    /// Sets whether subscribing methods other than `psub_client` subscribe
+    /// to patterns.
+    ///
+    /// See `PatternSubscriptions` for details.
+    pub fn set_pattern_subscriptions(&mut self, subscriptions: PatternSubscriptions) {
+        self.pattern_subscriptions = subscriptions;
+    }
+
+    /// Makes `owner` the only `Client` allowed to publish to a `Channel`.
+    ///
+    /// Publishes to the `Channel` made with `pub_message_from` on behalf of
+    /// any other `Client` fail with `PubSubError::NotChannelOwnerError`, as
+    /// do unattributed publishes if the `ChannelOwnershipPolicy` denies
+    /// them.  Ownership applies to the literal `Channel` name, including
+    /// when a publish to a pattern fans out to it.
+    ///
+    /// Results in a `PubSubError` when the `Channel` is already owned by
+    /// another `Client`, or when the `Channel` name is rejected by the
+    /// `ChannelNamePolicy`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{ChannelOwnershipPolicy, OwnerRemoval};
+    ///
+    /// let ticker: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let intruder: RecordingClient<_, &str> = RecordingClient::new(2);
+    /// let reader: RecordingClient<_, &str> = RecordingClient::new(3);
+    /// let not_owner = || Err(PubSubError::NotChannelOwnerError { channel: "prices".to_string() });
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// for client in [&ticker, &intruder, &reader] {
+    ///     pubsub.add_client(client.clone()).unwrap();
+    /// }
+    /// pubsub.sub_client(reader.clone(), "prices").unwrap();
+    /// pubsub.claim_channel("prices", &1).unwrap();
+    /// assert_eq!(pubsub.claim_channel("prices", &2), not_owner());
+    /// assert_eq!(pubsub.channel_owner("prices"), Some(&1));
+    ///
+    /// pubsub.pub_message_from(ticker.clone(), "prices", "100").unwrap();
+    /// assert_eq!(pubsub.pub_message_from(intruder.clone(), "prices", "0").map(drop), not_owner());
+    /// pubsub.pub_message("prices", "101").unwrap();
+    ///
+    /// pubsub.set_channel_ownership_policy(ChannelOwnershipPolicy {
+    ///     allow_unattributed: false,
+    ///     on_owner_removed: OwnerRemoval::Orphan,
+    /// });
+    /// assert_eq!(pubsub.pub_message("prices", "102").map(drop), not_owner());
+    ///
+    /// // An orphaned `Channel` stays closed until it is released.
+    /// pubsub.remove_client(ticker.clone());
+    /// assert_eq!(pubsub.pub_message_from(intruder.clone(), "prices", "0").map(drop), not_owner());
+    /// assert_eq!(pubsub.release_channel("prices"), Some(1));
+    /// pubsub.pub_message_from(intruder.clone(), "prices", "103").unwrap();
+    ///
+    /// assert_eq!(reader.contents(), vec!["100", "101", "103"]);
+    /// ```
+    pub fn claim_channel(&mut self, channel: &str, owner: &TIdentifier) -> Result<(), PubSubError>
+    where
+        TIdentifier: Clone,
+    {
+        let channel = self.channel_names.validate(channel)?;
+
+        match self.owners.entry(channel.to_string()) {
+            Entry::Occupied(entry) if entry.get() != owner => {
+                Err(PubSubError::NotChannelOwnerError {
+                    channel: channel.to_string(),
+                })
+            }
+            Entry::Occupied(_) => Ok(()),
+            Entry::Vacant(entry) => {
+                entry.insert(owner.clone());
+                Ok(())
+            }
+        }
+    }
+
+    /// Releases a `Channel`, returning its owner, if it had one.
+    pub fn release_channel(&mut self, channel: &str) -> Option<TIdentifier> {
+        self.owners.remove(channel)
+    }
+
+    /// Gets the owner of a `Channel`, if it has one.
+    pub fn channel_owner(&self, channel: &str) -> Option<&TIdentifier> {
+        self.owners.get(channel)
+    }
+
+    /// Sets the rules for publishing to owned `Channels`.
+    ///
+    /// Changing what happens when an owner is removed does not affect
+    /// `Channels` already orphaned.
+    pub fn set_channel_ownership_policy(&mut self, policy: ChannelOwnershipPolicy) {
+        self.ownership = policy;
+    }
+
+    /// Takes the sequence number of the next published `Message`.
+    fn take_sequence(&self) -> u64 {
+        let mut next_sequence = self.next_sequence.lock();
+        *next_sequence += 1;
+
+        *next_sequence - 1
+    }
+
+    fn check_owner(&self, channel: &str, sender: Option<&TIdentifier>) -> Result<(), PubSubError> {
+        let allowed = match (self.owners.get(channel), sender) {
+            (None, _) => true,
+            (Some(owner), Some(sender)) => owner == sender,
+            (Some(_), None) => self.ownership.allow_unattributed,
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            Err(PubSubError::NotChannelOwnerError {
+                channel: channel.to_string(),
+            })
+        }
+    }
+
+    /// Publishes a `Message` to all `Clients` subscribed to the `Channel`
+    /// referred to by a `ChannelHandle`.
+    ///
+    /// Results in a `PubSubError` when the handle does not refer to a
+    /// `Channel` of this `PubSub`.
+    pub fn pub_by_handle<TInputMessage: Into<TMessage>>(
+        &mut self,
+        handle: ChannelHandle,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        let channel = self.routing.channel_name(handle)?;
+
+        self.publish(&channel, Some(handle), msg.into(), None)
+    }
+
+    fn publish(
+        &mut self,
+        channel: &str,
+        handle: Option<ChannelHandle>,
+        msg: TMessage,
+        excluded: Option<TIdentifier>,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.check_owner(channel, excluded.as_ref())?;
+
+        let started = self.routing.clock.now();
+        let channel_existed = self.routing.has_subscribers(channel, handle);
+
+        let micro_batch = self.micro_batch_limits(channel);
+
+        let pending_size = match (self.delivery_mode, micro_batch) {
+            (DeliveryMode::Immediate, None) => 0,
+            _ => PendingMessage::<TIdentifier, TMessage>::reserved(channel, &msg, &self.sizer),
+        };
+        let history_capacity = self.retention.capacity_for(channel);
+        let history_size = match history_capacity {
+            0 => 0,
+            _ => self.sizer.entry(&msg),
+        };
+
+        // History is best-effort, so when there is no room for it the
+        // `Message` is still published, just not recorded.
+        let record_history =
+            history_size > 0 && self.reserve_memory(pending_size + history_size).is_ok();
+
+        if !record_history {
+            self.reserve_memory(pending_size)?;
+        }
+
+        // Only republishing needs the contents once they have been
+        // delivered, so only then are they cloned.
+        let republished = match !self.republishing && !self.republish_rules.is_empty() {
+            true => Some(msg.clone()),
+            false => None,
+        };
+
+        self.publish_metrics.get_mut().record_publish(channel);
+
+        let message = Message {
+            contents: msg,
+            source: channel,
+            sequence: self.take_sequence(),
+            via: Via::Channel,
+            tags: &[],
+        };
+
+        if record_history {
+            self.stored_bytes += history_size;
+            self.stored_bytes -= self
+                .retention
+                .histories
+                .entry(channel.to_string())
+                .or_insert_with(ChannelHistory::new)
+                .record(
+                    message.sequence,
+                    message.contents.clone(),
+                    history_capacity,
+                    &self.sizer,
+                );
+        }
+
+        let (delivered, failures) = match (self.delivery_mode, micro_batch) {
+            (DeliveryMode::Immediate, Some(limits)) => {
+                self.batch(handle, message, excluded, limits);
+                (0, Vec::new())
+            }
+            (DeliveryMode::Immediate, None) => self.deliver(handle, message, excluded),
+            (DeliveryMode::Deferred(capture), _) => {
+                self.defer(handle, message, excluded, capture);
+                (0, Vec::new())
+            }
+        };
+
+        self.publish_metrics
+            .get_mut()
+            .record_publish_latency(self.routing.clock.now().duration_since(started));
+
+        if let Some(msg) = republished {
+            self.republish_message(channel, msg);
+        }
+
+        Ok(PublishReceipt {
+            delivered,
+            channel_existed,
+            failures,
+        })
+    }
+
+    fn republish_message(&mut self, channel: &str, msg: TMessage) {
+        let targets: Vec<String> = self
+            .republish_rules
+            .iter()
+            .filter(|(_, selector, _)| self.routing.selects(selector, channel))
+            .filter_map(|(_, _, rewrite)| rewrite.apply(channel))
+            .collect();
+
+        self.republishing = true;
+
+        for target in targets {
+            let validated = match self.channel_names.validate(&target) {
+                Ok(target) => target,
+                Err(_) => {
+                    self.drops
+                        .record(&target, DropReason::RepublishFailed, None);
+                    continue;
+                }
+            };
+            let handle = self.routing.channels.get(validated).copied();
+
+            if self.publish(validated, handle, msg.clone(), None).is_err() {
+                self.drops
+                    .record(validated, DropReason::RepublishFailed, None);
+            }
+        }
+
+        self.republishing = false;
+    }
+
+    /// Republishes every `Message` published to a `Channel` matching
+    /// `from_pattern` to the `Channel` `rewrite` turns its name into, such
+    /// as to mirror traffic between environments.
+    ///
+    /// Republished `Messages` are published like any other, except that
+    /// they are never republished themselves, so rules cannot loop.  They
+    /// are dropped when `rewrite` does not match the `Channel`, or when the
+    /// rewritten name or the publish is rejected.
+    ///
+    /// `from_pattern` is matched by the `ChannelMatcher` of the `PubSub`,
+    /// like a pattern subscription, and compiled once here.
+    ///
+    /// Results in a `PubSubError` when `from_pattern` is rejected by the
+    /// `ChannelNamePolicy`, or is a malformed pattern.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{ChannelRewrite, PatternSyntax};
+    ///
+    /// let mirror: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::with_matcher(PatternSyntax::MQTT);
+    /// pubsub.add_client(mirror.clone()).unwrap();
+    /// pubsub.sub_client(mirror.clone(), "staging.orders.created").unwrap();
+    ///
+    /// let rewrite = ChannelRewrite::new("prod.{*}", "staging.{*}").unwrap();
+    /// pubsub.republish("prod.#", rewrite).unwrap();
+    /// pubsub.pub_message("prod.orders.created", "mirrored").unwrap();
+    /// assert_eq!(mirror.contents(), vec!["mirrored"]);
+    ///
+    /// let rewrite = ChannelRewrite::new("{*}", "{*}").unwrap();
+    /// assert!(matches!(
+    ///     pubsub.republish("a.#.b", rewrite),
+    ///     Err(PubSubError::InvalidPatternError { .. })
+    /// ));
+    /// ```
+    pub fn republish<TChannel: Into<ChannelName>>(
+        &mut self,
+        from_pattern: TChannel,
+        rewrite: ChannelRewrite,
+    ) -> Result<(), PubSubError> {
+        let from_pattern = self.channel_names.validate_name(from_pattern.into())?;
+        let selector = self.routing.selector(from_pattern.clone())?;
+
+        self.republish_rules.push((from_pattern, selector, rewrite));
+
+        Ok(())
+    }
+
+    /// Stops republishing `Messages` from `Channels` matching
+    /// `from_pattern`.
+    pub fn clear_republish(&mut self, from_pattern: &str) {
+        self.republish_rules
+            .retain(|(pattern, _, _)| pattern.as_str() != from_pattern);
+    }
+
+    /// Resolves the recipients of a `Message` when it is published, for
+    /// delivery later on.
+    fn capture_recipients(
+        &mut self,
+        handle: Option<ChannelHandle>,
+        message: &Message<TMessage>,
+        excluded: Option<TIdentifier>,
+    ) -> Vec<(TIdentifier, StoredVia, Vec<u32>)> {
+        let recipients =
+            self.connected_recipients(handle, message.source, &message.contents, excluded.as_ref());
+        self.record_recipients(message.source, &message.contents, excluded, &recipients);
+
+        recipients
+    }
+
+    /// Resolves the recipients of a `Message` which are `Clients` of the
+    /// `PubSub`, skipping detached and stale subscribers.
+    fn connected_recipients(
+        &mut self,
+        handle: Option<ChannelHandle>,
+        channel: &str,
+        contents: &TMessage,
+        excluded: Option<&TIdentifier>,
+    ) -> Vec<(TIdentifier, StoredVia, Vec<u32>)> {
+        let clients = &self.clients;
+        let detached = &self.detached.identifiers;
+        let filters = &self.filters;
+        let (stale_policy, stale_callback) = (self.stale_policy, &self.stale_callback);
+        let drops = &self.drops;
+        let mut stale = 0;
+
+        let mut resolved =
+            self.routing
+                .resolve(channel, handle, drops, |subscription, identifier| {
+                    filters.admits(subscription, identifier, contents)
+                });
+        resolved.retain(|(identifier, _, _)| Some(*identifier) != excluded);
+
+        if resolved.is_empty() {
+            drops.record(channel, DropReason::NoRecipients, None);
+        }
+
+        let recipients = resolved
+            .into_iter()
+            .filter_map(|(identifier, via, tags)| {
+                let client = match clients.get(identifier) {
+                    Some(client) => client,
+                    None => {
+                        if !detached.contains_key(identifier)
+                            && stale::report(
+                                stale_policy,
+                                stale_callback,
+                                drops,
+                                channel,
+                                identifier,
+                            )
+                        {
+                            stale += 1;
+                        }
+
+                        return None;
+                    }
+                };
+
+                Some((client.get_id(), StoredVia::new(via), tags))
+            })
+            .collect();
+
+        self.purge_stale(stale);
+
+        recipients
+    }
+
+    fn record_recipients(
+        &mut self,
+        channel: &str,
+        contents: &TMessage,
+        excluded: Option<TIdentifier>,
+        recipients: &[(TIdentifier, StoredVia, Vec<u32>)],
+    ) {
+        if self.recording.is_none() {
+            return;
+        }
+
+        let recorded = recipients
+            .iter()
+            .filter_map(|(identifier, _, _)| self.clients.get(identifier))
+            .map(Client::get_id)
+            .collect();
+
+        self.record_publish(channel, contents, excluded, recorded);
+    }
+
+    fn defer(
+        &mut self,
+        handle: Option<ChannelHandle>,
+        message: Message<TMessage>,
+        excluded: Option<TIdentifier>,
+        capture: RecipientCapture,
+    ) {
+        // Once the recipients are final, the sender is no longer needed.
+        let (recipients, excluded) = match capture {
+            RecipientCapture::AtPublish => (
+                Some(self.capture_recipients(handle, &message, excluded)),
+                None,
+            ),
+            RecipientCapture::AtFlush => (None, excluded),
+        };
+
+        self.queue_pending(PendingMessage {
+            channel: message.source.to_string(),
+            contents: message.contents,
+            sequence: message.sequence,
+            recipients,
+            excluded,
+        });
+    }
+
+    /// Queues a `Message` until the next `flush`.
+    fn queue_pending(&mut self, pending: PendingMessage<TIdentifier, TMessage>) {
+        self.stored_bytes += pending.size(&self.sizer);
+        self.pending.push(pending);
+    }
+
+    fn batch(
+        &mut self,
+        handle: Option<ChannelHandle>,
+        message: Message<TMessage>,
+        excluded: Option<TIdentifier>,
+        limits: MicroBatchLimits,
+    ) {
+        let recipients = self.capture_recipients(handle, &message, excluded);
+
+        self.hold_in_micro_batch(
+            PendingMessage {
+                channel: message.source.to_string(),
+                contents: message.contents,
+                sequence: message.sequence,
+                recipients: Some(recipients),
+                excluded: None,
+            },
+            limits,
+        );
+    }
+
+    fn hold_in_micro_batch(
+        &mut self,
+        pending: PendingMessage<TIdentifier, TMessage>,
+        limits: MicroBatchLimits,
+    ) {
+        let now = self.routing.clock.now();
+
+        if self
+            .micro_batching
+            .batches
+            .get(&pending.channel)
+            .is_some_and(|batch| batch.is_due(now))
+        {
+            self.deliver_micro_batch(&pending.channel);
+        }
+
+        let channel = pending.channel.clone();
+        let batch = self
+            .micro_batching
+            .batches
+            .entry(channel.clone())
+            .or_insert_with(|| MicroBatch::new(now, limits.max_delay));
+
+        self.stored_bytes += pending.size(&self.sizer);
+        batch.messages.push(pending);
+
+        if batch.messages.len() >= limits.max_batch {
+            self.deliver_micro_batch(&channel);
+        }
+    }
+
+    fn deliver_micro_batch(&mut self, channel: &str) -> DeliveryReport {
+        let batch = match self.micro_batching.batches.remove(channel) {
+            Some(batch) => batch,
+            None => return DeliveryReport::default(),
+        };
+        self.stored_bytes -= batch
+            .messages
+            .iter()
+            .map(|pending| pending.size(&self.sizer))
+            .sum::<usize>();
+
+        let mut batches: BTreeMap<&TIdentifier, Vec<Message<TMessage>>> = BTreeMap::new();
+
+        for pending in &batch.messages {
+            let mut delivered = 0;
+
+            for (identifier, via, tags) in pending.recipients.iter().flatten() {
+                if self.clients.contains_key(identifier) {
+                    batches.entry(identifier).or_default().push(Message {
+                        contents: pending.contents.clone(),
+                        source: &pending.channel,
+                        sequence: pending.sequence,
+                        via: via.as_via(),
+                        tags,
+                    });
+                    delivered += 1;
+                } else {
+                    self.drops.record(
+                        &pending.channel,
+                        DropReason::RecipientRemoved,
+                        Some(identifier),
+                    );
+                }
+            }
+
+            self.publish_metrics
+                .get_mut()
+                .record_fan_out(channel, delivered);
+        }
+
+        let mut report = DeliveryReport {
+            messages: batch.messages.len(),
+            ..DeliveryReport::default()
+        };
+        let mut spent = Vec::new();
+        let mut sender = Sender {
+            clients: &mut self.clients,
+            failures: &self.failures,
+            drops: &self.drops,
+            once: &self.routing.once,
+            flush: true,
+        };
+
+        for (identifier, messages) in batches {
+            match sender.send_batch(identifier, channel, &messages) {
+                Outcome::Sent(spends) => {
+                    report.deliveries += messages.len();
+                    spent.extend(spends);
+                }
+                Outcome::Failed(..) => report.failed += 1,
+                Outcome::Absent => {}
+            }
+        }
+
+        self.unsub_spent(spent);
+        self.evict_failing();
+
+        report
+    }
+
+    fn deliver(
+        &mut self,
+        handle: Option<ChannelHandle>,
+        message: Message<TMessage>,
+        excluded: Option<TIdentifier>,
+    ) -> (usize, Vec<(TIdentifier, TClient::Error)>) {
+        let channel = message.source;
+        let mut delivered = 0;
+        let mut failures = Vec::new();
+        let mut stale = 0;
+        let mut recorded = BTreeSet::new();
+        let mut spent = Vec::new();
+        let once = std::mem::take(&mut self.routing.once);
+
+        let filters = &self.filters;
+        let recipients =
+            self.routing
+                .resolve(channel, handle, &self.drops, |subscription, identifier| {
+                    filters.admits(subscription, identifier, &message.contents)
+                });
+        let mut message = message;
+
+        if recipients
+            .iter()
+            .all(|(identifier, _, _)| Some(*identifier) == excluded.as_ref())
+        {
+            self.drops.record(channel, DropReason::NoRecipients, None);
+        }
+
+        let mut sender = Sender {
+            clients: &mut self.clients,
+            failures: &self.failures,
+            drops: &self.drops,
+            once: &once,
+            flush: true,
+        };
+
+        for (identifier, via, tags) in &recipients {
+            let identifier = *identifier;
+
+            if Some(identifier) == excluded.as_ref() {
+                continue;
+            }
+
+            message.via = *via;
+            message.tags = tags;
+
+            match sender.send_one(identifier, &message) {
+                Outcome::Sent(spends) => {
+                    delivered += 1;
+                    spent.extend(spends);
+                }
+                Outcome::Failed(identifier, error) => failures.push((identifier, error)),
+                Outcome::Absent => {
+                    if let Some(detached) = self.detached.identifiers.get_mut(identifier) {
+                        let buffered = BufferedMessage {
+                            channel: channel.to_string(),
+                            contents: message.contents.clone(),
+                            sequence: message.sequence,
+                            via: StoredVia::new(*via),
+                            tags: tags.clone(),
+                        };
+                        self.stored_bytes += buffered.size(&self.sizer);
+
+                        if let Some(dropped) = detached.buffer(buffered, self.detached.capacity) {
+                            self.stored_bytes -= dropped.size(&self.sizer);
+                            sender.drops.record(
+                                &dropped.channel,
+                                DropReason::DetachedBufferFull,
+                                Some(identifier),
+                            );
+                        }
+                    } else if stale::report(
+                        self.stale_policy,
+                        &self.stale_callback,
+                        sender.drops,
+                        channel,
+                        identifier,
+                    ) {
+                        stale += 1;
+                    }
+
+                    continue;
+                }
+            }
+
+            if self.recording.is_some() {
+                recorded.insert(sender.clients[identifier].get_id());
+            }
+        }
+
+        let contents = message.contents;
+
+        self.routing.once = once;
+        self.unsub_spent(spent);
+        self.purge_stale(stale);
+        self.publish_metrics
+            .get_mut()
+            .record_fan_out(channel, delivered);
+        self.record_publish(channel, &contents, excluded, recorded);
+        self.evict_failing();
+
+        (delivered, failures)
+    }
+
+    /// Holds `Messages` published to `Channels` matching a pattern, to
+    /// deliver them to each recipient with a single `Client::send_batch`.
+    ///
+    /// A `Channel`'s `Messages` are held until `max_batch` of them are
+    /// waiting, or until a publish or `flush_micro_batches` finds the oldest
+    /// of them waiting for at least `max_delay`, measured with the `Clock`.
+    /// Recipients are resolved when each `Message` is published, so
+    /// detached `Clients` do not receive them (see `detach_client`).
+    ///
+    /// Setting a pattern again replaces its limits.  When several patterns
+    /// match a `Channel`, the one set first applies.  Micro-batching only
+    /// applies in `DeliveryMode::Immediate`, and control `Messages` are
+    /// never held.
+    ///
+    /// Results in a `PubSubError` when the pattern is rejected by the
+    /// `ChannelNamePolicy`.
+    ///
+    /// ```
+    /// use general_pub_sub::{Client, Clock, ManualClock, Message, PubSub};
+    /// use std::convert::Infallible;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    ///
+    /// struct Batches {
+    ///     id: u32,
+    ///     received: Arc<Mutex<Vec<Vec<u32>>>>,
+    /// }
+    ///
+    /// impl Client<u32, u32> for Batches {
+    ///     type Error = Infallible;
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         self.id
+    ///     }
+    ///
+    ///     fn send(&mut self, message: &Message<u32>) -> Result<(), Infallible> {
+    ///         self.received.lock().unwrap().push(vec![message.contents]);
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn send_batch(&mut self, messages: &[Message<u32>]) -> Result<(), Infallible> {
+    ///         let contents = messages.iter().map(|message| message.contents).collect();
+    ///         self.received.lock().unwrap().push(contents);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let received = Arc::new(Mutex::new(Vec::new()));
+    /// let take = || std::mem::take(&mut *received.lock().unwrap());
+    /// let clock = ManualClock::new();
+    /// let millis = Duration::from_millis;
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_clock(clock.clone());
+    /// pubsub.set_micro_batch("metrics.*", millis(10), 3).unwrap();
+    /// pubsub
+    ///     .add_client(Batches { id: 1, received: received.clone() })
+    ///     .unwrap();
+    /// pubsub.sub_id(&1, "metrics.cpu").unwrap();
+    /// pubsub.sub_id(&1, "news").unwrap();
+    ///
+    /// // Other `Channels` are delivered right away.
+    /// pubsub.pub_message("news", 100u32).unwrap();
+    /// assert_eq!(take(), vec![vec![100]]);
+    ///
+    /// // The third `Message` fills the batch.
+    /// pubsub.pub_message("metrics.cpu", 0u32).unwrap();
+    /// pubsub.pub_message("metrics.cpu", 1u32).unwrap();
+    /// assert!(take().is_empty());
+    /// pubsub.pub_message("metrics.cpu", 2u32).unwrap();
+    /// assert_eq!(take(), vec![vec![0, 1, 2]]);
+    ///
+    /// // A batch is due once its oldest `Message` waited exactly `max_delay`.
+    /// pubsub.pub_message("metrics.cpu", 3u32).unwrap();
+    /// clock.advance(millis(9));
+    /// assert_eq!(pubsub.flush_micro_batches(clock.now()).messages, 0);
+    /// clock.advance(millis(1));
+    /// assert_eq!(pubsub.flush_micro_batches(clock.now()).messages, 1);
+    /// assert_eq!(take(), vec![vec![3]]);
+    ///
+    /// // A publish delivers the due batch before starting the next one,
+    /// // whose delay runs from its own first `Message`.
+    /// pubsub.pub_message("metrics.cpu", 4u32).unwrap();
+    /// clock.advance(millis(10));
+    /// pubsub.pub_message("metrics.cpu", 5u32).unwrap();
+    /// assert_eq!(take(), vec![vec![4]]);
+    /// clock.advance(millis(9));
+    /// assert_eq!(pubsub.flush_micro_batches(clock.now()).messages, 0);
+    /// clock.advance(millis(1));
+    /// assert_eq!(pubsub.flush_micro_batches(clock.now()).messages, 1);
+    /// assert_eq!(take(), vec![vec![5]]);
+    /// ```
+    pub fn set_micro_batch<TChannel: Into<ChannelName>>(
+        &mut self,
+        pattern: TChannel,
+        max_delay: Duration,
+        max_batch: usize,
+    ) -> Result<(), PubSubError> {
+        let pattern = self.channel_names.validate_name(pattern.into())?;
+        let limits = MicroBatchLimits {
+            max_delay,
+            max_batch,
+        };
+
+        match self
+            .micro_batching
+            .rules
+            .iter_mut()
+            .find(|rule| rule.pattern == pattern)
+        {
+            Some(existing) => existing.limits = limits,
+            None => self
+                .micro_batching
+                .rules
+                .push(MicroBatchRule { pattern, limits }),
+        }
+
+        Ok(())
+    }
+
+    /// Stops micro-batching `Channels` matching a pattern, delivering the
+    /// `Messages` held for them.
+    pub fn clear_micro_batch(&mut self, pattern: &str) -> DeliveryReport {
+        self.micro_batching
+            .rules
+            .retain(|rule| rule.pattern.as_ref() != pattern);
+
+        let rules = &self.micro_batching.rules;
+        let channels: Vec<String> = self
+            .micro_batching
+            .batches
+            .keys()
+            .filter(|channel| !rules.iter().any(|rule| rule.matches(channel)))
+            .cloned()
+            .collect();
+
+        self.deliver_micro_batches(channels)
+    }
+
+    /// Delivers the micro-batches whose oldest `Message` has been waiting
+    /// for at least their `max_delay` at `now`.
+    ///
+    /// Meant to be called on a timer, so that `Messages` are delivered once
+    /// publishing to their `Channel` stops.
+    pub fn flush_micro_batches(&mut self, now: Instant) -> DeliveryReport {
+        let channels = self
+            .micro_batching
+            .batches
+            .iter()
+            .filter(|(_, batch)| batch.is_due(now))
+            .map(|(channel, _)| channel.clone())
+            .collect();
+
+        self.deliver_micro_batches(channels)
+    }
+
+    fn deliver_micro_batches(&mut self, mut channels: Vec<String>) -> DeliveryReport {
+        let batches = &self.micro_batching.batches;
+        channels.sort_by_key(|channel| batches[channel].started);
+
+        let mut report = DeliveryReport::default();
+
+        for channel in channels {
+            let delivered = self.deliver_micro_batch(&channel);
+
+            report.messages += delivered.messages;
+            report.deliveries += delivered.deliveries;
+            report.failed += delivered.failed;
+        }
+
+        report
+    }
+
+    /// Sets how published `Messages` are delivered.
+    ///
+    /// `Messages` already pending stay pending when switching back to
+    /// `DeliveryMode::Immediate`, until they are flushed or discarded.
+    pub fn set_delivery_mode(&mut self, mode: DeliveryMode) {
+        self.delivery_mode = mode;
+    }
+
+    /// Gets the number of `Messages` waiting to be flushed.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Delivers every pending `Message`, in the order they were published.
+    ///
+    /// Each recipient is flushed once, after all of its `Messages` were sent.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.sub_client(client.clone(), "sports").unwrap();
+    ///
+    /// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtPublish));
+    /// pubsub.pub_message("news", "first").unwrap();
+    /// pubsub.pub_message("sports", "second").unwrap();
+    /// pubsub.pub_message("news", "third").unwrap();
+    /// assert!(client.contents().is_empty());
+    /// assert_eq!(pubsub.metrics().pending, 3);
+    ///
+    /// let report = pubsub.flush();
+    /// assert_eq!(report.messages, 3);
+    /// assert_eq!(report.deliveries, 3);
+    /// assert_eq!(client.contents(), vec!["first", "second", "third"]);
+    /// assert_eq!(pubsub.pending_count(), 0);
+    /// ```
+    ///
+    /// A `Client` buffering its output is flushed once per `flush`, however
+    /// many `Messages` it received:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use std::convert::Infallible;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// struct Buffered {
+    ///     id: u32,
+    ///     flushes: Arc<AtomicUsize>,
+    /// }
+    ///
+    /// impl Client<u32, &str> for Buffered {
+    ///     type Error = Infallible;
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         self.id
+    ///     }
+    ///
+    ///     fn send(&mut self, _: &Message<&str>) -> Result<(), Infallible> {
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> Result<(), Infallible> {
+    ///         self.flushes.fetch_add(1, Ordering::SeqCst);
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// let flushes = Arc::new(AtomicUsize::new(0));
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(Buffered { id: 1, flushes: flushes.clone() }).unwrap();
+    /// pubsub.sub_id(&1, "news").unwrap();
+    ///
+    /// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtFlush));
+    /// for _ in 0..3 {
+    ///     pubsub.pub_message("news", "update").unwrap();
+    /// }
+    ///
+    /// assert_eq!(pubsub.flush().deliveries, 3);
+    /// assert_eq!(flushes.load(Ordering::SeqCst), 1);
+    /// ```
+    pub fn flush(&mut self) -> DeliveryReport {
+        let mut report = DeliveryReport::default();
+        let mut recipients = BTreeSet::new();
+
+        for pending in std::mem::take(&mut self.pending) {
+            self.stored_bytes -= pending.size(&self.sizer);
+            let excluded = pending.excluded;
+            let mut message = Message {
+                contents: pending.contents,
+                source: &pending.channel,
+                sequence: pending.sequence,
+                via: Via::Channel,
+                tags: &[],
+            };
+
+            let mut delivered = 0;
+            let mut spent = Vec::new();
+
+            match &pending.recipients {
+                Some(identifiers) => {
+                    let mut sender = Sender {
+                        clients: &mut self.clients,
+                        failures: &self.failures,
+                        drops: &self.drops,
+                        once: &self.routing.once,
+                        flush: false,
+                    };
+
+                    for (identifier, via, tags) in identifiers {
+                        message.via = via.as_via();
+                        message.tags = tags;
+
+                        match sender.send_one(identifier, &message) {
+                            Outcome::Sent(spends) => {
+                                delivered += 1;
+                                spent.extend(spends);
+                            }
+                            Outcome::Failed(..) => report.failed += 1,
+                            Outcome::Absent => {
+                                sender.drops.record(
+                                    &pending.channel,
+                                    DropReason::RecipientRemoved,
+                                    Some(identifier),
+                                );
+
+                                continue;
+                            }
+                        }
+
+                        recipients.insert(sender.clients[identifier].get_id());
+                    }
+                }
+                None => {
+                    let handle = self.routing.channels.get(pending.channel.as_str()).copied();
+                    let mut recorded = BTreeSet::new();
+                    let mut stale = 0;
+                    let once = std::mem::take(&mut self.routing.once);
+
+                    let filters = &self.filters;
+                    let contents = &message.contents;
+                    let resolved = self.routing.resolve(
+                        &pending.channel,
+                        handle,
+                        &self.drops,
+                        |subscription, identifier| {
+                            filters.admits(subscription, identifier, contents)
+                        },
+                    );
+
+                    if resolved
+                        .iter()
+                        .all(|(identifier, _, _)| Some(*identifier) == excluded.as_ref())
+                    {
+                        self.drops
+                            .record(&pending.channel, DropReason::NoRecipients, None);
+                    }
+
+                    let mut sender = Sender {
+                        clients: &mut self.clients,
+                        failures: &self.failures,
+                        drops: &self.drops,
+                        once: &once,
+                        flush: false,
+                    };
+
+                    for (identifier, via, tags) in &resolved {
+                        let identifier = *identifier;
+
+                        if Some(identifier) == excluded.as_ref() {
+                            continue;
+                        }
+
+                        message.via = *via;
+                        message.tags = tags;
+
+                        match sender.send_one(identifier, &message) {
+                            Outcome::Sent(spends) => {
+                                delivered += 1;
+                                spent.extend(spends);
+                            }
+                            Outcome::Failed(..) => report.failed += 1,
+                            Outcome::Absent => {
+                                if let Some(detached) =
+                                    self.detached.identifiers.get_mut(identifier)
+                                {
+                                    let buffered = BufferedMessage {
+                                        channel: pending.channel.clone(),
+                                        contents: message.contents.clone(),
+                                        sequence: pending.sequence,
+                                        via: StoredVia::new(*via),
+                                        tags: tags.clone(),
+                                    };
+                                    self.stored_bytes += buffered.size(&self.sizer);
+
+                                    if let Some(dropped) =
+                                        detached.buffer(buffered, self.detached.capacity)
+                                    {
+                                        self.stored_bytes -= dropped.size(&self.sizer);
+                                        sender.drops.record(
+                                            &dropped.channel,
+                                            DropReason::DetachedBufferFull,
+                                            Some(identifier),
+                                        );
+                                    }
+                                } else if stale::report(
+                                    self.stale_policy,
+                                    &self.stale_callback,
+                                    sender.drops,
+                                    &pending.channel,
+                                    identifier,
+                                ) {
+                                    report.stale += 1;
+                                    stale += 1;
+                                }
+
+                                continue;
+                            }
+                        }
+
+                        recipients.insert(sender.clients[identifier].get_id());
+
+                        if self.recording.is_some() {
+                            recorded.insert(sender.clients[identifier].get_id());
+                        }
+                    }
+
+                    let contents = message.contents;
+
+                    self.routing.once = once;
+                    self.purge_stale(stale);
+                    self.record_publish(&pending.channel, &contents, excluded, recorded);
+                }
+            }
+
+            self.unsub_spent(spent);
+
+            // Direct `Messages` have no `Channel` to record.
+            if !pending.channel.is_empty() {
+                self.publish_metrics
+                    .get_mut()
+                    .record_fan_out(&pending.channel, delivered);
+            }
+
+            report.messages += 1;
+            report.deliveries += delivered;
+        }
+
+        for identifier in recipients {
+            self.flush_client(&identifier);
+        }
+
+        self.evict_failing();
+
+        report
+    }
+
+    /// Drops every pending `Message` without delivering it, returning how
+    /// many were dropped.
+    ///
+    /// Each is reported as `DropReason::PendingDiscarded`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::DropReason;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    ///
+    /// pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtPublish));
+    /// for update in ["one", "two", "three"] {
+    ///     pubsub.pub_message("news", update).unwrap();
+    /// }
+    ///
+    /// assert_eq!(pubsub.discard_pending(), 3);
+    /// assert_eq!(pubsub.flush().deliveries, 0);
+    /// assert!(client.contents().is_empty());
+    /// assert_eq!(
+    ///     pubsub.metrics().drops.get(&DropReason::PendingDiscarded),
+    ///     Some(&3)
+    /// );
+    /// ```
+    pub fn discard_pending(&mut self) -> usize {
+        let discarded = self.pending.len();
+
+        for pending in self.pending.drain(..) {
+            self.stored_bytes -= pending.size(&self.sizer);
+            self.drops
+                .record(&pending.channel, DropReason::PendingDiscarded, None);
+        }
+
+        discarded
+    }
+
+    /// Sets how many `Channels` the patterns matching them are cached for,
+    /// or turns the cache off with 0.
+    ///
+    /// The patterns matching a `Channel` are found on the first publish to
+    /// it, and reused until a new pattern matching it is subscribed to.
+    /// Publishing to many distinct `Channels` clears the cache whenever it
+    /// reaches `capacity`, which defaults to 4096.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, String> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.psub_client(client.clone(), "sensor.*").unwrap();
+    /// pubsub.set_pattern_cache_capacity(2);
+    ///
+    /// for sensor in 0..5 {
+    ///     pubsub.pub_message(&format!("sensor.{}", sensor), "reading").unwrap();
+    ///     assert!(pubsub.metrics().pattern_cache_entries <= 2);
+    /// }
+    ///
+    /// // Cached as matching no pattern, until one matching it is added.
+    /// pubsub.pub_message("alerts.4", "unheard").unwrap();
+    /// pubsub.psub_client(client.clone(), "*.4").unwrap();
+    /// pubsub.pub_message("alerts.4", "heard").unwrap();
+    ///
+    /// assert_eq!(client.contents().len(), 6);
+    /// assert_eq!(client.contents().last().unwrap(), "heard");
+    /// ```
+    pub fn set_pattern_cache_capacity(&mut self, capacity: usize) {
+        self.routing.match_cache.get_mut().set_capacity(capacity);
+    }
+
+    /// Sets how the attachments of overlapping subscriptions are resolved.
+    ///
+    /// See `PatternResolution` for details.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{PatternResolution, PatternSubscriptions};
+    ///
+    /// let client: RecordingClient<_, u32> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_pattern_subscriptions(PatternSubscriptions::Detected);
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_id(&1, "orders.*").unwrap();
+    /// pubsub
+    ///     .sub_client_filtered(&1, "orders.eu.*", |amount: &u32| *amount > 1000)
+    ///     .unwrap();
+    ///
+    /// // `orders.*` admits what the filter of `orders.eu.*` rejects.
+    /// pubsub.pub_message("orders.eu.fr", 500u32).unwrap();
+    ///
+    /// // Only the filter of `orders.eu.*`, the most specific, applies.
+    /// pubsub.set_pattern_resolution(PatternResolution::MostSpecific);
+    /// pubsub.pub_message("orders.eu.fr", 600u32).unwrap();
+    /// pubsub.pub_message("orders.eu.fr", 1500u32).unwrap();
+    /// pubsub.pub_message("orders.us", 700u32).unwrap();
+    ///
+    /// assert_eq!(client.contents(), vec![500, 1500, 700]);
+    /// ```
+    pub fn set_pattern_resolution(&mut self, resolution: PatternResolution) {
+        self.routing.pattern_resolution = resolution;
+    }
+
+    /// Gets the subscription of a `Client` that wins for a `Channel`.
+    ///
+    /// This is the `Channel` itself if the `Client` has a literal subscription
+    /// to it, otherwise the most specific of the `Client`'s matching patterns.
+    /// Returns `None` when none of the `Client`'s subscriptions match.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.psub_client(client.clone(), "sensors.*").unwrap();
+    /// pubsub.psub_client(client.clone(), "sensors.kitchen.*").unwrap();
+    /// pubsub.sub_client(client.clone(), "sensors.hall").unwrap();
+    ///
+    /// assert_eq!(pubsub.winning_pattern(&1, "sensors.kitchen.temp"), Some("sensors.kitchen.*"));
+    /// assert_eq!(pubsub.winning_pattern(&1, "sensors.garage"), Some("sensors.*"));
+    /// assert_eq!(pubsub.winning_pattern(&1, "sensors.hall"), Some("sensors.hall"));
+    /// assert_eq!(pubsub.winning_pattern(&1, "alerts"), None);
+    /// assert_eq!(pubsub.winning_pattern(&2, "sensors.hall"), None);
+    /// ```
+    pub fn winning_pattern(&self, identifier: &TIdentifier, channel: &str) -> Option<&str> {
+        self.routing.winning_subscription(identifier, channel)
+    }
+
+    /// Applies a list of `Ops` in order.
+    ///
+    /// Unlike stopping at the first error, every `Op` is attempted, and the
+    /// result of each is returned at the same index as its `Op`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::Op;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let mut pubsub = PubSub::new();
+    ///
+    /// let results = pubsub.apply(vec![
+    ///     Op::AddClient(client.clone()),
+    ///     Op::Sub(1, "news".into()),
+    ///     Op::Unsub(1, "sports".into()),
+    ///     Op::Sub(1, "sports".into()),
+    ///     Op::Sub(2, "news".into()),
+    ///     Op::RemoveClient(2),
+    /// ]);
+    ///
+    /// assert_eq!(
+    ///     results,
+    ///     vec![
+    ///         Ok(()),
+    ///         Ok(()),
+    ///         Err(PubSubError::ChannelDoesNotExistError { channel: "sports".to_string() }),
+    ///         Ok(()),
+    ///         Err(PubSubError::ClientDoesNotExistError),
+    ///         Err(PubSubError::ClientDoesNotExistError),
+    ///     ]
+    /// );
+    ///
+    /// pubsub.pub_message("news", "a").unwrap();
+    /// pubsub.pub_message("sports", "b").unwrap();
+    /// assert_eq!(client.contents(), vec!["a", "b"]);
+    ///
+    /// assert_eq!(pubsub.apply(vec![Op::RemoveClient(1)]), vec![Ok(())]);
+    /// assert_eq!(pubsub.client_count(), 0);
+    /// ```
+    pub fn apply(&mut self, ops: Vec<Op<TClient, TIdentifier>>) -> Vec<Result<(), PubSubError>> {
+        ops.into_iter()
+            .map(|op| match op {
+                Op::AddClient(client) => self.add_client(client).map(|_| ()),
+                Op::RemoveClient(identifier) => self.remove_client_by_id(&identifier),
+                Op::Sub(identifier, channel) => self.sub_id(&identifier, channel),
+                Op::Unsub(identifier, channel) => self.unsub_id(&identifier, &channel),
+            })
+            .collect()
+    }
+
+    /// Loads `Clients` and their subscriptions in bulk, such as when
+    /// restoring a `PubSub` on startup.
+    ///
+    /// `client_for` creates the `Client` for each identifier that is not in
+    /// the `PubSub` yet; existing `Clients` are kept as they are.
+    ///
+    /// Every record is loaded even if some of its subscriptions are rejected.
+    /// Rejected subscriptions are listed in the `ImportReport`, with the same
+    /// `PubSubError` that `sub_client` would return for them.
+    ///
+    /// None of the callbacks set with `set_client_added_callback`,
+    /// `set_subscribe_callback` and the like are notified of the imported
+    /// `Clients` and subscriptions: the `ImportReport` sums them up instead.
+    ///
+    /// `benches/import.rs` measures it against adding each `Client` and
+    /// calling `sub_id` for each subscription.
+    ///
+    /// There is no `import_json` or `export_json`, since the crate does not
+    /// depend on `serde`: an application reads its `ImportRecords` from
+    /// whichever format it stores them in, and writes them from
+    /// `subscription_snapshot`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::ImportRecord;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let notified = Arc::new(AtomicUsize::new(0));
+    /// let mut pubsub: PubSub<RecordingClient<u32, &str>, u32, &str> = PubSub::new();
+    ///
+    /// let counter = notified.clone();
+    /// pubsub.set_client_added_callback(move |_| {
+    ///     counter.fetch_add(1, Ordering::Relaxed);
+    /// });
+    /// let counter = notified.clone();
+    /// pubsub.set_subscribe_callback(move |_, _| {
+    ///     counter.fetch_add(1, Ordering::Relaxed);
+    /// });
+    ///
+    /// let records = (0..100_000).map(|id| ImportRecord {
+    ///     id,
+    ///     channels: vec![format!("users.{}", id).into()],
+    /// });
+    /// let report = pubsub.import(records, |id| RecordingClient::new(*id));
+    ///
+    /// assert_eq!((report.clients, report.subscriptions), (100_000, 100_000));
+    /// assert!(report.errors.is_empty());
+    /// assert_eq!(pubsub.client_count(), 100_000);
+    /// assert_eq!(notified.load(Ordering::Relaxed), 0);
+    ///
+    /// pubsub.pub_message("users.99999", "hello").unwrap();
+    /// assert_eq!(pubsub.stats().deliveries, 1);
+    ///
+    /// // Changes made afterwards are notified as usual.
+    /// pubsub.sub_id(&1, "news").unwrap();
+    /// assert_eq!(notified.load(Ordering::Relaxed), 1);
+    /// ```
+    pub fn import<I, F>(&mut self, records: I, mut client_for: F) -> ImportReport
+    where
+        I: IntoIterator<Item = ImportRecord<TIdentifier>>,
+        F: FnMut(&TIdentifier) -> TClient,
+    {
+        let records = records.into_iter();
+        let mut report = ImportReport::default();
+        let hooks = std::mem::replace(&mut self.routing.hooks, Hooks::new());
+
+        self.clients.reserve(records.size_hint().0);
+
+        for (index, record) in records.enumerate() {
+            let identifier = match self.clients.entry(record.id) {
+                Entry::Occupied(entry) => entry.get().get_id(),
+                Entry::Vacant(entry) => {
+                    let client = client_for(entry.key());
+                    report.clients += 1;
+                    entry.insert(client).get_id()
+                }
+            };
+
+            self.import_subscriptions(
+                index,
+                record.channels,
+                |clients| clients[&identifier].get_id(),
+                &mut report,
+            );
+        }
+
+        self.routing.hooks = hooks;
+
+        report
+    }
+
+    fn import_subscriptions<F>(
+        &mut self,
+        index: usize,
+        channels: Vec<ChannelName>,
+        get_id: F,
+        report: &mut ImportReport,
+    ) where
+        F: Fn(&HashMap<TIdentifier, TClient>) -> TIdentifier,
+    {
+        for channel in channels {
+            let loaded = match self.channel_names.validate_name(channel) {
+                Ok(channel) => self.load_sub(Subscription::Name(&channel), &get_id),
+                Err(error) => Err(error),
+            };
+
+            match loaded {
+                Ok(()) => report.subscriptions += 1,
+                Err(error) => report.errors.push((index, error)),
+            }
+        }
+    }
+
+    /// Loads subscriptions in bulk without creating `Clients`, such as when
+    /// a standby takes over before its `Clients` reconnect.
+    ///
+    /// Subscriptions of identifiers whose `Client` is not in the `PubSub`
+    /// are held detached until `add_client` adds it: publishes skip them,
+    /// buffering up to `set_detached_buffer_capacity` `Messages` for each.
+    /// Buffering only applies to `Messages` whose recipients are resolved
+    /// at delivery, so not to `RecipientCapture::AtPublish` or
+    /// micro-batched `Channels`.
+    ///
+    /// Reports like `import`, where no `Clients` are ever created.  Each
+    /// subscription is checked and counted like one made by `sub_id`,
+    /// against the memory budget, `DuplicateSubscriptions`, and the `gc`
+    /// interval, but is neither confirmed nor sent retained `Messages`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{DuplicateSubscriptions, ImportRecord};
+    ///
+    /// let mut pubsub: PubSub<RecordingClient<u32, &str>, u32, &str> = PubSub::new();
+    /// pubsub.set_duplicate_subscriptions(DuplicateSubscriptions::Counted);
+    ///
+    /// let report = pubsub.restore_subscriptions(vec![
+    ///     ImportRecord { id: 1, channels: vec!["news".into(), "news".into()] },
+    ///     ImportRecord { id: 2, channels: vec!["news".into(), "orders.*".into()] },
+    /// ]);
+    ///
+    /// assert_eq!(report.subscriptions, 4);
+    /// assert!(report.errors.is_empty());
+    /// assert_eq!(pubsub.pending_identifiers(), vec![&1, &2]);
+    /// assert_eq!(pubsub.subscription_count_for(&1, "news"), 2);
+    ///
+    /// // Nothing is delivered until the `Client` is added.
+    /// let client = RecordingClient::new(2);
+    /// pubsub.pub_message("orders.created", "missed").unwrap();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.pub_message("orders.created", "heard").unwrap();
+    /// assert_eq!(client.contents(), vec!["heard"]);
+    ///
+    /// // Restored subscriptions need room within the memory budget.
+    /// pubsub.set_memory_budget(Some(pubsub.memory_estimate()));
+    /// let report = pubsub.restore_subscriptions(vec![ImportRecord {
+    ///     id: 3,
+    ///     channels: vec!["news".into()],
+    /// }]);
+    ///
+    /// assert_eq!(report.subscriptions, 0);
+    /// assert_eq!(report.errors, vec![(0, PubSubError::MemoryBudgetExceededError)]);
+    /// assert_eq!(pubsub.view().subscribers("news").count(), 2);
+    /// assert!(pubsub.check_invariants().is_ok());
+    /// ```
+    pub fn restore_subscriptions<I>(&mut self, records: I) -> ImportReport
+    where
+        I: IntoIterator<Item = ImportRecord<TIdentifier>>,
+        TIdentifier: Clone,
+    {
+        let now = self.routing.clock.now();
+        let mut report = ImportReport::default();
+
+        for (index, record) in records.into_iter().enumerate() {
+            if !self.clients.contains_key(&record.id) {
+                self.detached
+                    .identifiers
+                    .entry(record.id.clone())
+                    .or_insert_with(|| Detached::new(now));
+            }
+
+            let identifier = &record.id;
+
+            self.import_subscriptions(index, record.channels, |_| identifier.clone(), &mut report);
+        }
+
+        report
+    }
+
+    /// Copies every subscription of the `PubSub` into a
+    /// `SubscriptionSnapshot`, such as to save them before a restart.
+    ///
+    /// Unlike `snapshot_view`, subscribers which are not `Clients`, and
+    /// `Channels` and patterns nobody is subscribed to, are included, so
+    /// that `apply_snapshot` rebuilds the same topology.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let before: Vec<RecordingClient<u32, &str>> = (1..=3).map(RecordingClient::new).collect();
+    /// let mut pubsub = PubSub::new();
+    ///
+    /// for client in &before {
+    ///     pubsub.add_client(client.clone()).unwrap();
+    ///     pubsub.sub_client(client.clone(), "news").unwrap();
+    /// }
+    /// pubsub.psub_client(before[0].clone(), "orders.*").unwrap();
+    /// pubsub.sub_firehose(before[2].clone()).unwrap();
+    ///
+    /// let snapshot = pubsub.subscription_snapshot();
+    ///
+    /// // After a restart, the `Clients` are added back as they reconnect.
+    /// let after: Vec<RecordingClient<u32, &str>> = (1..=3).map(RecordingClient::new).collect();
+    /// let mut restored = PubSub::new();
+    /// let report = restored.apply_snapshot(snapshot.clone());
+    ///
+    /// assert_eq!(report.subscriptions, 5);
+    /// assert_eq!(restored.subscription_snapshot(), snapshot);
+    ///
+    /// restored.add_client(after[0].clone()).unwrap();
+    /// restored.pub_message("orders.created", "unheard by 2 and 3").unwrap();
+    ///
+    /// for client in &after[1..] {
+    ///     restored.add_client(client.clone()).unwrap();
+    /// }
+    ///
+    /// for (channel, message) in [("news", "a"), ("orders.created", "b")].iter() {
+    ///     pubsub.pub_message(channel, *message).unwrap();
+    ///     restored.pub_message(channel, *message).unwrap();
+    /// }
+    ///
+    /// assert_eq!(after[0].contents(), vec!["unheard by 2 and 3", "a", "b"]);
+    ///
+    /// for (before, after) in before.iter().zip(&after).skip(1) {
+    ///     assert_eq!(before.contents(), after.contents());
+    /// }
+    /// ```
+    pub fn subscription_snapshot(&self) -> SubscriptionSnapshot<TIdentifier>
+    where
+        TIdentifier: Clone,
+    {
+        let subscribers =
+            |subscribers: &BTreeSet<TIdentifier>| subscribers.iter().cloned().collect();
+
+        let mut patterns: Vec<(String, Vec<TIdentifier>)> = self
+            .routing
+            .pattern_channels
+            .iter()
+            .map(|(pattern, entry)| (pattern.to_string(), subscribers(&entry.subscribers)))
+            .collect();
+        patterns.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+        SubscriptionSnapshot {
+            channels: self
+                .routing
+                .live_entries()
+                .map(|(_, entry)| (entry.name.to_string(), subscribers(&entry.subscribers)))
+                .collect(),
+            patterns,
+            firehose: subscribers(&self.routing.firehose),
+        }
+    }
+
+    /// Loads the subscriptions of a `SubscriptionSnapshot`, on top of those
+    /// the `PubSub` already has.
+    ///
+    /// Subscribers whose `Client` is not in the `PubSub` are held as by
+    /// `restore_subscriptions`: publishes skip them until `add_client` adds
+    /// their `Client`.  `Channels` and patterns nobody is subscribed to are
+    /// created as well.
+    ///
+    /// Reports like `import`, where the index of a rejected subscription is
+    /// that of its `Channel` in `channels`, followed by `patterns`, then
+    /// the firehose.
+    pub fn apply_snapshot(&mut self, snapshot: SubscriptionSnapshot<TIdentifier>) -> ImportReport
+    where
+        TIdentifier: Clone,
+    {
+        let now = self.routing.clock.now();
+        let mut report = ImportReport::default();
+
+        let subscriptions = snapshot
+            .channels
+            .into_iter()
+            .map(|(channel, subscribers)| (Some(channel), subscribers))
+            .chain(
+                snapshot
+                    .patterns
+                    .into_iter()
+                    .map(|(pattern, subscribers)| (Some(pattern), subscribers)),
+            )
+            .chain(std::iter::once((None, snapshot.firehose)));
+
+        for (index, (channel, subscribers)) in subscriptions.enumerate() {
+            let channel = match channel
+                .map(|channel| self.channel_names.validate_name(channel.into()))
+                .transpose()
+            {
+                Ok(channel) => channel,
+                Err(error) => {
+                    report.errors.push((index, error));
+                    continue;
+                }
+            };
+
+            if let Some(channel) = &channel {
+                let created = match self.routing.is_pattern(channel) {
+                    true => self.routing.add_pattern(channel.clone()),
+                    false => {
+                        self.routing.channel_handle(channel.clone());
+                        Ok(())
+                    }
+                };
+
+                if let Err(error) = created {
+                    report.errors.push((index, error));
+                    continue;
+                }
+            }
+
+            for identifier in subscribers {
+                if !self.clients.contains_key(&identifier) {
+                    self.detached
+                        .identifiers
+                        .entry(identifier.clone())
+                        .or_insert_with(|| Detached::new(now));
+                }
+
+                let subscription = match &channel {
+                    Some(channel) => Subscription::Name(channel),
+                    None => Subscription::Firehose,
+                };
+
+                match self.load_sub(subscription, |_| identifier.clone()) {
+                    Ok(()) => report.subscriptions += 1,
+                    Err(error) => report.errors.push((index, error)),
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Sets how many `Messages` are buffered for each detached identifier,
+    /// dropping the oldest beyond that.  Defaults to 0, buffering nothing.
+    ///
+    /// See `restore_subscriptions`.
+    pub fn set_detached_buffer_capacity(&mut self, capacity: usize) {
+        self.detached.capacity = capacity;
+
+        for detached in self.detached.identifiers.values_mut() {
+            while detached.buffered.len() > capacity {
+                if let Some(dropped) = detached.buffered.pop_front() {
+                    self.stored_bytes -= dropped.size(&self.sizer);
+                }
+            }
+        }
+    }
+
+    /// Gets the identifiers whose subscriptions are waiting for their
+    /// `Client`, restored or detached, in order.
+    pub fn pending_identifiers(&self) -> Vec<&TIdentifier> {
+        let mut identifiers: Vec<&TIdentifier> = self.detached.identifiers.keys().collect();
+        identifiers.sort_unstable();
+        identifiers
+    }
+
+    /// Drops the subscriptions and buffered `Messages` of every identifier
+    /// that has been waiting for its `Client` for at least `older_than`,
+    /// returning how many were dropped.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{ImportRecord, ManualClock};
+    /// use std::time::Duration;
+    ///
+    /// let clock = ManualClock::new();
+    /// let mut pubsub: PubSub<RecordingClient<u32, &str>, u32, &str> = PubSub::new();
+    /// pubsub.set_clock(clock.clone());
+    ///
+    /// pubsub.restore_subscriptions(vec![ImportRecord { id: 1, channels: vec!["news".into()] }]);
+    /// clock.advance(Duration::from_secs(30));
+    /// pubsub.restore_subscriptions(vec![ImportRecord { id: 2, channels: vec!["news".into()] }]);
+    /// clock.advance(Duration::from_secs(30));
+    ///
+    /// assert_eq!(pubsub.expire_pending(Duration::from_secs(60)), 1);
+    /// assert_eq!(pubsub.pending_identifiers(), vec![&2]);
+    /// assert_eq!(pubsub.view().subscribers("news").collect::<Vec<_>>(), vec![&2]);
+    ///
+    /// // Once its `Client` is added, an identifier is no longer pending.
+    /// pubsub.add_client(RecordingClient::new(2)).unwrap();
+    /// clock.advance(Duration::from_secs(600));
+    ///
+    /// assert_eq!(pubsub.expire_pending(Duration::from_secs(60)), 0);
+    /// assert_eq!(pubsub.view().subscribers("news").count(), 1);
+    /// assert!(pubsub.check_invariants().is_ok());
+    /// ```
+    pub fn expire_pending(&mut self, older_than: Duration) -> usize {
+        let now = self.routing.clock.now();
+        let routing = &mut self.routing;
+        let drops = &self.drops;
+        let stored_bytes = &mut self.stored_bytes;
+        let sizer = &self.sizer;
+        let before = self.detached.identifiers.len();
+
+        self.detached.identifiers.retain(|identifier, detached| {
+            let expired = now.saturating_duration_since(detached.since) >= older_than;
+
+            if expired {
+                routing.remove_subscriber(identifier);
+                *stored_bytes -= detached.buffered_size(sizer);
+
+                for buffered in &detached.buffered {
+                    drops.record(
+                        &buffered.channel,
+                        DropReason::DetachedRemoved,
+                        Some(identifier),
+                    );
+                }
+            }
+
+            !expired
+        });
+
+        before - self.detached.identifiers.len()
+    }
+
+    /// Checks the internal state of the `PubSub` for inconsistencies.
+    ///
+    /// This walks every `Channel`, pattern, and sampler once, so it is cheap
+    /// enough to call after every operation in debug builds or tests.  Any
+    /// violation other than `InvariantViolation::UnknownSubscriber` is a bug
+    /// in the `PubSub`; please include the violations when reporting it.
+    pub fn check_invariants(&self) -> Result<(), Vec<InvariantViolation<TIdentifier>>>
+    where
+        TIdentifier: Clone,
+    {
+        let mut violations = Vec::new();
+
+        let unknown_subscribers = |channel: Option<&str>, subscribers: &BTreeSet<TIdentifier>| {
+            subscribers
+                .iter()
+                .filter(|identifier| {
+                    !self.clients.contains_key(*identifier)
+                        && !self.detached.identifiers.contains_key(*identifier)
+                })
+                .map(|identifier| InvariantViolation::UnknownSubscriber {
+                    channel: channel.map(str::to_string),
+                    identifier: identifier.clone(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (handle, entry) in self.routing.live_entries() {
+            if self.routing.channels.get(&entry.name) != Some(&handle) {
+                violations.push(InvariantViolation::MisindexedChannel {
+                    channel: entry.name.to_string(),
+                });
+            }
+
+            violations.extend(unknown_subscribers(Some(&entry.name), &entry.subscribers));
+        }
+
+        for (name, handle) in self.routing.channels.iter() {
+            let indexed = self.routing.channel_name(*handle).ok();
+
+            if indexed.as_ref() != Some(name) {
+                violations.push(InvariantViolation::MisindexedChannel {
+                    channel: name.to_string(),
+                });
+            }
+        }
+
+        for (pattern, entry) in self.routing.pattern_channels.iter() {
+            if !self.routing.pattern_stats.lock().contains_key(pattern) {
+                violations.push(InvariantViolation::MissingPatternStats {
+                    pattern: pattern.to_string(),
+                });
+            }
+
+            violations.extend(unknown_subscribers(Some(pattern), &entry.subscribers));
+        }
+
+        violations.extend(unknown_subscribers(None, &self.routing.firehose));
+
+        let subscriptions = self
+            .routing
+            .channel_entries
+            .iter()
+            .map(|entry| (&entry.name, &entry.subscribers))
+            .chain(
+                self.routing
+                    .pattern_channels
+                    .iter()
+                    .map(|(pattern, entry)| (pattern, &entry.subscribers)),
+            );
+
+        for (channel, subscribers) in subscriptions {
+            for identifier in subscribers {
+                if !self.routing.is_subscribed(identifier, channel) {
+                    violations.push(InvariantViolation::UnindexedSubscription {
+                        channel: channel.to_string(),
+                        identifier: identifier.clone(),
+                    });
+                }
+            }
+        }
+
+        for (identifier, channels) in self.routing.by_subscriber.iter() {
+            for channel in channels {
+                let subscribers = match self.routing.is_pattern(channel) {
+                    true => self
+                        .routing
+                        .pattern_channels
+                        .get(channel)
+                        .map(|entry| &entry.subscribers),
+                    false => self
+                        .routing
+                        .channels
+                        .get(channel)
+                        .and_then(|handle| self.routing.channel_entries.get(handle.0))
+                        .map(|entry| &entry.subscribers),
+                };
+
+                if !subscribers.is_some_and(|subscribers| subscribers.contains(identifier)) {
+                    violations.push(InvariantViolation::UnindexedSubscription {
+                        channel: channel.to_string(),
+                        identifier: identifier.clone(),
+                    });
+                }
+            }
+        }
+
+        let attachments = self
+            .routing
+            .samplers
+            .iter()
+            .map(|(channel, samplers)| (channel, samplers.keys().collect::<Vec<_>>(), false))
+            .chain(
+                self.routing
+                    .tags
+                    .iter()
+                    .map(|(channel, tags)| (channel, tags.keys().collect(), true)),
+            );
+
+        for (channel, identifiers, is_tag) in attachments {
+            let subscribers = match self.routing.is_pattern(channel) {
+                true => self
+                    .routing
+                    .pattern_channels
+                    .get(channel)
+                    .map(|entry| &entry.subscribers),
+                false => self
+                    .routing
+                    .channels
+                    .get(channel)
+                    .and_then(|handle| self.routing.channel_entries.get(handle.0))
+                    .map(|entry| &entry.subscribers),
+            };
+
+            for identifier in identifiers {
+                if subscribers.is_some_and(|subscribers| subscribers.contains(identifier)) {
+                    continue;
+                }
+
+                let channel = channel.to_string();
+                let identifier = identifier.clone();
+
+                violations.push(match is_tag {
+                    true => InvariantViolation::OrphanedTag {
+                        channel,
+                        identifier,
+                    },
+                    false => InvariantViolation::OrphanedSampler {
+                        channel,
+                        identifier,
+                    },
+                });
+            }
+        }
+
+        let estimated = self.memory_estimate();
+        let counted = self.estimate_memory(
+            self.routing
+                .by_subscriber
+                .values()
+                .map(|channels| channels.len())
+                .sum(),
+            self.count_stored_bytes(),
+        );
+
+        if estimated != counted {
+            violations.push(InvariantViolation::MemoryMiscounted { estimated, counted });
+        }
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
+
+    /// Sets what happens to a `Channel` or pattern once its last subscriber
+    /// leaves.
+    ///
+    /// Defaults to `EmptyChannels::Keep`.  Switching to
+    /// `EmptyChannels::Remove` leaves `Channels` which are already empty in
+    /// place; `remove_channel` removes them.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::EmptyChannels;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_empty_channels(EmptyChannels::Remove);
+    /// pubsub.add_client(client.clone()).unwrap();
+    ///
+    /// for session in ["session.1", "session.2", "session.3"].iter() {
+    ///     pubsub.sub_client(client.clone(), *session).unwrap();
+    /// }
+    /// pubsub.psub_client(client.clone(), "orders.*").unwrap();
+    ///
+    /// pubsub.unsub_client(client.clone(), "session.1").unwrap();
+    /// assert_eq!(pubsub.view().channels().count(), 2);
+    /// assert_eq!(
+    ///     pubsub.unsub_client(client.clone(), "session.1"),
+    ///     Err(PubSubError::ChannelDoesNotExistError { channel: "session.1".to_string() })
+    /// );
+    ///
+    /// pubsub.unsub_all(&1).unwrap();
+    /// assert_eq!(pubsub.view().channels().count(), 0);
+    /// assert_eq!(pubsub.view().patterns().count(), 0);
+    ///
+    /// pubsub.sub_client(client.clone(), "session.4").unwrap();
+    /// pubsub.remove_client(client.clone());
+    /// assert_eq!(pubsub.view().channels().count(), 0);
+    /// assert!(pubsub.check_invariants().is_ok());
+    /// ```
+    pub fn set_empty_channels(&mut self, policy: EmptyChannels) {
+        self.routing.empty_channels = policy;
+    }
+
+    /// Removes a `Channel` or pattern, unsubscribing everyone from it, and
+    /// returns who was subscribed, in identifier order.
+    ///
+    /// Each subscriber is notified as if it had unsubscribed.  The
+    /// `ChannelHandles` of a removed `Channel` become invalid, even once a
+    /// `Channel` with the same name is created again.  Its history and
+    /// retained `Message` are kept.  Nothing happens when the `Channel` does
+    /// not exist.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let mut pubsub: PubSub<RecordingClient<u32, &str>, _, _> = PubSub::new();
+    ///
+    /// for id in 1..=3 {
+    ///     pubsub.add_client(RecordingClient::new(id)).unwrap();
+    ///     pubsub.sub_id(&id, "room.42").unwrap();
+    /// }
+    ///
+    /// let handle = pubsub.channel_handle("room.42").unwrap();
+    ///
+    /// assert_eq!(pubsub.remove_channel("room.42"), vec![1, 2, 3]);
+    /// assert!(pubsub.remove_channel("room.42").is_empty());
+    /// assert!(!pubsub.view().channel_exists("room.42"));
+    ///
+    /// let recreated = pubsub.channel_handle("room.42").unwrap();
+    /// assert_ne!(handle, recreated);
+    /// assert!(matches!(
+    ///     pubsub.pub_by_handle(handle, "stale"),
+    ///     Err(PubSubError::InvalidChannelHandleError)
+    /// ));
+    /// assert!(pubsub.check_invariants().is_ok());
+    /// ```
+    pub fn remove_channel(&mut self, channel: &str) -> Vec<TIdentifier>
+    where
+        TIdentifier: Clone,
+    {
+        let subscribers: Vec<TIdentifier> = match self.routing.remove_channel(channel) {
+            Some(subscribers) => subscribers.into_iter().collect(),
+            None => return Vec::new(),
+        };
+
+        self.filters.remove_subscription(channel);
+        self.subscription_counts.remove(channel);
+        self.subscription_ids
+            .retain(|_, (_, subscription)| subscription.as_ref() != channel);
+
+        for identifier in &subscribers {
+            if self.confirm(identifier.clone(), ControlEventKind::Unsubscribed, channel) {
+                self.flush_delivered(identifier);
+            }
+
+            self.record(|| TraceEvent::Unsub(identifier.clone(), channel.to_string()));
+        }
+
+        self.count_mutation();
+
+        subscribers
+    }
+
+    /// Removes the subscriptions of identifiers which are not `Clients` of the
+    /// `PubSub`, then the patterns left without subscribers.
+    ///
+    /// Note that this includes `Clients` subscribed before being added with
+    /// `add_client`, but not identifiers waiting for their `Client` after
+    /// `detach_client` or `restore_subscriptions`.  Literal `Channels` are
+    /// not removed, so that their `ChannelHandles` stay valid; see
+    /// `remove_channel` for that.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{GcReport, InvariantViolation, UnknownSubscriberPolicy};
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let never_added: RecordingClient<_, &str> = RecordingClient::new(2);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Allow);
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.sub_client(never_added.clone(), "news").unwrap();
+    /// pubsub.psub_client(never_added.clone(), "sports.*").unwrap();
+    /// pubsub.psub_client(client.clone(), "orders.*").unwrap();
+    /// pubsub.punsub_client(client.clone(), "orders.*").unwrap();
+    ///
+    /// let violations = pubsub.check_invariants().unwrap_err();
+    /// assert_eq!(violations.len(), 2);
+    /// assert!(violations.contains(&InvariantViolation::UnknownSubscriber {
+    ///     channel: Some("news".to_string()),
+    ///     identifier: 2,
+    /// }));
+    /// assert!(violations.contains(&InvariantViolation::UnknownSubscriber {
+    ///     channel: Some("sports.*".to_string()),
+    ///     identifier: 2,
+    /// }));
+    ///
+    /// let report = pubsub.gc();
+    /// assert_eq!(report, GcReport { stale_subscriptions: 2, empty_patterns: 2 });
+    /// assert_eq!(pubsub.gc(), GcReport::default());
+    ///
+    /// assert_eq!(pubsub.pattern_count(), 0);
+    /// assert_eq!(pubsub.view().subscribers("news").collect::<Vec<_>>(), vec![&1]);
+    /// assert!(pubsub.check_invariants().is_ok());
+    /// ```
+    pub fn gc(&mut self) -> GcReport {
+        let report = GcReport {
+            stale_subscriptions: self.purge_dangling(),
+            empty_patterns: self.routing.prune_empty_patterns(),
+        };
+
+        self.mutations_since_gc = 0;
+
+        report
+    }
+
+    /// Runs `gc` automatically after every `interval` calls to `add_client`,
+    /// `remove_client`, `sub_client`, and `unsub_client`, or never when
+    /// `None` (the default).
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::UnknownSubscriberPolicy;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Allow);
+    /// pubsub.set_gc_interval(Some(3));
+    ///
+    /// pubsub.sub_client(RecordingClient::new(2), "news").unwrap();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// assert_eq!(pubsub.view().subscribers("news").collect::<Vec<_>>(), vec![&2]);
+    ///
+    /// // The third change runs `gc`, removing the subscription of 2.
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// assert_eq!(pubsub.view().subscribers("news").collect::<Vec<_>>(), vec![&1]);
+    /// ```
+    pub fn set_gc_interval(&mut self, interval: Option<usize>) {
+        self.gc_interval = interval;
+    }
+
+    fn count_mutation(&mut self) {
+        let interval = match self.gc_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+
+        self.mutations_since_gc += 1;
+
+        if self.mutations_since_gc >= interval {
+            self.gc();
+        }
+    }
+
+    /// Starts recording the operations performed on the `PubSub` into a new
+    /// `Trace`, replacing the one being recorded, if any.
+    ///
+    /// Recorded are added and removed `Clients`, subscriptions and
+    /// unsubscriptions that succeed, and the recipients of every published
+    /// `Message`.  Payloads are recorded only as the hash computed by
+    /// `hash_payload`.  Control `Messages` are not recorded.
+    ///
+    /// The `Trace` grows for as long as the recording runs, and is not
+    /// counted against the memory budget.  See `replay` to check it for
+    /// routing decisions that cannot be reproduced.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::TraceEvent;
+    /// use std::collections::BTreeSet;
+    ///
+    /// let client: RecordingClient<_, u64> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.start_recording(|payload: &u64| *payload);
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.pub_message("news", 42u64).unwrap();
+    /// pubsub.unsub_client(client.clone(), "news").unwrap();
+    /// assert_eq!(pubsub.trace().unwrap().events().len(), 4);
+    ///
+    /// let trace = pubsub.stop_recording().unwrap();
+    /// assert_eq!(
+    ///     trace.events(),
+    ///     &[
+    ///         TraceEvent::AddClient(1),
+    ///         TraceEvent::Sub(1, "news".to_string()),
+    ///         TraceEvent::Publish {
+    ///             channel: "news".to_string(),
+    ///             payload_hash: 42,
+    ///             excluded: None,
+    ///             recipients: BTreeSet::from([1]),
+    ///         },
+    ///         TraceEvent::Unsub(1, "news".to_string()),
+    ///     ]
+    /// );
+    /// assert!(pubsub.trace().is_none());
+    /// ```
+    pub fn start_recording<F>(&mut self, hash_payload: F)
+    where
+        F: Fn(&TMessage) -> u64 + Send + Sync + 'static,
+    {
+        let hash_payload: PayloadHasher<TMessage> = Arc::new(hash_payload);
+
+        self.recording = Some(Recording {
+            hash_payload,
+            trace: Trace::new(),
+        });
+    }
+
+    /// Stops recording, returning the recorded `Trace`, if any.
+    pub fn stop_recording(&mut self) -> Option<Trace<TIdentifier>> {
+        self.recording.take().map(|recording| recording.trace)
+    }
+
+    /// Gets the `Trace` being recorded, if any.
+    pub fn trace(&self) -> Option<&Trace<TIdentifier>> {
+        self.recording.as_ref().map(|recording| &recording.trace)
+    }
+
+    fn record<F: FnOnce() -> TraceEvent<TIdentifier>>(&mut self, event: F) {
+        if let Some(recording) = &mut self.recording {
+            recording.trace.push(event());
+        }
+    }
+
+    fn record_publish(
+        &mut self,
+        channel: &str,
+        contents: &TMessage,
+        excluded: Option<TIdentifier>,
+        recipients: BTreeSet<TIdentifier>,
+    ) {
+        if let Some(recording) = &mut self.recording {
+            let payload_hash = (recording.hash_payload)(contents);
+
+            recording.trace.push(TraceEvent::Publish {
+                channel: channel.to_string(),
+                payload_hash,
+                excluded,
+                recipients,
+            });
+        }
+    }
+
+    /// Borrows a read-only view of the `PubSub`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let alice: RecordingClient<_, &str> = RecordingClient::new(1);
+    /// let bob: RecordingClient<_, &str> = RecordingClient::new(2);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(alice.clone()).unwrap();
+    /// pubsub.add_client(bob.clone()).unwrap();
+    /// pubsub.sub_client(alice.clone(), "news").unwrap();
+    /// pubsub.sub_client(bob.clone(), "news").unwrap();
+    /// pubsub.psub_client(bob.clone(), "orders.*").unwrap();
+    ///
+    /// let view = pubsub.view();
+    /// assert_eq!(view.client_count(), 2);
+    /// assert!(view.contains_client(&1));
+    /// assert!(!view.contains_client(&3));
+    /// assert_eq!(view.subscribers("news").collect::<Vec<_>>(), vec![&1, &2]);
+    /// assert_eq!(view.subscribers("orders.*").collect::<Vec<_>>(), vec![&2]);
+    /// assert_eq!(view.subscribers("sports").count(), 0);
+    /// assert!(view.channel_exists("orders.*"));
+    /// assert!(!view.channel_exists("sports"));
+    /// ```
+    pub fn view(&self) -> PubSubView<'_, TClient, TIdentifier, TMessage, TMatcher> {
+        PubSubView { pubsub: self }
+    }
+
+    /// Copies the routing topology of the `PubSub` into an `OwnedView`.
+    ///
+    /// The copy is rebuilt on every call, so it is relatively expensive
+    /// compared to `view`, but can be queried from another thread without
+    /// borrowing the `PubSub`.
+    ///
+    /// The copy is internally consistent: every subscriber it lists is also
+    /// one of its `Clients`.  Subscribers that were never added with
+    /// `add_client`, and so would never receive a `Message`, are left out.
+    /// Since mutating the `PubSub` requires exclusive access, no mutation can
+    /// interleave with taking the copy.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::UnknownSubscriberPolicy;
+    /// use std::thread;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Allow);
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.psub_client(client.clone(), "orders.*").unwrap();
+    /// pubsub.sub_client(RecordingClient::new(2), "news").unwrap();
+    ///
+    /// let snapshot = pubsub.snapshot_view();
+    /// let reader = thread::spawn(move || {
+    ///     let subscribers: Vec<u32> = snapshot.subscribers("news").copied().collect();
+    ///     (snapshot.client_count(), subscribers, snapshot.patterns().count())
+    /// });
+    ///
+    /// // The snapshot does not see changes made after it was taken.
+    /// pubsub.unsub_client(client.clone(), "news").unwrap();
+    ///
+    /// assert_eq!(reader.join().unwrap(), (1, vec![1], 1));
+    /// assert_eq!(pubsub.snapshot_view().subscribers("news").count(), 0);
+    /// ```
+    ///
+    /// Subscribers without a `Client`, whether never added or detached, are
+    /// left out of every `Channel` and pattern:
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::UnknownSubscriberPolicy;
+    /// use std::time::Duration;
+    ///
+    /// let clients: Vec<RecordingClient<u32, &str>> = (1..=4).map(RecordingClient::new).collect();
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Allow);
+    /// for client in &clients[..3] {
+    ///     pubsub.add_client(client.clone()).unwrap();
+    /// }
+    /// for client in &clients {
+    ///     pubsub.sub_client(client.clone(), "news").unwrap();
+    ///     pubsub.psub_client(client.clone(), "orders.*").unwrap();
+    /// }
+    /// pubsub.detach_client(&2, Duration::from_secs(30)).unwrap();
+    /// pubsub.remove_client(clients[2].clone());
+    ///
+    /// let snapshot = pubsub.snapshot_view();
+    /// assert_eq!(snapshot.client_count(), 1);
+    /// for channel in ["news", "orders.*"] {
+    ///     assert_eq!(snapshot.subscribers(channel).collect::<Vec<_>>(), vec![&1]);
+    ///     assert!(snapshot.subscribers(channel).all(|id| snapshot.contains_client(id)));
+    ///     assert_eq!(pubsub.subscribers_snapshot(channel), vec![1]);
+    /// }
+    ///
+    /// // Adding the detached `Client` back brings its subscriptions back.
+    /// pubsub.reattach_client(clients[1].clone()).unwrap();
+    /// assert_eq!(pubsub.subscribers_snapshot("orders.*"), vec![1, 2]);
+    /// ```
+    pub fn snapshot_view(&self) -> OwnedView<TIdentifier, TMatcher>
+    where
+        TIdentifier: Clone,
+    {
+        let live = |subscribers: &BTreeSet<TIdentifier>| {
+            subscribers
+                .iter()
+                .filter(|identifier| self.clients.contains_key(*identifier))
+                .cloned()
+                .collect()
+        };
+
+        OwnedView {
+            clients: self.clients.keys().cloned().collect(),
+            channels: self
+                .routing
+                .live_entries()
+                .map(|(_, entry)| (entry.name.to_string(), live(&entry.subscribers)))
+                .collect(),
+            patterns: self
+                .routing
+                .pattern_channels
+                .iter()
+                .map(|(pattern, entry)| (pattern.to_string(), live(&entry.subscribers)))
+                .collect(),
+            matcher: self.routing.matcher.clone(),
+        }
+    }
+
+    /// Copies the identifiers of the `Clients` subscribed to a `Channel`.
+    ///
+    /// The `Channel` is interpreted the same way as in
+    /// `PubSubView::subscribers`.  Like `snapshot_view`, only subscribers
+    /// which are `Clients` of the `PubSub` are included.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::UnknownSubscriberPolicy;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_unknown_subscriber_policy(UnknownSubscriberPolicy::Allow);
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.sub_client(RecordingClient::new(2), "news").unwrap();
+    ///
+    /// assert_eq!(pubsub.subscribers_snapshot("news"), vec![1]);
+    /// assert_eq!(pubsub.view().subscribers("news").count(), 2);
+    /// assert!(pubsub.subscribers_snapshot("sports").is_empty());
+    /// ```
+    pub fn subscribers_snapshot(&self, channel: &str) -> Vec<TIdentifier>
+    where
+        TIdentifier: Clone,
+    {
+        self.view()
+            .subscribers(channel)
+            .filter(|identifier| self.clients.contains_key(*identifier))
+            .cloned()
+            .collect()
+    }
+
+    /// Flushes every `Client` in the `PubSub`.
+    ///
+    /// Useful for flushing buffered `Clients` on a timer rather than after
+    /// every publish.  Failures count towards the `EvictionPolicy` like
+    /// failed sends.
+    ///
+    /// ```
+    /// use general_pub_sub::{Client, EvictionPolicy, Message, PubSub};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// struct Socket {
+    ///     id: u32,
+    ///     flushes: Arc<AtomicUsize>,
+    ///     closed: bool,
+    /// }
+    ///
+    /// impl Client<u32, &str> for Socket {
+    ///     type Error = &'static str;
+    ///
+    ///     fn get_id(&self) -> u32 {
+    ///         self.id
+    ///     }
+    ///
+    ///     fn send(&mut self, _: &Message<&str>) -> Result<(), &'static str> {
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> Result<(), &'static str> {
+    ///         self.flushes.fetch_add(1, Ordering::SeqCst);
+    ///
+    ///         match self.closed {
+    ///             true => Err("closed"),
+    ///             false => Ok(()),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let flushes = Arc::new(AtomicUsize::new(0));
+    /// let socket = |id, closed| Socket { id, flushes: flushes.clone(), closed };
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.set_eviction_policy(EvictionPolicy::AfterConsecutiveFailures(2));
+    /// pubsub.add_client(socket(1, false)).unwrap();
+    /// pubsub.add_client(socket(2, true)).unwrap();
+    ///
+    /// pubsub.flush_all();
+    /// assert_eq!(flushes.load(Ordering::SeqCst), 2);
+    /// assert_eq!(pubsub.client_count(), 2);
+    ///
+    /// // The second failed flush in a row evicts 2.
+    /// pubsub.flush_all();
+    /// assert_eq!(flushes.load(Ordering::SeqCst), 4);
+    /// assert!(pubsub.view().contains_client(&1));
+    /// assert!(!pubsub.view().contains_client(&2));
+    /// ```
+    pub fn flush_all(&mut self) {
+        for (identifier, client) in self.clients.iter_mut() {
+            let flushed = client.flush();
+            self.failures
+                .get_mut()
+                .note_flush(&flushed, identifier, || client.get_id());
+        }
+
+        self.evict_failing();
+    }
+}
+
+impl<TClient: Client<TIdentifier, TMessage>, TIdentifier: UniqueIdentifier, TMessage: Clone> Default
+    for PubSub<TClient, TIdentifier, TMessage>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Summarizes the topology of the `PubSub`: how many `Clients` it has, and
+/// who is subscribed to each `Channel`, pattern, and the firehose.
+///
+/// Only the identifiers need to be `Debug`; neither the `Clients` nor the
+/// `Messages` are printed.  `Channels` are listed in creation order and
+/// patterns by name.  The alternate format, `{:#?}`, puts each on its own
+/// line.
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+///
+/// let clients: Vec<RecordingClient<u32, &str>> = (1..=2).map(RecordingClient::new).collect();
+/// let mut pubsub = PubSub::new();
+///
+/// for client in &clients {
+///     pubsub.add_client(client.clone()).unwrap();
+///     pubsub.sub_client(client.clone(), "news").unwrap();
+/// }
+/// pubsub.psub_client(clients[1].clone(), "orders.*").unwrap();
+///
+/// assert_eq!(
+///     format!("{:?}", pubsub),
+///     r#"PubSub { clients: 2, channels: {"news": {1, 2}}, patterns: {"orders.*": {2}}, firehose: {} }"#
+/// );
+/// assert!(format!("{:#?}", pubsub).contains("\n    channels: {\n        \"news\": {\n"));
+/// ```
+impl<TClient, TIdentifier, TMessage, TMatcher> std::fmt::Debug
+    for PubSub<TClient, TIdentifier, TMessage, TMatcher>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier + std::fmt::Debug,
+    TMatcher: ChannelMatcher,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        struct Subscriptions<'p, TIdentifier>(Vec<(&'p str, &'p BTreeSet<TIdentifier>)>);
+
+        impl<TIdentifier: std::fmt::Debug> std::fmt::Debug for Subscriptions<'_, TIdentifier> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(name, subscribers)| (name, subscribers)))
+                    .finish()
+            }
+        }
+
+        let channels = self
+            .routing
+            .live_entries()
+            .map(|(_, entry)| (entry.name.as_ref(), &entry.subscribers))
+            .collect();
+
+        let mut patterns: Vec<_> = self
+            .routing
+            .pattern_channels
+            .iter()
+            .map(|(pattern, entry)| (pattern.as_ref(), &entry.subscribers))
+            .collect();
+        patterns.sort_unstable_by_key(|(pattern, _)| *pattern);
+
+        f.debug_struct("PubSub")
+            .field("clients", &self.clients.len())
+            .field("channels", &Subscriptions(channels))
+            .field("patterns", &Subscriptions(patterns))
+            .field("firehose", &self.routing.firehose)
+            .finish()
     }
 }
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+/// Which subscribers `PubSub::pub_limited` picks when a `Channel` has more
+/// than the limit
+///
+/// Either way, subscribers are considered in delivery order: literal
+/// subscribers first, then those reached through patterns, most specific
+/// first, then the firehose.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LimitedFanOut {
+    /// The first subscribers in delivery order, so the same ones every
+    /// time while the subscribers do not change.  The default.
+    #[default]
+    InOrder,
+    /// Subscribers in turn: each limited publish to a `Channel` starts one
+    /// subscriber further along than the previous one, wrapping around.
+    RoundRobin,
+}
+
+/// The rotation of `LimitedFanOut::RoundRobin`, per `Channel`
+///
+/// Only a position is kept, so subscribers coming and going shift the
+/// rotation instead of breaking it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Rotations {
+    next: HashMap<String, usize>,
+}
+
+impl Rotations {
+    /// Gets where the next limited publish to a `Channel` with `len`
+    /// recipients starts, and advances the rotation.
+    pub(crate) fn advance(&mut self, channel: &str, len: usize) -> usize {
+        if len == 0 {
+            return 0;
+        }
+
+        let next = match self.next.get_mut(channel) {
+            Some(next) => next,
+            None => self.next.entry(channel.to_string()).or_insert(0),
+        };
+        let start = *next % len;
+        *next = start + 1;
+
+        start
+    }
+}
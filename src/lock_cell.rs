@@ -0,0 +1,41 @@
+use std::fmt;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// A value a publish updates through `&self`, so that a `SharedPubSub`
+/// can publish while only holding its read lock
+///
+/// A panic while the value is locked, such as in a drop callback, does not
+/// poison it: the value is used as the panic left it.
+pub(crate) struct LockCell<T>(Mutex<T>);
+
+impl<T> LockCell<T> {
+    pub(crate) fn new(value: T) -> LockCell<T> {
+        LockCell(Mutex::new(value))
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Clone> Clone for LockCell<T> {
+    fn clone(&self) -> Self {
+        LockCell::new(self.lock().clone())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for LockCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.lock().fmt(f)
+    }
+}
+
+impl<T: Default> Default for LockCell<T> {
+    fn default() -> Self {
+        LockCell::new(T::default())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{Client, Message, UniqueIdentifier};
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::sync::Arc;
+use wildmatch::WildMatch;
+
+type Formatter<TMessage> = Arc<dyn Fn(&Message<TMessage>) -> String + Send + Sync>;
+
+/// A `Client` which forwards every `Message` it receives to the `log` facade
+///
+/// Each `Message` is logged at the configured level with the target
+/// `<target_prefix>::<channel>`, where the channel is the concrete `Channel`
+/// the `Message` was published to.  Nothing is formatted when the level is
+/// disabled for that target.
+///
+/// Requires the `log` feature.
+///
+/// # Examples
+///
+/// ```
+/// use general_pub_sub::{LogClient, PubSub};
+///
+/// let client: LogClient<u32, &str> =
+///     LogClient::new(1, log::Level::Info, "pubsub").exclude("metrics.*");
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(client.clone()).unwrap();
+/// pubsub.psub_client(client, "*").unwrap();
+///
+/// pubsub
+///     .pub_message("orders.created", "Logged to the target pubsub::orders.created")
+///     .unwrap();
+/// pubsub.pub_message("metrics.cpu", "Not logged").unwrap();
+/// ```
+#[derive(Clone)]
+pub struct LogClient<TIdentifier, TMessage> {
+    id: TIdentifier,
+    level: log::Level,
+    target_prefix: String,
+    formatter: Formatter<TMessage>,
+    excluded: Vec<WildMatch>,
+}
+
+impl<TIdentifier, TMessage: Display> LogClient<TIdentifier, TMessage> {
+    /// Creates a `LogClient` which logs the `Display` rendering of each `Message`.
+    pub fn new(id: TIdentifier, level: log::Level, target_prefix: &str) -> Self {
+        Self::with_formatter(id, level, target_prefix, |message| {
+            message.contents.to_string()
+        })
+    }
+}
+
+impl<TIdentifier, TMessage> LogClient<TIdentifier, TMessage> {
+    /// Creates a `LogClient` which logs each `Message` as rendered by `formatter`.
+    pub fn with_formatter<F>(
+        id: TIdentifier,
+        level: log::Level,
+        target_prefix: &str,
+        formatter: F,
+    ) -> Self
+    where
+        F: Fn(&Message<TMessage>) -> String + Send + Sync + 'static,
+    {
+        LogClient {
+            id,
+            level,
+            target_prefix: target_prefix.to_string(),
+            formatter: Arc::new(formatter),
+            excluded: Vec::new(),
+        }
+    }
+
+    /// Excludes `Channels` matching a wildcard pattern from being logged.
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.excluded.push(WildMatch::new(pattern));
+        self
+    }
+}
+
+impl<TIdentifier, TMessage> Client<TIdentifier, TMessage> for LogClient<TIdentifier, TMessage>
+where
+    TIdentifier: UniqueIdentifier + Clone,
+{
+    type Error = Infallible;
+
+    fn get_id(&self) -> TIdentifier {
+        self.id.clone()
+    }
+
+    fn send(&mut self, message: &Message<TMessage>) -> Result<(), Infallible> {
+        if self.level > log::max_level() {
+            return Ok(());
+        }
+
+        if self
+            .excluded
+            .iter()
+            .any(|pattern| pattern.matches(message.source))
+        {
+            return Ok(());
+        }
+
+        let target = format!("{}::{}", self.target_prefix, message.source);
+
+        if log::log_enabled!(target: &target, self.level) {
+            log::log!(target: &target, self.level, "{}", (self.formatter)(message));
+        }
+
+        Ok(())
+    }
+}
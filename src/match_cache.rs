@@ -0,0 +1,89 @@
+use crate::pattern_index::PatternIndex;
+use crate::routing::{matching_patterns, PatternEntry};
+use crate::{ChannelMatcher, ChannelName};
+use std::collections::{BTreeSet, HashMap};
+
+/// The patterns matching each published `Channel`, so that patterns are
+/// only matched against a `Channel` the first time it is published to
+///
+/// Only creating a pattern can make it match a `Channel` it did not before,
+/// so that is when entries are invalidated: those of the `Channels` the new
+/// pattern matches.  Removed patterns are skipped when an entry is read, so
+/// removing one needs no invalidation.  Subscribing to or unsubscribing from
+/// an existing pattern changes nothing here.
+///
+/// Once `capacity` entries are cached the cache is cleared, to keep it from
+/// growing with the number of distinct `Channels` published to.
+#[derive(Clone, Debug)]
+pub(crate) struct MatchCache {
+    entries: HashMap<String, Vec<ChannelName>>,
+    capacity: usize,
+}
+
+impl MatchCache {
+    pub(crate) const DEFAULT_CAPACITY: usize = 4096;
+
+    pub(crate) fn new() -> MatchCache {
+        MatchCache {
+            entries: HashMap::new(),
+            capacity: MatchCache::DEFAULT_CAPACITY,
+        }
+    }
+
+    /// Sets the number of `Channels` cached before the cache is cleared, or
+    /// turns caching off with 0.
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+
+        if self.entries.len() >= capacity {
+            self.entries.clear();
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Finds the patterns matching a `Channel`, most specific first, from
+    /// the cache when possible.
+    pub(crate) fn patterns<'p, TIdentifier, TMatcher: ChannelMatcher>(
+        &mut self,
+        matcher: &TMatcher,
+        index: &PatternIndex,
+        pattern_channels: &'p HashMap<ChannelName, PatternEntry<TIdentifier, TMatcher::Compiled>>,
+        channel: &str,
+    ) -> Vec<(&'p str, &'p BTreeSet<TIdentifier>)> {
+        if let Some(patterns) = self.entries.get(channel) {
+            return patterns
+                .iter()
+                .filter_map(|pattern| pattern_channels.get_key_value(pattern))
+                .map(|(pattern, entry)| (pattern.as_ref(), &entry.subscribers))
+                .collect();
+        }
+
+        let patterns = matching_patterns(matcher, index, pattern_channels, channel);
+
+        if self.capacity > 0 {
+            if self.entries.len() >= self.capacity {
+                self.entries.clear();
+            }
+
+            let names = patterns
+                .iter()
+                .map(|(pattern, _)| ChannelName::from(pattern.to_string()))
+                .collect();
+
+            self.entries.insert(channel.to_string(), names);
+        }
+
+        patterns
+    }
+
+    /// Invalidates the `Channels` a newly created pattern matches.
+    pub(crate) fn pattern_added<F>(&mut self, matches: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.entries.retain(|channel, _| !matches(channel));
+    }
+}
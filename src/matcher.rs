@@ -0,0 +1,222 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+
+/// Decides which names are patterns and which `Channels` they match
+///
+/// A `PubSub` is generic over its matcher, given to `PubSub::with_matcher`.
+/// `PatternSyntax` is the default, with glob wildcards unless another syntax
+/// is chosen; `ExactMatcher` has no patterns at all.
+///
+/// Each pattern is compiled once, when the first subscription to it is
+/// made, and the compiled form is kept until the pattern is pruned.
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::{ChannelMatcher, PatternError};
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// /// Routing keys where a trailing `>` matches any suffix.
+/// #[derive(Clone, Default)]
+/// struct PrefixMatcher {
+///     consulted: Rc<Cell<usize>>,
+/// }
+///
+/// impl ChannelMatcher for PrefixMatcher {
+///     type Compiled = String;
+///
+///     fn is_pattern(&self, channel: &str) -> bool {
+///         channel.ends_with('>')
+///     }
+///
+///     fn compile(&self, pattern: &str) -> Result<String, PatternError> {
+///         match pattern[..pattern.len() - 1].contains('>') {
+///             true => Err(PatternError::new("`>` may only end a pattern")),
+///             false => Ok(pattern[..pattern.len() - 1].to_string()),
+///         }
+///     }
+///
+///     fn matches(&self, prefix: &String, channel: &str) -> bool {
+///         self.consulted.set(self.consulted.get() + 1);
+///         channel.starts_with(prefix.as_str())
+///     }
+/// }
+///
+/// let matcher = PrefixMatcher::default();
+/// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+///
+/// let mut pubsub = PubSub::with_matcher(matcher.clone());
+/// pubsub.add_client(client.clone()).unwrap();
+/// pubsub.psub_client(client.clone(), "orders.>").unwrap();
+///
+/// pubsub.pub_message("orders.eu.created", "created").unwrap();
+/// pubsub.pub_message("users.signup", "unheard").unwrap();
+///
+/// assert_eq!(client.contents(), vec!["created"]);
+/// assert!(matcher.consulted.get() > 0);
+///
+/// assert!(matches!(
+///     pubsub.psub_client(client.clone(), "a>b>"),
+///     Err(PubSubError::InvalidPatternError { .. })
+/// ));
+/// ```
+pub trait ChannelMatcher: Clone {
+    /// A pattern compiled for matching.
+    type Compiled: Clone;
+
+    /// Whether a name is a pattern rather than a literal `Channel`.
+    fn is_pattern(&self, channel: &str) -> bool;
+
+    /// Compiles a pattern, or explains why it is malformed.
+    fn compile(&self, pattern: &str) -> Result<Self::Compiled, PatternError>;
+
+    /// Whether a compiled pattern matches a literal `Channel`.
+    fn matches(&self, compiled: &Self::Compiled, channel: &str) -> bool;
+
+    /// Escapes the wildcards of a literal `Channel` name, so that it is not
+    /// a pattern.  See `PubSub::sub_client_exact`.
+    ///
+    /// By default names are returned unchanged, which suits matchers whose
+    /// names cannot be escaped.
+    fn escape<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        Cow::Borrowed(name)
+    }
+
+    /// Removes the escapes of a literal `Channel` name, giving the name
+    /// patterns are matched against.  Undoes `escape`.
+    fn unescape<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        Cow::Borrowed(name)
+    }
+
+    /// A prefix of every `Channel` a pattern matches.  Patterns are indexed
+    /// by it, so that a `Channel` is only matched against the patterns
+    /// whose prefix it starts with, after removing its escapes.
+    ///
+    /// By default the prefix is empty, so every pattern is matched against
+    /// every `Channel`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::{ChannelMatcher, PatternError, PatternSyntax};
+    /// use std::cell::Cell;
+    /// use std::rc::Rc;
+    ///
+    /// assert_eq!(PatternSyntax::Glob.literal_prefix("orders.*.created"), "orders.");
+    /// assert_eq!(PatternSyntax::Mqtt { delimiter: '/' }.literal_prefix("alerts/#"), "alerts");
+    ///
+    /// /// Routing keys where a trailing `>` matches any suffix.
+    /// #[derive(Clone, Default)]
+    /// struct PrefixMatcher {
+    ///     consulted: Rc<Cell<usize>>,
+    /// }
+    ///
+    /// impl ChannelMatcher for PrefixMatcher {
+    ///     type Compiled = String;
+    ///
+    ///     fn is_pattern(&self, channel: &str) -> bool {
+    ///         channel.ends_with('>')
+    ///     }
+    ///
+    ///     fn compile(&self, pattern: &str) -> Result<String, PatternError> {
+    ///         Ok(pattern[..pattern.len() - 1].to_string())
+    ///     }
+    ///
+    ///     fn matches(&self, prefix: &String, channel: &str) -> bool {
+    ///         self.consulted.set(self.consulted.get() + 1);
+    ///         channel.starts_with(prefix.as_str())
+    ///     }
+    ///
+    ///     fn literal_prefix<'p>(&self, pattern: &'p str) -> &'p str {
+    ///         &pattern[..pattern.len() - 1]
+    ///     }
+    /// }
+    ///
+    /// let matcher = PrefixMatcher::default();
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::with_matcher(matcher.clone());
+    /// pubsub.set_pattern_cache_capacity(0);
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.psub_client(client.clone(), "orders.>").unwrap();
+    ///
+    /// pubsub.pub_message("users.signup", "unheard").unwrap();
+    /// assert_eq!(matcher.consulted.get(), 0);
+    ///
+    /// pubsub.pub_message("orders.eu.created", "created").unwrap();
+    /// assert!(matcher.consulted.get() > 0);
+    /// assert_eq!(client.contents(), vec!["created"]);
+    /// ```
+    fn literal_prefix<'p>(&self, _pattern: &'p str) -> &'p str {
+        ""
+    }
+
+    /// The length of the literal prefix of a pattern and its number of
+    /// wildcards, which rank the patterns matching a `Channel`: longest
+    /// prefix first, then fewest wildcards.  See `PatternResolution`.
+    ///
+    /// By default every pattern ranks the same, so patterns are ordered by
+    /// name.
+    fn specificity(&self, _pattern: &str) -> (usize, usize) {
+        (0, 0)
+    }
+}
+
+/// Why a `ChannelMatcher` rejected a pattern
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatternError {
+    pub message: String,
+}
+
+impl PatternError {
+    pub fn new<S: Into<String>>(message: S) -> PatternError {
+        PatternError {
+            message: message.into(),
+        }
+    }
+}
+
+impl Error for PatternError {}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// A `ChannelMatcher` without patterns: every name is a literal `Channel`,
+/// wildcard characters included
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::ExactMatcher;
+///
+/// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+///
+/// let mut pubsub = PubSub::with_matcher(ExactMatcher);
+/// pubsub.add_client(client.clone()).unwrap();
+/// pubsub.sub_client(client.clone(), "orders.*").unwrap();
+///
+/// pubsub.pub_message("orders.created", "unheard").unwrap();
+/// pubsub.pub_message("orders.*", "literal").unwrap();
+///
+/// assert_eq!(client.contents(), vec!["literal"]);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExactMatcher;
+
+impl ChannelMatcher for ExactMatcher {
+    type Compiled = String;
+
+    fn is_pattern(&self, _channel: &str) -> bool {
+        false
+    }
+
+    fn compile(&self, pattern: &str) -> Result<String, PatternError> {
+        Ok(pattern.to_string())
+    }
+
+    fn matches(&self, compiled: &String, channel: &str) -> bool {
+        compiled == channel
+    }
+}
@@ -0,0 +1,177 @@
+use std::borrow::Cow;
+use std::mem::size_of;
+use std::sync::Arc;
+
+pub(crate) type ShedCallback = Arc<dyn Fn(ShedEvent) + Send + Sync>;
+
+pub(crate) type HeapSizeHook<TMessage> = Arc<dyn Fn(&TMessage) -> usize + Send + Sync>;
+
+/// A buffered `Message` dropped to stay within the memory budget
+///
+/// See `PubSub::set_memory_budget`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShedEvent<'c> {
+    /// The `Channel` whose history or retained `Message` the `Message` was
+    /// dropped from.
+    pub channel: &'c str,
+    /// The `sequence` of the dropped `Message`.
+    pub sequence: u64,
+}
+
+/// What is dropped to make room within the memory budget, in which order
+///
+/// Within history, and within retained `Messages`, the oldest `Message` is
+/// dropped first, whichever `Channel` it belongs to.  See
+/// `PubSub::set_shed_order`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShedOrder {
+    /// Only history `Messages` are dropped.  The default.
+    #[default]
+    History,
+    /// History `Messages` are dropped, then retained `Messages` once there
+    /// is no history left.
+    HistoryThenRetained,
+    /// Retained `Messages` are dropped, then history `Messages` once there
+    /// are no retained `Messages` left.
+    RetainedThenHistory,
+}
+
+impl ShedOrder {
+    /// Whether retained `Messages` are dropped before history.
+    pub(crate) fn retained_first(self) -> bool {
+        self == ShedOrder::RetainedThenHistory
+    }
+
+    /// Whether retained `Messages` are dropped at all.
+    pub(crate) fn sheds_retained(self) -> bool {
+        self != ShedOrder::History
+    }
+}
+
+/// The memory a value owns beyond its own size, such as the buffer of a
+/// `String`
+///
+/// `PubSub::memory_estimate` counts the size of the type of each stored
+/// `Message`, plus what `PubSub::set_heap_size` says it owns, which is
+/// nothing unless set.  Implemented for common payloads, so that their
+/// `heap_size` can be passed to it.  The default is 0, as for the `Copy`
+/// types, which own nothing.
+///
+/// ```
+/// use general_pub_sub::SizeOf;
+/// use std::sync::Arc;
+///
+/// assert_eq!(7u32.heap_size(), 0);
+/// assert_eq!(String::with_capacity(64).heap_size(), 64);
+/// assert_eq!(vec![String::from("ab"), String::new()].heap_size(), 2 * 24 + 2);
+/// assert_eq!(Arc::<str>::from("shared").heap_size(), 0);
+/// ```
+pub trait SizeOf {
+    /// The bytes owned by the value beyond `size_of_val`.
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+macro_rules! owns_nothing {
+    ($($owner:ty),*) => {
+        $(impl SizeOf for $owner {})*
+    };
+}
+
+owns_nothing!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64
+);
+
+/// Borrowed data is owned elsewhere.
+impl<T: ?Sized> SizeOf for &T {}
+
+/// Shared data is owned by all the `Arcs` together, so none of them counts
+/// it.
+impl<T: ?Sized> SizeOf for Arc<T> {}
+
+impl SizeOf for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl SizeOf for Box<str> {
+    fn heap_size(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T: SizeOf> SizeOf for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(SizeOf::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: SizeOf> SizeOf for Box<[T]> {
+    fn heap_size(&self) -> usize {
+        self.len() * size_of::<T>() + self.iter().map(SizeOf::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: SizeOf> SizeOf for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, SizeOf::heap_size)
+    }
+}
+
+impl SizeOf for Cow<'_, str> {
+    fn heap_size(&self) -> usize {
+        match self {
+            Cow::Borrowed(_) => 0,
+            Cow::Owned(owned) => owned.heap_size(),
+        }
+    }
+}
+
+/// Sizes the `Messages` a `PubSub` stores, for `PubSub::memory_estimate`
+pub(crate) struct Sizer<TMessage> {
+    pub(crate) heap_size: Option<HeapSizeHook<TMessage>>,
+}
+
+impl<TMessage> Sizer<TMessage> {
+    pub(crate) fn new() -> Sizer<TMessage> {
+        Sizer { heap_size: None }
+    }
+
+    /// The bytes owned by the contents of a `Message`.
+    pub(crate) fn heap(&self, contents: &TMessage) -> usize {
+        self.heap_size
+            .as_ref()
+            .map_or(0, |heap_size| heap_size(contents))
+    }
+
+    /// The bytes taken by a history or retained `Message`.
+    pub(crate) fn entry(&self, contents: &TMessage) -> usize {
+        size_of::<(u64, TMessage)>() + self.heap(contents)
+    }
+}
+
+impl<TMessage> Clone for Sizer<TMessage> {
+    fn clone(&self) -> Self {
+        Sizer {
+            heap_size: self.heap_size.clone(),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::DropReason;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+const FAN_OUT_BOUNDS: &[u64] = &[0, 1, 10, 100, 1_000];
+const PUBLISH_LATENCY_BOUNDS: &[u64] = &[1_000, 10_000, 100_000, 1_000_000, 10_000_000];
+
+/// A histogram with a fixed set of buckets
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    bounds: &'static [u64],
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [u64]) -> Histogram {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+        }
+    }
+
+    pub(crate) fn record(&mut self, value: u64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+
+        self.counts[bucket] += 1;
+    }
+
+    /// Iterates the buckets as `(upper_bound, count)` pairs.
+    ///
+    /// Upper bounds are inclusive, and the last bucket, which has no upper
+    /// bound, is reported with `None`.  Counts are not cumulative.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<u64>, u64)> + '_ {
+        self.bounds
+            .iter()
+            .map(|bound| Some(*bound))
+            .chain(std::iter::once(None))
+            .zip(self.counts.iter().copied())
+    }
+
+    /// Gets the total number of recorded values.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+/// The largest fan-out of any single publish
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaxFanOut {
+    pub recipients: usize,
+    pub channel: String,
+}
+
+/// Publish metrics of a `PubSub`
+///
+/// Returned by `PubSub::metrics`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Metrics {
+    /// Recipients per publish, in buckets of 0, 1, 2–10, 11–100, 101–1000,
+    /// and more than 1000.
+    pub fan_out: Histogram,
+    /// Time spent publishing, in nanoseconds, in buckets of up to 1µs, 10µs,
+    /// 100µs, 1ms, 10ms, and more than 10ms.
+    pub publish_latency_ns: Histogram,
+    /// The largest fan-out seen so far, and the `Channel` it was published to.
+    pub max_fan_out: Option<MaxFanOut>,
+    /// Deliveries skipped by sampled subscriptions.
+    pub sampled_out: u64,
+    /// `Messages` waiting to be flushed in `DeliveryMode::Deferred`.
+    pub pending: usize,
+    /// Undelivered `Messages`, by reason.  Reasons that never occurred are
+    /// left out.  See `PubSub::set_drop_callback`.
+    pub drops: BTreeMap<DropReason, u64>,
+    /// `Channels` whose matching patterns are cached.  See
+    /// `PubSub::set_pattern_cache_capacity`.
+    pub pattern_cache_entries: usize,
+}
+
+/// Counters of a `PubSub`, such as for a dashboard
+///
+/// Returned by `PubSub::stats`.  The counters start over with
+/// `PubSub::reset_stats`, while the counts of `Clients`, `Channels`, and
+/// patterns are always current.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PubSubStats {
+    /// `Messages` published, counting a `Message` published to several
+    /// `Channels` at once, or fanned out by a pattern, once per `Channel`.
+    pub published: u64,
+    /// `Messages` handed to a `Client` successfully.  In
+    /// `DeliveryMode::Deferred`, deliveries are counted by `flush`.
+    pub deliveries: u64,
+    /// Deliveries skipped because the subscriber is not a `Client` of the
+    /// `PubSub`.  See `StaleIdPolicy`.
+    pub skipped_stale: u64,
+    /// `Messages` published to each `Channel`, when enabled with
+    /// `PubSub::set_channel_stats`.  Empty otherwise.
+    pub per_channel: HashMap<String, u64>,
+    pub clients: usize,
+    /// Literal `Channels`, including empty ones unless they were removed.
+    pub channels: usize,
+    pub patterns: usize,
+}
+
+#[derive(Clone)]
+pub(crate) struct PublishMetrics {
+    pub(crate) fan_out: Histogram,
+    pub(crate) publish_latency_ns: Histogram,
+    pub(crate) max_fan_out: Option<MaxFanOut>,
+    pub(crate) published: u64,
+    pub(crate) deliveries: u64,
+    /// The stale drops counted when the stats were last reset, since stale
+    /// subscribers are already counted with the drops.
+    pub(crate) stale_baseline: u64,
+    /// Only kept when enabled, since it grows with every `Channel`
+    /// published to.
+    pub(crate) per_channel: Option<HashMap<String, u64>>,
+}
+
+impl PublishMetrics {
+    pub(crate) fn new() -> PublishMetrics {
+        PublishMetrics {
+            fan_out: Histogram::new(FAN_OUT_BOUNDS),
+            publish_latency_ns: Histogram::new(PUBLISH_LATENCY_BOUNDS),
+            max_fan_out: None,
+            published: 0,
+            deliveries: 0,
+            stale_baseline: 0,
+            per_channel: None,
+        }
+    }
+
+    pub(crate) fn record_publish(&mut self, channel: &str) {
+        self.published += 1;
+
+        if let Some(per_channel) = &mut self.per_channel {
+            match per_channel.get_mut(channel) {
+                Some(count) => *count += 1,
+                None => {
+                    per_channel.insert(channel.to_string(), 1);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn reset_stats(&mut self, stale: u64) {
+        self.published = 0;
+        self.deliveries = 0;
+        self.stale_baseline = stale;
+
+        if let Some(per_channel) = &mut self.per_channel {
+            per_channel.clear();
+        }
+    }
+
+    pub(crate) fn record_fan_out(&mut self, channel: &str, recipients: usize) {
+        self.deliveries += recipients as u64;
+        self.fan_out.record(recipients as u64);
+
+        let max_recipients = self.max_fan_out.as_ref().map_or(0, |max| max.recipients);
+
+        if recipients > max_recipients {
+            self.max_fan_out = Some(MaxFanOut {
+                recipients,
+                channel: channel.to_string(),
+            });
+        }
+    }
+
+    pub(crate) fn record_publish_latency(&mut self, latency: Duration) {
+        self.publish_latency_ns.record(latency.as_nanos() as u64);
+    }
+}
@@ -0,0 +1,45 @@
+/// How `PubSub::sub_client_with` subscribes a `Client`
+///
+/// The default subscribes as `sub_client` does, so only the options that
+/// differ need to be given:
+///
+/// ```
+/// use general_pub_sub::SubscribeOptions;
+///
+/// let options = SubscribeOptions {
+///     once: true,
+///     ..SubscribeOptions::default()
+/// };
+///
+/// assert!(options.deliver_retained);
+/// assert!(!options.idempotent);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubscribeOptions {
+    /// Sends the new subscriber the retained `Messages` of the `Channels`
+    /// the subscription matches.  On by default.
+    pub deliver_retained: bool,
+    /// Removes the subscription once it has delivered a `Message`, whether
+    /// it is to a `Channel` or a pattern.
+    ///
+    /// Retained `Messages` sent when subscribing do not count.  A `Message`
+    /// whose recipients were already resolved, such as one held in a
+    /// micro-batch or published with `RecipientCapture::AtPublish`, may
+    /// still be delivered after the first, and `Messages` buffered for a
+    /// detached `Client` are all delivered when it reattaches.
+    pub once: bool,
+    /// Makes being already subscribed not an error, leaving the existing
+    /// subscription, and under `DuplicateSubscriptions::Counted` its count,
+    /// as it is.
+    pub idempotent: bool,
+}
+
+impl Default for SubscribeOptions {
+    fn default() -> SubscribeOptions {
+        SubscribeOptions {
+            deliver_retained: true,
+            once: false,
+            idempotent: false,
+        }
+    }
+}
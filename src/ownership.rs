@@ -0,0 +1,31 @@
+/// What happens to the `Channels` owned by a `Client` when it is removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OwnerRemoval {
+    /// The `Channels` are released, so anybody may publish to them again.
+    Release,
+    /// The `Channels` stay owned by the removed identifier, so nobody else
+    /// may publish to them until they are released or the owner is added
+    /// again.
+    Orphan,
+}
+
+/// The rules for publishing to owned `Channels`
+///
+/// See `PubSub::claim_channel`.  By default, unattributed publishes are
+/// allowed and the `Channels` of removed owners are released.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelOwnershipPolicy {
+    /// Whether publishes not made on behalf of a `Client`, such as with
+    /// `PubSub::pub_message`, may reach owned `Channels`.
+    pub allow_unattributed: bool,
+    pub on_owner_removed: OwnerRemoval,
+}
+
+impl Default for ChannelOwnershipPolicy {
+    fn default() -> Self {
+        ChannelOwnershipPolicy {
+            allow_unattributed: true,
+            on_owner_removed: OwnerRemoval::Release,
+        }
+    }
+}
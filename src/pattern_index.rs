@@ -0,0 +1,70 @@
+use crate::ChannelName;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The patterns of a `PubSub` keyed by their literal prefix, so that a
+/// `Channel` is only matched against the patterns whose prefix it starts
+/// with
+///
+/// Patterns without a literal prefix are kept under the empty prefix, which
+/// every `Channel` starts with.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PatternIndex {
+    by_prefix: HashMap<String, BTreeSet<ChannelName>>,
+    /// How many of the indexed prefixes have each length, so that only the
+    /// prefixes of a `Channel` with those lengths are looked up.
+    lengths: BTreeMap<usize, usize>,
+}
+
+impl PatternIndex {
+    pub(crate) fn new() -> PatternIndex {
+        PatternIndex::default()
+    }
+
+    pub(crate) fn insert(&mut self, prefix: &str, pattern: ChannelName) {
+        let patterns = match self.by_prefix.get_mut(prefix) {
+            Some(patterns) => patterns,
+            None => {
+                *self.lengths.entry(prefix.len()).or_insert(0) += 1;
+                self.by_prefix.entry(prefix.to_string()).or_default()
+            }
+        };
+
+        patterns.insert(pattern);
+    }
+
+    pub(crate) fn remove(&mut self, prefix: &str, pattern: &str) {
+        let emptied = match self.by_prefix.get_mut(prefix) {
+            Some(patterns) => {
+                patterns.remove(pattern);
+                patterns.is_empty()
+            }
+            None => false,
+        };
+
+        if emptied {
+            self.by_prefix.remove(prefix);
+
+            if let Some(count) = self.lengths.get_mut(&prefix.len()) {
+                *count -= 1;
+
+                if *count == 0 {
+                    self.lengths.remove(&prefix.len());
+                }
+            }
+        }
+    }
+
+    /// Iterates the patterns whose prefix `channel` starts with, which are
+    /// the only ones that can match it.
+    pub(crate) fn candidates<'i>(
+        &'i self,
+        channel: &'i str,
+    ) -> impl Iterator<Item = &'i ChannelName> + 'i {
+        self.lengths
+            .keys()
+            .take_while(move |length| **length <= channel.len())
+            .filter_map(move |length| channel.get(..*length))
+            .filter_map(move |prefix| self.by_prefix.get(prefix))
+            .flatten()
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{ChannelMatcher, PatternError};
+use std::borrow::Cow;
+use wildmatch::WildMatch;
+
+/// How the wildcards of subscribed patterns are written and matched
+///
+/// The default `ChannelMatcher` of a `PubSub`.  Chosen when the `PubSub` is
+/// created, with `PubSub::with_pattern_syntax`, since it decides which names
+/// are patterns at all.  Publishing to a pattern (see
+/// `PatternPublishBehavior`) uses the same syntax.
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::PatternSyntax;
+///
+/// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+///
+/// let mut pubsub = PubSub::with_pattern_syntax(PatternSyntax::Mqtt { delimiter: '/' });
+/// pubsub.add_client(client.clone()).unwrap();
+/// pubsub.psub_client(client.clone(), "+/temperature").unwrap();
+/// pubsub.psub_client(client.clone(), "alerts/#").unwrap();
+///
+/// pubsub.pub_message("kitchen/temperature", "21").unwrap();
+/// pubsub.pub_message("kitchen/oven/temperature", "unheard").unwrap();
+/// pubsub.pub_message("alerts", "all").unwrap();
+/// pubsub.pub_message("alerts/fire/kitchen", "fire").unwrap();
+///
+/// assert_eq!(client.contents(), vec!["21", "all", "fire"]);
+///
+/// assert!(matches!(
+///     pubsub.psub_client(client.clone(), "alerts/#/kitchen"),
+///     Err(PubSubError::InvalidPatternError { .. })
+/// ));
+/// ```
+///
+/// A wildcard preceded by a backslash is an ordinary character, and the
+/// backslash is not part of the name it stands for, so `foo\*bar` is the
+/// literal `Channel` `foo*bar`.  Names are reported as written, escapes
+/// included.  Patterns cannot contain escaped wildcards.
+///
+/// `PubSub::sub_client` escapes names itself under the default
+/// `PatternSubscriptions::Explicit`, so escapes are mostly written when
+/// publishing.
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+///
+/// let literal: RecordingClient<_, &str> = RecordingClient::new(1);
+/// let neighbor: RecordingClient<_, &str> = RecordingClient::new(2);
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(literal.clone()).unwrap();
+/// pubsub.add_client(neighbor.clone()).unwrap();
+/// pubsub.sub_client(literal.clone(), "files/report*.pdf").unwrap();
+/// pubsub.sub_client(neighbor.clone(), "files/report2.pdf").unwrap();
+///
+/// pubsub.pub_message(r"files/report\*.pdf", "starred").unwrap();
+///
+/// assert_eq!(literal.contents(), vec!["starred"]);
+/// assert!(neighbor.contents().is_empty());
+///
+/// // Unescaped, the name is a pattern, which cannot be published to.
+/// assert!(pubsub.pub_message("files/report*.pdf", "unheard").is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatternSyntax {
+    /// `*` matches any run of characters, delimiters included, and `?` any
+    /// single character.  The default.
+    #[default]
+    Glob,
+    /// Names are split into segments at `delimiter`.  A `+` segment matches
+    /// exactly one segment, and a `#` segment, which has to be the last one,
+    /// matches the remaining segments, if any: `a.#` matches `a` as well as
+    /// `a.b.c`.  `+` and `#` are only valid as whole segments, and `*` and
+    /// `?` are ordinary characters.
+    Mqtt { delimiter: char },
+}
+
+impl PatternSyntax {
+    /// MQTT-style wildcards, with `.` as the delimiter.
+    pub const MQTT: PatternSyntax = PatternSyntax::Mqtt { delimiter: '.' };
+
+    fn wildcards(&self) -> [char; 2] {
+        match self {
+            PatternSyntax::Glob => ['*', '?'],
+            PatternSyntax::Mqtt { .. } => ['+', '#'],
+        }
+    }
+}
+
+impl ChannelMatcher for PatternSyntax {
+    type Compiled = CompiledPattern;
+
+    fn is_pattern(&self, channel: &str) -> bool {
+        let wildcards = self.wildcards();
+        let mut escaped = false;
+
+        for c in channel.chars() {
+            if wildcards.contains(&c) && !escaped {
+                return true;
+            }
+
+            escaped = c == '\\';
+        }
+
+        false
+    }
+
+    /// Patterns with escaped wildcards are malformed, and so are MQTT-style
+    /// patterns with misplaced wildcards.
+    fn compile(&self, pattern: &str) -> Result<CompiledPattern, PatternError> {
+        if self.unescape(pattern).len() != pattern.len() {
+            return Err(PatternError::new("a pattern cannot escape wildcards"));
+        }
+
+        let delimiter = match self {
+            PatternSyntax::Glob => {
+                return Ok(CompiledPattern(Compiled::Glob(WildMatch::new(pattern))))
+            }
+            PatternSyntax::Mqtt { delimiter } => *delimiter,
+        };
+
+        let texts: Vec<&str> = pattern.split(delimiter).collect();
+        let mut segments = Vec::with_capacity(texts.len());
+
+        for (index, text) in texts.iter().enumerate() {
+            segments.push(match *text {
+                "+" => Segment::One,
+                "#" if index == texts.len() - 1 => Segment::Rest,
+                "#" => return Err(PatternError::new("`#` is not the last segment")),
+                text if text.contains(['+', '#']) => {
+                    return Err(PatternError::new("`+` or `#` is part of a segment"))
+                }
+                text => Segment::Literal(text.to_string()),
+            });
+        }
+
+        Ok(CompiledPattern(Compiled::Mqtt {
+            segments,
+            delimiter,
+        }))
+    }
+
+    fn matches(&self, compiled: &CompiledPattern, channel: &str) -> bool {
+        compiled.matches(channel)
+    }
+
+    fn escape<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        let wildcards = self.wildcards();
+
+        if !name.contains(wildcards) {
+            return Cow::Borrowed(name);
+        }
+
+        let mut escaped = String::with_capacity(name.len() + 1);
+
+        for c in name.chars() {
+            if wildcards.contains(&c) {
+                escaped.push('\\');
+            }
+
+            escaped.push(c);
+        }
+
+        Cow::Owned(escaped)
+    }
+
+    fn unescape<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        if !name.contains('\\') {
+            return Cow::Borrowed(name);
+        }
+
+        let wildcards = self.wildcards();
+        let mut unescaped = String::with_capacity(name.len());
+        let mut chars = name.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            let escapes = c == '\\' && chars.peek().is_some_and(|next| wildcards.contains(next));
+
+            if !escapes {
+                unescaped.push(c);
+            }
+        }
+
+        match unescaped.len() == name.len() {
+            true => Cow::Borrowed(name),
+            false => Cow::Owned(unescaped),
+        }
+    }
+
+    /// The text before the first wildcard, without the delimiter ending it
+    /// in MQTT-style patterns, since `a.#` also matches `a`.
+    fn literal_prefix<'p>(&self, pattern: &'p str) -> &'p str {
+        let prefix = &pattern[..pattern.find(self.wildcards()).unwrap_or(pattern.len())];
+
+        match self {
+            PatternSyntax::Glob => prefix,
+            PatternSyntax::Mqtt { delimiter } => prefix.strip_suffix(*delimiter).unwrap_or(prefix),
+        }
+    }
+
+    fn specificity(&self, pattern: &str) -> (usize, usize) {
+        let wildcards = self.wildcards();
+
+        let literal_prefix = pattern.find(wildcards).unwrap_or(pattern.len());
+        let count = pattern.chars().filter(|c| wildcards.contains(c)).count();
+
+        (literal_prefix, count)
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Literal(String),
+    One,
+    Rest,
+}
+
+/// A pattern compiled by `PatternSyntax`
+#[derive(Clone, Debug)]
+pub struct CompiledPattern(Compiled);
+
+#[derive(Clone, Debug)]
+enum Compiled {
+    Glob(WildMatch),
+    Mqtt {
+        segments: Vec<Segment>,
+        delimiter: char,
+    },
+}
+
+impl CompiledPattern {
+    fn matches(&self, channel: &str) -> bool {
+        let (segments, delimiter) = match &self.0 {
+            Compiled::Glob(matcher) => return matcher.matches(channel),
+            Compiled::Mqtt {
+                segments,
+                delimiter,
+            } => (segments, *delimiter),
+        };
+
+        let mut parts = channel.split(delimiter);
+
+        for segment in segments {
+            match segment {
+                Segment::Rest => return true,
+                Segment::One => {
+                    if parts.next().is_none() {
+                        return false;
+                    }
+                }
+                Segment::Literal(literal) => {
+                    if parts.next() != Some(literal.as_str()) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        parts.next().is_none()
+    }
+}
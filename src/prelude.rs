@@ -0,0 +1,19 @@
+//! The types needed by most uses of the `PubSub`
+//!
+//! ```
+//! use general_pub_sub::prelude::*;
+//!
+//! let client: RecordingClient<_, i32> = RecordingClient::new("alice");
+//!
+//! let mut pubsub = PubSub::new();
+//! pubsub.add_client(client.clone()).unwrap();
+//! pubsub.sub_client(client.clone(), "chat").unwrap();
+//! pubsub.pub_message("chat", 42).unwrap();
+//!
+//! assert_eq!(client.contents(), vec![42]);
+//! ```
+
+pub use crate::{
+    ChannelName, Client, DeliveryMode, DeliveryReport, Message, PrintClient, PubSub, PubSubError,
+    RecipientCapture, RecordingClient, UniqueIdentifier, Via,
+};
@@ -0,0 +1,160 @@
+use crate::PubSubError;
+
+const PLACEHOLDER: &str = "{*}";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    /// `*`, matching any number of characters.
+    Any,
+    /// `?`, matching exactly one character.
+    One,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Capture,
+}
+
+/// Rewrites `Channel` names matching a pattern, carrying over what the
+/// wildcards matched
+///
+/// The pattern uses glob wildcards, `*` and `?`, whatever the
+/// `ChannelMatcher` of the `PubSub`, and `{*}` may be written for `*` to
+/// mirror the template.  Each wildcard captures the text it matched.  Every
+/// `{*}` in the template is replaced with the next capture, in order, so the
+/// template may use fewer placeholders than the pattern has wildcards, but
+/// not more.
+///
+/// When a name can be matched in several ways, earlier `*` wildcards match
+/// as little as possible.
+///
+/// ```
+/// use general_pub_sub::ChannelRewrite;
+///
+/// let rewrite = ChannelRewrite::new("prod.{*}.{*}", "staging.{*}.{*}").unwrap();
+///
+/// assert_eq!(
+///     rewrite.apply("prod.orders.eu.new"),
+///     Some("staging.orders.eu.new".to_string())
+/// );
+/// assert_eq!(rewrite.apply("dev.orders.new"), None);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelRewrite {
+    pattern: Vec<Token>,
+    template: Vec<Part>,
+}
+
+impl ChannelRewrite {
+    /// Creates a `ChannelRewrite` from a pattern and a template.
+    ///
+    /// Results in a `PubSubError` when the template has more placeholders
+    /// than the pattern has wildcards.
+    pub fn new(pattern: &str, template: &str) -> Result<ChannelRewrite, PubSubError> {
+        let pattern: Vec<Token> = pattern
+            .replace(PLACEHOLDER, "*")
+            .chars()
+            .map(|c| match c {
+                '*' => Token::Any,
+                '?' => Token::One,
+                c => Token::Literal(c),
+            })
+            .collect();
+
+        let mut parts = Vec::new();
+
+        for (index, literal) in template.split(PLACEHOLDER).enumerate() {
+            if index > 0 {
+                parts.push(Part::Capture);
+            }
+
+            if !literal.is_empty() {
+                parts.push(Part::Literal(literal.to_string()));
+            }
+        }
+
+        let wildcards = pattern
+            .iter()
+            .filter(|token| matches!(token, Token::Any | Token::One))
+            .count();
+        let placeholders = parts.iter().filter(|part| **part == Part::Capture).count();
+
+        if placeholders > wildcards {
+            return Err(PubSubError::InvalidChannelRewriteError {
+                template: template.to_string(),
+            });
+        }
+
+        Ok(ChannelRewrite {
+            pattern,
+            template: parts,
+        })
+    }
+
+    /// Rewrites a `Channel` name, or returns `None` when it does not match
+    /// the pattern.
+    pub fn apply(&self, channel: &str) -> Option<String> {
+        let chars: Vec<char> = channel.chars().collect();
+        let mut captures = Vec::new();
+
+        if !capture(&self.pattern, &chars, &mut captures) {
+            return None;
+        }
+
+        let mut captures = captures.into_iter();
+        let mut rewritten = String::with_capacity(channel.len());
+
+        for part in &self.template {
+            match part {
+                Part::Literal(literal) => rewritten.push_str(literal),
+                Part::Capture => rewritten.extend(captures.next()?),
+            }
+        }
+
+        Some(rewritten)
+    }
+}
+
+/// Matches `chars` against `pattern`, pushing what each wildcard matched
+/// onto `captures`.
+fn capture<'c>(pattern: &[Token], chars: &'c [char], captures: &mut Vec<&'c [char]>) -> bool {
+    let (token, rest) = match pattern.split_first() {
+        Some(split) => split,
+        None => return chars.is_empty(),
+    };
+
+    match token {
+        Token::Literal(literal) => match chars.split_first() {
+            Some((c, chars)) if c == literal => capture(rest, chars, captures),
+            _ => false,
+        },
+        Token::One => match chars.len() {
+            0 => false,
+            _ => {
+                captures.push(&chars[..1]);
+
+                if capture(rest, &chars[1..], captures) {
+                    return true;
+                }
+
+                captures.pop();
+                false
+            }
+        },
+        Token::Any => {
+            for length in 0..=chars.len() {
+                captures.push(&chars[..length]);
+
+                if capture(rest, &chars[length..], captures) {
+                    return true;
+                }
+
+                captures.pop();
+            }
+
+            false
+        }
+    }
+}
@@ -0,0 +1,1417 @@
+use crate::drops::{DropReason, Drops};
+use crate::hooks::Hooks;
+use crate::lock_cell::LockCell;
+use crate::match_cache::MatchCache;
+use crate::pattern_index::PatternIndex;
+use crate::time::{Clock, SystemClock};
+use crate::{ChannelMatcher, ChannelName, PubSubError, UniqueIdentifier, Via};
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::RandomState, BTreeSet, HashMap, HashSet},
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Instant,
+};
+
+/// A handle to a literal `Channel`
+///
+/// Obtained from `PubSub::channel_handle`.  Publishing or subscribing through
+/// a handle skips hashing the `Channel` name.
+///
+/// Handles are only meaningful to the `PubSub` that created them.  A handle
+/// stays valid until its `Channel` is removed, with `PubSub::remove_channel`
+/// or under `EmptyChannels::Remove`; after that, using it results in
+/// `PubSubError::InvalidChannelHandleError`, even once a `Channel` with the
+/// same name is created again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChannelHandle(pub(crate) usize, pub(crate) u32);
+
+/// The name the firehose is reported under to callbacks and in control
+/// `Messages`, since it is not a `Channel`
+pub(crate) const FIREHOSE: &str = "";
+
+/// What a subscription is made to: a `Channel` or pattern by name, a
+/// literal `Channel` by `ChannelHandle`, or the firehose
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Subscription<'a> {
+    Name(&'a ChannelName),
+    Handle(ChannelHandle),
+    Firehose,
+}
+
+/// A literal `Channel`, or the slot of a removed one, which the next
+/// `Channel` created reuses under a new generation
+#[derive(Clone)]
+pub(crate) struct ChannelEntry<TIdentifier> {
+    pub(crate) name: ChannelName,
+    pub(crate) subscribers: BTreeSet<TIdentifier>,
+    /// The `pattern_generation` at which no pattern matched the `Channel`.
+    unmatched_at: LockCell<Option<u64>>,
+    pub(crate) generation: u32,
+    pub(crate) live: bool,
+}
+
+impl<TIdentifier> ChannelEntry<TIdentifier> {
+    pub(crate) fn handle(&self, index: usize) -> ChannelHandle {
+        ChannelHandle(index, self.generation)
+    }
+}
+
+/// A pattern, compiled once when it is created rather than on every publish
+#[derive(Clone)]
+pub(crate) struct PatternEntry<TIdentifier, TCompiled> {
+    pub(crate) compiled: TCompiled,
+    specificity: (Reverse<usize>, usize),
+    pub(crate) subscribers: BTreeSet<TIdentifier>,
+}
+
+impl<TIdentifier, TCompiled> PatternEntry<TIdentifier, TCompiled> {
+    fn new<TMatcher>(
+        pattern: &str,
+        matcher: &TMatcher,
+    ) -> Result<PatternEntry<TIdentifier, TCompiled>, PubSubError>
+    where
+        TMatcher: ChannelMatcher<Compiled = TCompiled>,
+    {
+        let (literal_prefix, wildcards) = matcher.specificity(pattern);
+
+        Ok(PatternEntry {
+            compiled: compile(matcher, pattern)?,
+            specificity: (Reverse(literal_prefix), wildcards),
+            subscribers: BTreeSet::new(),
+        })
+    }
+}
+
+/// Compiles a pattern, turning a rejection into a `PubSubError`.
+fn compile<TMatcher: ChannelMatcher>(
+    matcher: &TMatcher,
+    pattern: &str,
+) -> Result<TMatcher::Compiled, PubSubError> {
+    matcher
+        .compile(pattern)
+        .map_err(|reason| PubSubError::InvalidPatternError {
+            pattern: pattern.to_string(),
+            reason,
+        })
+}
+
+/// A literal `Channel` or a compiled pattern, kept to match many `Channels`
+/// against, such as the source of a republish rule
+#[derive(Clone)]
+pub(crate) enum ChannelSelector<TCompiled> {
+    Literal(ChannelName),
+    Pattern(TCompiled),
+}
+
+/// A `Via` kept beyond the publish it was resolved for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum StoredVia {
+    Channel,
+    Pattern(String),
+    Firehose,
+    Direct,
+    Replay,
+}
+
+impl StoredVia {
+    pub(crate) fn new(via: Via) -> StoredVia {
+        match via {
+            Via::Channel => StoredVia::Channel,
+            Via::Pattern(pattern) => StoredVia::Pattern(pattern.to_string()),
+            Via::Firehose => StoredVia::Firehose,
+            Via::Direct => StoredVia::Direct,
+            Via::Replay => StoredVia::Replay,
+        }
+    }
+
+    /// The bytes the `StoredVia` owns beyond its own size: the name of its
+    /// pattern.
+    pub(crate) fn heap_size(&self) -> usize {
+        match self {
+            StoredVia::Pattern(pattern) => pattern.len(),
+            _ => 0,
+        }
+    }
+
+    pub(crate) fn as_via(&self) -> Via<'_> {
+        match self {
+            StoredVia::Channel => Via::Channel,
+            StoredVia::Pattern(pattern) => Via::Pattern(pattern),
+            StoredVia::Firehose => Via::Firehose,
+            StoredVia::Direct => Via::Direct,
+            StoredVia::Replay => Via::Replay,
+        }
+    }
+}
+
+/// How a sampled subscription selects the `Messages` it receives
+///
+/// See `PubSub::sub_sampled`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampling {
+    /// Each `Message` is delivered with the given probability, from `0.0`
+    /// (never) to `1.0` (always).
+    Rate(f32),
+    /// Every `n`th `Message` is delivered, starting with the first one.
+    EveryNth(u64),
+}
+
+#[derive(Clone)]
+pub(crate) struct Sampler {
+    pub(crate) sampling: Sampling,
+    pub(crate) seen: u64,
+}
+
+impl Sampler {
+    fn admits(&mut self, rng: &mut SampleRng) -> bool {
+        let seen = self.seen;
+        self.seen += 1;
+
+        match self.sampling {
+            Sampling::Rate(rate) => rng.next_f32() < rate,
+            Sampling::EveryNth(n) => seen.is_multiple_of(n.max(1)),
+        }
+    }
+}
+
+/// A small xorshift generator, good enough for sampling and cheap to seed.
+#[derive(Clone)]
+pub(crate) struct SampleRng(u64);
+
+impl SampleRng {
+    pub(crate) fn new(seed: u64) -> SampleRng {
+        // xorshift never leaves the all-zero state, so avoid seeding with it.
+        SampleRng(seed.max(1))
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// How often a pattern has matched a published `Channel`
+#[derive(Clone)]
+pub(crate) struct PatternStats {
+    pub(crate) matches: u64,
+    pub(crate) last_matched: Option<Instant>,
+    pub(crate) created: Instant,
+}
+
+/// How the attachments of overlapping subscriptions are resolved
+///
+/// A `Client` may reach a `Channel` through a literal subscription and any
+/// number of pattern subscriptions.  The `Message` is delivered to it at most
+/// once either way; this decides which of those subscriptions' attachments
+/// (such as `Sampling`) are applied.
+///
+/// A literal subscription is always the most specific.  Patterns are ranked
+/// by the length of their literal prefix (the text before the first
+/// wildcard, as ranked by the `ChannelMatcher`), longest first, then by
+/// their number of wildcards, fewest first, then by name.
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+///
+/// // Two patterns matching a `Channel`, and the one that wins.
+/// let table = [
+///     ("a.b.*", "a.*", "a.b.c", "a.b.*"),
+///     ("a.*.c", "a.b.*", "a.b.c", "a.b.*"),
+///     ("a.*", "a.b?", "a.bc", "a.b?"),
+///     ("*", "a*", "ab", "a*"),
+///     ("a.?c", "a.??", "a.bc", "a.?c"),
+///     ("a.*?", "a.*", "a.bc", "a.*"),
+///     ("a.*", "a.?", "a.b", "a.*"),
+/// ];
+///
+/// for (first, second, channel, winner) in table {
+///     for (first, second) in [(first, second), (second, first)] {
+///         let client: RecordingClient<_, &str> = RecordingClient::new(1);
+///
+///         let mut pubsub = PubSub::new();
+///         pubsub.add_client(client.clone()).unwrap();
+///         pubsub.psub_client(client.clone(), first).unwrap();
+///         pubsub.psub_client(client.clone(), second).unwrap();
+///
+///         assert_eq!(pubsub.winning_pattern(&1, channel), Some(winner));
+///
+///         // A literal subscription beats any pattern.
+///         pubsub.sub_client(client.clone(), channel).unwrap();
+///         assert_eq!(pubsub.winning_pattern(&1, channel), Some(channel));
+///     }
+/// }
+/// ```
+///
+/// Under `MostSpecific`, the filters of the other subscriptions are not
+/// even run:
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::{PatternResolution, PatternSubscriptions};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+/// let calls = Arc::new(AtomicUsize::new(0));
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.set_pattern_subscriptions(PatternSubscriptions::Detected);
+/// pubsub.set_pattern_resolution(PatternResolution::MostSpecific);
+/// pubsub.add_client(client.clone()).unwrap();
+/// pubsub.sub_client_filtered(&1, "a.b.*", |_: &&str| true).unwrap();
+/// {
+///     let calls = calls.clone();
+///     pubsub
+///         .sub_client_filtered(&1, "a.*", move |_: &&str| {
+///             calls.fetch_add(1, Ordering::SeqCst);
+///             true
+///         })
+///         .unwrap();
+/// }
+///
+/// pubsub.pub_message("a.b.c", "won by a.b.*").unwrap();
+/// assert_eq!(calls.load(Ordering::SeqCst), 0);
+///
+/// pubsub.pub_message("a.c", "won by a.*").unwrap();
+/// assert_eq!(calls.load(Ordering::SeqCst), 1);
+/// assert_eq!(client.contents(), vec!["won by a.b.*", "won by a.*"]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternResolution {
+    /// Every matching subscription applies, and the `Message` is delivered if
+    /// any of them admits it.
+    AllMatches,
+    /// Only the most specific matching subscription applies.
+    MostSpecific,
+}
+
+/// What publishing to a `Channel` name containing wildcards does
+///
+/// See `PubSub::set_pattern_publish_behavior`.
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::PatternPublishBehavior;
+///
+/// let literal: RecordingClient<_, &str> = RecordingClient::new(1);
+/// let pattern: RecordingClient<_, &str> = RecordingClient::new(2);
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(literal.clone()).unwrap();
+/// pubsub.add_client(pattern.clone()).unwrap();
+/// pubsub.sub_client(literal.clone(), "orders.new").unwrap();
+/// pubsub.sub_client(literal.clone(), "orders.paid").unwrap();
+/// pubsub.psub_client(pattern.clone(), "orders.*").unwrap();
+///
+/// assert_eq!(
+///     pubsub.pub_message("orders.*", "rejected"),
+///     Err(PubSubError::PublishedToPatternError { channel: "orders.*".to_string() })
+/// );
+/// assert!(literal.contents().is_empty());
+///
+/// pubsub.set_pattern_publish_behavior(PatternPublishBehavior::FanOut);
+/// pubsub.pub_message("orders.*", "fanned out").unwrap();
+/// assert_eq!(literal.contents(), vec!["fanned out", "fanned out"]);
+/// assert_eq!(pattern.contents(), vec!["fanned out", "fanned out"]);
+///
+/// pubsub.set_pattern_publish_behavior(PatternPublishBehavior::Literal);
+/// pubsub.pub_message("orders.*", "literal").unwrap();
+/// assert_eq!(literal.contents().len(), 2);
+/// assert_eq!(pattern.contents(), vec!["fanned out", "fanned out", "literal"]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternPublishBehavior {
+    /// The publish fails with `PubSubError::PublishedToPatternError`.
+    Error,
+    /// The `Message` is published to every existing literal `Channel` the
+    /// pattern matches, as if it was published to each of them in turn.
+    FanOut,
+    /// The name is treated as a literal `Channel`, which only pattern
+    /// subscribers matching the name itself receive.
+    Literal,
+}
+
+/// Whether subscribing methods such as `PubSub::sub_client` subscribe to
+/// patterns
+///
+/// See `PubSub::set_pattern_subscriptions`.
+///
+/// # Migrating
+///
+/// Before `Explicit` became the default, a name containing wildcards was
+/// always a pattern.  Subscriptions to patterns should move to
+/// `PubSub::psub_client` and `PubSub::punsub_client`, or the previous
+/// behavior can be kept with `Detected`.  Publishing is unaffected either
+/// way: see `PatternPublishBehavior`.
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::PatternSubscriptions;
+///
+/// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.add_client(client.clone()).unwrap();
+///
+/// // The literal `Channel` `orders.*`, published to as `orders.\*`.
+/// pubsub.sub_client(client.clone(), "orders.*").unwrap();
+/// pubsub.pub_message("orders.created", "unheard").unwrap();
+/// pubsub.pub_message(r"orders.\*", "literal").unwrap();
+///
+/// pubsub.psub_client(client.clone(), "users.*").unwrap();
+/// pubsub.pub_message("users.signup", "pattern").unwrap();
+///
+/// assert_eq!(client.contents(), vec!["literal", "pattern"]);
+///
+/// pubsub.set_pattern_subscriptions(PatternSubscriptions::Detected);
+/// pubsub.sub_client(client.clone(), "metrics.*").unwrap();
+/// pubsub.pub_message("metrics.cpu", "detected").unwrap();
+///
+/// assert_eq!(client.contents(), vec!["literal", "pattern", "detected"]);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatternSubscriptions {
+    /// Names are taken literally, wildcards included, as if escaped.  Only
+    /// `psub_client` and `punsub_client` subscribe to patterns.  The default.
+    #[default]
+    Explicit,
+    /// A name containing wildcards is a pattern, unless they are escaped.
+    Detected,
+}
+
+/// What happens to a `Channel` or pattern once its last subscriber leaves
+///
+/// See `PubSub::set_empty_channels`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyChannels {
+    /// `Channels` are kept, so that their `ChannelHandles` stay valid.
+    /// Patterns are kept until `PubSub::gc`.  The default.
+    #[default]
+    Keep,
+    /// `Channels` and patterns are removed as soon as their last subscriber
+    /// unsubscribes or is removed, as `PubSub::remove_channel` would, so
+    /// that short-lived `Channels` do not pile up.  Their `ChannelHandles`
+    /// become invalid.
+    Remove,
+}
+
+/// Finds the patterns matching a `Channel`, most specific first.
+///
+/// A pattern with a longer literal prefix (the characters before its first
+/// wildcard) is more specific.  Between equal prefixes, the pattern with fewer
+/// wildcards is more specific.  Remaining ties are broken alphabetically so
+/// the ordering is total.
+///
+/// Only the patterns the `PatternIndex` lists for the `Channel` are matched
+/// against it.
+pub(crate) fn matching_patterns<'p, TIdentifier, TMatcher: ChannelMatcher>(
+    matcher: &TMatcher,
+    index: &PatternIndex,
+    pattern_channels: &'p HashMap<ChannelName, PatternEntry<TIdentifier, TMatcher::Compiled>>,
+    channel: &str,
+) -> Vec<(&'p str, &'p BTreeSet<TIdentifier>)> {
+    let channel = matcher.unescape(channel);
+
+    let mut patterns: Vec<_> = index
+        .candidates(&channel)
+        .filter_map(|pattern| pattern_channels.get_key_value(pattern))
+        .filter(|(_, entry)| matcher.matches(&entry.compiled, &channel))
+        .collect();
+
+    patterns.sort_by_key(|(pattern, entry)| (entry.specificity, *pattern));
+
+    patterns
+        .into_iter()
+        .map(|(pattern, entry)| (pattern.as_ref(), &entry.subscribers))
+        .collect()
+}
+
+/// Chains the subscriptions reaching a `Channel` in delivery order: its
+/// literal subscribers, then the subscribers of each matching pattern, most
+/// specific first, then the firehose.  A subscriber reached through several
+/// subscriptions appears once for each.
+///
+/// Both publishing and `PubSubView::matching_subscribers` go through this,
+/// so they cannot disagree on who a `Channel` reaches.
+fn reaching<'r, TIdentifier>(
+    subscribers: Option<&'r BTreeSet<TIdentifier>>,
+    patterns: Vec<(&'r str, &'r BTreeSet<TIdentifier>)>,
+    firehose: &'r BTreeSet<TIdentifier>,
+) -> impl Iterator<Item = (Via<'r>, &'r TIdentifier)> {
+    let literal = subscribers.into_iter().flat_map(|subscribers| {
+        subscribers
+            .iter()
+            .map(|identifier| (Via::Channel, identifier))
+    });
+
+    let patterned = patterns.into_iter().flat_map(|(pattern, subscribers)| {
+        subscribers
+            .iter()
+            .map(move |identifier| (Via::Pattern(pattern), identifier))
+    });
+
+    let firehose = firehose
+        .iter()
+        .map(|identifier| (Via::Firehose, identifier));
+
+    literal.chain(patterned).chain(firehose)
+}
+
+/// The subscriptions of a `PubSub`, kept apart from its `Clients` so that
+/// recipients can be resolved while the `Clients` are borrowed mutably.
+#[derive(Clone)]
+pub(crate) struct Routing<TIdentifier, TMatcher: ChannelMatcher> {
+    pub(crate) channels: HashMap<ChannelName, ChannelHandle>,
+    pub(crate) channel_entries: Vec<ChannelEntry<TIdentifier>>,
+    /// The slots of removed `Channels`, reused before new ones are added.
+    free_entries: Vec<usize>,
+    pub(crate) pattern_channels:
+        HashMap<ChannelName, PatternEntry<TIdentifier, TMatcher::Compiled>>,
+    pattern_index: PatternIndex,
+    /// The `Channels` and patterns each subscriber is subscribed to, so that
+    /// a subscriber is removed without visiting every `Channel`.  Only
+    /// updated through `subscribe`, `unsubscribe`, and the removals below.
+    pub(crate) by_subscriber: HashMap<TIdentifier, HashSet<ChannelName>>,
+    /// How many subscriptions `by_subscriber` holds, so that they are not
+    /// counted for every memory estimate.
+    pub(crate) indexed_subscriptions: usize,
+    /// Bumped whenever a pattern is added, since only then can a pattern
+    /// start matching a `Channel` it did not match before.
+    pattern_generation: u64,
+    pub(crate) match_cache: LockCell<MatchCache>,
+    pub(crate) pattern_stats: LockCell<HashMap<ChannelName, PatternStats>>,
+    pub(crate) firehose: BTreeSet<TIdentifier>,
+    pub(crate) samplers: HashMap<ChannelName, HashMap<TIdentifier, LockCell<Sampler>>>,
+    pub(crate) tags: HashMap<ChannelName, HashMap<TIdentifier, BTreeSet<u32>>>,
+    /// The subscriptions removed once they delivered a `Message`.  See
+    /// `SubscribeOptions::once`.
+    pub(crate) once: HashMap<ChannelName, BTreeSet<TIdentifier>>,
+    pub(crate) sample_rng: LockCell<SampleRng>,
+    pub(crate) pattern_resolution: PatternResolution,
+    pub(crate) matcher: TMatcher,
+    pub(crate) clock: Arc<dyn Clock>,
+    /// Notified as subscriptions are added and removed, which all happens
+    /// in here.  The firehose is not a `Channel`, so it is not reported.
+    pub(crate) hooks: Hooks<TIdentifier>,
+    pub(crate) empty_channels: EmptyChannels,
+}
+
+impl<TIdentifier: UniqueIdentifier, TMatcher: ChannelMatcher> Routing<TIdentifier, TMatcher> {
+    pub(crate) fn new(matcher: TMatcher) -> Routing<TIdentifier, TMatcher> {
+        Routing {
+            channels: HashMap::new(),
+            channel_entries: Vec::new(),
+            free_entries: Vec::new(),
+            pattern_channels: HashMap::new(),
+            pattern_index: PatternIndex::new(),
+            by_subscriber: HashMap::new(),
+            indexed_subscriptions: 0,
+            pattern_generation: 0,
+            match_cache: LockCell::new(MatchCache::new()),
+            pattern_stats: LockCell::default(),
+            firehose: BTreeSet::new(),
+            samplers: HashMap::new(),
+            tags: HashMap::new(),
+            once: HashMap::new(),
+            sample_rng: LockCell::new(SampleRng::new(RandomState::new().build_hasher().finish())),
+            pattern_resolution: PatternResolution::AllMatches,
+            matcher,
+            clock: Arc::new(SystemClock),
+            hooks: Hooks::new(),
+            empty_channels: EmptyChannels::default(),
+        }
+    }
+
+    pub(crate) fn channel_handle(&mut self, name: ChannelName) -> ChannelHandle {
+        if let Some(handle) = self.channels.get(name.as_ref()) {
+            return *handle;
+        }
+
+        let handle = match self.free_entries.pop() {
+            Some(index) => {
+                let entry = &mut self.channel_entries[index];
+                entry.name = name.clone();
+                *entry.unmatched_at.get_mut() = None;
+                entry.generation = entry.generation.wrapping_add(1);
+                entry.live = true;
+
+                entry.handle(index)
+            }
+            None => {
+                self.channel_entries.push(ChannelEntry {
+                    name: name.clone(),
+                    subscribers: BTreeSet::new(),
+                    unmatched_at: LockCell::new(None),
+                    generation: 0,
+                    live: true,
+                });
+
+                ChannelHandle(self.channel_entries.len() - 1, 0)
+            }
+        };
+
+        self.channels.insert(name, handle);
+
+        handle
+    }
+
+    /// Iterates the literal `Channels` which were not removed, in order of
+    /// their slots, which is creation order unless some were removed.
+    pub(crate) fn live_entries(
+        &self,
+    ) -> impl Iterator<Item = (ChannelHandle, &ChannelEntry<TIdentifier>)> + '_ {
+        self.channel_entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.live)
+            .map(|(index, entry)| (entry.handle(index), entry))
+    }
+
+    /// Gets the `Channel` a `ChannelHandle` refers to, unless it was
+    /// removed since.
+    fn entry_mut(
+        &mut self,
+        handle: ChannelHandle,
+    ) -> Result<&mut ChannelEntry<TIdentifier>, PubSubError> {
+        self.channel_entries
+            .get_mut(handle.0)
+            .filter(|entry| entry.live && entry.generation == handle.1)
+            .ok_or(PubSubError::InvalidChannelHandleError)
+    }
+
+    /// Finds the literal `Channels` matching a pattern, in creation order.
+    ///
+    /// Results in a `PubSubError` when the pattern is malformed.
+    pub(crate) fn channels_matching(
+        &self,
+        pattern: &str,
+    ) -> Result<Vec<(ChannelName, ChannelHandle)>, PubSubError> {
+        let compiled = compile(&self.matcher, pattern)?;
+
+        Ok(self
+            .live_entries()
+            .filter(|(_, entry)| {
+                self.matcher
+                    .matches(&compiled, &self.matcher.unescape(&entry.name))
+            })
+            .map(|(handle, entry)| (entry.name.clone(), handle))
+            .collect())
+    }
+
+    /// Picks the `Channel` names which a pattern matches.
+    pub(crate) fn names_matching<'n, I>(
+        &self,
+        pattern: &str,
+        names: I,
+    ) -> Result<Vec<&'n str>, PubSubError>
+    where
+        I: IntoIterator<Item = &'n str>,
+    {
+        let compiled = compile(&self.matcher, pattern)?;
+
+        Ok(names
+            .into_iter()
+            .filter(|name| {
+                self.matcher
+                    .matches(&compiled, &self.matcher.unescape(name))
+            })
+            .collect())
+    }
+
+    /// Compiles a name into a `ChannelSelector`, once for every `Channel`
+    /// it is matched against.
+    ///
+    /// Results in a `PubSubError` when the pattern is malformed.
+    pub(crate) fn selector(
+        &self,
+        name: ChannelName,
+    ) -> Result<ChannelSelector<TMatcher::Compiled>, PubSubError> {
+        match self.is_pattern(&name) {
+            true => compile(&self.matcher, &name).map(ChannelSelector::Pattern),
+            false => Ok(ChannelSelector::Literal(name)),
+        }
+    }
+
+    /// Whether a `ChannelSelector` selects a `Channel`.
+    pub(crate) fn selects(
+        &self,
+        selector: &ChannelSelector<TMatcher::Compiled>,
+        channel: &str,
+    ) -> bool {
+        match selector {
+            ChannelSelector::Literal(name) => name.as_ref() == channel,
+            ChannelSelector::Pattern(compiled) => self
+                .matcher
+                .matches(compiled, &self.matcher.unescape(channel)),
+        }
+    }
+
+    /// Checks that subscribing to a name would not be rejected for being a
+    /// malformed pattern.
+    pub(crate) fn check_subscription(&self, channel: &str) -> Result<(), PubSubError> {
+        if !self.is_pattern(channel) || self.pattern_channels.contains_key(channel) {
+            return Ok(());
+        }
+
+        compile(&self.matcher, channel).map(|_| ())
+    }
+
+    /// Whether a name is a pattern rather than a literal `Channel`, as
+    /// decided by the `ChannelMatcher` of the `PubSub`.
+    pub(crate) fn is_pattern(&self, channel: &str) -> bool {
+        self.matcher.is_pattern(channel)
+    }
+
+    fn subscribers_for_subscription(
+        &mut self,
+        channel: ChannelName,
+    ) -> Result<&mut BTreeSet<TIdentifier>, PubSubError> {
+        if !self.is_pattern(&channel) {
+            let handle = self.channel_handle(channel);
+            return Ok(&mut self.channel_entries[handle.0].subscribers);
+        }
+
+        if !self.pattern_channels.contains_key(channel.as_ref()) {
+            let entry = PatternEntry::new(&channel, &self.matcher)?;
+            let matcher = &self.matcher;
+            self.match_cache
+                .get_mut()
+                .pattern_added(|name| matcher.matches(&entry.compiled, &matcher.unescape(name)));
+
+            self.pattern_stats.get_mut().insert(
+                channel.clone(),
+                PatternStats {
+                    matches: 0,
+                    last_matched: None,
+                    created: self.clock.now(),
+                },
+            );
+            self.pattern_generation += 1;
+            self.pattern_index
+                .insert(self.matcher.literal_prefix(&channel), channel.clone());
+            self.pattern_channels.insert(channel.clone(), entry);
+        }
+
+        Ok(&mut self
+            .pattern_channels
+            .get_mut(channel.as_ref())
+            .expect("The pattern was just created.")
+            .subscribers)
+    }
+
+    fn subscribers_mut(&mut self, channel: &str) -> Option<&mut BTreeSet<TIdentifier>> {
+        match self.is_pattern(channel) {
+            true => self
+                .pattern_channels
+                .get_mut(channel)
+                .map(|entry| &mut entry.subscribers),
+            false => {
+                let handle = self.channels.get(channel)?;
+                Some(&mut self.channel_entries[handle.0].subscribers)
+            }
+        }
+    }
+
+    fn index(&mut self, identifier: TIdentifier, channel: ChannelName) {
+        if self
+            .by_subscriber
+            .entry(identifier)
+            .or_default()
+            .insert(channel)
+        {
+            self.indexed_subscriptions += 1;
+        }
+    }
+
+    fn unindex(&mut self, identifier: &TIdentifier, channel: &str) {
+        if let Some(channels) = self.by_subscriber.get_mut(identifier) {
+            if channels.remove(channel) {
+                self.indexed_subscriptions -= 1;
+            }
+
+            if channels.is_empty() {
+                self.by_subscriber.remove(identifier);
+            }
+        }
+    }
+
+    /// Creates a pattern nobody is subscribed to yet, unless it exists.
+    ///
+    /// Results in a `PubSubError` when the pattern is malformed.
+    pub(crate) fn add_pattern(&mut self, pattern: ChannelName) -> Result<(), PubSubError> {
+        self.subscribers_for_subscription(pattern).map(|_| ())
+    }
+
+    /// Subscribes a subscriber to a `Channel` or pattern, creating it if
+    /// needed.  Returns `false`, changing nothing, when the subscriber was
+    /// already subscribed.
+    ///
+    /// Results in a `PubSubError` when the pattern is malformed.
+    pub(crate) fn subscribe<F>(
+        &mut self,
+        channel: ChannelName,
+        identifier: F,
+    ) -> Result<bool, PubSubError>
+    where
+        F: Fn() -> TIdentifier,
+    {
+        let subscribers = self.subscribers_for_subscription(channel.clone())?;
+        let inserted = subscribers.insert(identifier());
+        let first = subscribers.len() == 1;
+
+        if inserted {
+            self.hooks.subscribed(&identifier(), &channel, first);
+            self.index(identifier(), channel);
+        }
+
+        Ok(inserted)
+    }
+
+    /// Subscribes a subscriber to the `Channel` referred to by a
+    /// `ChannelHandle`, like `subscribe`.
+    pub(crate) fn subscribe_handle<F>(
+        &mut self,
+        handle: ChannelHandle,
+        identifier: F,
+    ) -> Result<bool, PubSubError>
+    where
+        F: Fn() -> TIdentifier,
+    {
+        let entry = self.entry_mut(handle)?;
+
+        let inserted = entry.subscribers.insert(identifier());
+
+        if inserted {
+            let first = entry.subscribers.len() == 1;
+            let channel = entry.name.clone();
+            self.hooks.subscribed(&identifier(), &channel, first);
+            self.index(identifier(), channel);
+        }
+
+        Ok(inserted)
+    }
+
+    /// Subscribes a subscriber like `subscribe` or `subscribe_handle`, or
+    /// puts it on the firehose.
+    pub(crate) fn subscribe_to<F>(
+        &mut self,
+        subscription: Subscription,
+        identifier: F,
+    ) -> Result<bool, PubSubError>
+    where
+        F: Fn() -> TIdentifier,
+    {
+        match subscription {
+            Subscription::Name(channel) => self.subscribe(channel.clone(), identifier),
+            Subscription::Handle(handle) => self.subscribe_handle(handle, identifier),
+            Subscription::Firehose => {
+                let inserted = self.firehose.insert(identifier());
+
+                if inserted {
+                    self.hooks.subscribed(&identifier(), FIREHOSE, false);
+                }
+
+                Ok(inserted)
+            }
+        }
+    }
+
+    /// Takes a subscriber off the firehose, returning whether it was on it.
+    pub(crate) fn unsubscribe_firehose(&mut self, identifier: &TIdentifier) -> bool {
+        let removed = self.firehose.remove(identifier);
+
+        if removed {
+            self.hooks.unsubscribed(identifier, FIREHOSE, false);
+        }
+
+        removed
+    }
+
+    /// Unsubscribes a subscriber from a `Channel` or pattern.
+    ///
+    /// Results in a `PubSubError` when the `Channel` or pattern does not
+    /// exist, or the subscriber is not subscribed to it.
+    pub(crate) fn unsubscribe(
+        &mut self,
+        channel: &str,
+        identifier: &TIdentifier,
+    ) -> Result<(), PubSubError> {
+        let subscribers =
+            self.subscribers_mut(channel)
+                .ok_or_else(|| PubSubError::ChannelDoesNotExistError {
+                    channel: channel.to_string(),
+                })?;
+
+        if !subscribers.remove(identifier) {
+            return Err(PubSubError::ClientNotSubscribedError {
+                channel: channel.to_string(),
+            });
+        }
+
+        let last = subscribers.is_empty();
+        self.hooks.unsubscribed(identifier, channel, last);
+        self.unindex(identifier, channel);
+
+        if last && self.empty_channels == EmptyChannels::Remove {
+            self.remove_channel(channel);
+        }
+
+        Ok(())
+    }
+
+    /// Removes a `Channel` or pattern along with its subscriptions,
+    /// returning its subscribers, or `None` when it does not exist.
+    ///
+    /// The slot of a literal `Channel` is kept for the next `Channel`
+    /// created, under a new generation, so that handles to the removed one
+    /// are rejected rather than reaching the new one.
+    pub(crate) fn remove_channel(&mut self, channel: &str) -> Option<BTreeSet<TIdentifier>> {
+        let subscribers = match self.is_pattern(channel) {
+            true => {
+                let (pattern, entry) = self.pattern_channels.remove_entry(channel)?;
+                self.pattern_index
+                    .remove(self.matcher.literal_prefix(&pattern), &pattern);
+                self.pattern_stats.get_mut().remove(channel);
+
+                entry.subscribers
+            }
+            false => {
+                let handle = self.channels.remove(channel)?;
+                let entry = &mut self.channel_entries[handle.0];
+                entry.live = false;
+                self.free_entries.push(handle.0);
+
+                std::mem::take(&mut entry.subscribers)
+            }
+        };
+
+        for identifier in &subscribers {
+            self.hooks.unsubscribed(identifier, channel, false);
+            self.unindex(identifier, channel);
+        }
+
+        if !subscribers.is_empty() {
+            self.hooks.emptied(channel);
+        }
+
+        self.samplers.remove(channel);
+        self.tags.remove(channel);
+        self.once.remove(channel);
+
+        Some(subscribers)
+    }
+
+    /// Checks whether anything is subscribed to a `Channel`: by name,
+    /// through a pattern, or through the firehose.
+    pub(crate) fn has_subscribers(&self, channel: &str, handle: Option<ChannelHandle>) -> bool {
+        handle.is_some_and(|handle| !self.channel_entries[handle.0].subscribers.is_empty())
+            || !self.firehose.is_empty()
+            || self
+                .match_cache
+                .lock()
+                .patterns(
+                    &self.matcher,
+                    &self.pattern_index,
+                    &self.pattern_channels,
+                    channel,
+                )
+                .iter()
+                .any(|(_, clients)| !clients.is_empty())
+    }
+
+    /// Gets the name of a `Channel`, which is only allocated if the
+    /// `Channel` was named by an owned `String`.
+    pub(crate) fn channel_name(&self, handle: ChannelHandle) -> Result<ChannelName, PubSubError> {
+        self.channel_entries
+            .get(handle.0)
+            .filter(|entry| entry.live && entry.generation == handle.1)
+            .map(|entry| entry.name.clone())
+            .ok_or(PubSubError::InvalidChannelHandleError)
+    }
+
+    pub(crate) fn remove_sampler(&mut self, channel: &str, identifier: &TIdentifier) {
+        if let Some(samplers) = self.samplers.get_mut(channel) {
+            samplers.remove(identifier);
+
+            if samplers.is_empty() {
+                self.samplers.remove(channel);
+            }
+        }
+    }
+
+    pub(crate) fn remove_tags(&mut self, channel: &str, identifier: &TIdentifier) {
+        if let Some(tags) = self.tags.get_mut(channel) {
+            tags.remove(identifier);
+
+            if tags.is_empty() {
+                self.tags.remove(channel);
+            }
+        }
+    }
+
+    pub(crate) fn remove_once(&mut self, channel: &str, identifier: &TIdentifier) {
+        if let Some(once) = self.once.get_mut(channel) {
+            once.remove(identifier);
+
+            if once.is_empty() {
+                self.once.remove(channel);
+            }
+        }
+    }
+
+    /// Counts the `Channels` and patterns a `Client` is subscribed to.
+    pub(crate) fn subscription_count(&self, identifier: &TIdentifier) -> usize {
+        self.by_subscriber.get(identifier).map_or(0, HashSet::len)
+    }
+
+    /// Whether a subscriber is subscribed to a `Channel` or pattern.
+    pub(crate) fn is_subscribed(&self, identifier: &TIdentifier, channel: &str) -> bool {
+        self.by_subscriber
+            .get(identifier)
+            .is_some_and(|channels| channels.contains(channel))
+    }
+
+    /// Gets the `Channels` and patterns a subscriber is subscribed to:
+    /// literal `Channels` in creation order, then patterns in name order.
+    pub(crate) fn subscriptions(&self, identifier: &TIdentifier) -> Vec<ChannelName> {
+        let mut subscriptions: Vec<&ChannelName> = self
+            .by_subscriber
+            .get(identifier)
+            .into_iter()
+            .flatten()
+            .collect();
+
+        subscriptions.sort_unstable_by_key(|channel| match self.channels.get(channel.as_ref()) {
+            Some(handle) => (false, handle.0, None),
+            None => (true, 0, Some(*channel)),
+        });
+
+        subscriptions.into_iter().cloned().collect()
+    }
+
+    /// Removes a `Client` from every `Channel` and pattern, visiting only
+    /// those it is subscribed to.
+    pub(crate) fn remove_subscriber(&mut self, identifier: &TIdentifier) {
+        let channels = self.by_subscriber.remove(identifier).unwrap_or_default();
+        self.indexed_subscriptions -= channels.len();
+
+        for channel in channels {
+            let removed = self
+                .subscribers_mut(&channel)
+                .and_then(|subscribers| match subscribers.remove(identifier) {
+                    true => Some(subscribers.is_empty()),
+                    false => None,
+                });
+
+            if let Some(last) = removed {
+                self.hooks.unsubscribed(identifier, &channel, last);
+
+                if last && self.empty_channels == EmptyChannels::Remove {
+                    self.remove_channel(&channel);
+                }
+            }
+
+            self.remove_sampler(&channel, identifier);
+            self.remove_tags(&channel, identifier);
+            self.remove_once(&channel, identifier);
+        }
+
+        self.unsubscribe_firehose(identifier);
+    }
+
+    /// Removes the subscribers for which `keep` returns `false` from every
+    /// `Channel` and pattern, returning how many subscriptions were removed.
+    pub(crate) fn retain_subscribers<F>(&mut self, keep: F) -> usize
+    where
+        F: Fn(&TIdentifier) -> bool,
+    {
+        let mut removed = 0;
+
+        let hooks = &self.hooks;
+        let subscriber_sets = self
+            .channel_entries
+            .iter_mut()
+            .map(|entry| (&entry.name, &mut entry.subscribers))
+            .chain(
+                self.pattern_channels
+                    .iter_mut()
+                    .map(|(name, entry)| (name, &mut entry.subscribers)),
+            );
+
+        for (channel, subscribers) in subscriber_sets {
+            let before = subscribers.len();
+            subscribers.retain(|identifier| {
+                let kept = keep(identifier);
+
+                if !kept {
+                    hooks.unsubscribed(identifier, channel, false);
+                }
+
+                kept
+            });
+            removed += before - subscribers.len();
+
+            if before > 0 && subscribers.is_empty() {
+                hooks.emptied(channel);
+            }
+        }
+
+        let before = self.firehose.len();
+        self.firehose.retain(|identifier| {
+            let kept = keep(identifier);
+
+            if !kept {
+                hooks.unsubscribed(identifier, FIREHOSE, false);
+            }
+
+            kept
+        });
+        removed += before - self.firehose.len();
+
+        self.samplers.retain(|_, samplers| {
+            samplers.retain(|identifier, _| keep(identifier));
+            !samplers.is_empty()
+        });
+
+        self.tags.retain(|_, tags| {
+            tags.retain(|identifier, _| keep(identifier));
+            !tags.is_empty()
+        });
+
+        self.once.retain(|_, once| {
+            once.retain(|identifier| keep(identifier));
+            !once.is_empty()
+        });
+
+        let indexed_subscriptions = &mut self.indexed_subscriptions;
+        self.by_subscriber.retain(|identifier, channels| {
+            let kept = keep(identifier);
+
+            if !kept {
+                *indexed_subscriptions -= channels.len();
+            }
+
+            kept
+        });
+
+        removed
+    }
+
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.channels.shrink_to_fit();
+        self.channel_entries.shrink_to_fit();
+        self.free_entries.shrink_to_fit();
+        self.pattern_channels.shrink_to_fit();
+        self.by_subscriber.shrink_to_fit();
+
+        for channels in self.by_subscriber.values_mut() {
+            channels.shrink_to_fit();
+        }
+
+        self.pattern_stats.get_mut().shrink_to_fit();
+        self.samplers.shrink_to_fit();
+        self.tags.shrink_to_fit();
+        self.once.shrink_to_fit();
+    }
+
+    /// Removes the patterns nobody is subscribed to, along with their
+    /// compiled matchers, returning how many were removed.
+    ///
+    /// Literal `Channels` are kept even when empty, so that their
+    /// `ChannelHandles` stay valid.
+    pub(crate) fn prune_empty_patterns(&mut self) -> usize {
+        let before = self.pattern_channels.len();
+
+        let matcher = &self.matcher;
+        let index = &mut self.pattern_index;
+        self.pattern_channels.retain(|pattern, entry| {
+            if entry.subscribers.is_empty() {
+                index.remove(matcher.literal_prefix(pattern), pattern);
+            }
+
+            !entry.subscribers.is_empty()
+        });
+
+        let pattern_channels = &self.pattern_channels;
+        self.pattern_stats
+            .get_mut()
+            .retain(|pattern, _| pattern_channels.contains_key(pattern));
+
+        before - self.pattern_channels.len()
+    }
+
+    /// Lists every subscriber a `Message` published to `channel` would reach,
+    /// once each, in delivery order, without applying sampling.
+    pub(crate) fn matching_subscribers(&self, channel: &str) -> Vec<&TIdentifier> {
+        let subscribers = self
+            .channels
+            .get(channel)
+            .map(|handle| &self.channel_entries[handle.0].subscribers);
+        let patterns = matching_patterns(
+            &self.matcher,
+            &self.pattern_index,
+            &self.pattern_channels,
+            channel,
+        );
+
+        let mut seen = HashSet::new();
+
+        reaching(subscribers, patterns, &self.firehose)
+            .map(|(_, identifier)| identifier)
+            .filter(|identifier| seen.insert(*identifier))
+            .collect()
+    }
+
+    /// Resolves the identifiers a `Message` published to `channel` should be
+    /// delivered to, each exactly once, along with the subscription each
+    /// delivery is attributed to.
+    ///
+    /// Literal subscribers come first, followed by pattern subscribers from
+    /// the most to the least specific pattern, and finally firehose
+    /// subscribers.  Recipients rejected by the filter `admits` of their
+    /// subscription, or by sampling, are left out, and reported to `drops`.
+    /// A recipient is attributed to the first of its subscriptions which
+    /// admits the `Message`, and carries the tags of all of its matching
+    /// subscriptions.
+    pub(crate) fn resolve<F>(
+        &self,
+        channel: &str,
+        handle: Option<ChannelHandle>,
+        drops: &Drops<TIdentifier>,
+        mut admits: F,
+    ) -> Vec<(&TIdentifier, Via<'_>, Vec<u32>)>
+    where
+        F: FnMut(&str, &TIdentifier) -> bool,
+    {
+        let sole_subscriber = match handle {
+            Some(handle) if self.has_sole_subscriber(channel, handle) => {
+                self.channel_entries[handle.0].subscribers.iter().next()
+            }
+            _ => None,
+        };
+
+        if let Some(identifier) = sole_subscriber {
+            if !admits(channel, identifier) {
+                drops.record(channel, DropReason::FilteredOut, Some(identifier));
+                return Vec::new();
+            }
+
+            let tags = self
+                .tags
+                .get(channel)
+                .and_then(|tags| tags.get(identifier))
+                .map_or_else(Vec::new, |tags| tags.iter().copied().collect());
+
+            return vec![(identifier, Via::Channel, tags)];
+        }
+
+        let patterns = self.match_cache.lock().patterns(
+            &self.matcher,
+            &self.pattern_index,
+            &self.pattern_channels,
+            channel,
+        );
+
+        if !patterns.is_empty() {
+            let now = self.clock.now();
+            let mut pattern_stats = self.pattern_stats.lock();
+
+            for (pattern, _) in patterns.iter() {
+                if let Some(stats) = pattern_stats.get_mut(*pattern) {
+                    stats.matches += 1;
+                    stats.last_matched = Some(now);
+                }
+            }
+        }
+
+        let channel_entries = &self.channel_entries;
+        let subbed_clients = handle.map(|handle| &channel_entries[handle.0].subscribers);
+        let subscriptions = reaching(subbed_clients, patterns, &self.firehose);
+
+        // Subscriptions arrive most specific first, so under
+        // `PatternResolution::MostSpecific` the first one seen for a
+        // recipient is the only one that applies.
+        let mut recipients: Vec<(&TIdentifier, Option<Via>, Vec<u32>, DropReason)> = Vec::new();
+        let mut positions: HashMap<&TIdentifier, usize> = HashMap::new();
+
+        for (via, identifier) in subscriptions {
+            let subscription = match via {
+                Via::Channel => Some(channel),
+                Via::Pattern(pattern) => Some(pattern),
+                Via::Firehose | Via::Direct | Via::Replay => None,
+            };
+
+            let tags = subscription
+                .and_then(|subscription| self.tags.get(subscription))
+                .and_then(|tags| tags.get(identifier));
+
+            let (position, is_first) = match positions.get(identifier) {
+                Some(position) => (*position, false),
+                None => {
+                    positions.insert(identifier, recipients.len());
+                    recipients.push((identifier, None, Vec::new(), DropReason::SampledOut));
+                    (recipients.len() - 1, true)
+                }
+            };
+
+            // Tags are carried from every matching subscription, whichever
+            // of them the delivery is attributed to.
+            recipients[position].2.extend(tags.into_iter().flatten());
+
+            if !is_first && self.pattern_resolution == PatternResolution::MostSpecific {
+                continue;
+            }
+
+            // Filtering comes first, so that samplers only count the
+            // `Messages` their subscription lets through.
+            if let Some(subscription) = subscription {
+                if !admits(subscription, identifier) {
+                    recipients[position].3 = DropReason::FilteredOut;
+                    continue;
+                }
+            }
+
+            let sampler = subscription
+                .and_then(|subscription| self.samplers.get(subscription))
+                .and_then(|samplers| samplers.get(identifier));
+
+            let admitted = match sampler {
+                Some(sampler) => sampler.lock().admits(&mut self.sample_rng.lock()),
+                None => true,
+            };
+
+            if !admitted {
+                recipients[position].3 = DropReason::SampledOut;
+            }
+
+            let attribution = &mut recipients[position].1;
+
+            if admitted && attribution.is_none() {
+                *attribution = Some(via);
+            }
+        }
+
+        recipients
+            .into_iter()
+            .filter_map(|(identifier, attribution, mut tags, rejection)| {
+                if attribution.is_none() {
+                    drops.record(channel, rejection, Some(identifier));
+                }
+
+                tags.sort_unstable();
+                tags.dedup();
+
+                attribution.map(|via| (identifier, via, tags))
+            })
+            .collect()
+    }
+
+    /// Whether a literal `Channel` has a single subscriber and nothing else
+    /// can reach it: no pattern matches the `Channel`, nobody is on the
+    /// firehose, and nothing on the `Channel` is sampled.
+    ///
+    /// Whether patterns match is cached per `Channel` until a pattern is
+    /// added, so that publishing to it skips matching altogether.
+    fn has_sole_subscriber(&self, channel: &str, handle: ChannelHandle) -> bool {
+        let entry = match self.channel_entries.get(handle.0) {
+            Some(entry) if entry.subscribers.len() == 1 => entry,
+            _ => return false,
+        };
+
+        if !self.firehose.is_empty() || self.samplers.contains_key(channel) {
+            return false;
+        }
+
+        let mut unmatched_at = entry.unmatched_at.lock();
+        let unmatched = self.pattern_channels.is_empty()
+            || *unmatched_at == Some(self.pattern_generation)
+            || self
+                .match_cache
+                .lock()
+                .patterns(
+                    &self.matcher,
+                    &self.pattern_index,
+                    &self.pattern_channels,
+                    channel,
+                )
+                .is_empty();
+
+        if unmatched {
+            *unmatched_at = Some(self.pattern_generation);
+        }
+
+        unmatched
+    }
+
+    /// Caches that no pattern matches a `Channel`, if none does, as
+    /// `has_sole_subscriber` would on the first publish.
+    pub(crate) fn prime_unmatched(&mut self, handle: ChannelHandle) {
+        let entry = &mut self.channel_entries[handle.0];
+
+        if matching_patterns(
+            &self.matcher,
+            &self.pattern_index,
+            &self.pattern_channels,
+            &entry.name,
+        )
+        .is_empty()
+        {
+            *entry.unmatched_at.get_mut() = Some(self.pattern_generation);
+        }
+    }
+
+    pub(crate) fn winning_subscription(
+        &self,
+        identifier: &TIdentifier,
+        channel: &str,
+    ) -> Option<&str> {
+        if let Some(handle) = self.channels.get(channel) {
+            let entry = &self.channel_entries[handle.0];
+
+            if entry.subscribers.contains(identifier) {
+                return Some(&entry.name);
+            }
+        }
+
+        matching_patterns(
+            &self.matcher,
+            &self.pattern_index,
+            &self.pattern_channels,
+            channel,
+        )
+        .into_iter()
+        .find(|(_, clients)| clients.contains(identifier))
+        .map(|(pattern, _)| pattern)
+    }
+}
+
+/// Gets the subscription a `Message` published to `channel` reached a
+/// subscriber through, if it is one of the `once` subscriptions to be
+/// removed now that it delivered.
+///
+/// Takes the map rather than the `Routing`, which the recipients being
+/// delivered to borrow.
+pub(crate) fn spent_once<TIdentifier: Ord>(
+    once: &HashMap<ChannelName, BTreeSet<TIdentifier>>,
+    channel: &str,
+    via: Via,
+    identifier: &TIdentifier,
+) -> Option<String> {
+    if once.is_empty() {
+        return None;
+    }
+
+    let subscription = match via {
+        Via::Channel => channel,
+        Via::Pattern(pattern) => pattern,
+        Via::Firehose | Via::Direct | Via::Replay => return None,
+    };
+
+    once.get(subscription)
+        .filter(|once| once.contains(identifier))
+        .map(|_| subscription.to_string())
+}
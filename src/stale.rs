@@ -0,0 +1,79 @@
+use crate::drops::{DropReason, Drops};
+use std::sync::Arc;
+
+pub(crate) type StaleCallback<TIdentifier> =
+    Arc<dyn Fn(StaleSubscriber<TIdentifier>) + Send + Sync>;
+
+/// How a publish treats subscribers which are not `Clients` of the `PubSub`
+///
+/// A subscriber is stale when it is subscribed to a `Channel` but was never
+/// added, or was removed without going through `remove_client`.  Identifiers
+/// waiting for their `Client` after `detach_client` or
+/// `restore_subscriptions` are not stale.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StaleIdPolicy {
+    /// Stale subscribers are silently skipped.
+    Skip,
+    /// Stale subscribers are skipped, reported to the stale callback, and
+    /// counted in the `DeliveryReport` of `flush`.  The default.
+    #[default]
+    SkipAndCollect,
+    /// Stale subscribers are reported like `SkipAndCollect`, then removed
+    /// from every `Channel` and pattern, so later publishes do not pay for
+    /// them again.
+    ///
+    /// Note that this also removes subscriptions made before their `Client`
+    /// was added.
+    Purge,
+}
+
+/// Whether `Clients` can be subscribed before they are added to the
+/// `PubSub`
+///
+/// See `PubSub::set_unknown_subscriber_policy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownSubscriberPolicy {
+    /// Any identifier can be subscribed, so that a `Client` can subscribe
+    /// before being added.  Until it is added, it is a stale subscriber.
+    Allow,
+    /// Subscribing results in `PubSubError::ClientDoesNotExistError` unless
+    /// the `Client` was added, or is waiting for its `Client` after
+    /// `detach_client` or `restore_subscriptions`.  The default.
+    #[default]
+    Reject,
+}
+
+/// A stale subscriber skipped by a publish
+///
+/// See `StaleIdPolicy`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StaleSubscriber<'c, TIdentifier> {
+    /// The `Channel` the `Message` was published to.
+    pub channel: &'c str,
+    pub identifier: &'c TIdentifier,
+}
+
+/// Records a stale subscriber as dropped, then reports it according to
+/// `policy`, returning whether it should be counted.
+pub(crate) fn report<TIdentifier>(
+    policy: StaleIdPolicy,
+    callback: &Option<StaleCallback<TIdentifier>>,
+    drops: &Drops<TIdentifier>,
+    channel: &str,
+    identifier: &TIdentifier,
+) -> bool {
+    drops.record(channel, DropReason::StaleSubscriber, Some(identifier));
+
+    if policy == StaleIdPolicy::Skip {
+        return false;
+    }
+
+    if let Some(callback) = callback {
+        callback(StaleSubscriber {
+            channel,
+            identifier,
+        });
+    }
+
+    true
+}
@@ -0,0 +1,1853 @@
+//! Sharing a `PubSub` between threads
+
+use crate::delivery::{Outcome, Recipients, Sender};
+use crate::{
+    stale, BulkSubError, ChannelHandle, ChannelMatcher, ChannelName, ChannelNamePolicy,
+    ChannelOwnershipPolicy, ChannelRewrite, Client, ClientGeneration, Clock, ControlEvent,
+    DeliveryMode, DeliveryReport, DropReason, DroppedMessage, DuplicateSubscriptions,
+    EmptyChannels, EvictionPolicy, GcReport, ImportRecord, ImportReport, InvariantViolation,
+    LimitedFanOut, Message, Metrics, OwnedView, PatternPublishBehavior, PatternResolution,
+    PatternSubscriptions, PatternSyntax, PubSub, PubSubError, PubSubStats, PublishReceipt,
+    ResumeOutcome, Sampling, ShedEvent, ShedOrder, StaleIdPolicy, StaleSubscriber,
+    SubscribeOptions, SubscriptionId, SubscriptionSnapshot, Trace, UniqueIdentifier,
+    UnknownSubscriberPolicy, Via,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+
+/// A `PubSub` behind a lock, which clones share
+///
+/// Every method takes `&self`, so clones can be handed to the threads
+/// accepting connections, publishing, and so on.  Changes to the `Clients`
+/// and subscriptions hold the write lock for their whole duration, so every
+/// thread sees them as they were before or after another thread's change,
+/// never halfway through.
+///
+/// `pub_message`, `pub_message_from`, `pub_message_except` and
+/// `pub_by_handle` only take the read lock, so threads publish at the same
+/// time, and each `Client` is locked while it is sent to (see `Locked`).
+/// They take the write lock instead when the publish does more than send to
+/// the subscribers right away: in `DeliveryMode::Deferred`, on micro-batched
+/// `Channels` or `Channels` keeping history, while republishing, recording,
+/// or `once` subscriptions are set up, while `Clients` are detached, and
+/// when publishing to a pattern, unless under
+/// `PatternPublishBehavior::Literal`.
+/// `Clients` evicted by a publish under the read lock, and stale subscribers
+/// purged by it, are removed right after it under the write lock, so other
+/// publishes may still send to them in between.  The rest of the publish
+/// family always takes the write lock.
+///
+/// `read` takes the read lock, for the methods of `PubSub` returning
+/// borrowed data, such as `view`, and `write` gives access to the rest of
+/// them, such as `apply`.  Both see the `Clients` wrapped in `Locked`.
+///
+/// A panic while the write lock is held, such as in a callback, poisons it,
+/// after which every call panics as well.  A panic in `Client::send` under
+/// the read lock does not.
+///
+/// # Examples
+///
+/// Publishing from four threads while a fifth subscribes and unsubscribes:
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::sync::SharedPubSub;
+/// use std::thread;
+///
+/// let listener: RecordingClient<u32, &str> = RecordingClient::new(0);
+/// let churner: RecordingClient<u32, &str> = RecordingClient::new(1);
+///
+/// let pubsub = SharedPubSub::new();
+/// pubsub.add_client(listener.clone()).unwrap();
+/// pubsub.add_client(churner.clone()).unwrap();
+/// pubsub.sub_client(listener.clone(), "news").unwrap();
+///
+/// let publishers: Vec<_> = (0..4)
+///     .map(|_| {
+///         let pubsub = pubsub.clone();
+///
+///         thread::spawn(move || {
+///             for _ in 0..250 {
+///                 pubsub.pub_message("news", "update").unwrap();
+///             }
+///         })
+///     })
+///     .collect();
+///
+/// let subscriber = {
+///     let pubsub = pubsub.clone();
+///
+///     thread::spawn(move || {
+///         for _ in 0..250 {
+///             pubsub.sub_client(churner.clone(), "news").unwrap();
+///             pubsub.unsub_client(churner.clone(), "news").unwrap();
+///         }
+///     })
+/// };
+///
+/// for publisher in publishers {
+///     publisher.join().unwrap();
+/// }
+/// subscriber.join().unwrap();
+///
+/// assert_eq!(listener.contents().len(), 1_000);
+/// assert!(pubsub.check_invariants().is_ok());
+/// assert_eq!(pubsub.subscribers_snapshot("news"), vec![0]);
+/// ```
+///
+/// Publishers holding the read lock do not wait for each other.  Here each
+/// `Client` blocks until both publishes reach it, which only works because
+/// they are sent at the same time:
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::sync::SharedPubSub;
+/// use std::convert::Infallible;
+/// use std::sync::{Arc, Barrier};
+/// use std::thread;
+///
+/// struct Rendezvous {
+///     id: u32,
+///     barrier: Arc<Barrier>,
+/// }
+///
+/// impl Client<u32, u32> for Rendezvous {
+///     type Error = Infallible;
+///
+///     fn get_id(&self) -> u32 {
+///         self.id
+///     }
+///
+///     fn send(&mut self, _: &Message<u32>) -> Result<(), Infallible> {
+///         self.barrier.wait();
+///         Ok(())
+///     }
+/// }
+///
+/// let barrier = Arc::new(Barrier::new(2));
+/// let pubsub = SharedPubSub::new();
+///
+/// for (id, channel) in [(1, "left"), (2, "right")] {
+///     pubsub.add_client(Rendezvous { id, barrier: barrier.clone() }).unwrap();
+///     pubsub.sub_id(&id, channel).unwrap();
+/// }
+///
+/// let left = {
+///     let pubsub = pubsub.clone();
+///     thread::spawn(move || pubsub.pub_message("left", 1u32).unwrap().delivered)
+/// };
+/// let right = pubsub.pub_message("right", 2u32).unwrap().delivered;
+///
+/// assert_eq!((left.join().unwrap(), right), (1, 1));
+/// assert_eq!(pubsub.stats().published, 2);
+/// ```
+pub struct SharedPubSub<
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMessage,
+    TMatcher: ChannelMatcher = PatternSyntax,
+> {
+    inner: Arc<RwLock<LockedPubSub<TClient, TIdentifier, TMessage, TMatcher>>>,
+}
+
+/// The `PubSub` a `SharedPubSub` shares
+type LockedPubSub<TClient, TIdentifier, TMessage, TMatcher> =
+    PubSub<Locked<TClient>, TIdentifier, TMessage, TMatcher>;
+
+/// What `SharedPubSub::split` splits a `SharedPubSub` into
+type Halves<TClient, TIdentifier, TMessage, TMatcher> = (
+    Publisher<TClient, TIdentifier, TMessage, TMatcher>,
+    Admin<TClient, TIdentifier, TMessage, TMatcher>,
+);
+
+/// A `Client` of a `SharedPubSub`, locked while it is sent to
+///
+/// Publishes under the read lock send to different `Clients` at the same
+/// time, but never to the same one.  A panic in `Client::send` leaves the
+/// `Client` usable, as the panic left it.
+pub struct Locked<TClient>(Mutex<TClient>);
+
+impl<TClient> Locked<TClient> {
+    /// Wraps a `Client` for a `SharedPubSub`.
+    pub fn new(client: TClient) -> Locked<TClient> {
+        Locked(Mutex::new(client))
+    }
+
+    /// Unwraps the `Client`.
+    pub fn into_inner(self) -> TClient {
+        self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the `Client`, such as to inspect it.
+    pub fn lock(&self) -> MutexGuard<'_, TClient> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn get_mut(&mut self) -> &mut TClient {
+        self.0.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<TClient, TIdentifier, TMessage> Client<TIdentifier, TMessage> for Locked<TClient>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+{
+    type Error = TClient::Error;
+
+    fn get_id(&self) -> TIdentifier {
+        self.lock().get_id()
+    }
+
+    fn send(&mut self, message: &Message<TMessage>) -> Result<(), Self::Error> {
+        self.get_mut().send(message)
+    }
+
+    fn send_on(&mut self, channel: &str, message: &Message<TMessage>) -> Result<(), Self::Error> {
+        self.get_mut().send_on(channel, message)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.get_mut().flush()
+    }
+
+    fn send_batch(&mut self, messages: &[Message<TMessage>]) -> Result<(), Self::Error> {
+        self.get_mut().send_batch(messages)
+    }
+}
+
+impl<TClient, TIdentifier: UniqueIdentifier> Recipients<TIdentifier>
+    for &HashMap<TIdentifier, Locked<TClient>>
+{
+    type Client = TClient;
+
+    fn with_client<R, F>(&mut self, identifier: &TIdentifier, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut TClient) -> R,
+    {
+        self.get(identifier).map(|client| f(&mut client.lock()))
+    }
+}
+
+/// What became of a publish tried under the read lock
+enum SharedPublish<TIdentifier, TError, TMessage> {
+    /// The `Message` was published.  Holds whether `upkeep` is due.
+    Published(
+        Result<PublishReceipt<TIdentifier, TError>, PubSubError>,
+        bool,
+    ),
+    /// Publishing the `Message` takes the write lock.
+    NeedsWrite(TMessage),
+}
+
+impl<TClient, TIdentifier, TMessage, TMatcher> PubSub<TClient, TIdentifier, TMessage, TMatcher>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMatcher: ChannelMatcher,
+{
+    fn into_locked(self) -> PubSub<Locked<TClient>, TIdentifier, TMessage, TMatcher> {
+        PubSub {
+            clients: self
+                .clients
+                .into_iter()
+                .map(|(identifier, client)| (identifier, Locked::new(client)))
+                .collect(),
+            routing: self.routing,
+            channel_names: self.channel_names,
+            delivery_mode: self.delivery_mode,
+            pending: self.pending,
+            publish_metrics: self.publish_metrics,
+            next_sequence: self.next_sequence,
+            control_encoder: self.control_encoder,
+            retention: self.retention,
+            pattern_publish: self.pattern_publish,
+            pattern_subscriptions: self.pattern_subscriptions,
+            memory_budget: self.memory_budget,
+            stored_bytes: self.stored_bytes,
+            shed_callback: self.shed_callback,
+            shed_order: self.shed_order,
+            sizer: self.sizer,
+            gc_interval: self.gc_interval,
+            mutations_since_gc: self.mutations_since_gc,
+            recording: self.recording,
+            owners: self.owners,
+            ownership: self.ownership,
+            micro_batching: self.micro_batching,
+            detached: self.detached,
+            duplicate_subscriptions: self.duplicate_subscriptions,
+            subscription_counts: self.subscription_counts,
+            subscription_ids: self.subscription_ids,
+            next_subscription_id: self.next_subscription_id,
+            generations: self.generations,
+            republish_rules: self.republish_rules,
+            republishing: self.republishing,
+            stale_policy: self.stale_policy,
+            unknown_subscribers: self.unknown_subscribers,
+            stale_callback: self.stale_callback,
+            limited_fan_out: self.limited_fan_out,
+            rotations: self.rotations,
+            drops: self.drops,
+            failures: self.failures,
+            eviction_callback: self.eviction_callback,
+            filters: self.filters,
+        }
+    }
+}
+
+impl<TClient, TIdentifier, TMessage, TMatcher>
+    PubSub<Locked<TClient>, TIdentifier, TMessage, TMatcher>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMessage: Clone,
+    TMatcher: ChannelMatcher,
+{
+    /// Whether publishing to a `Channel` only sends to its subscribers right
+    /// away, which is all a publish under the read lock does.
+    fn publishes_shared(&self, channel: &str) -> bool {
+        self.delivery_mode == DeliveryMode::Immediate
+            && self.micro_batch_limits(channel).is_none()
+            && self.retention.capacity_for(channel) == 0
+            && self.republish_rules.is_empty()
+            && self.recording.is_none()
+            && self.routing.once.is_empty()
+            && self.detached.identifiers.is_empty()
+            && (self.pattern_publish == PatternPublishBehavior::Literal
+                || !self.routing.is_pattern(channel))
+    }
+
+    /// Publishes a `Message` to a validated `Channel` like `publish` does,
+    /// through `&self`, unless `publishes_shared` says otherwise.
+    fn publish_shared(
+        &self,
+        channel: &str,
+        handle: Option<ChannelHandle>,
+        msg: TMessage,
+        excluded: Option<TIdentifier>,
+    ) -> SharedPublish<TIdentifier, TClient::Error, TMessage> {
+        if !self.publishes_shared(channel) {
+            return SharedPublish::NeedsWrite(msg);
+        }
+
+        if let Err(error) = self.check_owner(channel, excluded.as_ref()) {
+            return SharedPublish::Published(Err(error), false);
+        }
+
+        let started = self.routing.clock.now();
+        let channel_existed = self.routing.has_subscribers(channel, handle);
+
+        self.publish_metrics.lock().record_publish(channel);
+
+        let mut message = Message {
+            contents: msg,
+            source: channel,
+            sequence: self.take_sequence(),
+            via: Via::Channel,
+            tags: &[],
+        };
+
+        let filters = &self.filters;
+        let recipients =
+            self.routing
+                .resolve(channel, handle, &self.drops, |subscription, identifier| {
+                    filters.admits(subscription, identifier, &message.contents)
+                });
+
+        if recipients
+            .iter()
+            .all(|(identifier, _, _)| Some(*identifier) == excluded.as_ref())
+        {
+            self.drops.record(channel, DropReason::NoRecipients, None);
+        }
+
+        let mut sender = Sender {
+            clients: &self.clients,
+            failures: &self.failures,
+            drops: &self.drops,
+            once: &self.routing.once,
+            flush: true,
+        };
+        let mut delivered = 0;
+        let mut failures = Vec::new();
+        let mut stale = 0;
+
+        for (identifier, via, tags) in &recipients {
+            let identifier = *identifier;
+
+            if Some(identifier) == excluded.as_ref() {
+                continue;
+            }
+
+            message.via = *via;
+            message.tags = tags;
+
+            match sender.send_one(identifier, &message) {
+                Outcome::Sent(_) => delivered += 1,
+                Outcome::Failed(identifier, error) => failures.push((identifier, error)),
+                Outcome::Absent => {
+                    if stale::report(
+                        self.stale_policy,
+                        &self.stale_callback,
+                        &self.drops,
+                        channel,
+                        identifier,
+                    ) {
+                        stale += 1;
+                    }
+                }
+            }
+        }
+
+        let mut publish_metrics = self.publish_metrics.lock();
+        publish_metrics.record_fan_out(channel, delivered);
+        publish_metrics.record_publish_latency(self.routing.clock.now().duration_since(started));
+
+        let upkeep = (stale > 0 && self.stale_policy == StaleIdPolicy::Purge)
+            || self.failures.lock().has_evicted();
+
+        SharedPublish::Published(
+            Ok(PublishReceipt {
+                delivered,
+                channel_existed,
+                failures,
+            }),
+            upkeep,
+        )
+    }
+
+    /// Purges the stale subscribers and evicts the `Clients` a publish
+    /// under the read lock left behind.
+    fn upkeep(&mut self) {
+        if self.stale_policy == StaleIdPolicy::Purge {
+            self.purge_dangling();
+        }
+
+        self.evict_failing();
+    }
+}
+
+impl<TClient: Client<TIdentifier, TMessage>, TIdentifier: UniqueIdentifier, TMessage: Clone>
+    SharedPubSub<TClient, TIdentifier, TMessage>
+{
+    /// Creates a new `SharedPubSub`, sharing a new `PubSub`.
+    pub fn new() -> SharedPubSub<TClient, TIdentifier, TMessage> {
+        SharedPubSub::from(PubSub::new())
+    }
+}
+
+impl<TClient, TIdentifier, TMessage, TMatcher>
+    SharedPubSub<TClient, TIdentifier, TMessage, TMatcher>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMessage: Clone,
+    TMatcher: ChannelMatcher,
+{
+    /// Calls `f` with the `PubSub` while holding the read lock.
+    pub fn read<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&PubSub<Locked<TClient>, TIdentifier, TMessage, TMatcher>) -> R,
+    {
+        f(&self.inner.read().unwrap())
+    }
+
+    /// Calls `f` with the `PubSub` while holding the write lock.
+    pub fn write<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut PubSub<Locked<TClient>, TIdentifier, TMessage, TMatcher>) -> R,
+    {
+        f(&mut self.inner.write().unwrap())
+    }
+
+    /// Publishes under the read lock, falling back to `exclusive` under the
+    /// write lock.
+    fn publish<T, P, E>(
+        &self,
+        shared: P,
+        exclusive: E,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError>
+    where
+        P: FnOnce(
+            &PubSub<Locked<TClient>, TIdentifier, TMessage, TMatcher>,
+        ) -> SharedPublish<TIdentifier, TClient::Error, T>,
+        E: FnOnce(
+            &mut PubSub<Locked<TClient>, TIdentifier, TMessage, TMatcher>,
+            T,
+        ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError>,
+    {
+        match self.read(shared) {
+            SharedPublish::Published(receipt, upkeep) => {
+                if upkeep {
+                    self.write(PubSub::upkeep);
+                }
+
+                receipt
+            }
+            SharedPublish::NeedsWrite(msg) => self.write(|pubsub| exclusive(pubsub, msg)),
+        }
+    }
+
+    /// Publishes to a `Channel` under the read lock, falling back to
+    /// `pub_message_excluding` under the write lock.
+    fn publish_excluding(
+        &self,
+        channel: &str,
+        msg: TMessage,
+        excluded: Option<&dyn Fn() -> TIdentifier>,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.publish(
+            |pubsub| {
+                let channel = match pubsub.channel_names.validate(channel) {
+                    Ok(channel) => channel,
+                    Err(error) => return SharedPublish::Published(Err(error), false),
+                };
+                let handle = pubsub.routing.channels.get(channel).copied();
+
+                pubsub.publish_shared(channel, handle, msg, excluded.map(|excluded| excluded()))
+            },
+            |pubsub, msg| pubsub.pub_message_excluding(channel, msg, excluded),
+        )
+    }
+
+    /// See `PubSub::add_client`.
+    pub fn add_client(&self, client: TClient) -> Result<ClientGeneration, PubSubError> {
+        self.write(|pubsub| pubsub.add_client(Locked::new(client)))
+    }
+
+    /// See `PubSub::replace_client`.
+    pub fn replace_client(&self, client: TClient) -> ClientGeneration {
+        self.write(|pubsub| pubsub.replace_client(Locked::new(client)))
+    }
+
+    /// See `PubSub::remove_client`.
+    pub fn remove_client(&self, client: TClient) {
+        self.write(|pubsub| pubsub.remove_client(Locked::new(client)))
+    }
+
+    /// See `PubSub::remove_client_gen`.
+    pub fn remove_client_gen(
+        &self,
+        identifier: &TIdentifier,
+        generation: ClientGeneration,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.remove_client_gen(identifier, generation))
+    }
+
+    /// See `PubSub::remove_client_by_id`.
+    pub fn remove_client_by_id(&self, identifier: &TIdentifier) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.remove_client_by_id(identifier))
+    }
+
+    /// See `PubSub::client_generation`.
+    pub fn client_generation(&self, identifier: &TIdentifier) -> Option<ClientGeneration> {
+        self.read(|pubsub| pubsub.client_generation(identifier))
+    }
+
+    /// See `PubSub::client_count`.
+    pub fn client_count(&self) -> usize {
+        self.read(|pubsub| pubsub.client_count())
+    }
+
+    /// See `PubSub::channel_count`.
+    pub fn channel_count(&self) -> usize {
+        self.read(|pubsub| pubsub.channel_count())
+    }
+
+    /// See `PubSub::pattern_count`.
+    pub fn pattern_count(&self) -> usize {
+        self.read(|pubsub| pubsub.pattern_count())
+    }
+
+    /// See `PubSub::is_empty`.
+    pub fn is_empty(&self) -> bool {
+        self.read(|pubsub| pubsub.is_empty())
+    }
+
+    /// See `PubSub::clear`.
+    pub fn clear(&self) -> Vec<TClient> {
+        let clients = self.write(|pubsub| pubsub.clear());
+
+        clients.into_iter().map(Locked::into_inner).collect()
+    }
+
+    /// See `PubSub::shrink_to_fit`.
+    pub fn shrink_to_fit(&self) {
+        self.write(|pubsub| pubsub.shrink_to_fit())
+    }
+
+    /// See `PubSub::detach_client`.
+    pub fn detach_client(
+        &self,
+        identifier: &TIdentifier,
+        grace: Duration,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.detach_client(identifier, grace))
+    }
+
+    /// See `PubSub::reattach_client`.
+    pub fn reattach_client(&self, client: TClient) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.reattach_client(Locked::new(client)))
+    }
+
+    /// See `PubSub::sweep_detached`.
+    pub fn sweep_detached(&self) -> usize {
+        self.write(|pubsub| pubsub.sweep_detached())
+    }
+
+    /// See `PubSub::channel_handle`.
+    pub fn channel_handle<TChannel: Into<ChannelName>>(
+        &self,
+        name: TChannel,
+    ) -> Result<ChannelHandle, PubSubError> {
+        self.write(|pubsub| pubsub.channel_handle(name))
+    }
+
+    /// See `PubSub::warm_up`.
+    pub fn warm_up<C, P>(&self, channels: C, patterns: P) -> Result<(), PubSubError>
+    where
+        C: IntoIterator,
+        C::Item: Into<ChannelName>,
+        P: IntoIterator,
+        P::Item: Into<ChannelName>,
+    {
+        self.write(|pubsub| pubsub.warm_up(channels, patterns))
+    }
+
+    /// See `PubSub::warm_from_snapshot`.
+    pub fn warm_from_snapshot(
+        &self,
+        snapshot: &OwnedView<TIdentifier, TMatcher>,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.warm_from_snapshot(snapshot))
+    }
+
+    /// See `PubSub::set_channel_name_policy`.
+    pub fn set_channel_name_policy(&self, policy: ChannelNamePolicy) {
+        self.write(|pubsub| pubsub.set_channel_name_policy(policy))
+    }
+
+    /// See `PubSub::sub_client`.
+    pub fn sub_client<TChannel: Into<ChannelName>>(
+        &self,
+        client: TClient,
+        channel: TChannel,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.sub_client(Locked::new(client), channel))
+    }
+
+    /// See `PubSub::sub_client_exact`.
+    pub fn sub_client_exact<TChannel: Into<ChannelName>>(
+        &self,
+        client: TClient,
+        channel: TChannel,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.sub_client_exact(Locked::new(client), channel))
+    }
+
+    /// See `PubSub::sub_system`.
+    pub fn sub_system(&self, client: TClient, name: &str) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.sub_system(Locked::new(client), name))
+    }
+
+    /// See `PubSub::psub_client`.
+    pub fn psub_client<TChannel: Into<ChannelName>>(
+        &self,
+        client: TClient,
+        pattern: TChannel,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.psub_client(Locked::new(client), pattern))
+    }
+
+    /// See `PubSub::sub_id`.
+    pub fn sub_id<TChannel: Into<ChannelName>>(
+        &self,
+        identifier: &TIdentifier,
+        channel: TChannel,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.sub_id(identifier, channel))
+    }
+
+    /// See `PubSub::ensure_subscribed`.
+    pub fn ensure_subscribed<TChannel: Into<ChannelName>>(
+        &self,
+        identifier: &TIdentifier,
+        channel: TChannel,
+    ) -> Result<bool, PubSubError> {
+        self.write(|pubsub| pubsub.ensure_subscribed(identifier, channel))
+    }
+
+    /// See `PubSub::sub_client_with`.
+    pub fn sub_client_with<TChannel: Into<ChannelName>>(
+        &self,
+        identifier: &TIdentifier,
+        channel: TChannel,
+        options: SubscribeOptions,
+    ) -> Result<bool, PubSubError> {
+        self.write(|pubsub| pubsub.sub_client_with(identifier, channel, options))
+    }
+
+    /// See `PubSub::sub_client_many`.
+    pub fn sub_client_many(
+        &self,
+        identifier: &TIdentifier,
+        channels: &[&str],
+    ) -> Result<(), BulkSubError> {
+        self.write(|pubsub| pubsub.sub_client_many(identifier, channels))
+    }
+
+    /// See `PubSub::sub_by_handle`.
+    pub fn sub_by_handle(&self, client: TClient, handle: ChannelHandle) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.sub_by_handle(Locked::new(client), handle))
+    }
+
+    /// See `PubSub::sub_all_existing`.
+    pub fn sub_all_existing(&self, client: TClient) -> usize {
+        self.write(|pubsub| pubsub.sub_all_existing(Locked::new(client)))
+    }
+
+    /// See `PubSub::sub_firehose`.
+    pub fn sub_firehose(&self, client: TClient) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.sub_firehose(Locked::new(client)))
+    }
+
+    /// See `PubSub::sub_sampled`.
+    pub fn sub_sampled<TChannel: Into<ChannelName>>(
+        &self,
+        client: TClient,
+        channel: TChannel,
+        sampling: Sampling,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.sub_sampled(Locked::new(client), channel, sampling))
+    }
+
+    /// See `PubSub::sub_tagged`.
+    pub fn sub_tagged<TChannel: Into<ChannelName>>(
+        &self,
+        client: TClient,
+        channel: TChannel,
+        tag: u32,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.sub_tagged(Locked::new(client), channel, tag))
+    }
+
+    /// See `PubSub::sub_client_filtered`.
+    pub fn sub_client_filtered<TChannel, F>(
+        &self,
+        identifier: &TIdentifier,
+        channel: TChannel,
+        filter: F,
+    ) -> Result<(), PubSubError>
+    where
+        TChannel: Into<ChannelName>,
+        F: Fn(&TMessage) -> bool + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.sub_client_filtered(identifier, channel, filter))
+    }
+
+    /// See `PubSub::sub_with_id`.
+    pub fn sub_with_id<TChannel: Into<ChannelName>>(
+        &self,
+        client: TClient,
+        channel: TChannel,
+    ) -> Result<SubscriptionId, PubSubError> {
+        self.write(|pubsub| pubsub.sub_with_id(Locked::new(client), channel))
+    }
+
+    /// See `PubSub::unsub_client`.
+    pub fn unsub_client(&self, client: TClient, channel: &str) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.unsub_client(Locked::new(client), channel))
+    }
+
+    /// See `PubSub::unsub_system`.
+    pub fn unsub_system(&self, client: TClient, name: &str) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.unsub_system(Locked::new(client), name))
+    }
+
+    /// See `PubSub::unsub_client_gen`.
+    pub fn unsub_client_gen(
+        &self,
+        client: TClient,
+        channel: &str,
+        generation: ClientGeneration,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.unsub_client_gen(Locked::new(client), channel, generation))
+    }
+
+    /// See `PubSub::punsub_client`.
+    pub fn punsub_client(&self, client: TClient, pattern: &str) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.punsub_client(Locked::new(client), pattern))
+    }
+
+    /// See `PubSub::unsub_id`.
+    pub fn unsub_id(&self, identifier: &TIdentifier, channel: &str) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.unsub_id(identifier, channel))
+    }
+
+    /// See `PubSub::unsub_all`.
+    pub fn unsub_all(&self, identifier: &TIdentifier) -> Result<usize, PubSubError> {
+        self.write(|pubsub| pubsub.unsub_all(identifier))
+    }
+
+    /// See `PubSub::ensure_unsubscribed`.
+    pub fn ensure_unsubscribed(
+        &self,
+        identifier: &TIdentifier,
+        channel: &str,
+    ) -> Result<bool, PubSubError> {
+        self.write(|pubsub| pubsub.ensure_unsubscribed(identifier, channel))
+    }
+
+    /// See `PubSub::unsub_client_many`.
+    pub fn unsub_client_many(
+        &self,
+        identifier: &TIdentifier,
+        channels: &[&str],
+    ) -> Result<(), BulkSubError> {
+        self.write(|pubsub| pubsub.unsub_client_many(identifier, channels))
+    }
+
+    /// See `PubSub::unsub_firehose`.
+    pub fn unsub_firehose(&self, client: TClient) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.unsub_firehose(Locked::new(client)))
+    }
+
+    /// See `PubSub::unsub_tagged`.
+    pub fn unsub_tagged(
+        &self,
+        client: TClient,
+        channel: &str,
+        tag: u32,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.unsub_tagged(Locked::new(client), channel, tag))
+    }
+
+    /// See `PubSub::unsub_by_id`.
+    pub fn unsub_by_id(&self, id: SubscriptionId) -> Result<(), PubSubError>
+    where
+        TIdentifier: Clone,
+    {
+        self.write(|pubsub| pubsub.unsub_by_id(id))
+    }
+
+    /// See `PubSub::subscription_count_for`.
+    pub fn subscription_count_for(&self, identifier: &TIdentifier, channel: &str) -> usize {
+        self.read(|pubsub| pubsub.subscription_count_for(identifier, channel))
+    }
+
+    /// See `PubSub::set_duplicate_subscriptions`.
+    pub fn set_duplicate_subscriptions(&self, duplicates: DuplicateSubscriptions) {
+        self.write(|pubsub| pubsub.set_duplicate_subscriptions(duplicates))
+    }
+
+    /// See `PubSub::set_sampling_seed`.
+    pub fn set_sampling_seed(&self, seed: u64) {
+        self.write(|pubsub| pubsub.set_sampling_seed(seed))
+    }
+
+    /// See `PubSub::sampled_out`.
+    pub fn sampled_out(&self) -> u64 {
+        self.read(|pubsub| pubsub.sampled_out())
+    }
+
+    /// See `PubSub::metrics`.
+    pub fn metrics(&self) -> Metrics {
+        self.read(|pubsub| pubsub.metrics())
+    }
+
+    /// See `PubSub::stats`.
+    pub fn stats(&self) -> PubSubStats {
+        self.read(|pubsub| pubsub.stats())
+    }
+
+    /// See `PubSub::reset_stats`.
+    pub fn reset_stats(&self) {
+        self.write(|pubsub| pubsub.reset_stats())
+    }
+
+    /// See `PubSub::set_channel_stats`.
+    pub fn set_channel_stats(&self, enabled: bool) {
+        self.write(|pubsub| pubsub.set_channel_stats(enabled))
+    }
+
+    /// See `PubSub::set_clock`.
+    pub fn set_clock<C: Clock + 'static>(&self, clock: C) {
+        self.write(|pubsub| pubsub.set_clock(clock))
+    }
+
+    /// See `PubSub::set_control_encoder`.
+    pub fn set_control_encoder<F>(&self, encoder: F)
+    where
+        F: Fn(ControlEvent) -> TMessage + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_control_encoder(encoder))
+    }
+
+    /// See `PubSub::clear_control_encoder`.
+    pub fn clear_control_encoder(&self) {
+        self.write(|pubsub| pubsub.clear_control_encoder())
+    }
+
+    /// See `PubSub::set_memory_budget`.
+    pub fn set_memory_budget(&self, budget: Option<usize>) {
+        self.write(|pubsub| pubsub.set_memory_budget(budget))
+    }
+
+    /// See `PubSub::set_shed_callback`.
+    pub fn set_shed_callback<F>(&self, callback: F)
+    where
+        F: Fn(ShedEvent) + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_shed_callback(callback))
+    }
+
+    /// See `PubSub::set_shed_order`.
+    pub fn set_shed_order(&self, order: ShedOrder) {
+        self.write(|pubsub| pubsub.set_shed_order(order))
+    }
+
+    /// See `PubSub::set_heap_size`.
+    pub fn set_heap_size<F>(&self, heap_size: F)
+    where
+        F: Fn(&TMessage) -> usize + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_heap_size(heap_size))
+    }
+
+    /// See `PubSub::set_stale_id_policy`.
+    pub fn set_stale_id_policy(&self, policy: StaleIdPolicy) {
+        self.write(|pubsub| pubsub.set_stale_id_policy(policy))
+    }
+
+    /// See `PubSub::set_unknown_subscriber_policy`.
+    pub fn set_unknown_subscriber_policy(&self, policy: UnknownSubscriberPolicy) {
+        self.write(|pubsub| pubsub.set_unknown_subscriber_policy(policy))
+    }
+
+    /// See `PubSub::set_stale_callback`.
+    pub fn set_stale_callback<F>(&self, callback: F)
+    where
+        F: Fn(StaleSubscriber<TIdentifier>) + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_stale_callback(callback))
+    }
+
+    /// See `PubSub::set_eviction_policy`.
+    pub fn set_eviction_policy(&self, policy: EvictionPolicy) {
+        self.write(|pubsub| pubsub.set_eviction_policy(policy))
+    }
+
+    /// See `PubSub::set_eviction_callback`.
+    pub fn set_eviction_callback<F>(&self, callback: F)
+    where
+        F: Fn(&TIdentifier) + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_eviction_callback(callback))
+    }
+
+    /// See `PubSub::set_subscribe_callback`.
+    pub fn set_subscribe_callback<F>(&self, callback: F)
+    where
+        F: Fn(&TIdentifier, &str) + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_subscribe_callback(callback))
+    }
+
+    /// See `PubSub::set_unsubscribe_callback`.
+    pub fn set_unsubscribe_callback<F>(&self, callback: F)
+    where
+        F: Fn(&TIdentifier, &str) + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_unsubscribe_callback(callback))
+    }
+
+    /// See `PubSub::set_first_subscriber_callback`.
+    pub fn set_first_subscriber_callback<F>(&self, callback: F)
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_first_subscriber_callback(callback))
+    }
+
+    /// See `PubSub::set_last_unsubscriber_callback`.
+    pub fn set_last_unsubscriber_callback<F>(&self, callback: F)
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_last_unsubscriber_callback(callback))
+    }
+
+    /// See `PubSub::set_client_added_callback`.
+    pub fn set_client_added_callback<F>(&self, callback: F)
+    where
+        F: Fn(&TIdentifier) + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_client_added_callback(callback))
+    }
+
+    /// See `PubSub::set_client_removed_callback`.
+    pub fn set_client_removed_callback<F>(&self, callback: F)
+    where
+        F: Fn(&TIdentifier) + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_client_removed_callback(callback))
+    }
+
+    /// See `PubSub::set_drop_callback`.
+    pub fn set_drop_callback<F>(&self, callback: F)
+    where
+        F: Fn(DroppedMessage<TIdentifier>) + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.set_drop_callback(callback))
+    }
+
+    /// See `PubSub::purge_dangling`.
+    pub fn purge_dangling(&self) -> usize {
+        self.write(|pubsub| pubsub.purge_dangling())
+    }
+
+    /// See `PubSub::memory_estimate`.
+    pub fn memory_estimate(&self) -> usize {
+        self.read(|pubsub| pubsub.memory_estimate())
+    }
+
+    /// See `PubSub::set_history_capacity`.
+    pub fn set_history_capacity(&self, capacity: usize) {
+        self.write(|pubsub| pubsub.set_history_capacity(capacity))
+    }
+
+    /// See `PubSub::set_history`.
+    pub fn set_history<TPrefix: Into<String>>(&self, prefix: TPrefix, capacity: usize) {
+        self.write(|pubsub| pubsub.set_history(prefix, capacity))
+    }
+
+    /// See `PubSub::replay`.
+    pub fn replay(
+        &self,
+        channel: &str,
+        identifier: &TIdentifier,
+        count: usize,
+    ) -> Result<usize, PubSubError> {
+        self.write(|pubsub| pubsub.replay(channel, identifier, count))
+    }
+
+    /// See `PubSub::resume`.
+    pub fn resume<TChannel: Into<ChannelName>>(
+        &self,
+        client: TClient,
+        channel: TChannel,
+        last_seen: u64,
+    ) -> Result<ResumeOutcome, PubSubError> {
+        self.write(|pubsub| pubsub.resume(Locked::new(client), channel, last_seen))
+    }
+
+    /// See `PubSub::pub_message`.
+    pub fn pub_message<TInputMessage: Into<TMessage>>(
+        &self,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.publish_excluding(channel, msg.into(), None)
+    }
+
+    /// See `PubSub::pub_message_from`.
+    pub fn pub_message_from<TInputMessage: Into<TMessage>>(
+        &self,
+        sender: TClient,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.publish_excluding(channel, msg.into(), Some(&|| sender.get_id()))
+    }
+
+    /// See `PubSub::pub_message_except`.
+    pub fn pub_message_except<TInputMessage: Into<TMessage>>(
+        &self,
+        channel: &str,
+        msg: TInputMessage,
+        exclude: &TIdentifier,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError>
+    where
+        TIdentifier: Clone,
+    {
+        self.publish_excluding(channel, msg.into(), Some(&|| exclude.clone()))
+    }
+
+    /// See `PubSub::pub_from`.
+    pub fn pub_from<TInputMessage: Into<TMessage>>(
+        &self,
+        sender: &TIdentifier,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError>
+    where
+        TIdentifier: Clone,
+    {
+        if !self.read(|pubsub| pubsub.view().contains_client(sender)) {
+            return Err(PubSubError::ClientDoesNotExistError);
+        }
+
+        self.pub_message_except(channel, msg, sender)
+    }
+
+    /// See `PubSub::pub_system`.
+    pub fn pub_system<TInputMessage: Into<TMessage>>(
+        &self,
+        name: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.write(|pubsub| pubsub.pub_system(name, msg))
+    }
+
+    /// See `PubSub::pub_by_handle`.
+    pub fn pub_by_handle<TInputMessage: Into<TMessage>>(
+        &self,
+        handle: ChannelHandle,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.publish(
+            |pubsub| match pubsub.routing.channel_name(handle) {
+                Ok(channel) => pubsub.publish_shared(&channel, Some(handle), msg.into(), None),
+                Err(error) => SharedPublish::Published(Err(error), false),
+            },
+            |pubsub, msg| pubsub.pub_by_handle(handle, msg),
+        )
+    }
+
+    /// See `PubSub::pub_message_retained`.
+    pub fn pub_message_retained<TInputMessage: Into<TMessage>>(
+        &self,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.write(|pubsub| pubsub.pub_message_retained(channel, msg))
+    }
+
+    /// See `PubSub::clear_retained`.
+    pub fn clear_retained(&self, channel: &str) -> Option<TMessage> {
+        self.write(|pubsub| pubsub.clear_retained(channel))
+    }
+
+    /// See `PubSub::send_to`.
+    pub fn send_to<TInputMessage: Into<TMessage>>(
+        &self,
+        identifier: &TIdentifier,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.write(|pubsub| pubsub.send_to(identifier, msg))
+    }
+
+    /// See `PubSub::broadcast`.
+    pub fn broadcast<TInputMessage: Into<TMessage>>(
+        &self,
+        msg: TInputMessage,
+    ) -> PublishReceipt<TIdentifier, TClient::Error> {
+        self.write(|pubsub| pubsub.broadcast(msg))
+    }
+
+    /// See `PubSub::pub_limited`.
+    pub fn pub_limited<TInputMessage: Into<TMessage>>(
+        &self,
+        channel: &str,
+        msg: TInputMessage,
+        max_recipients: usize,
+    ) -> Result<Vec<TIdentifier>, PubSubError> {
+        self.write(|pubsub| pubsub.pub_limited(channel, msg, max_recipients))
+    }
+
+    /// See `PubSub::pub_message_multi`.
+    pub fn pub_message_multi<TInputMessage: Into<TMessage>>(
+        &self,
+        channels: &[&str],
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.write(|pubsub| pubsub.pub_message_multi(channels, msg))
+    }
+
+    /// See `PubSub::set_limited_fan_out`.
+    pub fn set_limited_fan_out(&self, fan_out: LimitedFanOut) {
+        self.write(|pubsub| pubsub.set_limited_fan_out(fan_out))
+    }
+
+    /// See `PubSub::set_pattern_publish_behavior`.
+    pub fn set_pattern_publish_behavior(&self, behavior: PatternPublishBehavior) {
+        self.write(|pubsub| pubsub.set_pattern_publish_behavior(behavior))
+    }
+
+    /// See `PubSub::set_pattern_subscriptions`.
+    pub fn set_pattern_subscriptions(&self, subscriptions: PatternSubscriptions) {
+        self.write(|pubsub| pubsub.set_pattern_subscriptions(subscriptions))
+    }
+
+    /// See `PubSub::claim_channel`.
+    pub fn claim_channel(&self, channel: &str, owner: &TIdentifier) -> Result<(), PubSubError>
+    where
+        TIdentifier: Clone,
+    {
+        self.write(|pubsub| pubsub.claim_channel(channel, owner))
+    }
+
+    /// See `PubSub::release_channel`.
+    pub fn release_channel(&self, channel: &str) -> Option<TIdentifier> {
+        self.write(|pubsub| pubsub.release_channel(channel))
+    }
+
+    /// See `PubSub::set_channel_ownership_policy`.
+    pub fn set_channel_ownership_policy(&self, policy: ChannelOwnershipPolicy) {
+        self.write(|pubsub| pubsub.set_channel_ownership_policy(policy))
+    }
+
+    /// See `PubSub::republish`.
+    pub fn republish<TChannel: Into<ChannelName>>(
+        &self,
+        from_pattern: TChannel,
+        rewrite: ChannelRewrite,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.republish(from_pattern, rewrite))
+    }
+
+    /// See `PubSub::clear_republish`.
+    pub fn clear_republish(&self, from_pattern: &str) {
+        self.write(|pubsub| pubsub.clear_republish(from_pattern))
+    }
+
+    /// See `PubSub::set_micro_batch`.
+    pub fn set_micro_batch<TChannel: Into<ChannelName>>(
+        &self,
+        pattern: TChannel,
+        max_delay: Duration,
+        max_batch: usize,
+    ) -> Result<(), PubSubError> {
+        self.write(|pubsub| pubsub.set_micro_batch(pattern, max_delay, max_batch))
+    }
+
+    /// See `PubSub::clear_micro_batch`.
+    pub fn clear_micro_batch(&self, pattern: &str) -> DeliveryReport {
+        self.write(|pubsub| pubsub.clear_micro_batch(pattern))
+    }
+
+    /// See `PubSub::flush_micro_batches`.
+    pub fn flush_micro_batches(&self, now: Instant) -> DeliveryReport {
+        self.write(|pubsub| pubsub.flush_micro_batches(now))
+    }
+
+    /// See `PubSub::set_delivery_mode`.
+    pub fn set_delivery_mode(&self, mode: DeliveryMode) {
+        self.write(|pubsub| pubsub.set_delivery_mode(mode))
+    }
+
+    /// See `PubSub::pending_count`.
+    pub fn pending_count(&self) -> usize {
+        self.read(|pubsub| pubsub.pending_count())
+    }
+
+    /// See `PubSub::flush`.
+    pub fn flush(&self) -> DeliveryReport {
+        self.write(|pubsub| pubsub.flush())
+    }
+
+    /// See `PubSub::discard_pending`.
+    pub fn discard_pending(&self) -> usize {
+        self.write(|pubsub| pubsub.discard_pending())
+    }
+
+    /// See `PubSub::flush_all`.
+    pub fn flush_all(&self) {
+        self.write(|pubsub| pubsub.flush_all())
+    }
+
+    /// See `PubSub::set_pattern_cache_capacity`.
+    pub fn set_pattern_cache_capacity(&self, capacity: usize) {
+        self.write(|pubsub| pubsub.set_pattern_cache_capacity(capacity))
+    }
+
+    /// See `PubSub::set_pattern_resolution`.
+    pub fn set_pattern_resolution(&self, resolution: PatternResolution) {
+        self.write(|pubsub| pubsub.set_pattern_resolution(resolution))
+    }
+
+    /// See `PubSub::import`.
+    pub fn import<I, F>(&self, records: I, mut client_for: F) -> ImportReport
+    where
+        I: IntoIterator<Item = ImportRecord<TIdentifier>>,
+        F: FnMut(&TIdentifier) -> TClient,
+    {
+        self.write(|pubsub| {
+            pubsub.import(records, |identifier| Locked::new(client_for(identifier)))
+        })
+    }
+
+    /// See `PubSub::restore_subscriptions`.
+    pub fn restore_subscriptions<I>(&self, records: I) -> ImportReport
+    where
+        I: IntoIterator<Item = ImportRecord<TIdentifier>>,
+        TIdentifier: Clone,
+    {
+        self.write(|pubsub| pubsub.restore_subscriptions(records))
+    }
+
+    /// See `PubSub::subscription_snapshot`.
+    pub fn subscription_snapshot(&self) -> SubscriptionSnapshot<TIdentifier>
+    where
+        TIdentifier: Clone,
+    {
+        self.read(|pubsub| pubsub.subscription_snapshot())
+    }
+
+    /// See `PubSub::apply_snapshot`.
+    pub fn apply_snapshot(&self, snapshot: SubscriptionSnapshot<TIdentifier>) -> ImportReport
+    where
+        TIdentifier: Clone,
+    {
+        self.write(|pubsub| pubsub.apply_snapshot(snapshot))
+    }
+
+    /// See `PubSub::set_detached_buffer_capacity`.
+    pub fn set_detached_buffer_capacity(&self, capacity: usize) {
+        self.write(|pubsub| pubsub.set_detached_buffer_capacity(capacity))
+    }
+
+    /// See `PubSub::expire_pending`.
+    pub fn expire_pending(&self, older_than: Duration) -> usize {
+        self.write(|pubsub| pubsub.expire_pending(older_than))
+    }
+
+    /// See `PubSub::check_invariants`.
+    pub fn check_invariants(&self) -> Result<(), Vec<InvariantViolation<TIdentifier>>>
+    where
+        TIdentifier: Clone,
+    {
+        self.read(|pubsub| pubsub.check_invariants())
+    }
+
+    /// See `PubSub::set_empty_channels`.
+    pub fn set_empty_channels(&self, policy: EmptyChannels) {
+        self.write(|pubsub| pubsub.set_empty_channels(policy))
+    }
+
+    /// See `PubSub::remove_channel`.
+    pub fn remove_channel(&self, channel: &str) -> Vec<TIdentifier>
+    where
+        TIdentifier: Clone,
+    {
+        self.write(|pubsub| pubsub.remove_channel(channel))
+    }
+
+    /// See `PubSub::gc`.
+    pub fn gc(&self) -> GcReport {
+        self.write(|pubsub| pubsub.gc())
+    }
+
+    /// See `PubSub::set_gc_interval`.
+    pub fn set_gc_interval(&self, interval: Option<usize>) {
+        self.write(|pubsub| pubsub.set_gc_interval(interval))
+    }
+
+    /// See `PubSub::start_recording`.
+    pub fn start_recording<F>(&self, hash_payload: F)
+    where
+        F: Fn(&TMessage) -> u64 + Send + Sync + 'static,
+    {
+        self.write(|pubsub| pubsub.start_recording(hash_payload))
+    }
+
+    /// See `PubSub::stop_recording`.
+    pub fn stop_recording(&self) -> Option<Trace<TIdentifier>> {
+        self.write(|pubsub| pubsub.stop_recording())
+    }
+
+    /// Splits the `SharedPubSub` into a `Publisher`, which can only publish,
+    /// and an `Admin`, which manages the `Clients` and subscriptions.
+    ///
+    /// Both share the `PubSub` with the `SharedPubSub` and its clones.  Hand
+    /// clones of the `Publisher` to the modules which only publish, so that
+    /// none of them can remove a `Client` or change a subscription by
+    /// mistake, and keep the `Admin` where they are managed.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::sync::SharedPubSub;
+    /// use std::thread;
+    ///
+    /// let client: RecordingClient<u32, u32> = RecordingClient::new(1);
+    ///
+    /// let (publisher, admin) = SharedPubSub::new().split();
+    /// admin.add_client(client.clone()).unwrap();
+    /// admin.sub_client(client.clone(), "jobs").unwrap();
+    ///
+    /// let threads: Vec<_> = (0..4u32)
+    ///     .map(|worker| {
+    ///         let publisher = publisher.clone();
+    ///
+    ///         thread::spawn(move || {
+    ///             for job in 0..100 {
+    ///                 let receipt = publisher.pub_message("jobs", worker * 100 + job).unwrap();
+    ///                 assert_eq!(receipt.delivered, 1);
+    ///             }
+    ///         })
+    ///     })
+    ///     .collect();
+    ///
+    /// for thread in threads {
+    ///     thread.join().unwrap();
+    /// }
+    ///
+    /// let mut contents = client.contents();
+    /// contents.sort_unstable();
+    /// assert_eq!(contents, (0..400).collect::<Vec<_>>());
+    ///
+    /// admin.remove_client(client);
+    /// assert_eq!(publisher.pub_message("jobs", 0u32).unwrap().delivered, 0);
+    /// ```
+    pub fn split(&self) -> Halves<TClient, TIdentifier, TMessage, TMatcher> {
+        let publisher = Publisher {
+            shared: self.clone(),
+        };
+        let admin = Admin {
+            shared: self.clone(),
+        };
+
+        (publisher, admin)
+    }
+
+    /// Copies the routing topology of the `PubSub` into an `OwnedView`, as
+    /// `PubSub::snapshot_view` does, while holding the read lock.
+    ///
+    /// Every change to the `Clients` and subscriptions holds the write lock,
+    /// so the copy reflects the topology between two of them: every
+    /// subscriber in it is one of its `Clients`, even while other threads
+    /// add, remove, and subscribe `Clients`.  Publishes under the read lock
+    /// carry on while it is copied, since they do not change the topology;
+    /// the `Clients` they evict are only removed once they take the write
+    /// lock.  The copy does not change afterward.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    /// use general_pub_sub::sync::SharedPubSub;
+    ///
+    /// let client: RecordingClient<u32, &str> = RecordingClient::new(1);
+    ///
+    /// let pubsub = SharedPubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    ///
+    /// let snapshot = pubsub.topology_snapshot();
+    /// pubsub.remove_client(client.clone());
+    ///
+    /// assert_eq!(snapshot.subscribers("news").collect::<Vec<_>>(), vec![&1]);
+    /// assert!(snapshot.contains_client(&1));
+    /// assert_eq!(pubsub.topology_snapshot().client_count(), 0);
+    /// ```
+    pub fn topology_snapshot(&self) -> OwnedView<TIdentifier, TMatcher>
+    where
+        TIdentifier: Clone,
+    {
+        self.read(|pubsub| pubsub.snapshot_view())
+    }
+
+    /// See `PubSub::subscribers_snapshot`.  Consistent in the same way as
+    /// `topology_snapshot`.
+    pub fn subscribers_snapshot(&self, channel: &str) -> Vec<TIdentifier>
+    where
+        TIdentifier: Clone,
+    {
+        self.read(|pubsub| pubsub.subscribers_snapshot(channel))
+    }
+}
+
+impl<TClient, TIdentifier, TMessage, TMatcher> Clone
+    for SharedPubSub<TClient, TIdentifier, TMessage, TMatcher>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMatcher: ChannelMatcher,
+{
+    fn clone(&self) -> Self {
+        SharedPubSub {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<TClient, TIdentifier, TMessage, TMatcher>
+    From<PubSub<TClient, TIdentifier, TMessage, TMatcher>>
+    for SharedPubSub<TClient, TIdentifier, TMessage, TMatcher>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMatcher: ChannelMatcher,
+{
+    fn from(pubsub: PubSub<TClient, TIdentifier, TMessage, TMatcher>) -> Self {
+        SharedPubSub {
+            inner: Arc::new(RwLock::new(pubsub.into_locked())),
+        }
+    }
+}
+
+impl<TClient: Client<TIdentifier, TMessage>, TIdentifier: UniqueIdentifier, TMessage: Clone> Default
+    for SharedPubSub<TClient, TIdentifier, TMessage>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The publishing half of a `SharedPubSub`
+///
+/// Created by `SharedPubSub::split`.  It publishes like the `SharedPubSub`
+/// does, under the read lock when it can, and offers nothing else, so
+/// whoever holds it cannot change the `Clients` or subscriptions.  Clones
+/// share the same `PubSub`.
+///
+/// ```compile_fail
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::sync::SharedPubSub;
+///
+/// let client: RecordingClient<u32, &str> = RecordingClient::new(1);
+/// let (publisher, _admin) = SharedPubSub::new().split();
+///
+/// publisher.remove_client(client);
+/// ```
+pub struct Publisher<
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMessage,
+    TMatcher: ChannelMatcher = PatternSyntax,
+> {
+    shared: SharedPubSub<TClient, TIdentifier, TMessage, TMatcher>,
+}
+
+impl<TClient, TIdentifier, TMessage, TMatcher> Publisher<TClient, TIdentifier, TMessage, TMatcher>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMessage: Clone,
+    TMatcher: ChannelMatcher,
+{
+    /// See `PubSub::pub_message`.
+    pub fn pub_message<TInputMessage: Into<TMessage>>(
+        &self,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.shared.pub_message(channel, msg)
+    }
+
+    /// See `PubSub::pub_message_from`.
+    pub fn pub_message_from<TInputMessage: Into<TMessage>>(
+        &self,
+        sender: TClient,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.shared.pub_message_from(sender, channel, msg)
+    }
+
+    /// See `PubSub::pub_message_except`.
+    pub fn pub_message_except<TInputMessage: Into<TMessage>>(
+        &self,
+        channel: &str,
+        msg: TInputMessage,
+        exclude: &TIdentifier,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError>
+    where
+        TIdentifier: Clone,
+    {
+        self.shared.pub_message_except(channel, msg, exclude)
+    }
+
+    /// See `PubSub::pub_from`.
+    pub fn pub_from<TInputMessage: Into<TMessage>>(
+        &self,
+        sender: &TIdentifier,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError>
+    where
+        TIdentifier: Clone,
+    {
+        self.shared.pub_from(sender, channel, msg)
+    }
+
+    /// See `PubSub::pub_system`.
+    pub fn pub_system<TInputMessage: Into<TMessage>>(
+        &self,
+        name: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.shared.pub_system(name, msg)
+    }
+
+    /// See `PubSub::pub_by_handle`.
+    pub fn pub_by_handle<TInputMessage: Into<TMessage>>(
+        &self,
+        handle: ChannelHandle,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.shared.pub_by_handle(handle, msg)
+    }
+
+    /// See `PubSub::pub_message_retained`.
+    pub fn pub_message_retained<TInputMessage: Into<TMessage>>(
+        &self,
+        channel: &str,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.shared.pub_message_retained(channel, msg)
+    }
+
+    /// See `PubSub::pub_message_multi`.
+    pub fn pub_message_multi<TInputMessage: Into<TMessage>>(
+        &self,
+        channels: &[&str],
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.shared.pub_message_multi(channels, msg)
+    }
+
+    /// See `PubSub::pub_limited`.
+    pub fn pub_limited<TInputMessage: Into<TMessage>>(
+        &self,
+        channel: &str,
+        msg: TInputMessage,
+        max_recipients: usize,
+    ) -> Result<Vec<TIdentifier>, PubSubError> {
+        self.shared.pub_limited(channel, msg, max_recipients)
+    }
+
+    /// See `PubSub::send_to`.
+    pub fn send_to<TInputMessage: Into<TMessage>>(
+        &self,
+        identifier: &TIdentifier,
+        msg: TInputMessage,
+    ) -> Result<PublishReceipt<TIdentifier, TClient::Error>, PubSubError> {
+        self.shared.send_to(identifier, msg)
+    }
+
+    /// See `PubSub::broadcast`.
+    pub fn broadcast<TInputMessage: Into<TMessage>>(
+        &self,
+        msg: TInputMessage,
+    ) -> PublishReceipt<TIdentifier, TClient::Error> {
+        self.shared.broadcast(msg)
+    }
+}
+
+impl<TClient, TIdentifier, TMessage, TMatcher> Clone
+    for Publisher<TClient, TIdentifier, TMessage, TMatcher>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMatcher: ChannelMatcher,
+{
+    fn clone(&self) -> Self {
+        Publisher {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// The managing half of a `SharedPubSub`
+///
+/// Created by `SharedPubSub::split`.  It adds, removes, and subscribes
+/// `Clients`, and copies the topology, but does not publish.  It is not
+/// `Clone`, so that the `Clients` are managed from a single place; the
+/// `SharedPubSub` it was split from still offers everything.
+pub struct Admin<
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMessage,
+    TMatcher: ChannelMatcher = PatternSyntax,
+> {
+    shared: SharedPubSub<TClient, TIdentifier, TMessage, TMatcher>,
+}
+
+impl<TClient, TIdentifier, TMessage, TMatcher> Admin<TClient, TIdentifier, TMessage, TMatcher>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMessage: Clone,
+    TMatcher: ChannelMatcher,
+{
+    /// See `PubSub::add_client`.
+    pub fn add_client(&self, client: TClient) -> Result<ClientGeneration, PubSubError> {
+        self.shared.add_client(client)
+    }
+
+    /// See `PubSub::replace_client`.
+    pub fn replace_client(&self, client: TClient) -> ClientGeneration {
+        self.shared.replace_client(client)
+    }
+
+    /// See `PubSub::remove_client`.
+    pub fn remove_client(&self, client: TClient) {
+        self.shared.remove_client(client)
+    }
+
+    /// See `PubSub::remove_client_by_id`.
+    pub fn remove_client_by_id(&self, identifier: &TIdentifier) -> Result<(), PubSubError> {
+        self.shared.remove_client_by_id(identifier)
+    }
+
+    /// See `PubSub::remove_client_gen`.
+    pub fn remove_client_gen(
+        &self,
+        identifier: &TIdentifier,
+        generation: ClientGeneration,
+    ) -> Result<(), PubSubError> {
+        self.shared.remove_client_gen(identifier, generation)
+    }
+
+    /// See `PubSub::detach_client`.
+    pub fn detach_client(
+        &self,
+        identifier: &TIdentifier,
+        grace: Duration,
+    ) -> Result<(), PubSubError> {
+        self.shared.detach_client(identifier, grace)
+    }
+
+    /// See `PubSub::reattach_client`.
+    pub fn reattach_client(&self, client: TClient) -> Result<(), PubSubError> {
+        self.shared.reattach_client(client)
+    }
+
+    /// See `PubSub::sweep_detached`.
+    pub fn sweep_detached(&self) -> usize {
+        self.shared.sweep_detached()
+    }
+
+    /// See `PubSub::sub_client`.
+    pub fn sub_client<TChannel: Into<ChannelName>>(
+        &self,
+        client: TClient,
+        channel: TChannel,
+    ) -> Result<(), PubSubError> {
+        self.shared.sub_client(client, channel)
+    }
+
+    /// See `PubSub::sub_client_exact`.
+    pub fn sub_client_exact<TChannel: Into<ChannelName>>(
+        &self,
+        client: TClient,
+        channel: TChannel,
+    ) -> Result<(), PubSubError> {
+        self.shared.sub_client_exact(client, channel)
+    }
+
+    /// See `PubSub::sub_system`.
+    pub fn sub_system(&self, client: TClient, name: &str) -> Result<(), PubSubError> {
+        self.shared.sub_system(client, name)
+    }
+
+    /// See `PubSub::psub_client`.
+    pub fn psub_client<TChannel: Into<ChannelName>>(
+        &self,
+        client: TClient,
+        pattern: TChannel,
+    ) -> Result<(), PubSubError> {
+        self.shared.psub_client(client, pattern)
+    }
+
+    /// See `PubSub::sub_id`.
+    pub fn sub_id<TChannel: Into<ChannelName>>(
+        &self,
+        identifier: &TIdentifier,
+        channel: TChannel,
+    ) -> Result<(), PubSubError> {
+        self.shared.sub_id(identifier, channel)
+    }
+
+    /// See `PubSub::ensure_subscribed`.
+    pub fn ensure_subscribed<TChannel: Into<ChannelName>>(
+        &self,
+        identifier: &TIdentifier,
+        channel: TChannel,
+    ) -> Result<bool, PubSubError> {
+        self.shared.ensure_subscribed(identifier, channel)
+    }
+
+    /// See `PubSub::sub_client_many`.
+    pub fn sub_client_many(
+        &self,
+        identifier: &TIdentifier,
+        channels: &[&str],
+    ) -> Result<(), BulkSubError> {
+        self.shared.sub_client_many(identifier, channels)
+    }
+
+    /// See `PubSub::sub_firehose`.
+    pub fn sub_firehose(&self, client: TClient) -> Result<(), PubSubError> {
+        self.shared.sub_firehose(client)
+    }
+
+    /// See `PubSub::unsub_client`.
+    pub fn unsub_client(&self, client: TClient, channel: &str) -> Result<(), PubSubError> {
+        self.shared.unsub_client(client, channel)
+    }
+
+    /// See `PubSub::unsub_system`.
+    pub fn unsub_system(&self, client: TClient, name: &str) -> Result<(), PubSubError> {
+        self.shared.unsub_system(client, name)
+    }
+
+    /// See `PubSub::punsub_client`.
+    pub fn punsub_client(&self, client: TClient, pattern: &str) -> Result<(), PubSubError> {
+        self.shared.punsub_client(client, pattern)
+    }
+
+    /// See `PubSub::unsub_id`.
+    pub fn unsub_id(&self, identifier: &TIdentifier, channel: &str) -> Result<(), PubSubError> {
+        self.shared.unsub_id(identifier, channel)
+    }
+
+    /// See `PubSub::unsub_all`.
+    pub fn unsub_all(&self, identifier: &TIdentifier) -> Result<usize, PubSubError> {
+        self.shared.unsub_all(identifier)
+    }
+
+    /// See `PubSub::ensure_unsubscribed`.
+    pub fn ensure_unsubscribed(
+        &self,
+        identifier: &TIdentifier,
+        channel: &str,
+    ) -> Result<bool, PubSubError> {
+        self.shared.ensure_unsubscribed(identifier, channel)
+    }
+
+    /// See `PubSub::unsub_client_many`.
+    pub fn unsub_client_many(
+        &self,
+        identifier: &TIdentifier,
+        channels: &[&str],
+    ) -> Result<(), BulkSubError> {
+        self.shared.unsub_client_many(identifier, channels)
+    }
+
+    /// See `PubSub::unsub_firehose`.
+    pub fn unsub_firehose(&self, client: TClient) -> Result<(), PubSubError> {
+        self.shared.unsub_firehose(client)
+    }
+
+    /// See `PubSub::client_count`.
+    pub fn client_count(&self) -> usize {
+        self.shared.client_count()
+    }
+
+    /// See `SharedPubSub::topology_snapshot`.
+    pub fn topology_snapshot(&self) -> OwnedView<TIdentifier, TMatcher>
+    where
+        TIdentifier: Clone,
+    {
+        self.shared.topology_snapshot()
+    }
+
+    /// See `PubSub::subscribers_snapshot`.
+    pub fn subscribers_snapshot(&self, channel: &str) -> Vec<TIdentifier>
+    where
+        TIdentifier: Clone,
+    {
+        self.shared.subscribers_snapshot(channel)
+    }
+}
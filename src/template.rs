@@ -0,0 +1,273 @@
+use crate::PubSubError;
+use std::marker::PhantomData;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Parameter,
+}
+
+/// A value that fills a placeholder of a `ChannelTemplate`
+///
+/// Implemented for the integer types, whose placeholders are named after
+/// them, such as `{u32}`, and for `String`, whose placeholder is `{str}`.
+pub trait TemplateParam: Sized {
+    /// The name of the placeholder, between the braces.
+    const KIND: &'static str;
+
+    /// Appends the value to a `Channel` name.
+    fn write(&self, name: &mut String);
+
+    /// Reads the value back from the part of a `Channel` name it filled.
+    fn parse(text: &str) -> Option<Self>;
+}
+
+macro_rules! integer_params {
+    ($($integer:ty),*) => {
+        $(
+            impl TemplateParam for $integer {
+                const KIND: &'static str = stringify!($integer);
+
+                fn write(&self, name: &mut String) {
+                    name.push_str(&self.to_string());
+                }
+
+                fn parse(text: &str) -> Option<Self> {
+                    text.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+integer_params!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl TemplateParam for String {
+    const KIND: &'static str = "str";
+
+    fn write(&self, name: &mut String) {
+        name.push_str(self);
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        Some(text.to_string())
+    }
+}
+
+/// The arguments of a `ChannelTemplate`, as a tuple of `TemplateParams` in
+/// the order of the placeholders
+pub trait TemplateArgs: Sized {
+    /// The placeholder names, in order.
+    fn kinds() -> Vec<&'static str>;
+
+    /// Appends the argument at `index` to a `Channel` name.
+    fn write(&self, index: usize, name: &mut String);
+
+    /// Reads the arguments back from the parts of a `Channel` name they
+    /// filled, in order.
+    fn parse(texts: &[&str]) -> Option<Self>;
+}
+
+impl TemplateArgs for () {
+    fn kinds() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    fn write(&self, _: usize, _: &mut String) {}
+
+    fn parse(_: &[&str]) -> Option<Self> {
+        Some(())
+    }
+}
+
+macro_rules! tuple_args {
+    ($($param:ident $index:tt),+) => {
+        impl<$($param: TemplateParam),+> TemplateArgs for ($($param,)+) {
+            fn kinds() -> Vec<&'static str> {
+                vec![$($param::KIND),+]
+            }
+
+            fn write(&self, index: usize, name: &mut String) {
+                match index {
+                    $($index => self.$index.write(name),)+
+                    _ => {}
+                }
+            }
+
+            fn parse(texts: &[&str]) -> Option<Self> {
+                Some(($($param::parse(texts.get($index)?)?,)+))
+            }
+        }
+    };
+}
+
+tuple_args!(A 0);
+tuple_args!(A 0, B 1);
+tuple_args!(A 0, B 1, C 2);
+tuple_args!(A 0, B 1, C 2, D 3);
+tuple_args!(A 0, B 1, C 2, D 3, E 4);
+tuple_args!(A 0, B 1, C 2, D 3, E 4, F 5);
+
+/// A `Channel` name with typed placeholders, such as
+/// `"user.{u32}.orders.{str}"`
+///
+/// The template is checked once, against the types of its arguments, when
+/// it is parsed.  `format` then builds names from arguments of exactly those
+/// types, and `matches` recovers the arguments from a name, such as from
+/// `Message::source` in `Client::send`.
+///
+/// A placeholder stands for part of one dot-separated segment, so it never
+/// matches text containing a `.`, and two placeholders may not be adjacent.
+/// `String` arguments containing a `.` or a wildcard still format, but do
+/// not round-trip through `matches`.
+///
+/// ```
+/// use general_pub_sub::ChannelTemplate;
+///
+/// let orders = ChannelTemplate::<(u32, String)>::parse("user.{u32}.orders.{str}").unwrap();
+///
+/// let channel = orders.format(&(42, "shipped".to_string()));
+/// assert_eq!(channel, "user.42.orders.shipped");
+///
+/// assert_eq!(orders.matches(&channel), Some((42, "shipped".to_string())));
+/// assert_eq!(orders.matches("user.bob.orders.shipped"), None);
+///
+/// // Malformed templates, and templates disagreeing with the argument
+/// // types, are rejected when parsed.
+/// assert!(ChannelTemplate::<(u32,)>::parse("user.{u32").is_err());
+/// assert!(ChannelTemplate::<(u32,)>::parse("user.{str}").is_err());
+/// ```
+///
+/// Arguments of the wrong types do not compile:
+///
+/// ```compile_fail
+/// use general_pub_sub::ChannelTemplate;
+///
+/// let orders = ChannelTemplate::<(u32, String)>::parse("user.{u32}.orders.{str}").unwrap();
+///
+/// orders.format(&("bob".to_string(), 42));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelTemplate<TArgs> {
+    segments: Vec<Segment>,
+    args: PhantomData<fn() -> TArgs>,
+}
+
+impl<TArgs: TemplateArgs> ChannelTemplate<TArgs> {
+    /// Parses a template, checking its placeholders against `TArgs`.
+    ///
+    /// Results in a `PubSubError` when a brace is unbalanced, a placeholder
+    /// is empty or names another type than the matching argument, two
+    /// placeholders are adjacent, the template contains a wildcard, or the
+    /// number of placeholders differs from the number of arguments.
+    pub fn parse(template: &str) -> Result<ChannelTemplate<TArgs>, PubSubError> {
+        let invalid = || PubSubError::InvalidChannelTemplateError {
+            template: template.to_string(),
+        };
+
+        let kinds = TArgs::kinds();
+        let mut segments = Vec::new();
+        let mut rest = template;
+
+        while !rest.is_empty() {
+            let (literal, placeholder) = match rest.find('{') {
+                Some(open) => {
+                    let close = rest[open..].find('}').ok_or_else(invalid)? + open;
+                    (&rest[..open], Some(&rest[open + 1..close]))
+                }
+                None => (rest, None),
+            };
+
+            if literal.contains(['}', '*', '?']) {
+                return Err(invalid());
+            }
+
+            if !literal.is_empty() {
+                segments.push(Segment::Literal(literal.to_string()));
+            }
+
+            match placeholder {
+                Some(kind) => {
+                    let index = segments
+                        .iter()
+                        .filter(|segment| **segment == Segment::Parameter)
+                        .count();
+
+                    if segments.last() == Some(&Segment::Parameter)
+                        || kinds.get(index) != Some(&kind)
+                    {
+                        return Err(invalid());
+                    }
+
+                    segments.push(Segment::Parameter);
+                    rest = &rest[literal.len() + kind.len() + 2..];
+                }
+                None => rest = "",
+            }
+        }
+
+        let parameters = segments
+            .iter()
+            .filter(|segment| **segment == Segment::Parameter)
+            .count();
+
+        if parameters != kinds.len() {
+            return Err(invalid());
+        }
+
+        Ok(ChannelTemplate {
+            segments,
+            args: PhantomData,
+        })
+    }
+
+    /// Builds the `Channel` name for a set of arguments.
+    pub fn format(&self, args: &TArgs) -> String {
+        let mut name = String::new();
+        let mut index = 0;
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => name.push_str(literal),
+                Segment::Parameter => {
+                    args.write(index, &mut name);
+                    index += 1;
+                }
+            }
+        }
+
+        name
+    }
+
+    /// Recovers the arguments from a `Channel` name, or returns `None` when
+    /// the name does not fit the template.
+    pub fn matches(&self, channel: &str) -> Option<TArgs> {
+        let mut texts = Vec::new();
+        let mut rest = channel;
+
+        for (position, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Literal(literal) => rest = rest.strip_prefix(literal.as_str())?,
+                Segment::Parameter => {
+                    let end = match self.segments.get(position + 1) {
+                        Some(Segment::Literal(next)) => rest.find(next.as_str())?,
+                        _ => rest.len(),
+                    };
+                    let text = &rest[..end];
+
+                    if text.is_empty() || text.contains('.') {
+                        return None;
+                    }
+
+                    texts.push(text);
+                    rest = &rest[end..];
+                }
+            }
+        }
+
+        match rest.is_empty() {
+            true => TArgs::parse(&texts),
+            false => None,
+        }
+    }
+}
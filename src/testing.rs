@@ -0,0 +1,37 @@
+//! Helpers for testing code built on the `PubSub`
+//!
+//! `MockClient` records every `Message` it is sent, so a test can assert
+//! what each subscriber received after the `PubSub` took the `Client`.
+//! Clones share their recording, so the test keeps one aside:
+//!
+//! ```
+//! use general_pub_sub::prelude::*;
+//! use general_pub_sub::testing::MockClient;
+//!
+//! let mock: MockClient<_, &str> = MockClient::new(1);
+//!
+//! let mut pubsub = PubSub::new();
+//! pubsub.add_client(mock.clone()).unwrap();
+//! pubsub.sub_client(mock.clone(), "news").unwrap();
+//! pubsub.sub_client(mock.clone(), "sports").unwrap();
+//!
+//! pubsub.pub_message("news", "headline").unwrap();
+//! pubsub.pub_message("sports", "goal").unwrap();
+//!
+//! let received = mock.received();
+//! assert_eq!(received.len(), 2);
+//! assert_eq!(received[0].channel, "news");
+//! assert!(received[0].sequence < received[1].sequence);
+//! assert_eq!(mock.received_on("sports")[0].contents, "goal");
+//!
+//! mock.clear();
+//! assert!(mock.received().is_empty());
+//! ```
+
+pub use crate::clients::RecordedMessage;
+
+/// A `Client` which records every `Message` it is sent, with its `Channel`
+/// and `sequence`
+///
+/// The same `Client` as `RecordingClient`, under the name tests look for.
+pub type MockClient<TIdentifier, TMessage> = crate::RecordingClient<TIdentifier, TMessage>;
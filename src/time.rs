@@ -0,0 +1,102 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A source of the current time
+///
+/// Every time-dependent feature of a `PubSub` reads the time from its
+/// `Clock`, set with `PubSub::set_clock`, so that tests and simulations can
+/// control it with a `ManualClock` instead of waiting.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The `Clock` of the operating system, used by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A `Clock` which only moves when told to
+///
+/// Clones share the same time, so a clone can be handed to a `PubSub`
+/// while the original is kept to advance it.
+///
+/// # Examples
+///
+/// ```
+/// use general_pub_sub::{Client, ManualClock, Message, PubSub};
+/// use std::convert::Infallible;
+/// use std::time::Duration;
+///
+/// struct Quiet(u32);
+///
+/// impl Client<u32, &str> for Quiet {
+///     type Error = Infallible;
+///
+///     fn get_id(&self) -> u32 {
+///         self.0
+///     }
+///
+///     fn send(&mut self, _message: &Message<&str>) -> Result<(), Infallible> {
+///         Ok(())
+///     }
+/// }
+///
+/// let clock = ManualClock::new();
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.set_clock(clock.clone());
+///
+/// pubsub.add_client(Quiet(1)).unwrap();
+/// pubsub.psub_client(Quiet(1), "orders.*").unwrap();
+///
+/// let hour = Duration::from_secs(60 * 60);
+/// assert!(pubsub.never_matched_patterns(hour).is_empty());
+///
+/// clock.advance(hour);
+/// assert_eq!(pubsub.never_matched_patterns(hour), vec!["orders.*"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    /// Creates a `ManualClock` set to the current time of the system.
+    pub fn new() -> ManualClock {
+        ManualClock::starting_at(SystemClock.now())
+    }
+
+    /// Creates a `ManualClock` set to the given time.
+    pub fn starting_at(now: Instant) -> ManualClock {
+        ManualClock {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Moves the time forward.
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+
+    /// Sets the time, which may move it backward.
+    pub fn set(&self, now: Instant) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
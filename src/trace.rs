@@ -0,0 +1,268 @@
+use crate::{Client, PatternPublishBehavior, PatternSubscriptions, PubSub, UniqueIdentifier};
+use std::collections::BTreeSet;
+use std::fmt::Debug;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub(crate) type PayloadHasher<TMessage> = Arc<dyn Fn(&TMessage) -> u64 + Send + Sync>;
+
+/// An operation recorded by `PubSub::start_recording`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceEvent<TIdentifier> {
+    /// Recorded by both `add_client` and `replace_client`.
+    AddClient(TIdentifier),
+    /// Recorded by `remove_client` and its variants, and by
+    /// `sweep_detached` for each expired identifier.
+    RemoveClient(TIdentifier),
+    /// Recorded by `detach_client`.  The grace period is not recorded;
+    /// it ends with a `RemoveClient` when swept, or an `AddClient` when
+    /// reattached.
+    Detach(TIdentifier),
+    Sub(TIdentifier, String),
+    Unsub(TIdentifier, String),
+    SubFirehose(TIdentifier),
+    UnsubFirehose(TIdentifier),
+    /// A `Message` was routed to `recipients`.
+    ///
+    /// Recorded when the recipients are resolved, so in
+    /// `RecipientCapture::AtFlush` it is recorded by `flush`.  Publishing to a
+    /// pattern with `PatternPublishBehavior::FanOut` records one event per
+    /// matching `Channel`.
+    Publish {
+        channel: String,
+        payload_hash: u64,
+        /// The sender excluded by `pub_message_from`, if any.
+        excluded: Option<TIdentifier>,
+        recipients: BTreeSet<TIdentifier>,
+    },
+}
+
+/// The operations performed on a `PubSub` while recording
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trace<TIdentifier> {
+    events: Vec<TraceEvent<TIdentifier>>,
+}
+
+impl<TIdentifier> Trace<TIdentifier> {
+    pub(crate) fn new() -> Trace<TIdentifier> {
+        Trace { events: Vec::new() }
+    }
+
+    pub(crate) fn push(&mut self, event: TraceEvent<TIdentifier>) {
+        self.events.push(event);
+    }
+
+    /// Gets the recorded operations, in the order they were performed.
+    pub fn events(&self) -> &[TraceEvent<TIdentifier>] {
+        &self.events
+    }
+
+    /// Writes the recorded operations to `writer`, one per line.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()>
+    where
+        TIdentifier: Debug,
+    {
+        for event in &self.events {
+            writeln!(writer, "{:?}", event)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Recording<TIdentifier, TMessage> {
+    pub(crate) hash_payload: PayloadHasher<TMessage>,
+    pub(crate) trace: Trace<TIdentifier>,
+}
+
+/// A publish whose recipients differ between a `Trace` and its replay
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayMismatch<'t, TIdentifier> {
+    /// The index of the publish in `Trace::events`.
+    pub event: usize,
+    pub channel: &'t str,
+    pub recorded: &'t BTreeSet<TIdentifier>,
+    pub replayed: BTreeSet<TIdentifier>,
+}
+
+/// Replays a `Trace` against a new `PubSub`, returning every publish whose
+/// recipients differ from the recorded ones.
+///
+/// The replayed `PubSub` delivers immediately, and its `Messages` are the
+/// recorded payload hashes.  `client_for` is called whenever an operation
+/// needs a `Client`, including to subscribe and unsubscribe `Clients` that
+/// were already added.
+///
+/// Only the operations listed by `TraceEvent` are replayed, so a `PubSub`
+/// configured with sampled subscriptions or `PatternResolution` other than
+/// the default is expected to differ.  Any other difference means routing
+/// depended on something outside the recorded operations.
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::{replay, ReplayMismatch};
+/// use std::collections::BTreeSet;
+///
+/// let alice: RecordingClient<_, u64> = RecordingClient::new(1u32);
+/// let bob: RecordingClient<_, u64> = RecordingClient::new(2u32);
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.start_recording(|payload: &u64| *payload);
+/// pubsub.add_client(alice.clone()).unwrap();
+/// pubsub.add_client(bob.clone()).unwrap();
+/// pubsub.sub_client(alice.clone(), "news").unwrap();
+/// pubsub.sub_client(bob.clone(), "news").unwrap();
+/// pubsub.psub_client(bob.clone(), "sports.*").unwrap();
+/// pubsub.pub_message("news", 10u64).unwrap();
+/// pubsub.pub_message("sports.golf", 20u64).unwrap();
+/// pubsub.unsub_client(alice.clone(), "news").unwrap();
+/// pubsub.pub_message("news", 30u64).unwrap();
+/// let trace = pubsub.stop_recording().unwrap();
+///
+/// assert!(replay(&trace, |identifier| RecordingClient::new(*identifier)).is_empty());
+///
+/// // Replaying the operations of bob on alice merges their subscriptions,
+/// // so every publish reaches alice alone, until she unsubscribes.
+/// let mismatches = replay(&trace, |_| RecordingClient::new(1u32));
+/// assert_eq!(
+///     mismatches,
+///     vec![
+///         ReplayMismatch {
+///             event: 5,
+///             channel: "news",
+///             recorded: &BTreeSet::from([1, 2]),
+///             replayed: BTreeSet::from([1]),
+///         },
+///         ReplayMismatch {
+///             event: 6,
+///             channel: "sports.golf",
+///             recorded: &BTreeSet::from([2]),
+///             replayed: BTreeSet::from([1]),
+///         },
+///         ReplayMismatch {
+///             event: 8,
+///             channel: "news",
+///             recorded: &BTreeSet::from([2]),
+///             replayed: BTreeSet::new(),
+///         },
+///     ]
+/// );
+/// ```
+///
+/// Detached `Clients` are replayed as detached until their trace reattaches
+/// or sweeps them:
+///
+/// ```
+/// use general_pub_sub::prelude::*;
+/// use general_pub_sub::{replay, ManualClock, TraceEvent};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let alice: RecordingClient<_, u64> = RecordingClient::new(1u32);
+/// let bob: RecordingClient<_, u64> = RecordingClient::new(2u32);
+///
+/// let mut pubsub = PubSub::new();
+/// pubsub.set_clock(clock.clone());
+/// pubsub.set_detached_buffer_capacity(10);
+/// pubsub.start_recording(|payload: &u64| *payload);
+/// pubsub.add_client(alice.clone()).unwrap();
+/// pubsub.add_client(bob.clone()).unwrap();
+/// pubsub.sub_client(alice.clone(), "news").unwrap();
+/// pubsub.sub_client(bob.clone(), "news").unwrap();
+/// pubsub.detach_client(&1, Duration::from_secs(5)).unwrap();
+/// pubsub.detach_client(&2, Duration::from_secs(60)).unwrap();
+/// pubsub.pub_message("news", 10u64).unwrap();
+/// pubsub.reattach_client(alice.clone()).unwrap();
+/// pubsub.detach_client(&1, Duration::from_secs(5)).unwrap();
+/// clock.advance(Duration::from_secs(5));
+/// assert_eq!(pubsub.sweep_detached(), 1);
+/// pubsub.reattach_client(bob.clone()).unwrap();
+/// pubsub.pub_message("news", 20u64).unwrap();
+/// let trace = pubsub.stop_recording().unwrap();
+///
+/// assert_eq!(trace.events()[4], TraceEvent::Detach(1));
+/// assert_eq!(trace.events()[9], TraceEvent::RemoveClient(1));
+/// assert_eq!(alice.contents(), vec![10]);
+/// assert_eq!(bob.contents(), vec![10, 20]);
+/// assert!(replay(&trace, |identifier| RecordingClient::new(*identifier)).is_empty());
+/// ```
+pub fn replay<'t, TClient, TIdentifier, F>(
+    trace: &'t Trace<TIdentifier>,
+    mut client_for: F,
+) -> Vec<ReplayMismatch<'t, TIdentifier>>
+where
+    TClient: Client<TIdentifier, u64>,
+    TIdentifier: UniqueIdentifier,
+    F: FnMut(&TIdentifier) -> TClient,
+{
+    let mut pubsub: PubSub<TClient, TIdentifier, u64> = PubSub::new();
+    pubsub.set_pattern_publish_behavior(PatternPublishBehavior::Literal);
+    pubsub.set_pattern_subscriptions(PatternSubscriptions::Detected);
+    pubsub.start_recording(|payload_hash| *payload_hash);
+
+    let mut mismatches = Vec::new();
+
+    for (event, recorded) in trace.events.iter().enumerate() {
+        // Failed operations are not recorded, so replaying one only fails
+        // when the replay already diverged.
+        let _ = match recorded {
+            TraceEvent::AddClient(identifier) => {
+                pubsub.replace_client(client_for(identifier));
+                Ok(())
+            }
+            TraceEvent::RemoveClient(identifier) => {
+                pubsub.remove_client(client_for(identifier));
+                Ok(())
+            }
+            TraceEvent::Detach(identifier) => {
+                pubsub.detach_client(&client_for(identifier).get_id(), Duration::MAX)
+            }
+            TraceEvent::Sub(identifier, channel) => {
+                pubsub.sub_client(client_for(identifier), channel.clone())
+            }
+            TraceEvent::Unsub(identifier, channel) => {
+                pubsub.unsub_client(client_for(identifier), channel)
+            }
+            TraceEvent::SubFirehose(identifier) => pubsub.sub_firehose(client_for(identifier)),
+            TraceEvent::UnsubFirehose(identifier) => pubsub.unsub_firehose(client_for(identifier)),
+            TraceEvent::Publish {
+                channel,
+                payload_hash,
+                excluded,
+                recipients,
+            } => {
+                let excluded = excluded
+                    .as_ref()
+                    .map(|identifier| client_for(identifier).get_id());
+                let handle = pubsub.routing.channels.get(channel.as_str()).copied();
+                let result = pubsub
+                    .publish(channel, handle, *payload_hash, excluded)
+                    .map(drop);
+
+                let replayed = match pubsub
+                    .recording
+                    .as_mut()
+                    .and_then(|recording| recording.trace.events.pop())
+                {
+                    Some(TraceEvent::Publish { recipients, .. }) => recipients,
+                    _ => BTreeSet::new(),
+                };
+
+                if replayed != *recipients {
+                    mismatches.push(ReplayMismatch {
+                        event,
+                        channel,
+                        recorded: recipients,
+                        replayed,
+                    });
+                }
+
+                result
+            }
+        };
+    }
+
+    mismatches
+}
@@ -0,0 +1,300 @@
+use crate::{ChannelMatcher, Client, PatternSyntax, PubSub, UniqueIdentifier};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What a `Client` is subscribed to, as returned by
+/// `PubSubView::subscriptions_of`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubscriptionInfo<'p> {
+    /// The literal `Channels`, in name order.
+    pub channels: Vec<&'p str>,
+    /// The patterns, in name order.
+    pub patterns: Vec<&'p str>,
+    /// Whether the `Client` is on the firehose.
+    pub firehose: bool,
+}
+
+/// A read-only view of a `PubSub`
+///
+/// Borrowed from `PubSub::view`.  It only exposes methods which inspect the
+/// `PubSub`, so it can be handed to code (metrics, admin endpoints) that
+/// should never be able to mutate it.
+pub struct PubSubView<
+    'p,
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMessage,
+    TMatcher: ChannelMatcher = PatternSyntax,
+> {
+    pub(crate) pubsub: &'p PubSub<TClient, TIdentifier, TMessage, TMatcher>,
+}
+
+impl<'p, TClient, TIdentifier, TMessage, TMatcher>
+    PubSubView<'p, TClient, TIdentifier, TMessage, TMatcher>
+where
+    TClient: Client<TIdentifier, TMessage>,
+    TIdentifier: UniqueIdentifier,
+    TMatcher: ChannelMatcher,
+{
+    /// Gets the number of `Clients` in the `PubSub`.
+    pub fn client_count(&self) -> usize {
+        self.pubsub.clients.len()
+    }
+
+    /// Checks whether a `Client` with the given identifier is in the `PubSub`.
+    pub fn contains_client(&self, identifier: &TIdentifier) -> bool {
+        self.pubsub.clients.contains_key(identifier)
+    }
+
+    /// Iterates the literal `Channels` along with their number of
+    /// subscribers, in creation order.
+    ///
+    /// A `Channel` is listed from when it is first subscribed to or given a
+    /// `ChannelHandle`, and stays listed with no subscribers once everyone
+    /// unsubscribed, so that its `ChannelHandles` stay valid, until
+    /// `PubSub::remove_channel` removes it.  Patterns without subscribers, on
+    /// the other hand, are removed by `PubSub::gc`.  See
+    /// `PubSub::set_empty_channels` to remove both as soon as they are
+    /// empty.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.psub_client(client.clone(), "orders.*").unwrap();
+    ///
+    /// pubsub.unsub_client(client.clone(), "news").unwrap();
+    /// pubsub.punsub_client(client.clone(), "orders.*").unwrap();
+    ///
+    /// assert_eq!(pubsub.view().channels().collect::<Vec<_>>(), vec![("news", 0)]);
+    /// assert_eq!(pubsub.view().patterns().collect::<Vec<_>>(), vec![("orders.*", 0)]);
+    ///
+    /// pubsub.gc();
+    ///
+    /// assert!(pubsub.view().channel_exists("news"));
+    /// assert!(!pubsub.view().channel_exists("orders.*"));
+    /// assert_eq!(pubsub.view().patterns().count(), 0);
+    /// ```
+    pub fn channels(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.pubsub
+            .routing
+            .live_entries()
+            .map(|(_, entry)| (entry.name.as_ref(), entry.subscribers.len()))
+    }
+
+    /// Iterates the pattern `Channels` along with their number of
+    /// subscribers, in no particular order.
+    ///
+    /// Patterns stay listed with no subscribers until `PubSub::gc` removes
+    /// them.  See `channels`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// pubsub.psub_client(client.clone(), "orders.*").unwrap();
+    /// pubsub.psub_client(client.clone(), "user.?.login").unwrap();
+    ///
+    /// pubsub.pub_message("orders.created", "order").unwrap();
+    /// pubsub.pub_message("user.7.login", "login").unwrap();
+    /// pubsub.pub_message("user.42.login", "unmatched").unwrap();
+    /// assert_eq!(client.contents(), vec!["order", "login"]);
+    ///
+    /// pubsub.punsub_client(client.clone(), "orders.*").unwrap();
+    /// let view = pubsub.view();
+    /// let mut patterns = view.patterns().collect::<Vec<_>>();
+    /// patterns.sort();
+    /// assert_eq!(patterns, vec![("orders.*", 0), ("user.?.login", 1)]);
+    ///
+    /// pubsub.pub_message("orders.shipped", "unheard").unwrap();
+    /// pubsub.gc();
+    /// assert_eq!(pubsub.view().patterns().collect::<Vec<_>>(), vec![("user.?.login", 1)]);
+    ///
+    /// pubsub.psub_client(client.clone(), "orders.*").unwrap();
+    /// pubsub.pub_message("orders.shipped", "shipped").unwrap();
+    /// assert_eq!(client.contents(), vec!["order", "login", "shipped"]);
+    /// ```
+    pub fn patterns(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.pubsub
+            .routing
+            .pattern_channels
+            .iter()
+            .map(|(pattern, entry)| (pattern.as_ref(), entry.subscribers.len()))
+    }
+
+    /// Checks whether a `Channel`, or a pattern, is listed by `channels` or
+    /// `patterns`, whether or not anyone is subscribed to it.
+    pub fn channel_exists(&self, channel: &str) -> bool {
+        match self.pubsub.routing.is_pattern(channel) {
+            true => self.pubsub.routing.pattern_channels.contains_key(channel),
+            false => self.pubsub.routing.channels.contains_key(channel),
+        }
+    }
+
+    /// Iterates the identifiers of the `Clients` subscribed to a `Channel`.
+    ///
+    /// A name with unescaped wildcards is a pattern, as under
+    /// `PatternSubscriptions::Detected`, so passing a pattern yields the
+    /// subscribers of that pattern.
+    pub fn subscribers(&self, channel: &str) -> impl Iterator<Item = &TIdentifier> + '_ {
+        let subscribers = match self.pubsub.routing.is_pattern(channel) {
+            true => self
+                .pubsub
+                .routing
+                .pattern_channels
+                .get(channel)
+                .map(|entry| &entry.subscribers),
+            false => self
+                .pubsub
+                .routing
+                .channels
+                .get(channel)
+                .map(|handle| &self.pubsub.routing.channel_entries[handle.0].subscribers),
+        };
+
+        subscribers.into_iter().flatten()
+    }
+
+    /// Lists the identifiers a `Message` published to a literal `Channel`
+    /// would reach, once each and in delivery order: subscribers of the
+    /// `Channel`, then of the matching patterns, most specific first, then
+    /// of the firehose.
+    ///
+    /// Subscribers which are not `Clients` are listed too, and sampling is
+    /// not applied, since it is decided for each `Message`.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let alice: RecordingClient<_, &str> = RecordingClient::new("alice");
+    /// let bob: RecordingClient<_, &str> = RecordingClient::new("bob");
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(alice.clone()).unwrap();
+    /// pubsub.add_client(bob.clone()).unwrap();
+    /// pubsub.psub_client(alice.clone(), "orders.*").unwrap();
+    /// pubsub.psub_client(alice.clone(), "*.shipped").unwrap();
+    /// pubsub.sub_client(bob.clone(), "orders.shipped").unwrap();
+    ///
+    /// let view = pubsub.view();
+    /// assert_eq!(view.matching_subscribers("orders.shipped"), vec![&"bob", &"alice"]);
+    ///
+    /// // Only reached through patterns.
+    /// assert_eq!(view.subscribers("orders.created").count(), 0);
+    /// assert_eq!(view.matching_subscribers("orders.created"), vec![&"alice"]);
+    /// ```
+    pub fn matching_subscribers(&self, channel: &str) -> Vec<&TIdentifier> {
+        self.pubsub.routing.matching_subscribers(channel)
+    }
+
+    /// Gets the `Channels` and patterns a `Client` is subscribed to, or
+    /// `None` when there is no `Client` with the identifier.
+    ///
+    /// ```
+    /// use general_pub_sub::prelude::*;
+    ///
+    /// let client: RecordingClient<_, &str> = RecordingClient::new(1);
+    ///
+    /// let mut pubsub = PubSub::new();
+    /// pubsub.add_client(client.clone()).unwrap();
+    /// assert_eq!(pubsub.view().subscriptions_of(&1).unwrap().channels, Vec::<&str>::new());
+    ///
+    /// pubsub.sub_client(client.clone(), "sports").unwrap();
+    /// pubsub.sub_client(client.clone(), "news").unwrap();
+    /// pubsub.psub_client(client.clone(), "orders.*").unwrap();
+    /// pubsub.unsub_client(client.clone(), "sports").unwrap();
+    ///
+    /// let view = pubsub.view();
+    /// let info = view.subscriptions_of(&1).unwrap();
+    /// assert_eq!(info.channels, vec!["news"]);
+    /// assert_eq!(info.patterns, vec!["orders.*"]);
+    /// assert!(!info.firehose);
+    ///
+    /// pubsub.remove_client(client.clone());
+    /// assert_eq!(pubsub.view().subscriptions_of(&1), None);
+    /// ```
+    pub fn subscriptions_of(&self, identifier: &TIdentifier) -> Option<SubscriptionInfo<'_>> {
+        if !self.pubsub.clients.contains_key(identifier) {
+            return None;
+        }
+
+        let routing = &self.pubsub.routing;
+        let mut info = SubscriptionInfo {
+            firehose: routing.firehose.contains(identifier),
+            ..SubscriptionInfo::default()
+        };
+
+        for channel in routing.by_subscriber.get(identifier).into_iter().flatten() {
+            match routing.is_pattern(channel) {
+                true => info.patterns.push(channel.as_ref()),
+                false => info.channels.push(channel.as_ref()),
+            }
+        }
+
+        info.channels.sort_unstable();
+        info.patterns.sort_unstable();
+
+        Some(info)
+    }
+}
+
+/// An owned, immutable copy of the routing topology of a `PubSub`
+///
+/// Created by `PubSub::snapshot_view`.  It holds the identifiers of the
+/// `Clients` and the subscribers of every `Channel` and pattern, but not the
+/// `Clients` themselves, so it can be sent to another thread and queried
+/// without any locking.  It does not change when the `PubSub` is mutated
+/// afterward; take a new snapshot to observe the changes.
+#[derive(Clone, Debug)]
+pub struct OwnedView<TIdentifier: UniqueIdentifier, TMatcher: ChannelMatcher = PatternSyntax> {
+    pub(crate) clients: BTreeSet<TIdentifier>,
+    pub(crate) channels: BTreeMap<String, BTreeSet<TIdentifier>>,
+    pub(crate) patterns: BTreeMap<String, BTreeSet<TIdentifier>>,
+    pub(crate) matcher: TMatcher,
+}
+
+impl<TIdentifier: UniqueIdentifier, TMatcher: ChannelMatcher> OwnedView<TIdentifier, TMatcher> {
+    /// Gets the number of `Clients` in the snapshot.
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Checks whether a `Client` with the given identifier is in the snapshot.
+    pub fn contains_client(&self, identifier: &TIdentifier) -> bool {
+        self.clients.contains(identifier)
+    }
+
+    /// Iterates the literal `Channels` along with their number of subscribers.
+    pub fn channels(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.channels
+            .iter()
+            .map(|(channel, subscribers)| (channel.as_str(), subscribers.len()))
+    }
+
+    /// Iterates the pattern `Channels` along with their number of subscribers.
+    pub fn patterns(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.patterns
+            .iter()
+            .map(|(pattern, subscribers)| (pattern.as_str(), subscribers.len()))
+    }
+
+    /// Iterates the identifiers of the `Clients` subscribed to a `Channel`.
+    ///
+    /// A name with unescaped wildcards is a pattern, as under
+    /// `PatternSubscriptions::Detected`, so passing a pattern yields the
+    /// subscribers of that pattern.
+    pub fn subscribers(&self, channel: &str) -> impl Iterator<Item = &TIdentifier> + '_ {
+        let subscribers = match self.matcher.is_pattern(channel) {
+            true => self.patterns.get(channel),
+            false => self.channels.get(channel),
+        };
+
+        subscribers.into_iter().flatten()
+    }
+}
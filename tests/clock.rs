@@ -0,0 +1,36 @@
+//! Every time-dependent feature reads the time from the `Clock` of the
+//! `PubSub`, so that a `ManualClock` controls all of them.
+
+use std::fs;
+use std::path::Path;
+
+/// The only place the time of the system may be read.
+const SYSTEM_CLOCK: &str = "time.rs";
+
+#[test]
+fn only_the_system_clock_reads_the_time() {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut readers = Vec::new();
+
+    for entry in fs::read_dir(&src).unwrap() {
+        let path = entry.unwrap().path();
+
+        if path.file_name().unwrap() == SYSTEM_CLOCK {
+            continue;
+        }
+
+        let source = fs::read_to_string(&path).unwrap();
+
+        for (line, text) in source.lines().enumerate() {
+            if text.contains("Instant::now") || text.contains("SystemTime::now") {
+                readers.push(format!("{}:{}", path.display(), line + 1));
+            }
+        }
+    }
+
+    assert!(
+        readers.is_empty(),
+        "reading the time directly: {:?}",
+        readers
+    );
+}
@@ -0,0 +1,289 @@
+//! Random operations against a `PubSub`, checking its invariants after each
+//!
+//! Every seed performs the same operations on every run, so a failure can
+//! be reproduced from the seed and operation it reports.
+
+use general_pub_sub::prelude::*;
+use general_pub_sub::{
+    Clock, DuplicateSubscriptions, EmptyChannels, EvictionPolicy, ImportRecord, ManualClock,
+    RecipientCapture, Sampling, SubscribeOptions, SubscriptionId,
+};
+use std::time::Duration;
+
+const SEEDS: u64 = 32;
+const OPERATIONS: usize = 2_000;
+
+const CHANNELS: &[&str] = &[
+    "orders.created",
+    "orders.eu.created",
+    "users.signup",
+    "metrics.cpu",
+    "chat",
+];
+
+const PATTERNS: &[&str] = &["orders.*", "*.created", "users.?ignup", "metrics.*", "*"];
+
+/// A small xorshift generator, so that every run with the same seed performs
+/// the same operations.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 % bound as u64) as usize
+    }
+
+    fn pick<'t>(&mut self, items: &[&'t str]) -> &'t str {
+        items[self.next(items.len())]
+    }
+}
+
+/// A `Client` whose sends always fail when its identifier is a multiple of
+/// 7, so that the `EvictionPolicy` comes into play
+#[derive(Clone, Copy)]
+struct FuzzClient {
+    id: u32,
+}
+
+impl Client<u32, u64> for FuzzClient {
+    type Error = ();
+
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    fn send(&mut self, _: &Message<u64>) -> Result<(), ()> {
+        match self.id % 7 {
+            0 => Err(()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn run(seed: u64) {
+    let mut rng = Rng(seed);
+    let clock = ManualClock::new();
+    let mut pubsub = PubSub::new();
+    let mut subscription_ids: Vec<SubscriptionId> = Vec::new();
+    let mut published: u64 = 0;
+
+    pubsub.set_clock(clock.clone());
+    pubsub.set_history("orders", 4);
+    pubsub.set_detached_buffer_capacity(3);
+    pubsub.set_eviction_policy(EvictionPolicy::AfterConsecutiveFailures(3));
+    pubsub.set_sampling_seed(seed);
+    pubsub
+        .set_micro_batch("metrics.*", Duration::from_millis(50), 4)
+        .unwrap();
+
+    // Vary the policies between seeds.
+    if seed.is_multiple_of(2) {
+        pubsub.set_duplicate_subscriptions(DuplicateSubscriptions::Counted);
+    }
+    if seed.is_multiple_of(3) {
+        pubsub.set_empty_channels(EmptyChannels::Remove);
+    }
+    if seed.is_multiple_of(4) {
+        pubsub.set_gc_interval(Some(16));
+    }
+    if seed % 5 < 2 {
+        pubsub.set_control_encoder(|_| u64::MAX);
+    }
+
+    for operation in 0..OPERATIONS {
+        let client = FuzzClient {
+            id: rng.next(24) as u32,
+        };
+        let channel = rng.pick(CHANNELS);
+        let pattern = rng.pick(PATTERNS);
+
+        // Most operations are expected to fail now and then, such as
+        // unsubscribing from a `Channel` the `Client` is not subscribed to,
+        // so only their effect on the invariants is checked.
+        let name = match rng.next(28) {
+            0 | 1 => {
+                let _ = pubsub.add_client(client);
+                "add_client"
+            }
+            2 => {
+                pubsub.remove_client(client);
+                "remove_client"
+            }
+            3 => {
+                let _ = pubsub.sub_client(client, channel);
+                "sub_client"
+            }
+            4 => {
+                let options = SubscribeOptions {
+                    once: rng.next(2) == 0,
+                    idempotent: rng.next(2) == 0,
+                    ..SubscribeOptions::default()
+                };
+                let _ = pubsub.sub_client_with(&client.id, channel, options);
+                "sub_client_with"
+            }
+            5 => {
+                let _ = pubsub.psub_client(client, pattern);
+                "psub_client"
+            }
+            6 => {
+                let _ = pubsub.unsub_client(client, channel);
+                let _ = pubsub.punsub_client(client, pattern);
+                "unsub_client"
+            }
+            7 => {
+                let tag = rng.next(3) as u32;
+
+                match rng.next(2) {
+                    0 => drop(pubsub.sub_tagged(client, channel, tag)),
+                    _ => drop(pubsub.unsub_tagged(client, channel, tag)),
+                }
+                "sub_tagged"
+            }
+            8 => {
+                if let Ok(id) = pubsub.sub_with_id(client, channel) {
+                    subscription_ids.push(id);
+                }
+                if !subscription_ids.is_empty() && rng.next(2) == 0 {
+                    let index = rng.next(subscription_ids.len());
+                    let _ = pubsub.unsub_by_id(subscription_ids.swap_remove(index));
+                }
+                "sub_with_id"
+            }
+            9 => {
+                let sampling = match rng.next(2) {
+                    0 => Sampling::EveryNth(2),
+                    _ => Sampling::Rate(0.5),
+                };
+                let _ = pubsub.sub_sampled(client, channel, sampling);
+                "sub_sampled"
+            }
+            10 => {
+                let _ =
+                    pubsub.sub_client_filtered(&client.id, channel, |contents| contents % 2 == 0);
+                "sub_client_filtered"
+            }
+            11 => {
+                match rng.next(2) {
+                    0 => drop(pubsub.sub_firehose(client)),
+                    _ => drop(pubsub.unsub_firehose(client)),
+                }
+                "sub_firehose"
+            }
+            12 => {
+                match rng.next(3) {
+                    0 => drop(pubsub.detach_client(&client.id, Duration::from_secs(5))),
+                    1 => drop(pubsub.reattach_client(client)),
+                    _ => {
+                        clock.advance(Duration::from_secs(rng.next(4) as u64));
+                        pubsub.sweep_detached();
+                    }
+                }
+                "detach_client"
+            }
+            13 => {
+                match rng.next(2) {
+                    0 => drop(pubsub.restore_subscriptions(vec![ImportRecord {
+                        id: client.id,
+                        channels: vec![channel.into()],
+                    }])),
+                    _ => drop(pubsub.expire_pending(Duration::from_secs(3))),
+                }
+                "restore_subscriptions"
+            }
+            14 => {
+                pubsub.remove_channel(channel);
+                "remove_channel"
+            }
+            15 => {
+                pubsub.gc();
+                "gc"
+            }
+            16 => {
+                match rng.next(4) {
+                    0 => pubsub.set_delivery_mode(DeliveryMode::Immediate),
+                    1 => pubsub
+                        .set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtPublish)),
+                    2 => {
+                        pubsub.set_delivery_mode(DeliveryMode::Deferred(RecipientCapture::AtFlush))
+                    }
+                    _ => drop(pubsub.discard_pending()),
+                }
+                pubsub.flush();
+                "set_delivery_mode"
+            }
+            17 => {
+                let _ = pubsub.pub_message_retained(channel, published);
+                published += 1;
+                "pub_message_retained"
+            }
+            18 => {
+                match rng.next(3) {
+                    0 => drop(pubsub.pub_limited(channel, published, 2)),
+                    1 => drop(pubsub.send_to(&client.id, published)),
+                    _ => drop(pubsub.pub_message_multi(&[channel, rng.pick(CHANNELS)], published)),
+                }
+                published += 1;
+                "pub_limited"
+            }
+            19 => {
+                match rng.next(2) {
+                    0 => drop(pubsub.replay(channel, &client.id, 2)),
+                    _ => drop(pubsub.resume(client, channel, published.saturating_sub(3))),
+                }
+                "replay"
+            }
+            20 => {
+                match rng.next(2) {
+                    0 => drop(pubsub.unsub_all(&client.id)),
+                    _ => drop(pubsub.unsub_client_many(&client.id, &[channel, rng.pick(CHANNELS)])),
+                }
+                "unsub_all"
+            }
+            21 => {
+                match rng.next(4) {
+                    0 => drop(pubsub.replace_client(client)),
+                    1 => drop(pubsub.sub_client_many(&client.id, &[channel, rng.pick(CHANNELS)])),
+                    2 => drop(pubsub.claim_channel(channel, &client.id)),
+                    _ => drop(pubsub.release_channel(channel)),
+                }
+                "replace_client"
+            }
+            22 => {
+                let budget = match rng.next(3) {
+                    0 => None,
+                    _ => Some(pubsub.memory_estimate() + rng.next(256)),
+                };
+                pubsub.set_memory_budget(budget);
+                "set_memory_budget"
+            }
+            23 => {
+                clock.advance(Duration::from_millis(rng.next(100) as u64));
+                pubsub.flush_micro_batches(clock.now());
+                "flush_micro_batches"
+            }
+            _ => {
+                let _ = pubsub.pub_message(channel, published);
+                published += 1;
+                "pub_message"
+            }
+        };
+
+        if let Err(violations) = pubsub.check_invariants() {
+            panic!(
+                "Seed {}, operation {} ({}) violated the invariants: {:?}",
+                seed, operation, name, violations
+            );
+        }
+    }
+}
+
+#[test]
+fn random_operations_keep_invariants() {
+    for seed in 1..=SEEDS {
+        run(seed);
+    }
+}
@@ -0,0 +1,711 @@
+//! End-to-end scenarios, each run against a `PubSub` and a `SharedPubSub`
+//!
+//! A `Scenario` is a timeline of steps.  Each step gives every simulated
+//! thread a list of `Ops`.  The `PubSub` runs the threads of a step one
+//! after the other, while the `SharedPubSub` runs them on threads of their
+//! own at the same time.  A step starts once the previous one is over.
+//!
+//! After each step the invariants of both are checked, and at the end the
+//! `Messages` each `Client` received are compared, as a multiset, with
+//! those the `Oracle` expects from the timeline.
+//!
+//! `Ops` given to different threads of the same step run in any order, so
+//! they have to commute.  The runner checks this by asking the `Oracle`
+//! with the threads in both orders.
+//!
+//! New features should come with a scenario here.
+
+use general_pub_sub::sync::SharedPubSub;
+use general_pub_sub::{Client, EvictionPolicy, ManualClock, Message, PubSub};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Add(u32),
+    Remove(u32),
+    Sub(u32, &'static str),
+    Psub(u32, &'static str),
+    Unsub(u32, &'static str),
+    Punsub(u32, &'static str),
+    /// Publishes `count` `Messages` to a `Channel`, numbered from `first`.
+    Burst {
+        channel: &'static str,
+        first: u32,
+        count: u32,
+    },
+    Detach {
+        id: u32,
+        grace: Duration,
+    },
+    Reattach(u32),
+    /// Makes every send to the `Client` fail from then on.
+    Break(u32),
+    /// Makes sends to the `Client` succeed again.
+    Repair(u32),
+    Advance(Duration),
+    SweepDetached,
+    Gc,
+}
+
+use Op::*;
+
+/// One step of a timeline: the `Ops` of each simulated thread
+type Step = Vec<Vec<Op>>;
+
+struct Scenario {
+    name: &'static str,
+    /// See `PubSub::set_detached_buffer_capacity`.
+    detached_capacity: usize,
+    /// See `EvictionPolicy::AfterConsecutiveFailures`.
+    evict_after: Option<u32>,
+    steps: Vec<Step>,
+}
+
+impl Scenario {
+    fn new(name: &'static str) -> Scenario {
+        Scenario {
+            name,
+            detached_capacity: 0,
+            evict_after: None,
+            steps: Vec::new(),
+        }
+    }
+
+    fn step(mut self, threads: Step) -> Scenario {
+        self.steps.push(threads);
+        self
+    }
+
+    fn configure(&self, pubsub: &mut ScenarioPubSub, clock: &ManualClock) {
+        pubsub.set_clock(clock.clone());
+        pubsub.set_detached_buffer_capacity(self.detached_capacity);
+
+        if let Some(threshold) = self.evict_after {
+            pubsub.set_eviction_policy(EvictionPolicy::AfterConsecutiveFailures(threshold));
+        }
+    }
+
+    fn client_ids(&self) -> BTreeSet<u32> {
+        self.steps
+            .iter()
+            .flatten()
+            .flatten()
+            .filter_map(|op| match *op {
+                Add(id)
+                | Remove(id)
+                | Sub(id, _)
+                | Psub(id, _)
+                | Unsub(id, _)
+                | Punsub(id, _)
+                | Detach { id, .. }
+                | Reattach(id)
+                | Break(id)
+                | Repair(id) => Some(id),
+                Burst { .. } | Advance(_) | SweepDetached | Gc => None,
+            })
+            .collect()
+    }
+
+    /// Runs the timeline against a `PubSub` and a `SharedPubSub`, and
+    /// compares what each `Client` received with the `Oracle`.
+    fn run(&self) {
+        let forward = Oracle::new(self).run(self.steps.iter().map(|step| step.iter().collect()));
+        let backward =
+            Oracle::new(self).run(self.steps.iter().map(|step| step.iter().rev().collect()));
+
+        assert_eq!(
+            forward, backward,
+            "{}: the threads of a step do not commute",
+            self.name
+        );
+
+        assert_eq!(self.run_serial(), forward, "{}: PubSub", self.name);
+        assert_eq!(self.run_shared(), forward, "{}: SharedPubSub", self.name);
+    }
+
+    fn run_serial(&self) -> Received {
+        let world = World::new(self);
+        let mut pubsub = PubSub::new();
+        self.configure(&mut pubsub, &world.clock);
+
+        for step in &self.steps {
+            for op in step.iter().flatten() {
+                pubsub.run(op, &world);
+            }
+
+            assert_eq!(pubsub.check_invariants(), Ok(()), "{}", self.name);
+        }
+
+        world.received()
+    }
+
+    fn run_shared(&self) -> Received {
+        let world = World::new(self);
+        let mut pubsub = PubSub::new();
+        self.configure(&mut pubsub, &world.clock);
+        let shared = SharedPubSub::from(pubsub);
+
+        for step in &self.steps {
+            thread::scope(|scope| {
+                for ops in step {
+                    let mut shared = shared.clone();
+                    let world = &world;
+
+                    scope.spawn(move || {
+                        for op in ops {
+                            shared.run(op, world);
+                        }
+                    });
+                }
+            });
+
+            assert_eq!(
+                shared.read(|pubsub| pubsub.check_invariants()),
+                Ok(()),
+                "{}",
+                self.name
+            );
+        }
+
+        world.received()
+    }
+}
+
+/// The `Messages` received by each `Client`, sorted
+type Received = BTreeMap<u32, Vec<(String, u32)>>;
+
+/// A `Client` recording what it receives, which can be made to fail
+#[derive(Clone)]
+struct Endpoint {
+    id: u32,
+    inbox: Arc<Mutex<Vec<(String, u32)>>>,
+    broken: Arc<AtomicBool>,
+}
+
+impl Client<u32, u32> for Endpoint {
+    type Error = ();
+
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    fn send(&mut self, message: &Message<u32>) -> Result<(), ()> {
+        if self.broken.load(Ordering::SeqCst) {
+            return Err(());
+        }
+
+        self.inbox
+            .lock()
+            .unwrap()
+            .push((message.source.to_string(), message.contents));
+
+        Ok(())
+    }
+}
+
+type ScenarioPubSub = PubSub<Endpoint, u32, u32>;
+
+/// The `Clients` and `Clock` of one run of a timeline
+struct World {
+    endpoints: BTreeMap<u32, Endpoint>,
+    clock: ManualClock,
+}
+
+impl World {
+    fn new(scenario: &Scenario) -> World {
+        World {
+            endpoints: scenario
+                .client_ids()
+                .into_iter()
+                .map(|id| {
+                    let endpoint = Endpoint {
+                        id,
+                        inbox: Arc::new(Mutex::new(Vec::new())),
+                        broken: Arc::new(AtomicBool::new(false)),
+                    };
+
+                    (id, endpoint)
+                })
+                .collect(),
+            clock: ManualClock::new(),
+        }
+    }
+
+    fn endpoint(&self, id: u32) -> Endpoint {
+        self.endpoints[&id].clone()
+    }
+
+    fn received(&self) -> Received {
+        self.endpoints
+            .iter()
+            .map(|(id, endpoint)| {
+                let mut inbox = endpoint.inbox.lock().unwrap().clone();
+                inbox.sort();
+                (*id, inbox)
+            })
+            .collect()
+    }
+}
+
+/// What a timeline is run against
+trait Target {
+    fn run(&mut self, op: &Op, world: &World);
+}
+
+impl Target for ScenarioPubSub {
+    fn run(&mut self, op: &Op, world: &World) {
+        match *op {
+            Add(id) => drop(self.add_client(world.endpoint(id)).unwrap()),
+            Remove(id) => self.remove_client(world.endpoint(id)),
+            Sub(id, channel) => self.sub_client(world.endpoint(id), channel).unwrap(),
+            Psub(id, pattern) => self.psub_client(world.endpoint(id), pattern).unwrap(),
+            Unsub(id, channel) => self.unsub_client(world.endpoint(id), channel).unwrap(),
+            Punsub(id, pattern) => self.punsub_client(world.endpoint(id), pattern).unwrap(),
+            Burst {
+                channel,
+                first,
+                count,
+            } => {
+                for contents in first..first + count {
+                    self.pub_message(channel, contents).unwrap();
+                }
+            }
+            Detach { id, grace } => self.detach_client(&id, grace).unwrap(),
+            Reattach(id) => self.reattach_client(world.endpoint(id)).unwrap(),
+            Break(id) => world.endpoints[&id].broken.store(true, Ordering::SeqCst),
+            Repair(id) => world.endpoints[&id].broken.store(false, Ordering::SeqCst),
+            Advance(duration) => world.clock.advance(duration),
+            SweepDetached => drop(self.sweep_detached()),
+            Gc => drop(self.gc()),
+        }
+    }
+}
+
+impl Target for SharedPubSub<Endpoint, u32, u32> {
+    fn run(&mut self, op: &Op, world: &World) {
+        match *op {
+            Add(id) => drop(self.add_client(world.endpoint(id)).unwrap()),
+            Remove(id) => self.remove_client(world.endpoint(id)),
+            Sub(id, channel) => self.sub_client(world.endpoint(id), channel).unwrap(),
+            Psub(id, pattern) => self.psub_client(world.endpoint(id), pattern).unwrap(),
+            Unsub(id, channel) => self.unsub_client(world.endpoint(id), channel).unwrap(),
+            Punsub(id, pattern) => self.punsub_client(world.endpoint(id), pattern).unwrap(),
+            Burst {
+                channel,
+                first,
+                count,
+            } => {
+                for contents in first..first + count {
+                    self.pub_message(channel, contents).unwrap();
+                }
+            }
+            Detach { id, grace } => self.detach_client(&id, grace).unwrap(),
+            Reattach(id) => self.reattach_client(world.endpoint(id)).unwrap(),
+            Break(id) => world.endpoints[&id].broken.store(true, Ordering::SeqCst),
+            Repair(id) => world.endpoints[&id].broken.store(false, Ordering::SeqCst),
+            Advance(duration) => world.clock.advance(duration),
+            SweepDetached => drop(self.sweep_detached()),
+            Gc => drop(self.gc()),
+        }
+    }
+}
+
+/// A model of the `PubSub`, covering only what the `Ops` do
+struct Oracle {
+    detached_capacity: usize,
+    evict_after: Option<u32>,
+    now: Duration,
+    connected: BTreeSet<u32>,
+    broken: BTreeSet<u32>,
+    failures: BTreeMap<u32, u32>,
+    /// The literal `Channels` and patterns of each identifier, marked `true`
+    /// for patterns.
+    subscriptions: BTreeMap<u32, BTreeSet<(bool, &'static str)>>,
+    /// When the grace period of each detached identifier ends, and the
+    /// `Messages` buffered for it.
+    detached: BTreeMap<u32, (Duration, VecDeque<(String, u32)>)>,
+    received: Received,
+}
+
+impl Oracle {
+    fn new(scenario: &Scenario) -> Oracle {
+        Oracle {
+            detached_capacity: scenario.detached_capacity,
+            evict_after: scenario.evict_after,
+            now: Duration::ZERO,
+            connected: BTreeSet::new(),
+            broken: BTreeSet::new(),
+            failures: BTreeMap::new(),
+            subscriptions: BTreeMap::new(),
+            detached: BTreeMap::new(),
+            received: scenario
+                .client_ids()
+                .into_iter()
+                .map(|id| (id, Vec::new()))
+                .collect(),
+        }
+    }
+
+    fn run<'s, S>(mut self, steps: S) -> Received
+    where
+        S: Iterator<Item = Vec<&'s Vec<Op>>>,
+    {
+        for step in steps {
+            for op in step.into_iter().flatten() {
+                self.apply(op);
+            }
+        }
+
+        for received in self.received.values_mut() {
+            received.sort();
+        }
+
+        self.received
+    }
+
+    fn apply(&mut self, op: &Op) {
+        match *op {
+            Add(id) | Reattach(id) => {
+                self.connected.insert(id);
+
+                if let Some((_, buffered)) = self.detached.remove(&id) {
+                    for (channel, contents) in buffered {
+                        self.send(id, channel, contents);
+                    }
+                }
+            }
+            Remove(id) => self.remove(id),
+            Sub(id, name) | Psub(id, name) => {
+                let pattern = matches!(op, Psub(..));
+                self.subscriptions
+                    .entry(id)
+                    .or_default()
+                    .insert((pattern, name));
+            }
+            Unsub(id, name) | Punsub(id, name) => {
+                let pattern = matches!(op, Punsub(..));
+                self.subscriptions
+                    .entry(id)
+                    .or_default()
+                    .remove(&(pattern, name));
+            }
+            Burst {
+                channel,
+                first,
+                count,
+            } => {
+                for contents in first..first + count {
+                    self.publish(channel, contents);
+                }
+            }
+            Detach { id, grace } => {
+                self.connected.remove(&id);
+                self.failures.remove(&id);
+                self.detached
+                    .insert(id, (self.now + grace, VecDeque::new()));
+            }
+            Break(id) => {
+                self.broken.insert(id);
+            }
+            Repair(id) => {
+                self.broken.remove(&id);
+            }
+            Advance(duration) => self.now += duration,
+            SweepDetached => {
+                let now = self.now;
+                let expired: Vec<u32> = self
+                    .detached
+                    .iter()
+                    .filter(|(_, (expires, _))| *expires <= now)
+                    .map(|(id, _)| *id)
+                    .collect();
+
+                for id in expired {
+                    self.detached.remove(&id);
+                    self.remove(id);
+                }
+            }
+            Gc => {}
+        }
+    }
+
+    fn remove(&mut self, id: u32) {
+        self.connected.remove(&id);
+        self.failures.remove(&id);
+        self.subscriptions.remove(&id);
+    }
+
+    fn publish(&mut self, channel: &str, contents: u32) {
+        let recipients: Vec<u32> = self
+            .subscriptions
+            .iter()
+            .filter(|(_, names)| {
+                names.iter().any(|&(pattern, name)| {
+                    if pattern {
+                        glob(name.as_bytes(), channel.as_bytes())
+                    } else {
+                        name == channel
+                    }
+                })
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in recipients {
+            if let Some((_, buffered)) = self.detached.get_mut(&id) {
+                buffered.push_back((channel.to_string(), contents));
+
+                if buffered.len() > self.detached_capacity {
+                    buffered.pop_front();
+                }
+            } else if self.connected.contains(&id) {
+                self.send(id, channel.to_string(), contents);
+            }
+        }
+    }
+
+    fn send(&mut self, id: u32, channel: String, contents: u32) {
+        if !self.broken.contains(&id) {
+            self.failures.remove(&id);
+            self.received
+                .get_mut(&id)
+                .unwrap()
+                .push((channel, contents));
+            return;
+        }
+
+        let failures = self.failures.entry(id).or_default();
+        *failures += 1;
+
+        if self
+            .evict_after
+            .is_some_and(|threshold| *failures >= threshold.max(1))
+        {
+            self.remove(id);
+        }
+    }
+}
+
+/// Whether a `PatternSyntax::Glob` pattern matches a name
+fn glob(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob(&pattern[1..], name) || (!name.is_empty() && glob(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+fn secs(secs: u64) -> Duration {
+    Duration::from_secs(secs)
+}
+
+#[test]
+fn wildcard_overlap() {
+    Scenario::new("wildcard_overlap")
+        .step(vec![
+            vec![Add(1), Sub(1, "a.b"), Psub(1, "a.*"), Psub(1, "a.?")],
+            vec![Add(2), Psub(2, "*")],
+            vec![Add(3), Psub(3, "a.?"), Sub(3, "a.bc")],
+        ])
+        .step(vec![
+            vec![Burst {
+                channel: "a.b",
+                first: 0,
+                count: 20,
+            }],
+            vec![Burst {
+                channel: "a.bc",
+                first: 100,
+                count: 20,
+            }],
+            vec![Burst {
+                channel: "b.x",
+                first: 200,
+                count: 20,
+            }],
+        ])
+        .step(vec![vec![Punsub(1, "a.*")], vec![Unsub(3, "a.bc")]])
+        .step(vec![
+            vec![Burst {
+                channel: "a.b",
+                first: 300,
+                count: 10,
+            }],
+            vec![Burst {
+                channel: "a.bc",
+                first: 400,
+                count: 10,
+            }],
+        ])
+        .run();
+}
+
+#[test]
+fn reconnect_with_grace() {
+    let mut scenario = Scenario::new("reconnect_with_grace")
+        .step(vec![
+            vec![Add(1), Sub(1, "feed")],
+            vec![Add(2), Sub(2, "feed")],
+            vec![Add(3), Psub(3, "f*")],
+        ])
+        .step(vec![
+            vec![Detach {
+                id: 1,
+                grace: secs(30),
+            }],
+            vec![Detach {
+                id: 2,
+                grace: secs(5),
+            }],
+        ])
+        .step(vec![
+            vec![Burst {
+                channel: "feed",
+                first: 0,
+                count: 3,
+            }],
+            vec![Burst {
+                channel: "feed",
+                first: 10,
+                count: 3,
+            }],
+        ])
+        .step(vec![vec![Advance(secs(10)), SweepDetached]])
+        // 1 gets what was buffered for it, while 2 comes back without its
+        // subscriptions.
+        .step(vec![vec![Reattach(1)], vec![Add(2)]])
+        .step(vec![vec![Burst {
+            channel: "feed",
+            first: 20,
+            count: 5,
+        }]]);
+    scenario.detached_capacity = 8;
+    scenario.run();
+}
+
+#[test]
+fn queue_overflow() {
+    let mut scenario = Scenario::new("queue_overflow")
+        .step(vec![
+            vec![Add(1), Sub(1, "ticks"), Sub(1, "alerts")],
+            vec![Add(2), Sub(2, "ticks"), Psub(2, "al*")],
+        ])
+        .step(vec![vec![Detach {
+            id: 1,
+            grace: secs(60),
+        }]])
+        // Only the last `Messages` of a single thread are kept, so the
+        // overflowing `Channel` is published to from one thread.
+        .step(vec![vec![Burst {
+            channel: "ticks",
+            first: 0,
+            count: 10,
+        }]])
+        .step(vec![vec![Reattach(1)]])
+        .step(vec![
+            vec![Burst {
+                channel: "ticks",
+                first: 10,
+                count: 5,
+            }],
+            vec![Burst {
+                channel: "alerts",
+                first: 100,
+                count: 5,
+            }],
+        ]);
+    scenario.detached_capacity = 3;
+    scenario.run();
+}
+
+#[test]
+fn auto_eviction() {
+    let burst = |channel, first, count| Burst {
+        channel,
+        first,
+        count,
+    };
+
+    let mut scenario = Scenario::new("auto_eviction")
+        .step(vec![
+            vec![Add(1), Psub(1, "jobs.*")],
+            vec![Add(2), Psub(2, "jobs.*"), Sub(2, "control")],
+            vec![Add(3), Sub(3, "jobs.a")],
+        ])
+        // Two failures, then a success starting the count over.
+        .step(vec![vec![Break(2), burst("jobs.a", 0, 2), Repair(2)]])
+        .step(vec![vec![burst("jobs.b", 10, 1)]])
+        // The third failure in a row, on either thread, evicts 2.
+        .step(vec![vec![Break(2)]])
+        .step(vec![
+            vec![burst("jobs.a", 20, 2)],
+            vec![burst("jobs.b", 30, 1)],
+        ])
+        .step(vec![vec![Repair(2), Gc]])
+        .step(vec![
+            vec![burst("control", 40, 2)],
+            vec![burst("jobs.b", 50, 2)],
+        ])
+        // Added back, 2 has no subscriptions left.
+        .step(vec![vec![Add(2)]])
+        .step(vec![vec![burst("control", 60, 2)]]);
+    scenario.evict_after = Some(3);
+    scenario.run();
+}
+
+#[test]
+fn churn_with_gc() {
+    Scenario::new("churn_with_gc")
+        .step(vec![
+            vec![Add(1), Add(2), Add(3)],
+            vec![Add(4), Add(5), Add(6)],
+        ])
+        .step(vec![
+            vec![Sub(1, "x"), Psub(2, "x*"), Sub(3, "y")],
+            vec![Psub(4, "?"), Sub(5, "x"), Sub(6, "z")],
+        ])
+        .step(vec![
+            vec![Burst {
+                channel: "x",
+                first: 0,
+                count: 10,
+            }],
+            vec![Burst {
+                channel: "y",
+                first: 100,
+                count: 10,
+            }],
+            vec![Burst {
+                channel: "xz",
+                first: 200,
+                count: 10,
+            }],
+        ])
+        .step(vec![
+            vec![Remove(1), Punsub(2, "x*")],
+            vec![Remove(4), Unsub(6, "z")],
+            vec![Gc],
+        ])
+        .step(vec![vec![Gc, Sub(6, "x")]])
+        .step(vec![
+            vec![Burst {
+                channel: "x",
+                first: 300,
+                count: 10,
+            }],
+            vec![Burst {
+                channel: "z",
+                first: 400,
+                count: 10,
+            }],
+        ])
+        .run();
+}
@@ -0,0 +1,117 @@
+//! Snapshots of a `SharedPubSub` taken while other threads change and
+//! publish to it
+//!
+//! Every snapshot must be internally consistent: each subscriber it lists
+//! is one of its `Clients`, and the subscriber counts agree with the
+//! subscribers listed.
+
+use general_pub_sub::prelude::*;
+use general_pub_sub::sync::SharedPubSub;
+use general_pub_sub::{OwnedView, PatternSyntax};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+const CHURNS: u32 = 2_000;
+
+const CHANNELS: &[&str] = &["orders.created", "orders.paid", "users.signup", "chat"];
+
+const PATTERNS: &[&str] = &["orders.*", "*.signup", "*"];
+
+/// Checks a snapshot, returning what is wrong with it.
+fn check(snapshot: &OwnedView<u32, PatternSyntax>) -> Result<(), String> {
+    let channels = snapshot.channels().chain(snapshot.patterns());
+
+    for (channel, count) in channels {
+        let subscribers: Vec<_> = snapshot.subscribers(channel).collect();
+
+        if subscribers.len() != count {
+            return Err(format!(
+                "{} counts {} subscribers but lists {:?}",
+                channel, count, subscribers
+            ));
+        }
+
+        if let Some(missing) = subscribers.iter().find(|id| !snapshot.contains_client(id)) {
+            return Err(format!(
+                "{} lists {}, which is not a Client",
+                channel, missing
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn snapshots_stay_consistent_while_churning() {
+    let pubsub: SharedPubSub<RecordingClient<u32, u32>, u32, u32> = SharedPubSub::new();
+    let done = Arc::new(AtomicBool::new(false));
+
+    let churner = {
+        let (pubsub, done) = (pubsub.clone(), done.clone());
+
+        thread::spawn(move || {
+            for round in 0..CHURNS {
+                let client = RecordingClient::new(round % 16);
+                let channel = CHANNELS[round as usize % CHANNELS.len()];
+                let pattern = PATTERNS[round as usize % PATTERNS.len()];
+
+                let _ = pubsub.add_client(client.clone());
+                let _ = pubsub.sub_client(client.clone(), channel);
+                let _ = pubsub.psub_client(client.clone(), pattern);
+
+                match round % 3 {
+                    0 => pubsub.remove_client(client),
+                    1 => drop(pubsub.unsub_client(client, channel)),
+                    _ => drop(pubsub.unsub_all(&(round % 16))),
+                }
+            }
+
+            done.store(true, Ordering::SeqCst);
+        })
+    };
+
+    let publisher = {
+        let (pubsub, done) = (pubsub.clone(), done.clone());
+
+        thread::spawn(move || {
+            let mut sent = 0u32;
+
+            while !done.load(Ordering::SeqCst) {
+                let channel = CHANNELS[sent as usize % CHANNELS.len()];
+                pubsub.pub_message(channel, sent).unwrap();
+                sent += 1;
+            }
+        })
+    };
+
+    let mut snapshots = 0;
+
+    while !done.load(Ordering::SeqCst) {
+        let snapshot = pubsub.topology_snapshot();
+
+        if let Err(problem) = check(&snapshot) {
+            panic!("snapshot {} is torn: {}", snapshots, problem);
+        }
+
+        pubsub.read(|pubsub| {
+            pubsub.check_invariants().unwrap();
+
+            for channel in CHANNELS {
+                for subscriber in pubsub.subscribers_snapshot(channel) {
+                    assert!(pubsub.view().contains_client(&subscriber));
+                }
+            }
+        });
+
+        snapshots += 1;
+    }
+
+    churner.join().unwrap();
+    publisher.join().unwrap();
+
+    assert!(snapshots > 0);
+    check(&pubsub.topology_snapshot()).unwrap();
+    assert!(pubsub.check_invariants().is_ok());
+}